@@ -1,8 +1,26 @@
+use crate::archive::dedup::{self, BlockSplitter};
+use crate::archive::journal::{Journal, JournalHeader, JournalRecord, JournalState, ResumePoint};
+use crate::archive::paths;
+use crate::archive::sparse;
+use crate::archive::special;
+use crate::archive::xattrs;
 use crate::checksum;
+use crate::chunking::streaming_erasure_chunker::ChunkInfo as StreamingChunkInfo;
+use crate::chunking::StreamingErasureChunkingWriter;
 use crate::compression;
+use crate::crypto::envelope::CIPHER;
+use crate::crypto::{
+    DataKey, Encryptor, IndexSignature, KdfParams, KeyDerivation, KeyRing, PublicKey, Recipients, Secret, SigningKey,
+};
+use crate::erasure::stripe::STRIPE_FOOTER_SIZE;
 use crate::error::{EctarError, Result};
-use crate::index::format::{ArchiveIndex, ArchiveParameters, ChunkInfo, FileEntry, FileType};
-use chrono::Utc;
+use crate::index::chain;
+use crate::index::format::{
+    self, ArchiveIndex, ArchiveParameters, ChunkInfo, EncryptionParameters, FileEntry, FileType, ParentArchive,
+};
+use crate::io::durable::{AtomicFile, Durability};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
@@ -15,10 +33,201 @@ pub struct ArchiveBuilder {
     chunk_size: Option<u64>,
     compression_level: i32,
     no_compression: bool,
+    shard_trailers: bool,
+    stripe_size: Option<u64>,
+    threads: usize,
+    durability: Durability,
     no_index: bool,
     exclude_patterns: Vec<String>,
     follow_symlinks: bool,
     preserve_permissions: bool,
+    incremental_from: Option<PathBuf>,
+    compare_checksums: bool,
+    dedup: bool,
+    dedup_block_size: u64,
+    resume: bool,
+    encryption: Option<Secret>,
+    recipients: Vec<PublicKey>,
+    kdf_params: KdfParams,
+    encrypt_index: bool,
+    sign_key: Option<SigningKey>,
+}
+
+/// Blocks of deduplicated files already in the archive
+#[derive(Default)]
+struct StoredBlocks {
+    hashes: HashSet<String>,
+    entries: Vec<FileEntry>,
+}
+
+/// First archived occurrence of each file with several hard links, keyed
+/// by (device, inode), along with its checksum
+#[derive(Default)]
+struct HardLinks {
+    first: HashMap<(u64, u64), (PathBuf, Option<String>)>,
+}
+
+impl HardLinks {
+    /// Archive path and checksum of the file this one is a link to, if
+    /// that was archived already
+    fn target(&self, metadata: &std::fs::Metadata) -> Option<(PathBuf, Option<String>)> {
+        inode_key(metadata).and_then(|key| self.first.get(&key).cloned())
+    }
+
+    /// Remember an archived regular file so later links to it refer back
+    fn record(&mut self, metadata: &std::fs::Metadata, entry: &FileEntry) {
+        if entry.entry_type == FileType::File {
+            if let Some(key) = inode_key(metadata) {
+                self.first.entry(key).or_insert_with(|| (entry.archive_path(), entry.checksum.clone()));
+            }
+        }
+    }
+}
+
+/// (device, inode) of a regular file that has other hard links
+#[cfg(unix)]
+fn inode_key(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.is_file() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode_key(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// What an interrupted run had completed, as read from its journal
+struct Resumed {
+    chunks: Vec<ChunkInfo>,
+    records: Vec<JournalRecord>,
+    point: ResumePoint,
+    /// Entries recorded, including any past the resume point
+    files: Vec<FileEntry>,
+    blocks: Vec<FileEntry>,
+}
+
+impl Resumed {
+    /// Check that the file written again across the resume point still
+    /// matches what the interrupted run wrote
+    fn check_replayed(&self, entry: &FileEntry) -> Result<()> {
+        let recorded = match self.files.get(self.point.files) {
+            Some(recorded) => recorded,
+            None => return Ok(()),
+        };
+        if recorded.archive_path() != entry.archive_path()
+            || recorded.size != entry.size
+            || recorded.mtime != entry.mtime
+            || recorded.checksum != entry.checksum
+            || recorded.attributes != entry.attributes
+        {
+            return Err(EctarError::InvalidParameters(format!(
+                "Cannot resume: {} changed since the interrupted run",
+                entry.path
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Appends a record to the journal each time a chunk's shards are complete
+struct JournalTracker {
+    journal: Journal,
+    /// Index of the file `starts` begins with
+    first_file: usize,
+    /// Stream position at which each file's entries start
+    starts: Vec<u64>,
+    /// Size and modification time of each file, as it was started
+    stamps: Vec<(u64, Option<DateTime<Utc>>)>,
+    chunks_recorded: usize,
+    files_recorded: usize,
+    blocks_recorded: usize,
+    stream_recorded: u64,
+}
+
+impl JournalTracker {
+    /// Start the journal, carrying over the records of a resumed run
+    fn start(output_base: &str, header: &JournalHeader, resumed: Option<&Resumed>) -> Result<Self> {
+        let mut tracker = Self {
+            journal: Journal::create(output_base, header)?,
+            first_file: 0,
+            starts: Vec::new(),
+            stamps: Vec::new(),
+            chunks_recorded: 0,
+            files_recorded: 0,
+            blocks_recorded: 0,
+            stream_recorded: 0,
+        };
+        if let Some(resumed) = resumed {
+            // Entries past the resume point are written again, so they are
+            // left out of the carried over records
+            for record in &resumed.records {
+                let mut record = record.clone();
+                record.files.truncate(resumed.point.files - tracker.files_recorded);
+                record.blocks.truncate(resumed.point.blocks - tracker.blocks_recorded);
+                tracker.files_recorded += record.files.len();
+                tracker.blocks_recorded += record.blocks.len();
+                tracker.journal.record(&record)?;
+            }
+            tracker.first_file = resumed.point.files;
+            tracker.chunks_recorded = resumed.chunks.len();
+            tracker.stream_recorded = resumed.chunks.iter().map(|c| c.uncompressed_size).sum();
+        }
+        Ok(tracker)
+    }
+
+    fn file_started(&mut self, stream_position: u64, metadata: &std::fs::Metadata) {
+        self.starts.push(stream_position);
+        self.stamps.push((metadata.len(), ArchiveBuilder::modified(metadata)));
+    }
+
+    /// Record the chunks completed since the last call, in chunk order
+    fn update(
+        &mut self,
+        writer: &StreamingErasureChunkingWriter,
+        files: &[FileEntry],
+        blocks: &[FileEntry],
+    ) -> Result<()> {
+        if writer.chunks_completed() <= self.chunks_recorded {
+            return Ok(());
+        }
+
+        for chunk in writer.completed_chunks_after(self.chunks_recorded) {
+            // A chunk finished out of order waits for the ones before it
+            if chunk.chunk_number != self.chunks_recorded + 1 {
+                break;
+            }
+            self.stream_recorded += chunk.uncompressed_size;
+
+            // The last file to start before the chunk ends is written again
+            let started = self.starts.partition_point(|&start| start <= self.stream_recorded);
+            let stream_offset = self.starts[started - 1];
+            let (size, mtime) = self.stamps[started - 1];
+            let resume = ResumePoint {
+                files: self.first_file + started - 1,
+                blocks: blocks.partition_point(|b| b.stream_offset.is_some_and(|o| o < stream_offset)),
+                stream_offset,
+                size: Some(size),
+                mtime,
+            };
+
+            self.journal.record(&JournalRecord {
+                chunk: ArchiveBuilder::index_chunk_info(&chunk),
+                resume,
+                files: files[self.files_recorded..].to_vec(),
+                blocks: blocks[self.blocks_recorded..].to_vec(),
+            })?;
+            self.chunks_recorded += 1;
+            self.files_recorded = files.len();
+            self.blocks_recorded = blocks.len();
+        }
+        Ok(())
+    }
+}
+
+/// What an incremental archive records about the archive it is based on
+struct Increment {
+    parent: ParentArchive,
+    deleted: Vec<PathBuf>,
 }
 
 impl ArchiveBuilder {
@@ -30,10 +239,24 @@ impl ArchiveBuilder {
             chunk_size: None,
             compression_level: compression::zstd::DEFAULT_COMPRESSION_LEVEL,
             no_compression: false,
+            shard_trailers: false,
+            stripe_size: None,
+            threads: 1,
+            durability: Durability::default(),
             no_index: false,
             exclude_patterns: Vec::new(),
             follow_symlinks: false,
             preserve_permissions: true,
+            incremental_from: None,
+            compare_checksums: false,
+            dedup: false,
+            dedup_block_size: dedup::DEFAULT_BLOCK_SIZE,
+            resume: false,
+            encryption: None,
+            recipients: Vec::new(),
+            kdf_params: KdfParams::default(),
+            encrypt_index: false,
+            sign_key: None,
         }
     }
 
@@ -62,6 +285,33 @@ impl ArchiveBuilder {
         self
     }
 
+    /// Append a checksum trailer to every shard so it can be verified without the index
+    pub fn shard_trailers(mut self, trailers: bool) -> Self {
+        self.shard_trailers = trailers;
+        self
+    }
+
+    /// Erasure-code each chunk in stripes of this many bytes per shard, so
+    /// creating the archive needs memory for one stripe instead of one chunk
+    pub fn stripe_size(mut self, size: Option<u64>) -> Self {
+        self.stripe_size = size;
+        self
+    }
+
+    /// Erasure-code and write chunks on this many threads while the next
+    /// chunk is compressed
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// How far shard and index files are synced to disk before they are
+    /// renamed into place
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
     pub fn no_index(mut self, no_idx: bool) -> Self {
         self.no_index = no_idx;
         self
@@ -82,6 +332,75 @@ impl ArchiveBuilder {
         self
     }
 
+    /// Store only entries that are new or changed since the archive with
+    /// this index, recording it as the parent
+    pub fn incremental_from(mut self, index_path: Option<PathBuf>) -> Self {
+        self.incremental_from = index_path;
+        self
+    }
+
+    /// Also compare file checksums against the parent, catching changes
+    /// that keep size and mtime
+    pub fn compare_checksums(mut self, compare: bool) -> Self {
+        self.compare_checksums = compare;
+        self
+    }
+
+    /// Split file data into content-defined blocks and store each unique
+    /// block once
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Average size of deduplicated blocks
+    pub fn dedup_block_size(mut self, size: u64) -> Self {
+        self.dedup_block_size = size;
+        self
+    }
+
+    /// Continue an interrupted run from its journal instead of starting over
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Encrypt each chunk with a key file or a key derived from a passphrase
+    ///
+    /// An identity encrypts to its own public key unless recipients are
+    /// given; either way it also reads encrypted parent archives.
+    pub fn encryption(mut self, secret: Option<Secret>) -> Self {
+        self.encryption = secret;
+        self
+    }
+
+    /// Encrypt each chunk under a random key wrapped to these public keys,
+    /// so any one of the recipients' identities can read the archive
+    pub fn recipients(mut self, recipients: Vec<PublicKey>) -> Self {
+        self.recipients = recipients;
+        self
+    }
+
+    /// Argon2id cost of deriving the key from a passphrase
+    pub fn kdf_params(mut self, params: KdfParams) -> Self {
+        self.kdf_params = params;
+        self
+    }
+
+    /// Encrypt the index too, so file names and sizes are not readable
+    /// without the key
+    pub fn encrypt_index(mut self, encrypt: bool) -> Self {
+        self.encrypt_index = encrypt;
+        self
+    }
+
+    /// Sign the index with an Ed25519 key, so the archive can later be
+    /// shown to be unaltered
+    pub fn sign_key(mut self, key: Option<SigningKey>) -> Self {
+        self.sign_key = key;
+        self
+    }
+
     /// Validate parameters before creating archive
     pub fn validate(&self) -> Result<()> {
         if self.data_shards < 1 {
@@ -96,6 +415,18 @@ impl ArchiveBuilder {
             ));
         }
 
+        if self.chunk_size == Some(0) {
+            return Err(EctarError::InvalidChunkSize(
+                "Chunk size must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.threads < 1 {
+            return Err(EctarError::InvalidParameters(
+                "Threads must be at least 1".to_string(),
+            ));
+        }
+
         if self.data_shards + self.parity_shards > 256 {
             return Err(EctarError::InvalidParameters(
                 "Total shards (data + parity) cannot exceed 256".to_string(),
@@ -106,6 +437,90 @@ impl ArchiveBuilder {
             compression::zstd::validate_compression_level(self.compression_level)?;
         }
 
+        // Later incrementals find their parent's entries through its index
+        if self.incremental_from.is_some() && self.no_index {
+            return Err(EctarError::InvalidParameters(
+                "Incremental archives need an index".to_string(),
+            ));
+        }
+
+        if self.sign_key.is_some() && self.no_index {
+            return Err(EctarError::InvalidParameters(
+                "Signing needs an index".to_string(),
+            ));
+        }
+
+        if self.resume && self.chunk_size.is_none() {
+            return Err(EctarError::InvalidParameters(
+                "Resuming needs a chunk size".to_string(),
+            ));
+        }
+
+        if self.dedup {
+            if self.chunk_size.is_none() {
+                return Err(EctarError::InvalidParameters(
+                    "Deduplication needs a chunk size".to_string(),
+                ));
+            }
+            if !(dedup::MIN_BLOCK_SIZE..=dedup::MAX_BLOCK_SIZE).contains(&self.dedup_block_size) {
+                return Err(EctarError::InvalidParameters(format!(
+                    "Dedup block size must be between {} and {} bytes",
+                    dedup::MIN_BLOCK_SIZE,
+                    dedup::MAX_BLOCK_SIZE
+                )));
+            }
+        }
+
+        if !self.recipients.is_empty() && matches!(self.encryption, Some(Secret::Key(_) | Secret::Passphrase(_))) {
+            return Err(EctarError::InvalidParameters(
+                "Recipients cannot be combined with a key file or passphrase".to_string(),
+            ));
+        }
+
+        if self.encryption.is_some() || !self.recipients.is_empty() {
+            if self.chunk_size.is_none() {
+                return Err(EctarError::InvalidParameters(
+                    "Encryption needs a chunk size".to_string(),
+                ));
+            }
+            if self.no_compression {
+                return Err(EctarError::InvalidParameters(
+                    "Encryption needs compression".to_string(),
+                ));
+            }
+            // Striped shards are written before the chunk can be sealed
+            if self.stripe_size.is_some() {
+                return Err(EctarError::InvalidParameters(
+                    "Encryption cannot be combined with striped encoding".to_string(),
+                ));
+            }
+        } else if self.encrypt_index {
+            return Err(EctarError::InvalidParameters(
+                "Encrypting the index needs a key, passphrase or recipients".to_string(),
+            ));
+        }
+
+        if let Some(stripe_size) = self.stripe_size {
+            if self.chunk_size.is_none() {
+                return Err(EctarError::InvalidParameters(
+                    "Striped encoding needs a chunk size".to_string(),
+                ));
+            }
+            // The stripe footer is a zstd frame, which would corrupt a raw tar stream
+            if self.no_compression {
+                return Err(EctarError::InvalidParameters(
+                    "Striped encoding needs compression".to_string(),
+                ));
+            }
+            if stripe_size < STRIPE_FOOTER_SIZE as u64 || stripe_size > u32::MAX as u64 {
+                return Err(EctarError::InvalidParameters(format!(
+                    "Stripe size must be between {} and {} bytes",
+                    STRIPE_FOOTER_SIZE,
+                    u32::MAX
+                )));
+            }
+        }
+
         Ok(())
     }
 
@@ -120,44 +535,198 @@ impl ArchiveBuilder {
         if let Some(cs) = self.chunk_size {
             log::info!("  Chunk size: {} bytes", cs);
         }
+        if let Some(stripe_size) = self.stripe_size {
+            log::info!("  Stripe size: {} bytes per shard", stripe_size);
+        }
         log::info!("  Paths: {} items", paths.len());
 
         // Collect all files to archive
-        let files_to_archive = self.collect_files(paths)?;
+        let mut files_to_archive = self.collect_files(paths)?;
         log::info!("Collected {} files to archive", files_to_archive.len());
 
+        let mut files_unchanged = 0;
+        let increment = match self.incremental_from {
+            Some(ref parent_path) => {
+                let collected = files_to_archive.len();
+                let (changed, increment) = self.select_changed(paths, files_to_archive, parent_path)?;
+                files_to_archive = changed;
+                files_unchanged = collected - files_to_archive.len();
+                log::info!(
+                    "{} new or changed, {} unchanged, {} deleted since {}",
+                    files_to_archive.len(),
+                    files_unchanged,
+                    increment.deleted.len(),
+                    increment.parent.archive_name
+                );
+                Some(increment)
+            }
+            None => None,
+        };
+
         // Choose between chunked and non-chunked creation
-        if let Some(chunk_size) = self.chunk_size {
-            self.create_chunked(paths, &files_to_archive, chunk_size)
+        let mut metadata = if let Some(chunk_size) = self.chunk_size {
+            self.create_chunked(paths, &files_to_archive, chunk_size, increment.as_ref())?
+        } else {
+            self.create_single(paths, &files_to_archive, increment.as_ref())?
+        };
+        metadata.files_unchanged = files_unchanged;
+        metadata.files_deleted = increment.map_or(0, |i| i.deleted.len());
+        Ok(metadata)
+    }
+
+    /// Compare the collected files against the snapshot at the end of the
+    /// parent's chain, keeping those that are new or changed
+    fn select_changed(
+        &self,
+        paths: &[PathBuf],
+        files_to_archive: Vec<PathBuf>,
+        parent_path: &Path,
+    ) -> Result<(Vec<PathBuf>, Increment)> {
+        let keys = self.encryption.clone().map(KeyRing::new);
+        let chain = chain::load_chain(parent_path, keys.as_ref())?;
+        let previous = chain::snapshot(&chain);
+        let parent = &chain.last().expect("chain is never empty").index;
+        // A key must unlock every archive of a chain or none; reading an
+        // unencrypted parent with a key has already failed
+        if parent.parameters.encryption.is_some() && self.encryption.is_none() {
+            return Err(EctarError::InvalidParameters(format!(
+                "{} is encrypted, so an incremental archive of it must be too",
+                parent.archive_name
+            )));
+        }
+        log::info!(
+            "Incremental from {} (chain of {} archives, {} entries)",
+            parent.archive_name,
+            chain.len(),
+            previous.len()
+        );
+
+        let base_path = Self::base_path(paths);
+        let mut seen = HashSet::new();
+        let mut changed = Vec::new();
+        for file_path in files_to_archive {
+            let tar_path = Self::tar_path(&file_path, base_path);
+            let unchanged = match previous.get(&tar_path) {
+                Some((_, entry)) => self.is_unchanged(&file_path, entry)?,
+                None => false,
+            };
+            if !unchanged {
+                changed.push(file_path);
+            }
+            seen.insert(tar_path);
+        }
+
+        let deleted = previous.into_keys().filter(|path| !seen.contains(path)).collect();
+
+        let increment = Increment {
+            parent: chain::parent_reference(parent_path, parent, &self.output_base)?,
+            deleted,
+        };
+        Ok((changed, increment))
+    }
+
+    /// Whether a file still matches the entry recorded for it
+    fn is_unchanged(&self, file_path: &Path, previous: &FileEntry) -> Result<bool> {
+        let metadata = std::fs::symlink_metadata(file_path)?;
+        // A hard link was recorded as such only for not being the first
+        let recorded_type = match previous.entry_type {
+            FileType::Hardlink => FileType::File,
+            ref entry_type => entry_type.clone(),
+        };
+        let file_type = self.classify_file_type(&metadata);
+        let device = Self::device_numbers(&metadata, &file_type);
+        if recorded_type != file_type
+            || (previous.device_major, previous.device_minor) != (device.map(|d| d.0), device.map(|d| d.1))
+            || previous.size != metadata.len()
+            || previous.mtime != Self::get_mtime(&metadata)
+            || previous.mode != Self::get_file_mode(&metadata)
+            || previous.attributes != xattrs::read(file_path)?
+        {
+            return Ok(false);
+        }
+
+        if metadata.is_symlink() {
+            return Ok(previous.link_target() == Some(std::fs::read_link(file_path)?));
+        }
+
+        if self.compare_checksums && metadata.is_file() {
+            let checksum = checksum::sha256::compute_checksum(File::open(file_path)?)?;
+            return Ok(previous.checksum.as_deref() == Some(checksum.as_str()));
+        }
+
+        Ok(true)
+    }
+
+    /// Directory that archive paths are made relative to
+    fn base_path(paths: &[PathBuf]) -> &Path {
+        if paths.len() == 1 && paths[0].is_dir() {
+            paths[0].parent().unwrap_or(&paths[0])
+        } else {
+            Path::new("")
+        }
+    }
+
+    /// Index form of the target of a hard link to an archived file or of a
+    /// symlink, with its raw bytes if it is not valid UTF-8
+    fn link_target(
+        file_path: &Path,
+        metadata: &std::fs::Metadata,
+        link: Option<&(PathBuf, Option<String>)>,
+    ) -> Result<(Option<String>, Option<Vec<u8>>)> {
+        let target = match link {
+            Some((target, _)) => target.clone(),
+            None if metadata.is_symlink() => std::fs::read_link(file_path)?,
+            None => return Ok((None, None)),
+        };
+        let (target, target_bytes) = format::path_fields(&target);
+        Ok((Some(target), target_bytes))
+    }
+
+    /// Path of a file inside the archive (tar requires relative paths)
+    fn tar_path(file_path: &Path, base_path: &Path) -> PathBuf {
+        if base_path.as_os_str().is_empty() {
+            // No base path - use just the filename to ensure relative path
+            file_path.file_name()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| file_path.to_path_buf())
         } else {
-            self.create_single(paths, &files_to_archive)
+            file_path.strip_prefix(base_path).unwrap_or(file_path).to_path_buf()
         }
     }
 
     /// Create a non-chunked archive
-    fn create_single(&self, paths: &[PathBuf], files_to_archive: &[PathBuf]) -> Result<ArchiveMetadata> {
+    fn create_single(
+        &self,
+        paths: &[PathBuf],
+        files_to_archive: &[PathBuf],
+        increment: Option<&Increment>,
+    ) -> Result<ArchiveMetadata> {
         let archive_path = format!("{}.tar.zst", self.output_base);
         let mut file_entries = Vec::new();
 
         // Create tar builder
-        let output_file = File::create(&archive_path)?;
+        let mut output_file = AtomicFile::create(PathBuf::from(&archive_path), self.durability)?;
 
         if self.no_compression {
-            let mut writer = output_file;
-            let mut tar_builder = tar::Builder::new(&mut writer);
-            self.add_files_to_tar(&mut tar_builder, &files_to_archive, paths, &mut file_entries, 1)?;
-            tar_builder.finish()?;
+            {
+                let mut tar_builder = tar::Builder::new(CountingWriter::new(&mut output_file));
+                tar_builder.sparse(false);
+                self.add_files_to_tar(&mut tar_builder, files_to_archive, paths, &mut file_entries)?;
+                tar_builder.finish()?;
+            }
+            output_file.commit()?;
         } else {
             let mut encoder = compression::zstd::create_encoder(
                 output_file,
                 self.compression_level,
             )?;
             {
-                let mut tar_builder = tar::Builder::new(&mut encoder);
-                self.add_files_to_tar(&mut tar_builder, &files_to_archive, paths, &mut file_entries, 1)?;
+                let mut tar_builder = tar::Builder::new(CountingWriter::new(&mut encoder));
+                tar_builder.sparse(false);
+                self.add_files_to_tar(&mut tar_builder, files_to_archive, paths, &mut file_entries)?;
                 tar_builder.finish()?;
             }
-            encoder.finish()?;
+            encoder.finish()?.commit()?;
         }
 
         // Get final file size
@@ -170,7 +739,7 @@ impl ArchiveBuilder {
                 compressed_size: final_size,
                 uncompressed_size: file_entries.iter().map(|e| e.size).sum(),
             }];
-            self.create_index(&file_entries, &chunks_info)?;
+            self.create_index(&file_entries, &chunks_info, increment)?;
         }
 
         Ok(ArchiveMetadata {
@@ -178,14 +747,52 @@ impl ArchiveBuilder {
             total_size: file_entries.iter().map(|e| e.size).sum(),
             compressed_size: final_size,
             chunks: 1,
+            files_unchanged: 0,
+            files_deleted: 0,
         })
     }
 
     /// Create a chunked archive with independent compression per chunk
-    fn create_chunked(&self, paths: &[PathBuf], files_to_archive: &[PathBuf], chunk_size: u64) -> Result<ArchiveMetadata> {
-        use crate::chunking::StreamingErasureChunkingWriter;
+    fn create_chunked(
+        &self,
+        paths: &[PathBuf],
+        files_to_archive: &[PathBuf],
+        chunk_size: u64,
+        increment: Option<&Increment>,
+    ) -> Result<ArchiveMetadata> {
+        // Determine base path for making relative paths
+        let base_path = Self::base_path(paths);
+        let encryptor = self.encryptor()?;
+
+        let header = JournalHeader {
+            parameters: self.parameters(encryptor.as_ref()),
+            no_compression: self.no_compression,
+            dedup_block_size: self.dedup.then_some(self.dedup_block_size),
+            paths: paths.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+        };
+        let resumed = if self.resume {
+            Self::load_journal(&self.output_base, &header, files_to_archive, base_path)?
+        } else {
+            None
+        };
+        let mut journal = JournalTracker::start(&self.output_base, &header, resumed.as_ref())?;
+
+        // Written first, so the chunks can be decrypted even without an index
+        if let Some(recipients) = encryptor.as_ref().and_then(Encryptor::recipients) {
+            recipients.write(&self.output_base, self.data_shards, self.parity_shards, self.durability)?;
+        }
 
         let mut file_entries = Vec::new();
+        let mut stored_blocks = StoredBlocks::default();
+        if let Some(ref resumed) = resumed {
+            file_entries = resumed.files[..resumed.point.files].to_vec();
+            stored_blocks.entries = resumed.blocks[..resumed.point.blocks].to_vec();
+            stored_blocks.hashes = stored_blocks
+                .entries
+                .iter()
+                .filter_map(|b| dedup::block_hash_of(Path::new(&b.path)).map(str::to_string))
+                .collect();
+        }
 
         // Create streaming erasure chunking writer
         let output_base = PathBuf::from(&self.output_base);
@@ -196,98 +803,66 @@ impl ArchiveBuilder {
             self.data_shards,
             self.parity_shards,
         )
-        .no_compression(self.no_compression);
+        .no_compression(self.no_compression)
+        .shard_trailers(self.shard_trailers)
+        .stripe_size(self.stripe_size.map(|size| size as usize))
+        .threads(self.threads)
+        .durability(self.durability)
+        .encryption(encryptor.clone());
+        if let Some(ref resumed) = resumed {
+            let chunks = resumed.chunks.iter().map(Self::streaming_chunk_info).collect();
+            chunking_writer = chunking_writer.resume(chunks, resumed.point.stream_offset);
+        }
 
-        // Determine base path for making relative paths
-        let base_path = if paths.len() == 1 && paths[0].is_dir() {
-            paths[0].parent().unwrap_or(&paths[0])
-        } else {
-            Path::new("")
-        };
+        let splitter = self.dedup.then(|| BlockSplitter::new(self.dedup_block_size as usize));
+
+        let mut hard_links = HardLinks::default();
+        for (file_path, entry) in files_to_archive.iter().zip(&file_entries) {
+            if let Ok(metadata) = std::fs::symlink_metadata(file_path) {
+                hard_links.record(&metadata, entry);
+            }
+        }
 
         // Create tar builder on top of chunking writer and add files
         {
             let mut tar_builder = tar::Builder::new(&mut chunking_writer);
+            // Holes are found by `sparse`, which also reports what they save
+            tar_builder.sparse(false);
 
             // Add files to tar archive, tracking which chunk each file is in
-            for file_path in files_to_archive {
-                // Get chunk number before adding file
-                let chunk_number = tar_builder.get_ref().current_chunk_number();
-
-                log::debug!("Adding file to chunk {}: {}", chunk_number, file_path.display());
-
+            let first_file = resumed.as_ref().map_or(0, |r| r.point.files);
+            for (position, file_path) in files_to_archive.iter().enumerate().skip(first_file) {
                 let metadata = std::fs::symlink_metadata(file_path)?;
-                let file_type = self.classify_file_type(&metadata);
-
-                // Make path relative for tar (tar requires relative paths)
-                let tar_path = if base_path.as_os_str().is_empty() {
-                    // No base path - use just the filename to ensure relative path
-                    file_path.file_name()
-                        .map(PathBuf::from)
-                        .unwrap_or_else(|| file_path.clone())
-                } else {
-                    file_path.strip_prefix(base_path).unwrap_or(file_path).to_path_buf()
+                journal.file_started(tar_builder.get_ref().stream_position(), &metadata);
+                let link = hard_links.target(&metadata);
+
+                // A deduplicated file's new blocks go ahead of its own entry
+                let blocks = match splitter {
+                    Some(ref splitter) if metadata.is_file() && metadata.len() > 0 && link.is_none() => Some(
+                        Self::append_blocks(
+                            &mut tar_builder,
+                            splitter,
+                            file_path,
+                            Self::get_mtime(&metadata),
+                            &mut stored_blocks,
+                        )?,
+                    ),
+                    _ => None,
                 };
 
-                // Add to tar
-                if metadata.is_file() {
-                    let mut file = File::open(file_path)?;
-                    tar_builder.append_file(&tar_path, &mut file)?;
-                } else if metadata.is_dir() {
-                    tar_builder.append_dir(&tar_path, file_path)?;
-                } else if metadata.is_symlink() {
-                    let mut header = tar::Header::new_gnu();
-                    header.set_metadata(&metadata);
-                    header.set_entry_type(tar::EntryType::Symlink);
-                    let target = std::fs::read_link(file_path)?;
-                    header.set_link_name(&target)?;
-                    header.set_size(0);
-                    header.set_cksum();
-                    tar_builder.append_data(&mut header, &tar_path, std::io::empty())?;
-                }
-
-                // Flush to ensure we get accurate chunk tracking
-                tar_builder.get_mut().flush()?;
-
-                // Get chunk number after writing (file might have crossed chunk boundary)
-                let final_chunk = tar_builder.get_ref().current_chunk_number();
-
-                // Compute checksum for regular files
-                let checksum = if metadata.is_file() {
-                    let file = File::open(file_path)?;
-                    Some(checksum::sha256::compute_checksum(file)?)
-                } else {
-                    None
-                };
-
-                // Create file entry for index
-                let entry = FileEntry {
-                    path: tar_path.to_string_lossy().to_string(),
-                    chunk: chunk_number,
-                    offset: 0,
-                    size: metadata.len(),
-                    compressed_size: None,
-                    checksum,
-                    mode: Self::get_file_mode(&metadata),
-                    mtime: Self::get_mtime(&metadata),
-                    uid: Self::get_uid(&metadata),
-                    gid: Self::get_gid(&metadata),
-                    user: None,
-                    group: None,
-                    entry_type: file_type,
-                    target: if metadata.is_symlink() {
-                        Some(std::fs::read_link(file_path)?.to_string_lossy().to_string())
-                    } else {
-                        None
-                    },
-                    spans_chunks: if final_chunk != chunk_number {
-                        Some((chunk_number..=final_chunk).collect())
-                    } else {
-                        None
-                    },
-                };
+                let tar_path = Self::tar_path(file_path, base_path);
+                let entry = self.append_file(&mut tar_builder, file_path, &tar_path, &metadata, link.as_ref(), blocks)?;
+                log::debug!("Added file to chunk {}: {}", entry.chunk, file_path.display());
 
+                if let Some(ref resumed) = resumed {
+                    if position == first_file {
+                        resumed.check_replayed(&entry)?;
+                    }
+                }
+                hard_links.record(&metadata, &entry);
                 file_entries.push(entry);
+
+                journal.update(tar_builder.get_ref(), &file_entries, &stored_blocks.entries)?;
             }
 
             tar_builder.finish()?;
@@ -296,12 +871,28 @@ impl ArchiveBuilder {
         // Finish chunking and get chunk metadata (shards already written!)
         let chunks_info = chunking_writer.finish()?;
 
+        if self.dedup {
+            log::info!(
+                "Stored {} unique blocks ({} bytes) for {} bytes of file data",
+                stored_blocks.entries.len(),
+                stored_blocks.entries.iter().map(|b| b.size).sum::<u64>(),
+                file_entries.iter().filter(|f| f.blocks.is_some()).map(|f| f.size).sum::<u64>()
+            );
+        }
+
         log::info!("Created {} chunks with {} shards each", chunks_info.len(), self.data_shards + self.parity_shards);
 
         // Create index if requested
         if !self.no_index {
-            self.create_index_from_streaming(&file_entries, &chunks_info)?;
+            self.create_index_from_streaming(
+                &file_entries,
+                &stored_blocks.entries,
+                &chunks_info,
+                increment,
+                encryptor.as_ref(),
+            )?;
         }
+        Journal::remove(&self.output_base)?;
 
         let total_uncompressed: u64 = chunks_info.iter().map(|c| c.uncompressed_size).sum();
         // Total shard size = sum of (shard_size * number of shards) for each chunk
@@ -314,97 +905,240 @@ impl ArchiveBuilder {
             total_size: total_uncompressed,
             compressed_size: total_shard_size, // Report shard size instead of compressed
             chunks: chunks_info.len(),
+            files_unchanged: 0,
+            files_deleted: 0,
         })
     }
 
     /// Add files to tar archive (non-chunked)
     fn add_files_to_tar<W: Write>(
         &self,
-        tar_builder: &mut tar::Builder<W>,
+        tar_builder: &mut tar::Builder<CountingWriter<W>>,
         files_to_archive: &[PathBuf],
         paths: &[PathBuf],
         file_entries: &mut Vec<FileEntry>,
-        chunk_number: usize,
     ) -> Result<()> {
         // Determine base path for making relative paths
-        let base_path = if paths.len() == 1 && paths[0].is_dir() {
-            paths[0].parent().unwrap_or(&paths[0])
-        } else {
-            Path::new("")
-        };
+        let base_path = Self::base_path(paths);
+
+        let mut hard_links = HardLinks::default();
 
         // Add files to tar archive
         for file_path in files_to_archive {
             log::debug!("Adding file: {}", file_path.display());
 
             let metadata = std::fs::symlink_metadata(file_path)?;
-            let file_type = self.classify_file_type(&metadata);
-
-            // Make path relative for tar (tar requires relative paths)
-            let tar_path = if base_path.as_os_str().is_empty() {
-                // No base path - use just the filename to ensure relative path
-                file_path.file_name()
-                    .map(PathBuf::from)
-                    .unwrap_or_else(|| file_path.clone())
-            } else {
-                file_path.strip_prefix(base_path).unwrap_or(file_path).to_path_buf()
-            };
+            let link = hard_links.target(&metadata);
+
+            let tar_path = Self::tar_path(file_path, base_path);
+            let entry = self.append_file(tar_builder, file_path, &tar_path, &metadata, link.as_ref(), None)?;
+
+            hard_links.record(&metadata, &entry);
+            file_entries.push(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Append a file's tar entry and build its index entry
+    ///
+    /// `link` is the earlier entry this file is a hard link to, whose data
+    /// it shares; `blocks` lists the already appended blocks of a
+    /// deduplicated file, which then gets an empty entry of its own.
+    fn append_file<S: EntrySink>(
+        &self,
+        tar_builder: &mut tar::Builder<S>,
+        file_path: &Path,
+        tar_path: &Path,
+        metadata: &std::fs::Metadata,
+        link: Option<&(PathBuf, Option<String>)>,
+        blocks: Option<Vec<String>>,
+    ) -> Result<FileEntry> {
+        let file_type = self.classify_file_type(metadata);
+
+        // Compute checksum for regular files, links sharing their target's
+        let checksum = if let Some((_, checksum)) = link {
+            checksum.clone()
+        } else if metadata.is_file() {
+            let file = File::open(file_path)?;
+            Some(checksum::sha256::compute_checksum(file)?)
+        } else {
+            None
+        };
+        let attributes = xattrs::read(file_path)?;
+        let stores_data = metadata.is_file() && blocks.is_none() && link.is_none();
+        let regions = if stores_data {
+            sparse::data_regions(&File::open(file_path)?, metadata.len())?
+        } else {
+            None
+        };
+        let device = Self::device_numbers(metadata, &file_type);
+        let (path, path_bytes) = format::path_fields(tar_path);
+        let (target, target_bytes) = Self::link_target(file_path, metadata, link)?;
+
+        // Raw names, attributes and block lists go into one set of PAX
+        // records ahead of the entry
+        let mut pax_records = paths::pax_records(path_bytes.as_deref(), target_bytes.as_deref());
+        pax_records.extend(attributes.as_ref().map(xattrs::pax_records).unwrap_or_default());
+        if let Some(ref blocks) = blocks {
+            pax_records.push((dedup::PAX_BLOCKS.to_string(), blocks.join(",").into_bytes()));
+            pax_records.push((dedup::PAX_SIZE.to_string(), metadata.len().to_string().into_bytes()));
+        }
 
-            // Add to tar
-            if metadata.is_file() {
+        let deduplicated = blocks.is_some();
+        let entry = FileEntry {
+            path,
+            path_bytes,
+            size: metadata.len(),
+            checksum,
+            mode: Self::get_file_mode(metadata),
+            mtime: Self::get_mtime(metadata),
+            uid: Self::get_uid(metadata),
+            gid: Self::get_gid(metadata),
+            entry_type: if link.is_some() { FileType::Hardlink } else { file_type.clone() },
+            target,
+            target_bytes,
+            blocks,
+            attributes,
+            sparse: regions.is_some(),
+            stored_size: regions.as_deref().map(sparse::stored_size),
+            device_major: device.map(|d| d.0),
+            device_minor: device.map(|d| d.1),
+            ..Default::default()
+        };
+        let data_len = if stores_data { entry.stored_size.unwrap_or(entry.size) } else { 0 };
+
+        Self::append_entry(tar_builder, entry, data_len, |tar_builder| {
+            Self::append_pax_records(tar_builder, &pax_records)?;
+
+            if let Some((target, _)) = link {
+                // The data was stored with the first link to the file
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(metadata);
+                header.set_entry_type(tar::EntryType::Link);
+                header.set_size(0);
+                tar_builder.append_link(&mut header, tar_path, target)?;
+            } else if deduplicated {
+                // An empty entry whose PAX records list its blocks
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(metadata);
+                header.set_size(0);
+                tar_builder.append_data(&mut header, tar_path, io::empty())?;
+            } else if let Some(ref regions) = regions {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(metadata);
+                let mut file = File::open(file_path)?;
+                sparse::append(tar_builder, &mut header, tar_path, &mut file, metadata.len(), regions)?;
+            } else if metadata.is_file() {
                 let mut file = File::open(file_path)?;
-                tar_builder.append_file(&tar_path, &mut file)?;
+                tar_builder.append_file(tar_path, &mut file)?;
             } else if metadata.is_dir() {
-                tar_builder.append_dir(&tar_path, file_path)?;
+                tar_builder.append_dir(tar_path, file_path)?;
             } else if metadata.is_symlink() {
-                // For symlinks, we need to use append_path with proper header
                 let mut header = tar::Header::new_gnu();
-                header.set_metadata(&metadata);
+                header.set_metadata(metadata);
                 header.set_entry_type(tar::EntryType::Symlink);
                 let target = std::fs::read_link(file_path)?;
                 header.set_link_name(&target)?;
                 header.set_size(0);
                 header.set_cksum();
-                tar_builder.append_data(&mut header, &tar_path, std::io::empty())?;
+                tar_builder.append_data(&mut header, tar_path, io::empty())?;
+            } else if special::entry_type(&file_type).is_some() {
+                special::append(tar_builder, metadata, tar_path, &file_type, device)?;
             }
+            Ok(())
+        })
+    }
 
-            // Compute checksum for regular files
-            let checksum = if metadata.is_file() {
-                let file = File::open(file_path)?;
-                Some(checksum::sha256::compute_checksum(file)?)
-            } else {
-                None
-            };
-
-            // Create file entry for index
-            let entry = FileEntry {
-                path: tar_path.to_string_lossy().to_string(),
-                chunk: chunk_number,
-                offset: 0, // TODO: Track actual offset
-                size: metadata.len(),
-                compressed_size: None,
-                checksum,
-                mode: Self::get_file_mode(&metadata),
-                mtime: Self::get_mtime(&metadata),
-                uid: Self::get_uid(&metadata),
-                gid: Self::get_gid(&metadata),
-                user: None,
-                group: None,
-                entry_type: file_type,
-                target: if metadata.is_symlink() {
-                    Some(std::fs::read_link(file_path)?.to_string_lossy().to_string())
-                } else {
-                    None
-                },
-                spans_chunks: None,
-            };
-
-            file_entries.push(entry);
+    /// Append an entry through `append`, recording where it landed: its
+    /// chunk and offsets, the chunks it spans and the size of its headers
+    ///
+    /// `data_len` is how much data `append` writes after the headers. The
+    /// entry goes into the manifest of the chunk it starts in.
+    fn append_entry<S: EntrySink>(
+        tar_builder: &mut tar::Builder<S>,
+        mut entry: FileEntry,
+        data_len: u64,
+        append: impl FnOnce(&mut tar::Builder<S>) -> Result<()>,
+    ) -> Result<FileEntry> {
+        let (chunk, offset) = tar_builder.get_ref().chunk_position();
+        let stream_offset = tar_builder.get_ref().stream_position();
+        entry.chunk = chunk;
+        entry.offset = offset;
+        entry.stream_offset = Some(stream_offset);
+        tar_builder.get_mut().add_manifest_entry(entry.clone());
+
+        append(tar_builder)?;
+        tar_builder.get_mut().entry_appended()?;
+
+        // The entry may have crossed into later chunks
+        let final_chunk = tar_builder.get_ref().current_chunk_number();
+        if final_chunk != chunk {
+            entry.spans_chunks = Some((chunk..=final_chunk).collect());
         }
+        entry.header_size = Some(Self::header_size(
+            tar_builder.get_ref().stream_position() - stream_offset,
+            data_len,
+        ));
+        Ok(entry)
+    }
 
+    /// Write a PAX header carrying these records for the entry appended next
+    fn append_pax_records<W: Write>(
+        tar_builder: &mut tar::Builder<W>,
+        records: &[(String, Vec<u8>)],
+    ) -> Result<()> {
+        if !records.is_empty() {
+            tar_builder.append_pax_extensions(records.iter().map(|(key, value)| (key.as_str(), value.as_slice())))?;
+        }
         Ok(())
     }
 
+    /// Bytes of headers an appended entry wrote ahead of its data, given
+    /// everything it wrote (headers plus block-padded data)
+    fn header_size(written: u64, data_len: u64) -> u64 {
+        written.saturating_sub(data_len.div_ceil(512) * 512)
+    }
+
+    /// Split a file into content-defined blocks, appending an entry for each
+    /// block not stored yet; returns the file's block list
+    fn append_blocks(
+        tar_builder: &mut tar::Builder<&mut StreamingErasureChunkingWriter>,
+        splitter: &BlockSplitter,
+        file_path: &Path,
+        mtime: DateTime<Utc>,
+        stored: &mut StoredBlocks,
+    ) -> Result<Vec<String>> {
+        let mut blocks = Vec::new();
+        splitter.split(File::open(file_path)?, |block| {
+            let hash = dedup::block_hash(block);
+            if stored.hashes.insert(hash.clone()) {
+                let path = dedup::block_path(&hash);
+                let entry = FileEntry {
+                    path: path.clone(),
+                    size: block.len() as u64,
+                    checksum: Some(format!("sha256:{}", hash)),
+                    mode: 0o644,
+                    mtime,
+                    entry_type: FileType::File,
+                    ..Default::default()
+                };
+                let entry = Self::append_entry(tar_builder, entry, block.len() as u64, |tar_builder| {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_size(block.len() as u64);
+                    header.set_mode(0o644);
+                    header.set_mtime(mtime.timestamp() as u64);
+                    tar_builder.append_data(&mut header, &path, block)?;
+                    Ok(())
+                })?;
+                stored.entries.push(entry);
+            }
+            blocks.push(hash);
+            Ok(())
+        })?;
+        Ok(blocks)
+    }
+
     /// Collect all files to be archived
     fn collect_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
         let mut files = Vec::new();
@@ -417,25 +1151,30 @@ impl ArchiveBuilder {
                 )));
             }
 
-            if path.is_file() {
-                if !self.is_excluded(path) {
-                    files.push(path.clone());
-                }
-            } else if path.is_dir() {
+            if path.is_dir() {
+                // Sorted, so a resumed run sees the files in the same order
                 let walker = WalkDir::new(path)
+                    .sort_by_file_name()
                     .follow_links(self.follow_symlinks)
                     .into_iter()
                     .filter_entry(|e| !self.is_excluded(e.path()));
 
                 for entry in walker {
                     let entry = entry.map_err(|e| {
-                        EctarError::Io(io::Error::new(io::ErrorKind::Other, e.to_string()))
+                        EctarError::Io(io::Error::other(e.to_string()))
                     })?;
 
-                    if !self.is_excluded(entry.path()) {
+                    if Self::is_socket(&entry.file_type()) {
+                        log::warn!("Skipping socket {}", entry.path().display());
+                    } else if !self.is_excluded(entry.path()) {
                         files.push(entry.path().to_path_buf());
                     }
                 }
+            } else if Self::is_socket(&std::fs::metadata(path)?.file_type()) {
+                log::warn!("Skipping socket {}", path.display());
+            } else if !self.is_excluded(path) {
+                // A file, or a device node or FIFO
+                files.push(path.clone());
             }
         }
 
@@ -457,8 +1196,6 @@ impl ArchiveBuilder {
 
     /// Classify file type from metadata
     fn classify_file_type(&self, metadata: &std::fs::Metadata) -> FileType {
-        use std::os::unix::fs::FileTypeExt;
-
         let file_type = metadata.file_type();
 
         if file_type.is_file() {
@@ -467,11 +1204,49 @@ impl ArchiveBuilder {
             FileType::Directory
         } else if file_type.is_symlink() {
             FileType::Symlink
+        } else {
+            Self::classify_special(&file_type)
+        }
+    }
+
+    /// Classify a file that is not a regular file, directory or symlink
+    #[cfg(unix)]
+    fn classify_special(file_type: &std::fs::FileType) -> FileType {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_char_device() {
+            FileType::CharDevice
+        } else if file_type.is_block_device() {
+            FileType::BlockDevice
+        } else if file_type.is_fifo() {
+            FileType::Fifo
         } else {
             FileType::Other
         }
     }
 
+    #[cfg(not(unix))]
+    fn classify_special(_file_type: &std::fs::FileType) -> FileType {
+        FileType::Other
+    }
+
+    /// Whether a file is a socket, which cannot be archived
+    #[cfg(unix)]
+    fn is_socket(file_type: &std::fs::FileType) -> bool {
+        use std::os::unix::fs::FileTypeExt;
+        file_type.is_socket()
+    }
+
+    #[cfg(not(unix))]
+    fn is_socket(_file_type: &std::fs::FileType) -> bool {
+        false
+    }
+
+    /// Device numbers of a device node
+    fn device_numbers(metadata: &std::fs::Metadata, file_type: &FileType) -> Option<(u32, u32)> {
+        matches!(file_type, FileType::CharDevice | FileType::BlockDevice).then(|| special::device_numbers(metadata))
+    }
+
     /// Get file mode from metadata
     #[cfg(unix)]
     fn get_file_mode(metadata: &std::fs::Metadata) -> u32 {
@@ -496,6 +1271,11 @@ impl ArchiveBuilder {
             .unwrap_or_else(Utc::now)
     }
 
+    /// Modification time at full precision, to tell whether a file changed
+    fn modified(metadata: &std::fs::Metadata) -> Option<DateTime<Utc>> {
+        metadata.modified().ok().map(DateTime::from)
+    }
+
     /// Get UID from metadata
     #[cfg(unix)]
     fn get_uid(metadata: &std::fs::Metadata) -> Option<u64> {
@@ -521,7 +1301,12 @@ impl ArchiveBuilder {
     }
 
     /// Create the index file (for non-chunked archives)
-    fn create_index(&self, file_entries: &[FileEntry], chunks_info: &[crate::chunking::ChunkInfo]) -> Result<()> {
+    fn create_index(
+        &self,
+        file_entries: &[FileEntry],
+        chunks_info: &[crate::chunking::ChunkInfo],
+        increment: Option<&Increment>,
+    ) -> Result<()> {
         // Convert chunking::ChunkInfo to index::format::ChunkInfo
         let chunks = chunks_info
             .iter()
@@ -531,64 +1316,265 @@ impl ArchiveBuilder {
                 uncompressed_size: c.uncompressed_size,
                 shard_size: 0, // Non-chunked archives don't use erasure coding
                 checksum: String::new(),
+                uncompressed_checksum: None,
+                shard_checksums: Vec::new(),
             })
             .collect();
 
-        self.write_index(file_entries, chunks)
+        self.write_index(file_entries, &[], chunks, increment, None)
     }
 
     /// Create the index file from streaming chunk info (per-chunk shard sizes)
     fn create_index_from_streaming(
         &self,
         file_entries: &[FileEntry],
+        block_entries: &[FileEntry],
         chunks_info: &[crate::chunking::streaming_erasure_chunker::ChunkInfo],
+        increment: Option<&Increment>,
+        encryptor: Option<&Encryptor>,
     ) -> Result<()> {
         // Convert streaming ChunkInfo to index::format::ChunkInfo with per-chunk shard sizes
-        let chunks = chunks_info
-            .iter()
-            .map(|c| ChunkInfo {
-                chunk_number: c.chunk_number,
-                compressed_size: c.compressed_size,
-                uncompressed_size: c.uncompressed_size,
-                shard_size: c.shard_size,
-                checksum: String::new(), // TODO: Compute chunk checksum
-            })
-            .collect();
+        let chunks = chunks_info.iter().map(Self::index_chunk_info).collect();
+
+        self.write_index(file_entries, block_entries, chunks, increment, encryptor)
+    }
+
+    /// Index record of a chunk written by the streaming writer
+    fn index_chunk_info(c: &StreamingChunkInfo) -> ChunkInfo {
+        ChunkInfo {
+            chunk_number: c.chunk_number,
+            compressed_size: c.compressed_size,
+            uncompressed_size: c.uncompressed_size,
+            shard_size: c.shard_size,
+            checksum: c.checksum.clone(),
+            uncompressed_checksum: Some(c.uncompressed_checksum.clone()),
+            shard_checksums: c.shard_checksums.clone(),
+        }
+    }
+
+    /// Streaming writer record of a chunk from the index or journal
+    fn streaming_chunk_info(c: &ChunkInfo) -> StreamingChunkInfo {
+        StreamingChunkInfo {
+            chunk_number: c.chunk_number,
+            compressed_size: c.compressed_size,
+            uncompressed_size: c.uncompressed_size,
+            shard_size: c.shard_size,
+            checksum: c.checksum.clone(),
+            uncompressed_checksum: c.uncompressed_checksum.clone().unwrap_or_default(),
+            shard_checksums: c.shard_checksums.clone(),
+        }
+    }
+
+    /// Read the journal of an interrupted run and check it can be continued
+    ///
+    /// Returns `None` when none of its chunks survived intact, so the
+    /// archive is written from the start.
+    fn load_journal(
+        output_base: &str,
+        header: &JournalHeader,
+        files_to_archive: &[PathBuf],
+        base_path: &Path,
+    ) -> Result<Option<Resumed>> {
+        let mut state = JournalState::load(output_base)?;
+        let key_check = |h: &JournalHeader| h.parameters.encryption.as_ref().map(|e| e.key_check.clone());
+        if key_check(&state.header).is_some() && key_check(&state.header) != key_check(header) {
+            return Err(EctarError::InvalidParameters(
+                "Cannot resume: the key differs from the interrupted run's".to_string(),
+            ));
+        }
+        if state.header != *header {
+            return Err(EctarError::InvalidParameters(
+                "Cannot resume: the interrupted run used different paths or settings".to_string(),
+            ));
+        }
+
+        let chunks = state.keep_intact_chunks(output_base);
+        let point = match state.resume_point() {
+            Some(point) => point,
+            None => {
+                log::warn!("No completed chunks to resume from, starting over");
+                return Ok(None);
+            }
+        };
+
+        let files = state.files();
+        let recorded = files.iter().take(point.files);
+        if files_to_archive.len() < point.files
+            || recorded
+                .zip(files_to_archive)
+                .any(|(entry, path)| entry.archive_path() != Self::tar_path(path, base_path))
+        {
+            return Err(EctarError::InvalidParameters(
+                "Cannot resume: the files to archive changed since the interrupted run".to_string(),
+            ));
+        }
+
+        // The file crossing the resume point is written again on top of the
+        // part the chunk holds, which has to be of the same file
+        if let Some(path) = files_to_archive.get(point.files) {
+            let changed = match std::fs::symlink_metadata(path) {
+                Ok(metadata) => {
+                    point.size.is_some_and(|size| size != metadata.len())
+                        || point.mtime.is_some_and(|mtime| Some(mtime) != Self::modified(&metadata))
+                }
+                Err(_) => true,
+            };
+            if changed {
+                return Err(EctarError::InvalidParameters(format!(
+                    "Cannot resume: {} changed since the interrupted run",
+                    path.display()
+                )));
+            }
+        }
+
+        log::info!(
+            "Resuming after {} chunks, at file {} of {}",
+            chunks,
+            point.files + 1,
+            files_to_archive.len()
+        );
+        Ok(Some(Resumed {
+            chunks: state.chunks(),
+            records: state.records.clone(),
+            point,
+            files,
+            blocks: state.blocks(),
+        }))
+    }
+
+    /// The encryptor for a new archive, or for the interrupted run being
+    /// resumed, whose chunks were encrypted under the salt in its journal
+    fn encryptor(&self) -> Result<Option<Encryptor>> {
+        let interrupted = if self.resume {
+            // A missing journal is reported when it is loaded for resuming
+            JournalState::load(&self.output_base)
+                .ok()
+                .and_then(|state| state.header.parameters.encryption)
+        } else {
+            None
+        };
+
+        let secret = match self.encryption {
+            Some(Secret::Identity(ref identity)) if self.recipients.is_empty() => {
+                return self.recipients_encryptor(&[identity.public_key()], interrupted).map(Some);
+            }
+            _ if !self.recipients.is_empty() => {
+                return self.recipients_encryptor(&self.recipients, interrupted).map(Some);
+            }
+            Some(ref secret) => secret,
+            None => return Ok(None),
+        };
+
+        let derivation = match interrupted {
+            Some(encryption) => encryption.key,
+            None => KeyDerivation::generate(secret, self.kdf_params),
+        };
+
+        let key = secret.derive(&derivation)?;
+        Ok(Some(Encryptor::new(key, derivation)))
+    }
+
+    /// The encryptor for an archive encrypted to recipients
+    ///
+    /// Resuming needs the interrupted run's data key, which only one of its
+    /// recipients' identities can unwrap.
+    fn recipients_encryptor(
+        &self,
+        public_keys: &[PublicKey],
+        interrupted: Option<EncryptionParameters>,
+    ) -> Result<Encryptor> {
+        let recipients = match interrupted.as_ref().and_then(Recipients::from_parameters) {
+            Some(recipients) => recipients,
+            None => {
+                let key = DataKey::generate();
+                return Ok(Encryptor::with_recipients(key.clone(), Recipients::new(&key, public_keys)?));
+            }
+        };
+
+        if recipients.public_keys() != public_keys {
+            return Err(EctarError::InvalidParameters(
+                "Cannot resume: the recipients differ from the interrupted run's".to_string(),
+            ));
+        }
+        let key = match self.encryption {
+            Some(Secret::Identity(ref identity)) => recipients.unwrap(identity)?,
+            _ => {
+                return Err(EctarError::Decryption(
+                    "Resuming an archive encrypted to recipients needs the identity of one of them".to_string(),
+                ))
+            }
+        };
+        Ok(Encryptor::with_recipients(key, recipients))
+    }
 
-        self.write_index(file_entries, chunks)
+    /// Parameters recorded in the index
+    fn parameters(&self, encryptor: Option<&Encryptor>) -> ArchiveParameters {
+        ArchiveParameters {
+            data_shards: self.data_shards,
+            parity_shards: self.parity_shards,
+            chunk_size: self.chunk_size,
+            compression_level: self.compression_level,
+            shard_trailers: self.shard_trailers,
+            stripe_size: self.stripe_size,
+            encryption: encryptor.map(|encryptor| EncryptionParameters {
+                cipher: CIPHER.to_string(),
+                key: encryptor.derivation().clone(),
+                key_check: encryptor.key().check_value(),
+                index_encrypted: self.encrypt_index,
+                recipients: encryptor
+                    .recipients()
+                    .map(|recipients| recipients.recipients.clone())
+                    .unwrap_or_default(),
+            }),
+        }
     }
 
     /// Write the index file
     fn write_index(
         &self,
         file_entries: &[FileEntry],
+        block_entries: &[FileEntry],
         chunks: Vec<ChunkInfo>,
+        increment: Option<&Increment>,
+        encryptor: Option<&Encryptor>,
     ) -> Result<()> {
+        // Deleted paths that are not valid UTF-8 are kept as raw bytes
+        let deleted = increment.map(|i| i.deleted.as_slice()).unwrap_or_default();
 
         let index = ArchiveIndex {
             version: "1.0".to_string(),
             created: Utc::now(),
             tool_version: env!("CARGO_PKG_VERSION").to_string(),
             archive_name: self.output_base.clone(),
-            parameters: ArchiveParameters {
-                data_shards: self.data_shards,
-                parity_shards: self.parity_shards,
-                chunk_size: self.chunk_size,
-                compression_level: self.compression_level,
-            },
+            last_repaired: None,
+            parameters: self.parameters(encryptor),
+            parent: increment.map(|i| i.parent.clone()),
+            deleted: deleted.iter().filter_map(|p| p.to_str().map(str::to_string)).collect(),
+            deleted_bytes: deleted.iter().filter_map(|p| format::path_fields(p).1).collect(),
             chunks,
             files: file_entries.to_vec(),
+            blocks: block_entries.to_vec(),
         };
 
-        // Serialize to JSON
-        let json = serde_json::to_string_pretty(&index)?;
-
-        // Write compressed index
-        let index_path = format!("{}.index.zst", self.output_base);
-        let index_file = File::create(&index_path)?;
-        compression::compress(json.as_bytes(), index_file, 19)?;
-
-        log::info!("Created index file: {}", index_path);
+        // Write compressed index and its erasure-coded index shards
+        let index_path = crate::index::builder::write_index_with(
+            &index,
+            &self.output_base,
+            self.durability,
+            encryptor.filter(|_| self.encrypt_index),
+        )?;
+
+        log::info!("Created index file: {}", index_path.display());
+
+        if let Some(ref key) = self.sign_key {
+            let signature_path = IndexSignature::sign(&index, key)?.write(
+                &self.output_base,
+                self.data_shards,
+                self.parity_shards,
+                self.durability,
+            )?;
+            log::info!("Signed index with {}: {}", key.verifying_key(), signature_path.display());
+        }
 
         Ok(())
     }
@@ -599,6 +1585,92 @@ pub struct ArchiveMetadata {
     pub total_size: u64,
     pub compressed_size: u64,
     pub chunks: usize,
+    /// Entries left out of an incremental archive as unchanged
+    pub files_unchanged: usize,
+    /// Entries an incremental archive records as deleted
+    pub files_deleted: usize,
+}
+
+/// Tracks how many bytes have been written, i.e. the tar stream position
+struct CountingWriter<W: Write> {
+    inner: W,
+    position: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, position: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output entries are appended to, telling where each one lands
+trait EntrySink: Write {
+    /// Chunk the next byte goes into, and its offset within that chunk
+    fn chunk_position(&self) -> (usize, u64);
+
+    /// Bytes written so far, i.e. the tar stream position
+    fn stream_position(&self) -> u64;
+
+    /// Chunk the last byte went into
+    fn current_chunk_number(&self) -> usize;
+
+    /// Record an entry in the manifest of the chunk it starts in
+    fn add_manifest_entry(&mut self, _entry: FileEntry) {}
+
+    /// Called once an entry is completely written
+    fn entry_appended(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl EntrySink for &mut StreamingErasureChunkingWriter {
+    fn chunk_position(&self) -> (usize, u64) {
+        StreamingErasureChunkingWriter::chunk_position(self)
+    }
+
+    fn stream_position(&self) -> u64 {
+        StreamingErasureChunkingWriter::stream_position(self)
+    }
+
+    fn current_chunk_number(&self) -> usize {
+        StreamingErasureChunkingWriter::current_chunk_number(self)
+    }
+
+    fn add_manifest_entry(&mut self, entry: FileEntry) {
+        StreamingErasureChunkingWriter::add_manifest_entry(self, entry)
+    }
+
+    fn entry_appended(&mut self) -> io::Result<()> {
+        // Flush to ensure we get accurate chunk tracking
+        self.flush()
+    }
+}
+
+/// A single archive is one chunk holding the whole tar stream
+impl<W: Write> EntrySink for CountingWriter<W> {
+    fn chunk_position(&self) -> (usize, u64) {
+        (1, self.position)
+    }
+
+    fn stream_position(&self) -> u64 {
+        self.position
+    }
+
+    fn current_chunk_number(&self) -> usize {
+        1
+    }
 }
 
 #[cfg(test)]
@@ -658,6 +1730,13 @@ mod tests {
         assert!(builder.no_compression);
     }
 
+    #[test]
+    fn test_builder_shard_trailers() {
+        let builder = ArchiveBuilder::new("test".to_string())
+            .shard_trailers(true);
+        assert!(builder.shard_trailers);
+    }
+
     #[test]
     fn test_builder_no_index() {
         let builder = ArchiveBuilder::new("test".to_string())
@@ -728,6 +1807,27 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_validate_threads_zero() {
+        let builder = ArchiveBuilder::new("test".to_string())
+            .threads(0);
+        assert!(builder.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_stripe_size() {
+        let striped = |size| {
+            ArchiveBuilder::new("test".to_string())
+                .chunk_size(Some(1024 * 1024))
+                .stripe_size(Some(size))
+        };
+        assert!(striped(64 * 1024).validate().is_ok());
+        assert!(striped(8).validate().is_err());
+        assert!(striped(u32::MAX as u64 + 1).validate().is_err());
+        assert!(striped(1024).no_compression(true).validate().is_err());
+        assert!(striped(1024).chunk_size(None).validate().is_err());
+    }
+
     #[test]
     fn test_create_nonexistent_path() {
         let temp_dir = TempDir::new().unwrap();
@@ -949,6 +2049,31 @@ mod tests {
         assert_eq!(file_type, FileType::Directory);
     }
 
+    #[test]
+    fn test_classify_special_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let builder = ArchiveBuilder::new("test".to_string());
+
+        let null = std::fs::symlink_metadata("/dev/null").unwrap();
+        assert_eq!(builder.classify_file_type(&null), FileType::CharDevice);
+        assert_eq!(ArchiveBuilder::device_numbers(&null, &FileType::CharDevice), Some((1, 3)));
+
+        let fifo = temp_dir.path().join("fifo");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        // SAFETY: c_path is a valid NUL-terminated path
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+        let metadata = std::fs::symlink_metadata(&fifo).unwrap();
+        assert_eq!(builder.classify_file_type(&metadata), FileType::Fifo);
+        assert_eq!(ArchiveBuilder::device_numbers(&metadata, &FileType::Fifo), None);
+
+        let socket = temp_dir.path().join("socket");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        assert!(ArchiveBuilder::is_socket(&std::fs::symlink_metadata(&socket).unwrap().file_type()));
+        let files = builder.collect_files(&[temp_dir.path().to_path_buf()]).unwrap();
+        assert!(files.contains(&fifo));
+        assert!(!files.contains(&socket));
+    }
+
     #[test]
     fn test_is_excluded() {
         let builder = ArchiveBuilder::new("test".to_string())
@@ -1025,6 +2150,710 @@ mod tests {
         assert_eq!(file_type, FileType::Symlink);
     }
 
+    #[test]
+    fn test_chunks_embed_manifest() {
+        use crate::erasure::decoder;
+        use crate::index::manifest::ChunkManifest;
+        use crate::io::shard_reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.txt"), b"alpha").unwrap();
+        fs::write(test_dir.join("b.txt"), b"bravo").unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(1024 * 1024))
+            .create(&[test_dir])
+            .unwrap();
+
+        let mut shards = shard_reader::discover_shards(&format!("{}.c*.s*", archive_base)).unwrap();
+        let chunk_path = temp_dir.path().join("chunk001");
+        decoder::decode_chunk(shards.remove(&1).unwrap(), 3, 2, &chunk_path, None).unwrap();
+
+        let manifest = ChunkManifest::read_from_file(&chunk_path).unwrap().unwrap();
+        assert_eq!(manifest.chunk_number, 1);
+
+        let file = manifest.files.iter().find(|f| f.path == "data/a.txt").unwrap();
+        assert_eq!(file.size, 5);
+        assert_eq!(file.checksum, Some(checksum::sha256::checksum_bytes(b"alpha")));
+        assert!(manifest.files.iter().any(|f| f.path == "data/b.txt"));
+        assert!(manifest.files.iter().any(|f| f.path == "data"));
+    }
+
+    #[test]
+    fn test_chunked_archive_records_tar_offsets() {
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.txt"), vec![1u8; 3000]).unwrap();
+        fs::write(test_dir.join("b.txt"), b"bravo").unwrap();
+        fs::write(test_dir.join(format!("{}.txt", "c".repeat(120))), b"long name").unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(1024))
+            .create(&[test_dir])
+            .unwrap();
+
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let mut next_start = 0;
+        for entry in &index.files {
+            let stream_offset = entry.stream_offset.unwrap();
+            assert_eq!(stream_offset, next_start, "{}", entry.path);
+            assert_eq!(stream_offset, (entry.chunk as u64 - 1) * 1024 + entry.offset);
+            assert!(entry.offset < 1024);
+
+            let data_len = if entry.entry_type == FileType::File { entry.size } else { 0 };
+            next_start = stream_offset + entry.header_size.unwrap() + data_len.div_ceil(512) * 512;
+        }
+
+        let short = index.files.iter().find(|f| f.path == "data/b.txt").unwrap();
+        assert_eq!(short.header_size, Some(512));
+        // GNU long-name header plus its name block come first
+        let long = index.files.iter().find(|f| f.path.ends_with("ccc.txt")).unwrap();
+        assert_eq!(long.header_size, Some(3 * 512));
+    }
+
+    #[test]
+    fn test_validate_incremental_needs_index() {
+        let builder = ArchiveBuilder::new("test".to_string())
+            .incremental_from(Some(PathBuf::from("full.index.zst")))
+            .no_index(true);
+        assert!(builder.validate().is_err());
+    }
+
+    #[test]
+    fn test_incremental_stores_only_changes() {
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("same.txt"), b"unchanged").unwrap();
+        fs::write(test_dir.join("edit.txt"), b"before").unwrap();
+        fs::write(test_dir.join("gone.txt"), b"deleted later").unwrap();
+        let create = |name: &str, parent: Option<PathBuf>| {
+            let base = temp_dir.path().join(name).to_string_lossy().to_string();
+            let metadata = ArchiveBuilder::new(base.clone())
+                .data_shards(2)
+                .parity_shards(1)
+                .chunk_size(Some(4096))
+                .incremental_from(parent)
+                .create(std::slice::from_ref(&test_dir))
+                .unwrap();
+            let index_path = PathBuf::from(format!("{}.index.zst", base));
+            (metadata, reader::read_index(&index_path).unwrap(), index_path)
+        };
+
+        let (_, full, full_path) = create("full", None);
+        assert!(full.parent.is_none());
+
+        fs::write(test_dir.join("edit.txt"), b"after the edit").unwrap();
+        fs::remove_file(test_dir.join("gone.txt")).unwrap();
+        fs::write(test_dir.join("new.txt"), b"added").unwrap();
+        let (metadata, inc, _) = create("inc", Some(full_path));
+
+        let parent = inc.parent.unwrap();
+        assert_eq!(parent.index, "full.index.zst");
+        assert_eq!(parent.created, full.created);
+        assert_eq!(inc.deleted, vec!["data/gone.txt"]);
+        let stored: Vec<&str> = inc.files.iter().map(|f| f.path.as_str()).collect();
+        assert!(stored.contains(&"data/edit.txt"));
+        assert!(stored.contains(&"data/new.txt"));
+        assert!(!stored.contains(&"data/same.txt"));
+        assert_eq!(metadata.files_deleted, 1);
+        assert!(metadata.files_unchanged >= 1);
+    }
+
+    #[test]
+    fn test_incremental_non_utf8_names() {
+        use crate::archive::extract::ArchiveExtractor;
+        use crate::index::reader;
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let kept = OsStr::from_bytes(b"\xe9t\xe9.txt");
+        let gone = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(test_dir.join(kept), b"kept").unwrap();
+        fs::write(test_dir.join(gone), b"deleted later").unwrap();
+        // Reads the same as `gone` once made lossy
+        fs::write(test_dir.join("caf\u{fffd}.txt"), b"look-alike").unwrap();
+        let create = |name: &str, parent: Option<PathBuf>| {
+            let base = temp_dir.path().join(name).to_string_lossy().to_string();
+            ArchiveBuilder::new(base.clone())
+                .data_shards(2)
+                .parity_shards(1)
+                .chunk_size(Some(4096))
+                .incremental_from(parent)
+                .create(std::slice::from_ref(&test_dir))
+                .unwrap();
+            let index_path = PathBuf::from(format!("{}.index.zst", base));
+            (reader::read_index(&index_path).unwrap(), base)
+        };
+
+        let (full, full_base) = create("full", None);
+        assert_eq!(full.files.iter().filter(|f| f.path_bytes.is_some()).count(), 2);
+
+        fs::remove_file(test_dir.join(gone)).unwrap();
+        let (inc, inc_base) = create("inc", Some(PathBuf::from(format!("{}.index.zst", full_base))));
+        assert!(inc.deleted.is_empty());
+        assert_eq!(inc.deleted_bytes, vec![b"data/caf\xe9.txt".to_vec()]);
+        assert!(inc.files.iter().all(|f| f.path_bytes.is_none()));
+
+        let extract_dir = temp_dir.path().join("out");
+        ArchiveExtractor::new(format!("{}.c*.s*", inc_base), Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        let out = extract_dir.join("data");
+        assert_eq!(fs::read(out.join(kept)).unwrap(), b"kept");
+        assert_eq!(fs::read(out.join("caf\u{fffd}.txt")).unwrap(), b"look-alike");
+        assert!(!out.join(gone).exists());
+    }
+
+    #[test]
+    fn test_incremental_compare_checksums() {
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let file_path = test_dir.join("file.txt");
+        fs::write(&file_path, b"original").unwrap();
+
+        let full_base = temp_dir.path().join("full").to_string_lossy().to_string();
+        ArchiveBuilder::new(full_base.clone())
+            .chunk_size(Some(4096))
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        // Same size and mtime, different content
+        let mtime = fs::metadata(&file_path).unwrap().modified().unwrap();
+        fs::write(&file_path, b"modified").unwrap();
+        File::options().write(true).open(&file_path).unwrap().set_modified(mtime).unwrap();
+
+        let stored = |name: &str, compare: bool| {
+            let base = temp_dir.path().join(name).to_string_lossy().to_string();
+            ArchiveBuilder::new(base.clone())
+                .chunk_size(Some(4096))
+                .incremental_from(Some(PathBuf::from(format!("{}.index.zst", full_base))))
+                .compare_checksums(compare)
+                .create(std::slice::from_ref(&test_dir))
+                .unwrap();
+            let index = reader::read_index(Path::new(&format!("{}.index.zst", base))).unwrap();
+            index.files.iter().any(|f| f.path == "data/file.txt")
+        };
+
+        assert!(!stored("quick", false));
+        assert!(stored("checked", true));
+    }
+
+    #[test]
+    fn test_validate_dedup() {
+        let builder = ArchiveBuilder::new("test".to_string()).dedup(true);
+        assert!(builder.validate().is_err());
+
+        let builder = ArchiveBuilder::new("test".to_string())
+            .chunk_size(Some(1024 * 1024))
+            .dedup(true)
+            .dedup_block_size(100);
+        assert!(builder.validate().is_err());
+    }
+
+    #[test]
+    fn test_dedup_stores_shared_blocks_once() {
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let mut state = 7u32;
+        let content: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        fs::write(test_dir.join("a.bin"), &content).unwrap();
+        fs::write(test_dir.join("b.bin"), &content).unwrap();
+        fs::write(test_dir.join("empty.txt"), b"").unwrap();
+
+        let base = temp_dir.path().join("dedup").to_string_lossy().to_string();
+        ArchiveBuilder::new(base.clone())
+            .data_shards(2)
+            .parity_shards(1)
+            .chunk_size(Some(64 * 1024))
+            .dedup(true)
+            .dedup_block_size(16 * 1024)
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", base))).unwrap();
+        let file = |name: &str| index.files.iter().find(|f| f.path == name).unwrap();
+        let a = file("data/a.bin");
+        assert_eq!(a.size, content.len() as u64);
+        assert!(a.blocks.as_ref().unwrap().len() > 1);
+        assert_eq!(a.blocks, file("data/b.bin").blocks);
+        assert!(file("data/empty.txt").blocks.is_none());
+
+        // Each block is stored once
+        assert_eq!(index.blocks.len(), a.blocks.as_ref().unwrap().len());
+        let stored: u64 = index.blocks.iter().map(|b| b.size).sum();
+        assert_eq!(stored, content.len() as u64);
+    }
+
+    /// Make a run fail part way, at a file that vanished after the files
+    /// were collected, then resume it; returns the resumed archive's index
+    fn interrupt_and_resume(
+        temp_dir: &TempDir,
+        configure: impl Fn(ArchiveBuilder) -> ArchiveBuilder,
+    ) -> ArchiveIndex {
+        use crate::archive::journal::{Journal, JournalState};
+        use crate::index::reader;
+
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let mut state = 11u32;
+        for i in 0..10 {
+            let content: Vec<u8> = (0..3000)
+                .map(|_| {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (state >> 16) as u8
+                })
+                .collect();
+            fs::write(test_dir.join(format!("file{:02}.bin", i)), content).unwrap();
+        }
+        let paths = std::slice::from_ref(&test_dir);
+        let builder = |name: &str| {
+            let base = temp_dir.path().join(name).to_string_lossy().to_string();
+            configure(ArchiveBuilder::new(base).data_shards(2).parity_shards(1).chunk_size(Some(4096)))
+        };
+
+        let interrupted = builder("resumed");
+        let mut files = interrupted.collect_files(paths).unwrap();
+        files.insert(8, temp_dir.path().join("vanished"));
+        assert!(interrupted.create_chunked(paths, &files, 4096, None).is_err());
+        let base = interrupted.output_base.clone();
+        assert!(!JournalState::load(&base).unwrap().records.is_empty());
+
+        // Completed chunks are not written again
+        let first_shard = crate::erasure::encoder::format_shard_path(&base, 1, 0);
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options().write(true).open(&first_shard).unwrap().set_modified(old).unwrap();
+
+        builder("resumed").resume(true).create(paths).unwrap();
+        assert!(!Journal::path(&base).exists());
+        assert_eq!(fs::metadata(&first_shard).unwrap().modified().unwrap(), old);
+        let resumed = reader::read_index(Path::new(&format!("{}.index.zst", base))).unwrap();
+
+        // Byte for byte what an uninterrupted run writes
+        builder("fresh").create(paths).unwrap();
+        let fresh_base = temp_dir.path().join("fresh").to_string_lossy().to_string();
+        let fresh = reader::read_index(Path::new(&format!("{}.index.zst", fresh_base))).unwrap();
+        assert_eq!(resumed.chunks.len(), fresh.chunks.len());
+        for (a, b) in resumed.chunks.iter().zip(&fresh.chunks) {
+            assert_eq!(a.checksum, b.checksum, "chunk {}", a.chunk_number);
+            assert_eq!(a.shard_checksums, b.shard_checksums, "chunk {}", a.chunk_number);
+        }
+        let positions = |index: &ArchiveIndex| {
+            index.files.iter().chain(&index.blocks)
+                .map(|f| (f.path.clone(), f.chunk, f.offset, f.stream_offset, f.spans_chunks.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(positions(&resumed), positions(&fresh));
+        resumed
+    }
+
+    #[test]
+    fn test_resume_interrupted_create() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = interrupt_and_resume(&temp_dir, |builder| builder);
+        assert_eq!(index.files.len(), 11);
+    }
+
+    #[test]
+    fn test_resume_interrupted_dedup_create() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = interrupt_and_resume(&temp_dir, |builder| {
+            builder.dedup(true).dedup_block_size(dedup::MIN_BLOCK_SIZE).threads(3)
+        });
+        assert!(!index.blocks.is_empty());
+    }
+
+    #[test]
+    fn test_resume_needs_matching_journal() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("file.txt"), vec![7u8; 10_000]).unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let paths = std::slice::from_ref(&test_dir);
+
+        let builder = || ArchiveBuilder::new(base.clone()).chunk_size(Some(4096));
+        assert!(matches!(
+            builder().resume(true).create(paths),
+            Err(EctarError::InvalidParameters(_))
+        ));
+
+        let mut files = builder().collect_files(paths).unwrap();
+        files.push(temp_dir.path().join("vanished"));
+        assert!(builder().create_chunked(paths, &files, 4096, None).is_err());
+        assert!(matches!(
+            builder().data_shards(4).resume(true).create(paths),
+            Err(EctarError::InvalidParameters(_))
+        ));
+        builder().resume(true).create(paths).unwrap();
+    }
+
+    #[test]
+    fn test_resume_refuses_changed_crossing_file() {
+        use crate::archive::journal::{Journal, JournalState};
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        for i in 0..6u8 {
+            fs::write(test_dir.join(format!("file{}.bin", i)), vec![i; 5000]).unwrap();
+        }
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let paths = std::slice::from_ref(&test_dir);
+
+        let builder = || ArchiveBuilder::new(base.clone()).data_shards(2).parity_shards(1).chunk_size(Some(4096));
+        let mut files = builder().collect_files(paths).unwrap();
+        files.push(temp_dir.path().join("vanished"));
+        assert!(builder().create_chunked(paths, &files, 4096, None).is_err());
+
+        // Grow the file the last completed chunk ends in
+        let point = JournalState::load(&base).unwrap().resume_point().unwrap();
+        let crossing = &files[point.files];
+        assert_eq!(point.size, Some(5000));
+        fs::OpenOptions::new().append(true).open(crossing).unwrap().write_all(b"grown").unwrap();
+
+        // Refused before anything is written, leaving the journal as it was
+        let journal = fs::read(Journal::path(&base)).unwrap();
+        match builder().resume(true).create(paths) {
+            Err(EctarError::InvalidParameters(message)) => assert!(message.contains("changed"), "{}", message),
+            other => panic!("resumed onto a changed file: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(fs::read(Journal::path(&base)).unwrap(), journal);
+    }
+
+    #[test]
+    fn test_validate_encryption() {
+        let key = || Some(Secret::Key([1u8; 32]));
+        let builder = ArchiveBuilder::new("test".to_string()).encryption(key());
+        assert!(builder.validate().is_err());
+
+        let builder = || {
+            ArchiveBuilder::new("test".to_string())
+                .chunk_size(Some(1024 * 1024))
+                .encryption(key())
+        };
+        assert!(builder().validate().is_ok());
+        assert!(builder().no_compression(true).validate().is_err());
+        assert!(builder().stripe_size(Some(4096)).validate().is_err());
+
+        let builder = ArchiveBuilder::new("test".to_string())
+            .chunk_size(Some(1024 * 1024))
+            .encrypt_index(true);
+        assert!(builder.validate().is_err());
+    }
+
+    #[test]
+    fn test_encrypted_archive_hides_contents() {
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let marker = b"plainly visible text ".repeat(200);
+        fs::write(test_dir.join("secret-name.txt"), &marker).unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let secret = Secret::Passphrase("pass".to_string());
+
+        ArchiveBuilder::new(base.clone())
+            .data_shards(1)
+            .parity_shards(1)
+            .chunk_size(Some(4096))
+            .encryption(Some(secret.clone()))
+            .kdf_params(crate::crypto::TEST_KDF)
+            .encrypt_index(true)
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let mut shards = crate::io::shard_reader::discover_shards(&format!("{}.c*.s*", base)).unwrap();
+        let chunk = crate::erasure::decoder::decode_chunk_bytes(shards.remove(&1).unwrap(), 1, 1, None).unwrap();
+        assert!(crate::crypto::envelope::is_sealed(&chunk));
+        assert!(!chunk.windows(11).any(|w| w == b"secret-name"));
+        let raw = fs::read(format!("{}.index.zst", base)).unwrap();
+        assert!(crate::crypto::envelope::is_sealed(&raw));
+        assert!(!raw.windows(11).any(|w| w == b"secret-name"));
+
+        let index_path = PathBuf::from(format!("{}.index.zst", base));
+        assert!(matches!(reader::read_index(&index_path), Err(EctarError::Decryption(_))));
+        let keys = KeyRing::new(secret);
+        let index = reader::read_index_with_keys(&index_path, Some(&keys)).unwrap();
+        let encryption = index.parameters.encryption.unwrap();
+        assert_eq!(encryption.cipher, CIPHER);
+        assert!(encryption.index_encrypted);
+        assert!(matches!(encryption.key, KeyDerivation::Argon2id { .. }));
+        assert!(index.files.iter().any(|f| f.path == "data/secret-name.txt"));
+    }
+
+    #[test]
+    fn test_incremental_encrypted_like_its_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("file.txt"), b"chain content").unwrap();
+        let key = || Some(Secret::Key([8u8; 32]));
+        let create = |name: &str, secret: Option<Secret>, parent: Option<&str>| {
+            ArchiveBuilder::new(temp_dir.path().join(name).to_string_lossy().to_string())
+                .chunk_size(Some(4096))
+                .encryption(secret)
+                .incremental_from(parent.map(|p| temp_dir.path().join(format!("{}.index.zst", p))))
+                .create(std::slice::from_ref(&test_dir))
+        };
+
+        create("plain", None, None).unwrap();
+        create("sealed", key(), None).unwrap();
+        assert!(matches!(
+            create("inc1", None, Some("sealed")),
+            Err(EctarError::InvalidParameters(_))
+        ));
+        assert!(create("inc2", key(), Some("plain")).is_err());
+        create("inc3", key(), Some("sealed")).unwrap();
+    }
+
+    #[test]
+    fn test_archive_encrypted_to_recipients() {
+        use crate::crypto::Identity;
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let content: Vec<u8> = (0..20_000u32).map(|i| (i * 13 % 251) as u8).collect();
+        fs::write(test_dir.join("shared.bin"), &content).unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let (alice, bob) = (Identity::generate(), Identity::generate());
+
+        ArchiveBuilder::new(base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(8 * 1024))
+            .recipients(vec![alice.public_key(), bob.public_key()])
+            .encrypt_index(true)
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let index_path = PathBuf::from(format!("{}.index.zst", base));
+        let keys = KeyRing::new(Secret::Identity(bob.clone()));
+        let index = reader::read_index_with_keys(&index_path, Some(&keys)).unwrap();
+        let encryption = index.parameters.encryption.unwrap();
+        assert!(matches!(encryption.key, KeyDerivation::X25519 { .. }));
+        assert_eq!(encryption.recipients.len(), 2);
+        let outsider = KeyRing::new(Secret::Identity(Identity::generate()));
+        assert!(matches!(
+            reader::read_index_with_keys(&index_path, Some(&outsider)),
+            Err(EctarError::Decryption(_))
+        ));
+
+        // Either identity restores the archive, even with the index and the
+        // recipients file gone, from the recipients shards alone
+        fs::remove_file(&index_path).unwrap();
+        for shard in reader::find_index_shards(&base) {
+            fs::remove_file(shard).unwrap();
+        }
+        fs::remove_file(format!("{}.recipients", base)).unwrap();
+        for identity in [alice, bob] {
+            let extract_dir = temp_dir.path().join(identity.public_key().to_string());
+            crate::archive::extract::ArchiveExtractor::new(format!("{}.c*.s*", base), Some(extract_dir.clone()))
+                .decryption(Some(Secret::Identity(identity)))
+                .extract()
+                .unwrap();
+            assert_eq!(fs::read(extract_dir.join("data/shared.bin")).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn test_validate_recipients() {
+        let recipients = || vec![crate::crypto::Identity::generate().public_key()];
+        let builder = || ArchiveBuilder::new("test".to_string()).chunk_size(Some(1024 * 1024));
+        assert!(builder().recipients(recipients()).validate().is_ok());
+        assert!(builder().recipients(recipients()).encrypt_index(true).validate().is_ok());
+        assert!(ArchiveBuilder::new("test".to_string())
+            .recipients(recipients())
+            .validate()
+            .is_err());
+        assert!(builder()
+            .recipients(recipients())
+            .encryption(Some(Secret::Key([1u8; 32])))
+            .validate()
+            .is_err());
+        assert!(builder()
+            .recipients(recipients())
+            .encryption(Some(Secret::Identity(crate::crypto::Identity::generate())))
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_resume_encrypted_create() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        for i in 0..6u8 {
+            fs::write(test_dir.join(format!("file{}.bin", i)), vec![i; 6000]).unwrap();
+        }
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let paths = std::slice::from_ref(&test_dir);
+        let builder = |passphrase: &str| {
+            ArchiveBuilder::new(base.clone())
+                .chunk_size(Some(4096))
+                .encryption(Some(Secret::Passphrase(passphrase.to_string())))
+                .kdf_params(crate::crypto::TEST_KDF)
+        };
+
+        let mut files = builder("pass").collect_files(paths).unwrap();
+        files.insert(4, temp_dir.path().join("vanished"));
+        assert!(builder("pass").create_chunked(paths, &files, 4096, None).is_err());
+
+        assert!(matches!(
+            builder("other").resume(true).create(paths),
+            Err(EctarError::InvalidParameters(_))
+        ));
+        builder("pass").resume(true).create(paths).unwrap();
+
+        // Chunks from before and after the interruption share one key
+        let extract_dir = temp_dir.path().join("extract");
+        crate::archive::extract::ArchiveExtractor::new(format!("{}.c*.s*", base), Some(extract_dir.clone()))
+            .decryption(Some(Secret::Passphrase("pass".to_string())))
+            .extract()
+            .unwrap();
+        for i in 0..6u8 {
+            assert_eq!(fs::read(extract_dir.join(format!("data/file{}.bin", i))).unwrap(), vec![i; 6000]);
+        }
+    }
+
+    #[test]
+    fn test_interrupted_create_leaves_no_partial_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("file.txt"), vec![7u8; 10_000]).unwrap();
+        let out_dir = temp_dir.path().join("out");
+        fs::create_dir(&out_dir).unwrap();
+        let base = out_dir.join("archive").to_string_lossy().to_string();
+        let paths = std::slice::from_ref(&test_dir);
+        let names = || -> Vec<String> {
+            fs::read_dir(&out_dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect()
+        };
+
+        let builder = ArchiveBuilder::new(base.clone())
+            .chunk_size(Some(4096))
+            .no_compression(true)
+            .durability(Durability::None);
+        let mut files = builder.collect_files(paths).unwrap();
+        files.push(temp_dir.path().join("vanished"));
+        assert!(builder.create_chunked(paths, &files, 4096, None).is_err());
+        assert!(names().iter().all(|name| !name.starts_with('.')));
+        assert!(!names().contains(&"archive.index.zst".to_string()));
+
+        builder.create(paths).unwrap();
+        ArchiveBuilder::new(base.clone())
+            .durability(Durability::File)
+            .create(paths)
+            .unwrap();
+        let names = names();
+        assert!(names.iter().all(|name| !name.starts_with('.')));
+        assert!(names.contains(&"archive.c001.s00".to_string()));
+        assert!(names.contains(&"archive.tar.zst".to_string()));
+    }
+
+    #[test]
+    fn test_single_archive_records_tar_offsets() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.txt"), vec![1u8; 700]).unwrap();
+        fs::write(test_dir.join("b.txt"), b"bravo").unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .no_compression(true)
+            .create(&[test_dir])
+            .unwrap();
+
+        let index = crate::index::reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let tar_bytes = fs::read(format!("{}.tar.zst", archive_base)).unwrap();
+
+        let entry = index.files.iter().find(|f| f.path == "data/b.txt").unwrap();
+        let offset = entry.stream_offset.unwrap() as usize;
+        assert_eq!(entry.offset as usize, offset);
+        assert_eq!(&tar_bytes[offset..offset + 10], b"data/b.txt");
+
+        let data_start = offset + entry.header_size.unwrap() as usize;
+        assert_eq!(&tar_bytes[data_start..data_start + 5], b"bravo");
+    }
+
+    #[test]
+    fn test_hard_links_stored_once() {
+        use crate::index::reader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.bin"), vec![7u8; 30_000]).unwrap();
+        fs::hard_link(test_dir.join("a.bin"), test_dir.join("b.bin")).unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .no_compression(true)
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let index_path = PathBuf::from(format!("{}.index.zst", archive_base));
+        let index = reader::read_index(&index_path).unwrap();
+        let link = index.files.iter().find(|f| f.path == "data/b.bin").unwrap();
+        assert_eq!(link.entry_type, FileType::Hardlink);
+        assert_eq!(link.target.as_deref(), Some("data/a.bin"));
+
+        // The data is in the tar once, the second name being a link entry
+        let tar_bytes = fs::read(format!("{}.tar.zst", archive_base)).unwrap();
+        assert!(tar_bytes.len() < 2 * 30_000);
+        let mut archive = tar::Archive::new(tar_bytes.as_slice());
+        let link_entry = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .find(|e| e.path().unwrap() == Path::new("data/b.bin"))
+            .unwrap();
+        assert_eq!(link_entry.header().entry_type(), tar::EntryType::Link);
+        assert_eq!(link_entry.link_name().unwrap().unwrap(), Path::new("data/a.bin"));
+
+        // Untouched links count as unchanged in an incremental archive
+        let inc_base = temp_dir.path().join("inc").to_string_lossy().to_string();
+        let metadata = ArchiveBuilder::new(inc_base)
+            .incremental_from(Some(index_path))
+            .create(&[test_dir])
+            .unwrap();
+        assert_eq!(metadata.files_unchanged, 3);
+    }
+
     #[test]
     fn test_get_file_mode() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,378 @@
+use crate::checksum::sha256::ChecksumWriter;
+use crate::error::{EctarError, Result};
+use digest::Digest;
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
+
+/// Directory of the tar stream holding deduplicated blocks, one entry per
+/// unique block named by its SHA-256
+pub const BLOCK_DIR: &str = ".ectar-blocks";
+
+/// PAX key listing, comma-separated and in order, the blocks a
+/// deduplicated file is made of
+pub const PAX_BLOCKS: &str = "ECTAR.blocks";
+
+/// PAX key holding the size of a deduplicated file, whose tar entry is empty
+pub const PAX_SIZE: &str = "ECTAR.size";
+
+pub const DEFAULT_BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Smallest average block size accepted
+pub const MIN_BLOCK_SIZE: u64 = 4096;
+
+/// Largest average block size accepted
+pub const MAX_BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Random values mixed into the rolling hash, one per byte value
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64, so the table (and therefore block boundaries) never changes
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6563_7461_7262_6c6b;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Splits data into blocks at content-defined boundaries
+///
+/// A boundary is placed where a gear rolling hash of the preceding bytes
+/// matches a mask, so inserting or removing bytes only changes the blocks
+/// around the edit and identical content yields identical blocks wherever
+/// it sits in a file. Blocks are between a quarter and four times the
+/// average size.
+pub struct BlockSplitter {
+    min_size: usize,
+    max_size: usize,
+    mask: u64,
+}
+
+impl BlockSplitter {
+    /// Aim for blocks of about `average_size` bytes, rounded up to a power of two
+    pub fn new(average_size: usize) -> Self {
+        let average = average_size.next_power_of_two();
+        Self {
+            min_size: average / 4,
+            max_size: average * 4,
+            mask: average as u64 - 1,
+        }
+    }
+
+    /// Length of the block at the start of `data`, which holds the rest of
+    /// the input or at least `max_size` bytes of it
+    fn cut_point(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+
+        let end = data.len().min(self.max_size);
+        let mut hash = 0u64;
+        for (i, &byte) in data.iter().enumerate().take(end).skip(self.min_size) {
+            hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+            if hash & self.mask == 0 {
+                return i + 1;
+            }
+        }
+        end
+    }
+
+    /// Read `reader` to the end, passing each block to `emit` in order
+    pub fn split<R: Read>(&self, mut reader: R, mut emit: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
+        let mut buffer = vec![0u8; self.max_size];
+        let mut filled = 0;
+
+        loop {
+            while filled < buffer.len() {
+                let n = reader.read(&mut buffer[filled..])?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                return Ok(());
+            }
+
+            let cut = self.cut_point(&buffer[..filled]);
+            emit(&buffer[..cut])?;
+            buffer.copy_within(cut..filled, 0);
+            filled -= cut;
+        }
+    }
+}
+
+/// Name of a block: the hex SHA-256 of its data
+pub fn block_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Path of a block's entry in the tar stream
+pub fn block_path(hash: &str) -> String {
+    format!("{}/{}", BLOCK_DIR, hash)
+}
+
+/// The block an entry path names, if it is a block entry
+pub fn block_hash_of(path: &Path) -> Option<&str> {
+    let mut components = path.components();
+    match (components.next(), components.next(), components.next()) {
+        (Some(Component::Normal(dir)), Some(Component::Normal(name)), None) if dir == BLOCK_DIR => {
+            name.to_str().filter(|n| n.len() == 64 && n.bytes().all(|b| b.is_ascii_hexdigit()))
+        }
+        _ => None,
+    }
+}
+
+/// A file stored as a list of blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupedFile {
+    pub blocks: Vec<String>,
+    pub size: u64,
+}
+
+impl DedupedFile {
+    /// Read the block list from a tar entry's PAX records; `None` for
+    /// entries that carry their data themselves
+    pub fn from_entry<R: Read>(entry: &mut tar::Entry<R>) -> Result<Option<Self>> {
+        let extensions = match entry.pax_extensions()? {
+            Some(extensions) => extensions,
+            None => return Ok(None),
+        };
+
+        let mut blocks = None;
+        let mut size = None;
+        for extension in extensions {
+            let extension = extension?;
+            match extension.key() {
+                Ok(PAX_BLOCKS) => {
+                    let value = extension.value().map_err(|e| EctarError::Tar(e.to_string()))?;
+                    blocks = Some(value.split(',').filter(|h| !h.is_empty()).map(String::from).collect());
+                }
+                Ok(PAX_SIZE) => {
+                    let value = extension.value().map_err(|e| EctarError::Tar(e.to_string()))?;
+                    size = Some(value.parse().map_err(|_| {
+                        EctarError::Tar(format!("Invalid {} record: {}", PAX_SIZE, value))
+                    })?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(blocks.map(|blocks| Self {
+            blocks,
+            size: size.unwrap_or(0),
+        }))
+    }
+}
+
+/// Blocks unpacked so far, kept on disk until the files made of them have
+/// been reassembled
+///
+/// The store is a hidden temporary directory inside the output directory,
+/// so reassembled files can be renamed into place; it is removed when the
+/// store is dropped.
+pub struct BlockStore {
+    parent: PathBuf,
+    dir: Option<TempDir>,
+    /// Blocks of the files being extracted; `None` keeps every block
+    wanted: Option<HashSet<String>>,
+}
+
+impl BlockStore {
+    pub fn new(parent: &Path) -> Self {
+        Self {
+            parent: parent.to_path_buf(),
+            dir: None,
+            wanted: None,
+        }
+    }
+
+    /// Keep only these blocks
+    ///
+    /// A decoded run of chunks can end inside a block no selected file
+    /// uses; such blocks are skipped instead of read.
+    pub fn only(mut self, hashes: HashSet<String>) -> Self {
+        self.wanted = Some(hashes);
+        self
+    }
+
+    /// Whether a block is needed by the files being extracted
+    pub fn wants(&self, hash: &str) -> bool {
+        self.wanted.as_ref().map_or(true, |wanted| wanted.contains(hash))
+    }
+
+    /// Keep a block read from the tar stream, checking it against its name
+    pub fn insert<R: Read>(&mut self, hash: &str, mut data: R) -> Result<()> {
+        let dir = match self.dir {
+            Some(ref dir) => dir,
+            None => {
+                std::fs::create_dir_all(&self.parent)?;
+                self.dir.insert(tempfile::Builder::new().prefix(BLOCK_DIR).tempdir_in(&self.parent)?)
+            }
+        };
+
+        let path = dir.path().join(hash);
+        let mut writer = ChecksumWriter::new(File::create(&path)?);
+        io::copy(&mut data, &mut writer)?;
+        let (_, checksum) = writer.finish();
+
+        if checksum.strip_prefix("sha256:") != Some(hash) {
+            std::fs::remove_file(&path)?;
+            return Err(EctarError::ChecksumMismatch { file: block_path(hash) });
+        }
+        Ok(())
+    }
+
+    /// Fill a file unpacked from an empty entry with its blocks, keeping
+    /// the permissions and mtime it was unpacked with
+    pub fn reassemble(&self, file: &DedupedFile, output_path: &Path) -> Result<()> {
+        if file.blocks.is_empty() {
+            return Ok(());
+        }
+        let dir = self.dir.as_ref().ok_or_else(|| missing_block(&file.blocks[0]))?;
+
+        let mut assembled = tempfile::NamedTempFile::new_in(dir.path())?;
+        for hash in &file.blocks {
+            let mut block = File::open(dir.path().join(hash)).map_err(|_| missing_block(hash))?;
+            io::copy(&mut block, &mut assembled)?;
+        }
+
+        let unpacked = std::fs::metadata(output_path)?;
+        assembled.as_file().set_modified(unpacked.modified()?)?;
+        std::fs::set_permissions(assembled.path(), unpacked.permissions())?;
+        assembled.persist(output_path).map_err(|e| EctarError::Io(e.error))?;
+        Ok(())
+    }
+}
+
+fn missing_block(hash: &str) -> EctarError {
+    EctarError::FileNotFound(block_path(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed.max(1);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn split(splitter: &BlockSplitter, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut blocks = Vec::new();
+        splitter
+            .split(data, |block| {
+                blocks.push(block.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        blocks
+    }
+
+    #[test]
+    fn test_split_reassembles_and_respects_bounds() {
+        let splitter = BlockSplitter::new(4096);
+        let data = noise(200_000, 1);
+        let blocks = split(&splitter, &data);
+
+        assert_eq!(blocks.concat(), data);
+        assert!(blocks.len() > 10);
+        for block in &blocks[..blocks.len() - 1] {
+            assert!(block.len() > 1024 && block.len() <= 4 * 4096);
+        }
+    }
+
+    #[test]
+    fn test_boundaries_survive_insertion() {
+        let splitter = BlockSplitter::new(4096);
+        let data = noise(100_000, 2);
+        let mut shifted = b"inserted at the front".to_vec();
+        shifted.extend_from_slice(&data);
+
+        let original: Vec<String> = split(&splitter, &data).iter().map(|b| block_hash(b)).collect();
+        let moved: Vec<String> = split(&splitter, &shifted).iter().map(|b| block_hash(b)).collect();
+
+        // Only the blocks around the edit differ
+        let shared = moved.iter().filter(|h| original.contains(h)).count();
+        assert!(shared >= original.len() - 2, "{} of {} shared", shared, original.len());
+    }
+
+    #[test]
+    fn test_split_empty_and_small_input() {
+        let splitter = BlockSplitter::new(4096);
+        assert!(split(&splitter, &[]).is_empty());
+        assert_eq!(split(&splitter, b"tiny"), vec![b"tiny".to_vec()]);
+    }
+
+    #[test]
+    fn test_block_hash_of() {
+        let hash = block_hash(b"data");
+        assert_eq!(block_hash_of(Path::new(&block_path(&hash))), Some(hash.as_str()));
+        assert_eq!(block_hash_of(Path::new(".ectar-blocks/short")), None);
+        assert_eq!(block_hash_of(Path::new("data/file.txt")), None);
+        assert_eq!(block_hash_of(Path::new(&format!("x/{}", block_path(&hash)))), None);
+    }
+
+    #[test]
+    fn test_store_reassembles_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut store = BlockStore::new(temp_dir.path());
+        let first = b"first block ".to_vec();
+        let second = b"second block".to_vec();
+        store.insert(&block_hash(&first), &first[..]).unwrap();
+        store.insert(&block_hash(&second), &second[..]).unwrap();
+
+        let output = temp_dir.path().join("file.txt");
+        std::fs::write(&output, b"").unwrap();
+        let file = DedupedFile {
+            blocks: vec![block_hash(&first), block_hash(&second), block_hash(&first)],
+            size: 36,
+        };
+        store.reassemble(&file, &output).unwrap();
+
+        assert_eq!(std::fs::read(&output).unwrap(), b"first block second blockfirst block ");
+    }
+
+    #[test]
+    fn test_store_rejects_corrupt_block() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut store = BlockStore::new(temp_dir.path());
+        let hash = block_hash(b"expected");
+
+        let result = store.insert(&hash, &b"something else"[..]);
+        assert!(matches!(result, Err(EctarError::ChecksumMismatch { .. })));
+
+        std::fs::write(temp_dir.path().join("file"), b"").unwrap();
+        let file = DedupedFile { blocks: vec![hash], size: 8 };
+        assert!(store.reassemble(&file, &temp_dir.path().join("file")).is_err());
+    }
+
+    #[test]
+    fn test_store_is_removed_on_drop() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut store = BlockStore::new(temp_dir.path());
+        store.insert(&block_hash(b"x"), &b"x"[..]).unwrap();
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        drop(store);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+}
@@ -1,21 +1,46 @@
-use crate::compression;
-use crate::erasure::decoder;
+use crate::archive::dedup::{self, BlockStore, DedupedFile};
+use crate::archive::special;
+use crate::archive::tar_header;
+use crate::archive::xattrs;
+use crate::checksum::sha256;
+use crate::chunking::ordered_pool::map_ordered;
+use crate::chunking::{ChunkSender, ChunkStreamReader, StreamChunk};
+use crate::crypto::{self, envelope, Context, KeyRing, Secret};
+use crate::erasure::{decoder, encoder, hunt, ShardData};
 use crate::error::{EctarError, Result};
-use crate::index::format::ArchiveIndex;
+use crate::index::chain;
+use crate::index::format::{ArchiveIndex, ChunkInfo, ExtendedAttributes, FileEntry, FileType};
+use crate::index::manifest::ChunkManifest;
+use crate::index::reader;
 use crate::io::shard_reader;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-use tempfile::TempDir;
+use std::io::{Cursor, Read};
+use std::ops::ControlFlow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
+#[derive(Clone)]
 pub struct ArchiveExtractor {
     shard_pattern: String,
     output_dir: PathBuf,
     verify_checksums: bool,
     partial: bool,
-    file_filters: Vec<String>,
+    file_filters: Vec<OsString>,
     exclude_patterns: Vec<String>,
     strip_components: usize,
+    hunt_corruption: bool,
+    hunt_attempts: usize,
+    threads: usize,
+    follow_chain: bool,
+    restore_attributes: bool,
+    /// Key or passphrase for encrypted archives
+    keys: Option<Arc<KeyRing>>,
+    /// Exact archive paths to extract, when restoring one link of a chain
+    only_paths: Option<HashSet<PathBuf>>,
+    /// Hard links unpacked in place of a target the filters skip, by target
+    link_stand_ins: HashMap<PathBuf, PathBuf>,
 }
 
 impl ArchiveExtractor {
@@ -28,6 +53,14 @@ impl ArchiveExtractor {
             file_filters: Vec::new(),
             exclude_patterns: Vec::new(),
             strip_components: 0,
+            hunt_corruption: false,
+            hunt_attempts: hunt::DEFAULT_MAX_ATTEMPTS,
+            threads: 1,
+            follow_chain: true,
+            restore_attributes: true,
+            keys: None,
+            only_paths: None,
+            link_stand_ins: HashMap::new(),
         }
     }
 
@@ -41,8 +74,10 @@ impl ArchiveExtractor {
         self
     }
 
-    pub fn file_filters(mut self, filters: Vec<String>) -> Self {
-        self.file_filters = filters;
+    /// Paths or globs of the entries to extract; paths match byte for byte,
+    /// so names that are not valid UTF-8 can be given as they are
+    pub fn file_filters<S: Into<OsString>>(mut self, filters: Vec<S>) -> Self {
+        self.file_filters = filters.into_iter().map(Into::into).collect();
         self
     }
 
@@ -56,14 +91,65 @@ impl ArchiveExtractor {
         self
     }
 
+    /// When a chunk fails to decode or validate, reconstruct it from
+    /// different subsets of its shards to get past corrupt ones
+    pub fn hunt_corruption(mut self, hunt: bool) -> Self {
+        self.hunt_corruption = hunt;
+        self
+    }
+
+    /// Give up hunting a chunk's corrupt shards after this many reconstructions
+    pub fn hunt_attempts(mut self, attempts: usize) -> Self {
+        self.hunt_attempts = attempts;
+        self
+    }
+
+    /// Reconstruct up to this many chunks at once
+    ///
+    /// Chunks are still unpacked in order; each extra thread holds one more
+    /// reconstructed chunk in memory.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Restore an incremental archive together with the archives it is
+    /// based on, as of the time it was created (the default); when off,
+    /// only the entries stored in the archive itself are extracted
+    pub fn follow_chain(mut self, follow: bool) -> Self {
+        self.follow_chain = follow;
+        self
+    }
+
+    /// Set the extended attributes, ACLs and SELinux labels recorded for
+    /// each entry (the default); failures to set them are only warned about
+    pub fn restore_attributes(mut self, restore: bool) -> Self {
+        self.restore_attributes = restore;
+        self
+    }
+
+    /// Key file, passphrase or identity to decrypt encrypted archives with
+    pub fn decryption(mut self, secret: Option<Secret>) -> Self {
+        self.keys = secret.map(|secret| Arc::new(KeyRing::new(secret)));
+        self
+    }
+
     pub fn extract(&self) -> Result<ExtractionMetadata> {
         log::info!("Extracting archive from pattern: {}", self.shard_pattern);
 
+        // Archives encrypted to recipients are readable without their index
+        if let Some(ref keys) = self.keys {
+            if let Err(e) = keys.load_recipients(&shard_reader::archive_base(&self.shard_pattern)) {
+                log::warn!("Could not read the archive's recipients: {}", e);
+            }
+        }
+
         // Try to read index file (optional)
-        let index_opt = match shard_reader::find_index_file(&self.shard_pattern) {
-            Some(index_path) => {
+        let index_path = shard_reader::find_index_file(&self.shard_pattern);
+        let index_opt = match index_path {
+            Some(ref index_path) => {
                 log::info!("Found index file: {}", index_path.display());
-                match self.read_index(&index_path) {
+                match self.read_index(index_path) {
                     Ok(index) => {
                         log::info!("Archive: {}", index.archive_name);
                         log::info!("  Data shards: {}", index.parameters.data_shards);
@@ -72,6 +158,8 @@ impl ArchiveExtractor {
                         log::info!("  Files: {}", index.files.len());
                         Some(index)
                     }
+                    // Falling back to the shards would fail the same way
+                    Err(e @ EctarError::Decryption(_)) => return Err(e),
                     Err(e) => {
                         log::warn!("Failed to read index file: {}", e);
                         None
@@ -80,68 +168,121 @@ impl ArchiveExtractor {
             }
             None => {
                 log::warn!("No index file found - will extract from shard headers only");
-                log::warn!("File filtering and metadata will not be available");
+                log::warn!("Index metadata will not be available; file checksums come from chunk manifests");
                 None
             }
         };
 
         // Extract using index if available, otherwise extract from shards only
-        if let Some(index) = index_opt {
-            self.extract_with_index(index)
-        } else {
-            self.extract_from_shards_only()
+        match (index_opt, index_path) {
+            (Some(index), Some(index_path)) if self.follow_chain && index.parent.is_some() => {
+                self.extract_chain(index_path, index)
+            }
+            (Some(index), _) => self.clone().with_link_stand_ins(&index).extract_with_index(index),
+            (None, _) => self.extract_from_shards_only(),
         }
     }
 
+    /// Restore the state an incremental archive records by taking each
+    /// entry from the newest archive of its chain that stores it
+    fn extract_chain(&self, index_path: PathBuf, index: ArchiveIndex) -> Result<ExtractionMetadata> {
+        let chain = chain::load_ancestors(index_path, index, self.keys.as_deref())?;
+        log::info!("Restoring from a chain of {} archives", chain.len());
+
+        let mut sources = vec![HashSet::new(); chain.len()];
+        for (path, (position, _)) in chain::snapshot(&chain) {
+            sources[position].insert(path);
+        }
+
+        let mut total = ExtractionMetadata::default();
+        for (link, paths) in chain.into_iter().zip(sources) {
+            log::info!("Extracting {} entries from {}", paths.len(), link.index.archive_name);
+            let extractor = Self {
+                shard_pattern: link.shard_pattern(),
+                follow_chain: false,
+                only_paths: Some(paths),
+                ..self.clone()
+            }
+            .with_link_stand_ins(&link.index);
+            total.absorb(extractor.extract_with_index(link.index)?);
+        }
+
+        Ok(total)
+    }
+
+    /// Pick, for each hard link target the filters skip, the first selected
+    /// link to it to receive its data instead
+    fn with_link_stand_ins(self, index: &ArchiveIndex) -> Self {
+        let stored: HashSet<PathBuf> = index.files.iter().map(FileEntry::archive_path).collect();
+        let mut link_stand_ins = HashMap::new();
+        for entry in &index.files {
+            let Some(target) = entry.link_target() else { continue };
+            let path = entry.archive_path();
+            if entry.entry_type == FileType::Hardlink
+                && stored.contains(&target)
+                && self.output_path(&target).is_none()
+                && self.output_path(&path).is_some()
+            {
+                link_stand_ins.entry(target).or_insert(path);
+            }
+        }
+        Self { link_stand_ins, ..self }
+    }
+
     /// Extract archive using index file (full functionality)
     fn extract_with_index(&self, index: ArchiveIndex) -> Result<ExtractionMetadata> {
+        if let Some(ref encryption) = index.parameters.encryption {
+            crypto::check_key(self.keys.as_deref(), encryption)?;
+        }
 
-        // Discover available shards
-        let shards_by_chunk = shard_reader::discover_shards(&self.shard_pattern)?;
-
-        // Create temporary directory for reconstructed chunks
-        let temp_dir = TempDir::new()?;
+        // Discover available shards, dropping any that fail their checksum
+        let shards_by_chunk =
+            shard_reader::discover_verified_shards(&self.shard_pattern, &index.chunks)?;
+
+        // Only the chunks holding selected entries need to be decoded
+        let selected_chunks = self.selected_chunks(&index);
+        if let Some(ref selected) = selected_chunks {
+            log::info!(
+                "Decoding {}/{} chunks holding the selected files",
+                selected.len(),
+                index.chunks.len()
+            );
+        }
+        let chunks_skipped = selected_chunks
+            .as_ref()
+            .map_or(0, |selected| index.chunks.len() - selected.len());
+
+        if selected_chunks.as_ref().is_some_and(|selected| selected.is_empty()) {
+            log::info!("No entries match the selection");
+            return Ok(ExtractionMetadata {
+                chunks_total: index.chunks.len(),
+                chunks_recovered: 0,
+                chunks_failed: 0,
+                chunks_skipped,
+                files_extracted: 0,
+                corrupt_shards: Vec::new(),
+            });
+        }
 
-        // Reconstruct each chunk
-        let mut chunks_recovered = 0;
+        // Chunks without enough shards are lost before any decoding starts
         let mut chunks_failed = Vec::new();
-
         for chunk_info in &index.chunks {
             let chunk_num = chunk_info.chunk_number;
+            if !is_selected(&selected_chunks, chunk_num) {
+                continue;
+            }
 
             match shards_by_chunk.get(&chunk_num) {
-                Some(shards) => {
-                    if shards.len() < index.parameters.data_shards {
-                        log::error!(
-                            "Chunk {}: insufficient shards ({}/{})",
-                            chunk_num,
-                            shards.len(),
-                            index.parameters.data_shards
-                        );
-                        chunks_failed.push(chunk_num);
-                        continue;
-                    }
-
-                    // Reconstruct chunk
-                    let chunk_path = temp_dir.path().join(format!("chunk{:03}.tar.zst", chunk_num));
-
-                    match decoder::decode_chunk(
-                        shards.clone(),
-                        index.parameters.data_shards,
-                        index.parameters.parity_shards,
-                        &chunk_path,
-                        Some(chunk_info.compressed_size),
-                    ) {
-                        Ok(_) => {
-                            log::info!("Chunk {} reconstructed successfully", chunk_num);
-                            chunks_recovered += 1;
-                        }
-                        Err(e) => {
-                            log::error!("Failed to reconstruct chunk {}: {}", chunk_num, e);
-                            chunks_failed.push(chunk_num);
-                        }
-                    }
+                Some(shards) if shards.len() < index.parameters.data_shards => {
+                    log::error!(
+                        "Chunk {}: insufficient shards ({}/{})",
+                        chunk_num,
+                        shards.len(),
+                        index.parameters.data_shards
+                    );
+                    chunks_failed.push(chunk_num);
                 }
+                Some(_) => {}
                 None => {
                     log::error!("Chunk {}: no shards found", chunk_num);
                     chunks_failed.push(chunk_num);
@@ -149,7 +290,8 @@ impl ArchiveExtractor {
             }
         }
 
-        if chunks_recovered == 0 {
+        let chunks_selected = index.chunks.len() - chunks_skipped;
+        if chunks_failed.len() == chunks_selected {
             if self.partial {
                 // In partial mode, return success with zero files extracted
                 log::warn!("No chunks could be recovered (partial mode)");
@@ -157,7 +299,9 @@ impl ArchiveExtractor {
                     chunks_total: index.chunks.len(),
                     chunks_recovered: 0,
                     chunks_failed: chunks_failed.len(),
+                    chunks_skipped,
                     files_extracted: 0,
+                    corrupt_shards: Vec::new(),
                 });
             }
             return Err(EctarError::ErasureCoding(
@@ -173,25 +317,272 @@ impl ArchiveExtractor {
             )));
         }
 
-        log::info!(
-            "Recovered {}/{} chunks",
-            chunks_recovered,
-            index.chunks.len()
-        );
+        // Ensure output directory exists
+        std::fs::create_dir_all(&self.output_dir)?;
 
-        // Concatenate and extract tar stream from all reconstructed chunks
         log::info!("Extracting files from reconstructed archive...");
 
-        let files_extracted = self.extract_all_chunks(&temp_dir, &index, &chunks_failed, self.partial)?;
+        let mut progress = DecodeProgress {
+            chunks_failed,
+            ..Default::default()
+        };
+        let mut files_extracted = 0;
+        let wanted_blocks = index
+            .files
+            .iter()
+            .filter(|f| self.unpacks(f))
+            .flat_map(|f| f.blocks.iter().flatten().cloned())
+            .collect();
+        let mut block_store = BlockStore::new(&self.output_dir).only(wanted_blocks);
+
+        // Sort by chunk number to ensure correct ordering
+        let mut chunks: Vec<&ChunkInfo> = index.chunks.iter().collect();
+        chunks.sort_by_key(|c| c.chunk_number);
+
+        for run in chunk_runs(&chunks, &selected_chunks) {
+            // A run that starts after skipped chunks begins mid-entry
+            let starts_stream = run[0].chunk_number == chunks[0].chunk_number;
+            let ends_stream = run[run.len() - 1].chunk_number == chunks[chunks.len() - 1].chunk_number;
+
+            files_extracted += self.extract_run(
+                &index,
+                &run,
+                &shards_by_chunk,
+                &mut progress,
+                &mut block_store,
+                (starts_stream, ends_stream),
+            )?;
+        }
+
+        log::info!(
+            "Recovered {}/{} chunks",
+            progress.chunks_recovered,
+            chunks_selected
+        );
+        log::info!("Extracted {} entries", files_extracted);
 
         Ok(ExtractionMetadata {
             chunks_total: index.chunks.len(),
-            chunks_recovered,
-            chunks_failed: chunks_failed.len(),
+            chunks_recovered: progress.chunks_recovered,
+            chunks_failed: progress.chunks_failed.len(),
+            chunks_skipped,
             files_extracted,
+            corrupt_shards: progress.corrupt_shards,
         })
     }
 
+    /// Stream a run of consecutive chunks into the tar unpacker
+    ///
+    /// A second thread reconstructs the chunks in order, so the next chunk
+    /// is decoded while the current one is unpacked.
+    fn extract_run(
+        &self,
+        index: &ArchiveIndex,
+        run: &[&ChunkInfo],
+        shards_by_chunk: &HashMap<usize, Vec<ShardData>>,
+        progress: &mut DecodeProgress,
+        block_store: &mut BlockStore,
+        (starts_stream, ends_stream): (bool, bool),
+    ) -> Result<usize> {
+        let (sender, stream) = ChunkStreamReader::channel();
+        let mut stream = stream
+            .verify_checksums(self.verify_checksums)
+            .tolerate_mismatch(self.partial);
+        let known_failed = progress.chunks_failed.clone();
+
+        let (unpacked, stream_error, run_progress) = std::thread::scope(|scope| {
+            let decoder = scope.spawn(|| {
+                self.decode_run(index, run, shards_by_chunk, &known_failed, sender)
+            });
+
+            let unpacked =
+                self.unpack_run(&mut stream, index, run, block_store, starts_stream, ends_stream);
+            if unpacked.is_ok() {
+                // tar stops at the end-of-archive blocks; read the rest so
+                // the last chunk's checksum is still verified
+                let _ = std::io::copy(&mut stream, &mut std::io::sink());
+            }
+            let stream_error = stream.take_error();
+            // Stops the decoding thread if unpacking ended early
+            drop(stream);
+
+            let run_progress = decoder.join().expect("chunk decoding thread panicked");
+            (unpacked, stream_error, run_progress)
+        });
+
+        progress.chunks_recovered += run_progress.chunks_recovered;
+        progress.chunks_failed.extend(run_progress.chunks_failed);
+        progress.corrupt_shards.extend(run_progress.corrupt_shards);
+
+        // A chunk that failed to decode or verify is reported as such rather
+        // than as the tar error it caused
+        if let Some(e) = stream_error {
+            return Err(e);
+        }
+        unpacked
+    }
+
+    /// Reconstruct the chunks of a run and send them to the tar stream
+    ///
+    /// Up to `threads` chunks are reconstructed at once; they are sent on in
+    /// chunk order.
+    fn decode_run(
+        &self,
+        index: &ArchiveIndex,
+        run: &[&ChunkInfo],
+        shards_by_chunk: &HashMap<usize, Vec<ShardData>>,
+        known_failed: &[usize],
+        sender: ChunkSender,
+    ) -> DecodeProgress {
+        let mut progress = DecodeProgress::default();
+        let archive_base = shard_reader::archive_base(&self.shard_pattern);
+
+        let decode = |chunk_info: &&ChunkInfo| {
+            let chunk_num = chunk_info.chunk_number;
+            if known_failed.contains(&chunk_num) {
+                return None;
+            }
+            Some(
+                self.decode_indexed_chunk(index, chunk_info, &shards_by_chunk[&chunk_num])
+                    .and_then(|(data, corrupt)| Ok((self.decrypt_chunk(chunk_num, data)?, corrupt))),
+            )
+        };
+
+        let mut chunks = run.iter();
+        map_ordered(run, self.threads, decode, |decoded| {
+            let chunk_info = chunks.next().expect("one result per chunk");
+            let chunk_num = chunk_info.chunk_number;
+            let data = match decoded {
+                None => {
+                    log::warn!("Skipping failed chunk {} during extraction", chunk_num);
+                    return ControlFlow::Continue(());
+                }
+                Some(Ok((data, corrupt_shards))) => {
+                    progress.corrupt_shards.extend(corrupt_shards.iter().map(|&n| {
+                        encoder::format_shard_path(&archive_base, chunk_num, n)
+                    }));
+                    data
+                }
+                Some(Err(e)) => {
+                    progress.chunks_failed.push(chunk_num);
+                    if self.partial {
+                        return ControlFlow::Continue(());
+                    }
+                    // Nothing more can be unpacked; end the stream with the cause
+                    let e = match e {
+                        EctarError::ChunkChecksumMismatch { .. } | EctarError::Decryption(_) => e,
+                        _ => EctarError::ErasureCoding(format!(
+                            "Failed to recover chunk {}: {}",
+                            chunk_num, e
+                        )),
+                    };
+                    let _ = sender.send(Err(e));
+                    return ControlFlow::Break(());
+                }
+            };
+
+            progress.chunks_recovered += 1;
+            let chunk = StreamChunk {
+                chunk_number: chunk_num,
+                data,
+                uncompressed_checksum: chunk_info.uncompressed_checksum.clone(),
+            };
+            if sender.send(Ok(chunk)).is_err() {
+                // The unpacker has everything it needs
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+
+        progress
+    }
+
+    /// Reconstruct and validate one chunk, hunting for corrupt shards if it
+    /// fails and hunting is enabled
+    ///
+    /// Returns the chunk data and the shards found to be corrupt.
+    fn decode_indexed_chunk(
+        &self,
+        index: &ArchiveIndex,
+        chunk_info: &ChunkInfo,
+        shards: &[ShardData],
+    ) -> Result<(Vec<u8>, Vec<usize>)> {
+        let chunk_num = chunk_info.chunk_number;
+
+        // Reconstruct chunk
+        let result = decoder::decode_chunk_bytes(
+            shards.to_vec(),
+            index.parameters.data_shards,
+            index.parameters.parity_shards,
+            Some(chunk_info.compressed_size),
+        )
+        .and_then(|data| {
+            self.validate_decoded_chunk(&data, chunk_info)?;
+            Ok(data)
+        });
+
+        let e = match result {
+            Ok(data) => {
+                log::info!("Chunk {} reconstructed successfully", chunk_num);
+                return Ok((data, Vec::new()));
+            }
+            Err(e) => e,
+        };
+        log::error!("Failed to reconstruct chunk {}: {}", chunk_num, e);
+
+        if !self.hunt_corruption {
+            return Err(e);
+        }
+
+        log::info!("Chunk {}: hunting for corrupt shards", chunk_num);
+        match hunt::hunt_corruption(
+            shards,
+            index.parameters.data_shards,
+            index.parameters.parity_shards,
+            chunk_info,
+            self.hunt_attempts,
+        ) {
+            Ok(hunted) => Ok((hunted.data, hunted.corrupt_shards)),
+            Err(hunt_err) => {
+                log::error!("{}", hunt_err);
+                Err(e)
+            }
+        }
+    }
+
+    /// Decrypt a reconstructed chunk of an encrypted archive
+    ///
+    /// With a key every chunk must be sealed; an index with encryption
+    /// parameters has already been refused without one.
+    fn decrypt_chunk(&self, chunk_number: usize, data: Vec<u8>) -> Result<Vec<u8>> {
+        let keys = self.keys.as_deref();
+        envelope::open_if_sealed(keys, Context::Chunk(chunk_number), data, keys.is_some())
+    }
+
+    /// Check a reconstructed chunk against the index before it is unpacked
+    fn validate_decoded_chunk(&self, data: &[u8], chunk_info: &ChunkInfo) -> Result<()> {
+        if chunk_info.checksum.is_empty() {
+            // Older archives have no chunk checksum; when hunting, a zstd
+            // decode has to stand in for one
+            if self.hunt_corruption && !hunt::chunk_is_valid(data, chunk_info) {
+                return Err(EctarError::Decompression(format!(
+                    "Chunk {} does not decode to {} bytes",
+                    chunk_info.chunk_number, chunk_info.uncompressed_size
+                )));
+            }
+            return Ok(());
+        }
+
+        if self.verify_checksums && sha256::checksum_bytes(data) != chunk_info.checksum {
+            return Err(EctarError::ChunkChecksumMismatch {
+                chunk: chunk_info.chunk_number,
+                stage: "compressed",
+            });
+        }
+
+        Ok(())
+    }
+
     /// Extract archive from shards only (no index file)
     /// Uses zfec headers from shards to determine parameters
     fn extract_from_shards_only(&self) -> Result<ExtractionMetadata> {
@@ -230,161 +621,296 @@ impl ArchiveExtractor {
             }
         };
 
-        // Create temporary directory for reconstructed chunks
-        let temp_dir = TempDir::new()?;
-
-        // Reconstruct each chunk
-        let mut chunks_recovered = 0;
-        let mut chunks_failed = Vec::new();
         let chunks_total = shards_by_chunk.len();
 
         // Sort chunk numbers for consistent ordering
         let mut chunk_numbers: Vec<usize> = shards_by_chunk.keys().copied().collect();
         chunk_numbers.sort();
 
+        // Chunks without enough shards are lost before any decoding starts
+        let mut chunks_failed = Vec::new();
         for chunk_num in &chunk_numbers {
-            match shards_by_chunk.get(chunk_num) {
-                Some(shards) => {
-                    if shards.len() < data_shards {
-                        log::error!(
-                            "Chunk {}: insufficient shards ({}/{})",
-                            chunk_num,
-                            shards.len(),
-                            data_shards
-                        );
-                        chunks_failed.push(*chunk_num);
-                        continue;
-                    }
-
-                    // Calculate compressed_size from zfec header padlen
-                    let compressed_size = if let Some(ref header) = shards[0].header {
-                        // shard_size * data_shards - padlen = actual compressed size
-                        let shard_size = shards[0].data.len();
-                        let total_size = shard_size * data_shards;
-                        let actual_size = total_size - header.padlen;
-                        log::debug!(
-                            "Chunk {}: calculated compressed_size={} (shard_size={}, padlen={})",
-                            chunk_num, actual_size, shard_size, header.padlen
-                        );
-                        Some(actual_size as u64)
-                    } else {
-                        None
-                    };
-
-                    // Reconstruct chunk
-                    let chunk_path = temp_dir.path().join(format!("chunk{:03}.tar.zst", chunk_num));
-
-                    match decoder::decode_chunk(
-                        shards.clone(),
-                        data_shards,
-                        parity_shards,
-                        &chunk_path,
-                        compressed_size,
-                    ) {
-                        Ok(_) => {
-                            log::info!("Chunk {} reconstructed successfully", chunk_num);
-                            chunks_recovered += 1;
-                        }
-                        Err(e) => {
-                            log::error!("Failed to reconstruct chunk {}: {}", chunk_num, e);
-                            chunks_failed.push(*chunk_num);
-                        }
-                    }
-                }
-                None => {
-                    log::error!("Chunk {}: no shards found", chunk_num);
-                    chunks_failed.push(*chunk_num);
-                }
+            let shards = &shards_by_chunk[chunk_num];
+            if shards.len() < data_shards {
+                log::error!(
+                    "Chunk {}: insufficient shards ({}/{})",
+                    chunk_num,
+                    shards.len(),
+                    data_shards
+                );
+                chunks_failed.push(*chunk_num);
             }
         }
 
-        if chunks_recovered == 0 {
+        if chunks_failed.len() == chunks_total {
+            return Err(EctarError::ErasureCoding(
+                "No chunks could be recovered".to_string(),
+            ));
+        }
+
+        // Ensure output directory exists
+        std::fs::create_dir_all(&self.output_dir)?;
+
+        log::info!("Extracting files from reconstructed archive...");
+
+        // Entries listed in the decoded chunks' manifests, by path
+        let manifest_files: Mutex<HashMap<PathBuf, FileEntry>> = Mutex::new(HashMap::new());
+        let mut block_store = BlockStore::new(&self.output_dir);
+
+        let (sender, mut stream) = ChunkStreamReader::channel();
+        let (unpacked, stream_error, progress) = std::thread::scope(|scope| {
+            let decoder = scope.spawn(|| {
+                self.decode_chunks_no_index(
+                    &shards_by_chunk,
+                    &chunk_numbers,
+                    &chunks_failed,
+                    (data_shards, parity_shards),
+                    &manifest_files,
+                    sender,
+                )
+            });
+
+            let unpacked = self.extract_chunks_no_index(&mut stream, &manifest_files, &mut block_store);
+            let stream_error = stream.take_error();
+            // Stops the decoding thread if unpacking failed
+            drop(stream);
+
+            let progress = decoder.join().expect("chunk decoding thread panicked");
+            (unpacked, stream_error, progress)
+        });
+
+        if let Some(e @ EctarError::Decryption(_)) = stream_error {
+            return Err(e);
+        }
+        if progress.chunks_recovered == 0 {
             return Err(EctarError::ErasureCoding(
                 "No chunks could be recovered".to_string(),
             ));
         }
 
+        let files_extracted = unpacked?;
+
         log::info!(
             "Recovered {}/{} chunks",
-            chunks_recovered,
+            progress.chunks_recovered,
             chunks_total
         );
 
-        // Extract all chunks without index (no file filtering available)
-        log::info!("Extracting files from reconstructed archive (no filtering)...");
-
-        let files_extracted = self.extract_chunks_no_index(&temp_dir, &chunk_numbers, &chunks_failed)?;
-
         Ok(ExtractionMetadata {
             chunks_total,
-            chunks_recovered,
-            chunks_failed: chunks_failed.len(),
+            chunks_recovered: progress.chunks_recovered,
+            chunks_failed: chunks_failed.len() + progress.chunks_failed.len(),
+            chunks_skipped: 0,
             files_extracted,
+            corrupt_shards: Vec::new(),
         })
     }
 
-    fn read_index(&self, index_path: &Path) -> Result<ArchiveIndex> {
-        let index_file = File::open(index_path)?;
-        let mut decoder = compression::create_decoder(index_file)?;
+    /// Reconstruct chunks from shard headers alone and send them to the tar
+    /// stream, collecting their manifests on the way
+    fn decode_chunks_no_index(
+        &self,
+        shards_by_chunk: &HashMap<usize, Vec<ShardData>>,
+        chunk_numbers: &[usize],
+        known_failed: &[usize],
+        (data_shards, parity_shards): (usize, usize),
+        manifest_files: &Mutex<HashMap<PathBuf, FileEntry>>,
+        sender: ChunkSender,
+    ) -> DecodeProgress {
+        let mut progress = DecodeProgress::default();
+
+        let decode = |chunk_num: &usize| {
+            if known_failed.contains(chunk_num) {
+                return None;
+            }
+            let shards = &shards_by_chunk[chunk_num];
+
+            // Calculate compressed_size from zfec header padlen
+            let compressed_size = if let Some(ref header) = shards[0].header {
+                // shard_size * data_shards - padlen = actual compressed size
+                let shard_size = shards[0].data.len();
+                let total_size = shard_size * data_shards;
+                let actual_size = total_size - header.padlen;
+                log::debug!(
+                    "Chunk {}: calculated compressed_size={} (shard_size={}, padlen={})",
+                    chunk_num, actual_size, shard_size, header.padlen
+                );
+                Some(actual_size as u64)
+            } else {
+                None
+            };
+
+            // Reconstruct chunk
+            Some(
+                decoder::decode_chunk_bytes(shards.clone(), data_shards, parity_shards, compressed_size)
+                    .and_then(|data| self.decrypt_chunk(*chunk_num, data)),
+            )
+        };
+
+        let mut chunks = chunk_numbers.iter();
+        map_ordered(chunk_numbers, self.threads, decode, |decoded| {
+            let chunk_num = *chunks.next().expect("one result per chunk");
+            let data = match decoded {
+                None => {
+                    log::warn!("Skipping failed chunk {} during extraction", chunk_num);
+                    return ControlFlow::Continue(());
+                }
+                Some(Ok(data)) => {
+                    log::info!("Chunk {} reconstructed successfully", chunk_num);
+                    data
+                }
+                // Every other chunk would fail the same way
+                Some(Err(e @ EctarError::Decryption(_))) => {
+                    let _ = sender.send(Err(e));
+                    return ControlFlow::Break(());
+                }
+                Some(Err(e)) => {
+                    log::error!("Failed to reconstruct chunk {}: {}", chunk_num, e);
+                    progress.chunks_failed.push(chunk_num);
+                    return ControlFlow::Continue(());
+                }
+            };
 
-        let mut json = String::new();
-        std::io::Read::read_to_string(&mut decoder, &mut json)?;
+            // The manifest is in place before any of the chunk's entries are unpacked
+            match ChunkManifest::from_chunk(&data) {
+                Ok(Some(manifest)) => {
+                    log::debug!("Chunk {}: manifest lists {} entries", chunk_num, manifest.files.len());
+                    manifest_files
+                        .lock()
+                        .expect("manifest lock poisoned")
+                        .extend(manifest.files.into_iter().map(|f| (f.archive_path(), f)));
+                }
+                Ok(None) => log::debug!("Chunk {}: no manifest", chunk_num),
+                Err(e) => log::warn!("Chunk {}: unreadable manifest: {}", chunk_num, e),
+            }
 
-        let index: ArchiveIndex = serde_json::from_str(&json)?;
+            progress.chunks_recovered += 1;
+            let chunk = StreamChunk {
+                chunk_number: chunk_num,
+                data,
+                uncompressed_checksum: None,
+            };
+            if sender.send(Ok(chunk)).is_err() {
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
 
-        Ok(index)
+        progress
     }
 
-    fn extract_all_chunks(
+    fn read_index(&self, index_path: &Path) -> Result<ArchiveIndex> {
+        reader::read_index_with_keys(index_path, self.keys.as_deref())
+    }
+
+    /// Unpack the selected entries of a run's tar stream
+    fn unpack_run(
         &self,
-        temp_dir: &TempDir,
+        stream: &mut ChunkStreamReader,
         index: &ArchiveIndex,
-        chunks_failed: &[usize],
-        partial: bool,
+        run: &[&ChunkInfo],
+        block_store: &mut BlockStore,
+        starts_stream: bool,
+        ends_stream: bool,
     ) -> Result<usize> {
+        if starts_stream {
+            return self.unpack_tar(stream, self.partial, block_store, ends_stream);
+        }
 
-        // Ensure output directory exists
-        std::fs::create_dir_all(&self.output_dir)?;
-
-        // Create a temporary file to hold the concatenated decompressed tar stream
-        let concat_path = temp_dir.path().join("combined.tar");
-        let mut concat_file = File::create(&concat_path)?;
-
-        // Decompress and concatenate all chunks in order
-        // Sort by chunk number to ensure correct ordering
-        let mut chunk_numbers: Vec<usize> = index.chunks.iter()
-            .map(|c| c.chunk_number)
-            .collect();
-        chunk_numbers.sort();
+        // The first header lies within the first chunk, or right at the
+        // start of the next one when an entry filled the first chunk exactly
+        let limit = run[0].uncompressed_size + 2 * tar_header::TAR_BLOCK_SIZE as u64;
+        let mut prefix = Vec::new();
+        (&mut *stream).take(limit).read_to_end(&mut prefix)?;
 
-        for chunk_num in chunk_numbers {
-            if chunks_failed.contains(&chunk_num) {
-                log::warn!("Skipping failed chunk {} during extraction", chunk_num);
-                continue;
+        match Self::find_first_header(&prefix, index, run)? {
+            Some(offset) => {
+                let reader = Cursor::new(&prefix[offset..]).chain(stream);
+                self.unpack_tar(reader, self.partial, block_store, ends_stream)
             }
+            None => {
+                log::warn!("Chunk {}: no entry header found", run[0].chunk_number);
+                Ok(0)
+            }
+        }
+    }
 
-            let chunk_path = temp_dir.path().join(format!("chunk{:03}.tar.zst", chunk_num));
-
-            if !chunk_path.exists() {
-                continue;
+    /// Locate the first entry header in the tar bytes of a run that starts
+    /// mid-stream
+    ///
+    /// Uses the header offsets recorded in the index. Older archives record
+    /// none, so candidate headers are confirmed against the index instead:
+    /// the entry must be one that lives in the run, with the size the index
+    /// records.
+    fn find_first_header(
+        data: &[u8],
+        index: &ArchiveIndex,
+        run: &[&ChunkInfo],
+    ) -> Result<Option<usize>> {
+        let first = run[0].chunk_number;
+        let last = run[run.len() - 1].chunk_number;
+
+        // Archives that record header positions need no scan
+        let recorded = index
+            .files
+            .iter()
+            .chain(&index.blocks)
+            .filter(|f| f.chunk == first && f.stream_offset.is_some())
+            .map(|f| f.offset as usize)
+            .min();
+        if let Some(offset) = recorded {
+            let block = data.get(offset..).unwrap_or_default();
+            if tar_header::is_tar_header(block) {
+                log::debug!("Chunk {}: first entry at recorded byte {}", first, offset);
+                return Ok(Some(offset));
             }
+            log::warn!("Chunk {}: no tar header at recorded byte {}, scanning", first, offset);
+        }
 
-            log::debug!("Decompressing chunk {}...", chunk_num);
+        let expected: HashMap<PathBuf, &FileEntry> = index
+            .files
+            .iter()
+            .chain(&index.blocks)
+            .filter(|f| {
+                let end = f.spans_chunks.as_ref().and_then(|s| s.last()).copied().unwrap_or(f.chunk);
+                f.chunk <= last && end >= first
+            })
+            .map(|f| (f.archive_path(), f))
+            .collect();
 
-            // Decompress chunk and append to concatenated tar
-            let chunk_file = File::open(&chunk_path)?;
-            let mut decoder = compression::create_decoder(chunk_file)?;
+        for offset in tar_header::header_offsets(data) {
+            let mut archive = tar::Archive::new(&data[offset..]);
+            let entry = match archive.entries()?.next() {
+                Some(Ok(entry)) => entry,
+                _ => continue,
+            };
 
-            std::io::copy(&mut decoder, &mut concat_file)?;
+            let path = entry.path()?;
+            let matches = expected.get(path.as_ref()).is_some_and(|f| {
+                f.entry_type != FileType::File || f.blocks.is_some() || f.size == entry.size()
+            });
+            if matches {
+                log::debug!("Chunk {}: first entry {} at byte {}", first, path.display(), offset);
+                return Ok(Some(offset));
+            }
         }
 
-        concat_file.flush()?;
-        drop(concat_file);
+        Ok(None)
+    }
 
-        // Extract from the concatenated tar file
+    /// Unpack the entries of a tar stream that pass the filters
+    ///
+    /// When the stream does not reach the end of the archive it stops
+    /// mid-entry; running out of entries there is expected, not an error.
+    fn unpack_tar<R: Read>(
+        &self,
+        reader: R,
+        partial: bool,
+        block_store: &mut BlockStore,
+        ends_stream: bool,
+    ) -> Result<usize> {
         log::info!("Extracting tar archive...");
-        let concat_file = File::open(&concat_path)?;
-        let mut archive = tar::Archive::new(concat_file);
+        let mut archive = tar::Archive::new(reader);
 
         // Unpack and count entries
         let mut file_count = 0;
@@ -403,6 +929,10 @@ impl ArchiveExtractor {
             let mut entry = match entry {
                 Ok(e) => e,
                 Err(e) => {
+                    if !ends_stream {
+                        log::debug!("End of decoded chunks: {}", e);
+                        break;
+                    }
                     if partial {
                         log::warn!("Failed to read tar entry (partial mode): {}", e);
                         break; // Stop processing entries when we hit corruption
@@ -424,41 +954,31 @@ impl ArchiveExtractor {
                 }
             };
 
-            let path_str = path.to_string_lossy();
-
-            // Check file filters (if specified, only extract matching files)
-            if !self.file_filters.is_empty() {
-                let matches = self.file_filters.iter().any(|f| {
-                    path_str.contains(f) || glob::Pattern::new(f)
-                        .map(|p| p.matches(&path_str))
-                        .unwrap_or(false)
-                });
-                if !matches {
-                    log::debug!("Skipping {} (not in file filter)", path.display());
+            // Blocks of deduplicated files are kept aside until their files
+            // are unpacked
+            if let Some(hash) = dedup::block_hash_of(&path) {
+                if !block_store.wants(hash) {
                     continue;
                 }
-            }
-
-            // Check exclude patterns
-            if self.exclude_patterns.iter().any(|p| {
-                path_str.contains(p) || glob::Pattern::new(p)
-                    .map(|pat| pat.matches(&path_str))
-                    .unwrap_or(false)
-            }) {
-                log::debug!("Skipping {} (excluded)", path.display());
+                if let Err(e) = block_store.insert(hash, &mut entry) {
+                    if partial {
+                        log::warn!("Failed to store block {} (partial mode): {}", hash, e);
+                        continue;
+                    }
+                    return Err(e);
+                }
                 continue;
             }
 
-            // Apply strip_components
-            let stripped_path = if self.strip_components > 0 {
-                let components: Vec<_> = path.components().collect();
-                if components.len() <= self.strip_components {
-                    log::debug!("Skipping {} (not enough path components to strip)", path.display());
-                    continue;
-                }
-                components[self.strip_components..].iter().collect::<PathBuf>()
-            } else {
-                path.clone()
+            // A skipped hard link target is unpacked as the link standing in
+            let stand_in = self.link_stand_ins.get(&path);
+            let stripped_path = match (self.output_path(&path), stand_in) {
+                (Some(p), _) => p,
+                (None, Some(link)) => match self.output_path(link) {
+                    Some(p) => p,
+                    None => continue,
+                },
+                (None, None) => continue,
             };
 
             log::debug!("Extracting: {} -> {}", path.display(), stripped_path.display());
@@ -470,68 +990,232 @@ impl ArchiveExtractor {
                 std::fs::create_dir_all(parent)?;
             }
 
-            if let Err(e) = entry.unpack(&output_path) {
-                if partial {
+            let unpacked = if entry.header().entry_type().is_hard_link() {
+                self.unpack_hardlink(&entry, &path, &output_path)
+            } else if let Some(kind) = special::kind(entry.header().entry_type()) {
+                self.unpack_special(&mut entry, &kind, &path, &output_path)
+            } else {
+                self.unpack_entry(&mut entry, &path, &output_path, block_store)
+                    .map(|()| self.output_path(&path).is_some())
+            };
+            match unpacked {
+                // The stand-in link counts once its own entry comes
+                Ok(counted) => file_count += usize::from(counted),
+                Err(e) if partial => {
                     log::warn!("Failed to unpack {} (partial mode): {}", path.display(), e);
-                    continue;
-                } else {
-                    return Err(EctarError::Tar(format!("Failed to unpack {}: {}", path.display(), e)));
                 }
+                Err(e) => return Err(e),
             }
-
-            file_count += 1;
         }
 
-        log::info!("Extracted {} entries", file_count);
-
         Ok(file_count)
     }
 
-    /// Extract chunks without using index (no file filtering, simpler extraction)
-    fn extract_chunks_no_index(
+    /// Unpack one entry, reassembling a deduplicated file from its blocks
+    /// and then restoring its extended attributes
+    fn unpack_entry<R: Read>(
         &self,
-        temp_dir: &TempDir,
-        chunk_numbers: &[usize],
-        chunks_failed: &[usize],
-    ) -> Result<usize> {
-        // Ensure output directory exists
-        std::fs::create_dir_all(&self.output_dir)?;
+        entry: &mut tar::Entry<R>,
+        path: &Path,
+        output_path: &Path,
+        block_store: &BlockStore,
+    ) -> Result<()> {
+        let deduped = DedupedFile::from_entry(entry)?;
+        let attributes = if self.restore_attributes { xattrs::from_entry(entry)? } else { None };
+        entry
+            .unpack(output_path)
+            .map_err(|e| EctarError::Tar(format!("Failed to unpack {}: {}", path.display(), e)))?;
+        if let Some(deduped) = deduped {
+            block_store.reassemble(&deduped, output_path)?;
+        }
+        Self::apply_attributes(attributes, path, output_path);
+        Ok(())
+    }
 
-        // Create a temporary file to hold the concatenated decompressed tar stream
-        let concat_path = temp_dir.path().join("combined.tar");
-        let mut concat_file = File::create(&concat_path)?;
+    /// Recreate a device node or FIFO entry; returns whether it was created
+    ///
+    /// Creating device nodes needs root, so without it they are skipped
+    /// with a warning. FIFOs can always be created.
+    fn unpack_special<R: Read>(
+        &self,
+        entry: &mut tar::Entry<R>,
+        kind: &FileType,
+        path: &Path,
+        output_path: &Path,
+    ) -> Result<bool> {
+        if *kind != FileType::Fifo && !special::can_create_devices() {
+            log::warn!("Skipping device node {}: creating it needs root", path.display());
+            return Ok(false);
+        }
 
-        // Decompress and concatenate all chunks in order
-        for chunk_num in chunk_numbers {
-            if chunks_failed.contains(chunk_num) {
-                log::warn!("Skipping failed chunk {} during extraction", chunk_num);
-                continue;
+        let attributes = if self.restore_attributes { xattrs::from_entry(entry)? } else { None };
+        if output_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(output_path)?;
+        }
+        special::create(output_path, entry.header(), kind)?;
+        Self::apply_attributes(attributes, path, output_path);
+        Ok(true)
+    }
+
+    /// Set the extended attributes an entry carried on what it unpacked to,
+    /// warning about any the filesystem refuses
+    fn apply_attributes(attributes: Option<ExtendedAttributes>, path: &Path, output_path: &Path) {
+        if let Some(attributes) = attributes {
+            if let Err(e) = xattrs::apply(output_path, &attributes) {
+                log::warn!("Cannot restore extended attributes of {}: {}", path.display(), e);
             }
+        }
+    }
 
-            let chunk_path = temp_dir.path().join(format!("chunk{:03}.tar.zst", chunk_num));
+    /// Recreate a hard link entry by linking to the file unpacked for its
+    /// target, or for the link that stood in for it; returns whether the
+    /// link exists afterwards
+    fn unpack_hardlink<R: Read>(&self, entry: &tar::Entry<R>, path: &Path, output_path: &Path) -> Result<bool> {
+        let target = entry
+            .link_name()?
+            .ok_or_else(|| EctarError::Tar(format!("Hard link {} has no target", path.display())))?;
+
+        // A target the filters skip may still have been unpacked by an
+        // earlier archive of a chain, so only its components are stripped
+        let source = match self.link_stand_ins.get(target.as_ref()) {
+            Some(link) => self.output_path(link),
+            None => self.strip_path(&target),
+        };
+        let Some(source) = source else {
+            log::warn!(
+                "Cannot link {} to {}: target is outside the extraction",
+                path.display(),
+                target.display()
+            );
+            return Ok(false);
+        };
+        // An absolute or climbing target would link a host file into the
+        // output directory, where later entries could overwrite it
+        if !source.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(EctarError::Tar(format!(
+                "Hard link {} points outside the output directory: {}",
+                path.display(),
+                target.display()
+            )));
+        }
+        let source = self.output_dir.join(source);
 
-            if !chunk_path.exists() {
-                continue;
+        if source == output_path {
+            // This link received the target's data itself
+            return Ok(true);
+        }
+        if !source.is_file() {
+            log::warn!("Cannot link {} to {}: target was not extracted", path.display(), target.display());
+            return Ok(false);
+        }
+        if output_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(output_path)?;
+        }
+        std::fs::hard_link(&source, output_path)?;
+        Ok(true)
+    }
+
+    /// Whether an indexed entry's data is unpacked, under its own path or
+    /// that of a hard link standing in for it
+    fn unpacks(&self, entry: &FileEntry) -> bool {
+        let path = entry.archive_path();
+        self.output_path(&path).is_some() || self.link_stand_ins.contains_key(&path)
+    }
+
+    /// Apply file filters, exclude patterns and strip_components to an
+    /// archive path; `None` means the entry is skipped
+    fn output_path(&self, path: &Path) -> Option<PathBuf> {
+        let path_str = path.to_string_lossy();
+
+        // Paths compare by component, so a directory header's trailing
+        // slash does not matter
+        if let Some(ref only_paths) = self.only_paths {
+            if !only_paths.contains(path) {
+                return None;
             }
+        }
 
-            log::debug!("Decompressing chunk {}...", chunk_num);
+        // Check file filters (if specified, only extract matching files)
+        if !self.file_filters.is_empty() {
+            // Globs have to be text, so a filter that is not valid UTF-8 only
+            // matches as bytes
+            let matches = self.file_filters.iter().any(|f| {
+                contains_bytes(path.as_os_str(), f) || f.to_str()
+                    .and_then(|f| glob::Pattern::new(f).ok())
+                    .is_some_and(|p| p.matches(&path_str))
+            });
+            if !matches {
+                log::debug!("Skipping {} (not in file filter)", path.display());
+                return None;
+            }
+        }
 
-            // Decompress chunk and append to concatenated tar
-            let chunk_file = File::open(&chunk_path)?;
-            let mut decoder = compression::create_decoder(chunk_file)?;
+        // Check exclude patterns
+        if self.exclude_patterns.iter().any(|p| {
+            path_str.contains(p) || glob::Pattern::new(p)
+                .map(|pat| pat.matches(&path_str))
+                .unwrap_or(false)
+        }) {
+            log::debug!("Skipping {} (excluded)", path.display());
+            return None;
+        }
 
-            std::io::copy(&mut decoder, &mut concat_file)?;
+        self.strip_path(path)
+    }
+
+    /// Apply strip_components to an archive path; `None` when too short
+    fn strip_path(&self, path: &Path) -> Option<PathBuf> {
+        if self.strip_components > 0 {
+            let components: Vec<_> = path.components().collect();
+            if components.len() <= self.strip_components {
+                log::debug!("Skipping {} (not enough path components to strip)", path.display());
+                return None;
+            }
+            Some(components[self.strip_components..].iter().collect::<PathBuf>())
+        } else {
+            Some(path.to_path_buf())
         }
+    }
 
-        concat_file.flush()?;
-        drop(concat_file);
+    /// Chunks that hold the header or data of an entry selected by the
+    /// filters; `None` when every chunk is needed
+    fn selected_chunks(&self, index: &ArchiveIndex) -> Option<BTreeSet<usize>> {
+        let blocks: HashMap<&str, &FileEntry> = index
+            .blocks
+            .iter()
+            .filter_map(|b| Some((dedup::block_hash_of(Path::new(&b.path))?, b)))
+            .collect();
+
+        let mut selected = BTreeSet::new();
+        for entry in &index.files {
+            if self.unpacks(entry) {
+                // A deduplicated file also needs the chunks its blocks are in
+                let used_blocks = entry.blocks.iter().flatten().filter_map(|h| blocks.get(h.as_str()).copied());
+                for entry in std::iter::once(entry).chain(used_blocks) {
+                    selected.insert(entry.chunk);
+                    selected.extend(entry.spans_chunks.iter().flatten());
+                }
+            }
+        }
 
-        // Extract from the concatenated tar file
+        if index.chunks.iter().all(|c| selected.contains(&c.chunk_number)) {
+            None
+        } else {
+            Some(selected)
+        }
+    }
+
+    /// Unpack the tar stream of an archive without an index, using the
+    /// manifests embedded in each chunk for file checksums
+    fn extract_chunks_no_index<R: Read>(
+        &self,
+        reader: R,
+        manifest_files: &Mutex<HashMap<PathBuf, FileEntry>>,
+        block_store: &mut BlockStore,
+    ) -> Result<usize> {
         log::info!("Extracting tar archive...");
-        let concat_file = File::open(&concat_path)?;
-        let mut archive = tar::Archive::new(concat_file);
+        let mut archive = tar::Archive::new(reader);
 
-        // Unpack all entries (no filtering)
         let mut file_count = 0;
 
         for entry in archive.entries()? {
@@ -541,17 +1225,52 @@ impl ArchiveExtractor {
                 .map_err(|e| EctarError::Tar(e.to_string()))?
                 .to_path_buf();
 
-            log::debug!("Extracting: {}", path.display());
+            if let Some(hash) = dedup::block_hash_of(&path) {
+                block_store.insert(hash, &mut entry)?;
+                continue;
+            }
+
+            let stripped_path = match self.output_path(&path) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            log::debug!("Extracting: {} -> {}", path.display(), stripped_path.display());
 
-            let output_path = self.output_dir.join(&path);
+            let output_path = self.output_dir.join(&stripped_path);
 
             // Create parent directories if needed
             if let Some(parent) = output_path.parent() {
                 std::fs::create_dir_all(parent)?;
             }
 
-            entry.unpack(&output_path)
-                .map_err(|e| EctarError::Tar(format!("Failed to unpack {}: {}", path.display(), e)))?;
+            if entry.header().entry_type().is_hard_link() {
+                if self.unpack_hardlink(&entry, &path, &output_path)? {
+                    file_count += 1;
+                }
+                continue;
+            }
+            if let Some(kind) = special::kind(entry.header().entry_type()) {
+                if self.unpack_special(&mut entry, &kind, &path, &output_path)? {
+                    file_count += 1;
+                }
+                continue;
+            }
+
+            self.unpack_entry(&mut entry, &path, &output_path, block_store)?;
+
+            let expected = manifest_files
+                .lock()
+                .expect("manifest lock poisoned")
+                .get(&path)
+                .and_then(|f| f.checksum.clone());
+            if let Some(expected) = expected {
+                if self.verify_checksums && sha256::compute_checksum(File::open(&output_path)?)? != expected {
+                    return Err(EctarError::ChecksumMismatch {
+                        file: path.to_string_lossy().to_string(),
+                    });
+                }
+            }
 
             file_count += 1;
         }
@@ -562,17 +1281,79 @@ impl ArchiveExtractor {
     }
 }
 
+/// Whether a path holds a filter's bytes anywhere, compared raw so names
+/// that are not valid UTF-8 match exactly
+fn contains_bytes(path: &OsStr, filter: &OsStr) -> bool {
+    let (path, filter) = (path.as_encoded_bytes(), filter.as_encoded_bytes());
+    filter.is_empty() || path.windows(filter.len()).any(|window| window == filter)
+}
+
+/// Chunk outcomes gathered by a decoding thread
+#[derive(Default)]
+struct DecodeProgress {
+    chunks_recovered: usize,
+    chunks_failed: Vec<usize>,
+    /// Shard files found to be corrupt by `hunt_corruption`
+    corrupt_shards: Vec<PathBuf>,
+}
+
+/// Split the selected chunks into runs of consecutive chunks; each run is a
+/// contiguous stretch of the tar stream
+fn chunk_runs<'a>(
+    chunks: &[&'a ChunkInfo],
+    selected_chunks: &Option<BTreeSet<usize>>,
+) -> Vec<Vec<&'a ChunkInfo>> {
+    let mut runs: Vec<Vec<&ChunkInfo>> = Vec::new();
+    let mut in_run = false;
+    for chunk_info in chunks {
+        if is_selected(selected_chunks, chunk_info.chunk_number) {
+            if !in_run {
+                runs.push(Vec::new());
+            }
+            runs.last_mut().expect("run exists").push(chunk_info);
+            in_run = true;
+        } else {
+            in_run = false;
+        }
+    }
+    runs
+}
+
+fn is_selected(selected_chunks: &Option<BTreeSet<usize>>, chunk_number: usize) -> bool {
+    selected_chunks
+        .as_ref()
+        .map_or(true, |selected| selected.contains(&chunk_number))
+}
+
+#[derive(Default)]
 pub struct ExtractionMetadata {
     pub chunks_total: usize,
     pub chunks_recovered: usize,
     pub chunks_failed: usize,
+    /// Chunks not decoded because they hold no selected files
+    pub chunks_skipped: usize,
     pub files_extracted: usize,
+    /// Shard files found to be corrupt by `hunt_corruption`
+    pub corrupt_shards: Vec<PathBuf>,
+}
+
+impl ExtractionMetadata {
+    /// Add up the results of extracting another archive of a chain
+    fn absorb(&mut self, other: ExtractionMetadata) {
+        self.chunks_total += other.chunks_total;
+        self.chunks_recovered += other.chunks_recovered;
+        self.chunks_failed += other.chunks_failed;
+        self.chunks_skipped += other.chunks_skipped;
+        self.files_extracted += other.files_extracted;
+        self.corrupt_shards.extend(other.corrupt_shards);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::archive::create::ArchiveBuilder;
+    use crate::compression;
     use std::fs::{self, File};
     use std::io::Write as IoWriteTrait;
     use tempfile::TempDir;
@@ -593,6 +1374,17 @@ mod tests {
         archive_base
     }
 
+    /// Rewrite the archive's index after applying `edit` to it
+    fn edit_index(archive_base: &str, edit: impl FnOnce(&mut ArchiveIndex)) {
+        let index_path = PathBuf::from(format!("{}.index.zst", archive_base));
+        let extractor = ArchiveExtractor::new(String::new(), None);
+        let mut index = extractor.read_index(&index_path).unwrap();
+        edit(&mut index);
+
+        let json = serde_json::to_string(&index).unwrap();
+        compression::compress(json.as_bytes(), File::create(&index_path).unwrap(), 3).unwrap();
+    }
+
     fn create_multi_file_archive(temp_dir: &TempDir) -> String {
         let test_dir = temp_dir.path().join("testdata");
         fs::create_dir(&test_dir).unwrap();
@@ -674,6 +1466,21 @@ mod tests {
         assert_eq!(extractor.strip_components, 2);
     }
 
+    #[test]
+    fn test_threads_setter() {
+        let extractor = ArchiveExtractor::new("test".to_string(), None).threads(4);
+        assert_eq!(extractor.threads, 4);
+    }
+
+    #[test]
+    fn test_hunt_corruption_setter() {
+        let extractor = ArchiveExtractor::new("pattern".to_string(), None)
+            .hunt_corruption(true)
+            .hunt_attempts(50);
+        assert!(extractor.hunt_corruption);
+        assert_eq!(extractor.hunt_attempts, 50);
+    }
+
     #[test]
     fn test_extract_basic() {
         let temp_dir = TempDir::new().unwrap();
@@ -850,6 +1657,910 @@ mod tests {
         assert!(metadata.files_extracted >= 1);
     }
 
+    #[test]
+    fn test_extract_compressed_checksum_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, b"Checksummed content");
+        edit_index(&archive_base, |index| {
+            index.chunks[0].checksum = format!("sha256:{}", "0".repeat(64));
+        });
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let result = ArchiveExtractor::new(pattern, Some(temp_dir.path().join("extract"))).extract();
+
+        assert!(matches!(
+            result,
+            Err(EctarError::ChunkChecksumMismatch { chunk: 1, stage: "compressed" })
+        ));
+    }
+
+    #[test]
+    fn test_extract_uncompressed_checksum_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, b"Checksummed content");
+        edit_index(&archive_base, |index| {
+            index.chunks[0].uncompressed_checksum = Some(format!("sha256:{}", "0".repeat(64)));
+        });
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let result = ArchiveExtractor::new(pattern, Some(temp_dir.path().join("extract"))).extract();
+
+        assert!(matches!(
+            result,
+            Err(EctarError::ChunkChecksumMismatch { chunk: 1, stage: "uncompressed" })
+        ));
+    }
+
+    #[test]
+    fn test_extract_checksum_mismatch_ignored_without_verify() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, b"Checksummed content");
+        edit_index(&archive_base, |index| {
+            index.chunks[0].checksum = format!("sha256:{}", "0".repeat(64));
+        });
+
+        let extract_dir = temp_dir.path().join("extract");
+        let pattern = format!("{}.c*.s*", archive_base);
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .verify_checksums(false)
+            .extract()
+            .unwrap();
+
+        assert_eq!(fs::read(extract_dir.join("test.txt")).unwrap(), b"Checksummed content");
+    }
+
+    #[test]
+    fn test_extract_hunt_corruption() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, b"Content that suffers silent bit rot");
+        // Without shard checksums nothing flags the bad shard before decoding
+        edit_index(&archive_base, |index| {
+            index.chunks[0].shard_checksums.clear();
+        });
+
+        let bad_shard = PathBuf::from(format!("{}.c001.s00", archive_base));
+        let mut bytes = fs::read(&bad_shard).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xFF;
+        fs::write(&bad_shard, bytes).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let result = ArchiveExtractor::new(pattern.clone(), Some(temp_dir.path().join("plain"))).extract();
+        assert!(result.is_err());
+
+        let extract_dir = temp_dir.path().join("hunted");
+        let metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .hunt_corruption(true)
+            .extract()
+            .unwrap();
+
+        assert_eq!(metadata.chunks_recovered, 1);
+        assert_eq!(metadata.corrupt_shards, vec![bad_shard]);
+        assert_eq!(
+            fs::read(extract_dir.join("test.txt")).unwrap(),
+            b"Content that suffers silent bit rot"
+        );
+    }
+
+    #[test]
+    fn test_extract_without_index_checks_manifest_checksums() {
+        use crate::chunking::StreamingErasureChunkingWriter;
+        use crate::index::format::FileType;
+
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = temp_dir.path().join("archive");
+
+        // A chunk whose manifest disagrees with the file it holds
+        let mut writer = StreamingErasureChunkingWriter::new(archive_base.clone(), 1024 * 1024, 3, 3, 2);
+        writer.add_manifest_entry(FileEntry {
+            path: "a.txt".to_string(),
+            chunk: 1,
+            offset: 0,
+            stream_offset: None,
+            header_size: None,
+            size: 5,
+            compressed_size: None,
+            checksum: Some(sha256::checksum_bytes(b"other")),
+            mode: 0o644,
+            mtime: chrono::Utc::now(),
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            entry_type: FileType::File,
+            target: None,
+            spans_chunks: None,
+            blocks: None,
+            attributes: None,
+            sparse: false,
+            stored_size: None,
+            device_major: None,
+            device_minor: None,
+            path_bytes: None,
+            target_bytes: None,
+        });
+        {
+            let mut tar_builder = tar::Builder::new(&mut writer);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar_builder.append_data(&mut header, "a.txt", &b"hello"[..]).unwrap();
+            tar_builder.finish().unwrap();
+        }
+        writer.finish().unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base.display());
+        let result = ArchiveExtractor::new(pattern.clone(), Some(temp_dir.path().join("checked"))).extract();
+        assert!(matches!(result, Err(EctarError::ChecksumMismatch { ref file }) if file == "a.txt"));
+
+        let extract_dir = temp_dir.path().join("unchecked");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .verify_checksums(false)
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(extract_dir.join("a.txt")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_extract_without_index_applies_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_multi_file_archive(&temp_dir);
+        fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+        for shard in 0..6 {
+            fs::remove_file(format!("{}.index.s{:02}", archive_base, shard)).unwrap();
+        }
+
+        let extract_dir = temp_dir.path().join("extract");
+        let pattern = format!("{}.c*.s*", archive_base);
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .file_filters(vec!["file1".to_string()])
+            .strip_components(1)
+            .extract()
+            .unwrap();
+
+        assert_eq!(fs::read(extract_dir.join("file1.txt")).unwrap(), b"Content of file 1");
+        assert!(!extract_dir.join("file2.txt").exists());
+        assert!(!extract_dir.join("subdir").exists());
+    }
+
+    /// Pseudo-random file contents that zstd cannot shrink much
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2654435761).max(1);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// Many files over many small chunks; returns the archive base
+    fn create_many_chunk_archive(temp_dir: &TempDir) -> String {
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        for i in 0..12u32 {
+            fs::write(test_dir.join(format!("file{:02}.bin", i)), noise(3000, i + 1)).unwrap();
+        }
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(4096))
+            .create(&[test_dir])
+            .unwrap();
+        archive_base
+    }
+
+    fn delete_chunk_shards(archive_base: &str, chunk: usize) {
+        for shard in 0..5 {
+            let _ = fs::remove_file(encoder::format_shard_path(archive_base, chunk, shard));
+        }
+    }
+
+    #[test]
+    fn test_extract_single_file_decodes_only_its_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_many_chunk_archive(&temp_dir);
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+
+        let entry = index.files.iter().find(|f| f.path == "data/file07.bin").unwrap();
+        let needed = entry.spans_chunks.clone().unwrap_or_else(|| vec![entry.chunk]);
+
+        // Chunks the file does not live in are not needed at all
+        for chunk in &index.chunks {
+            if !needed.contains(&chunk.chunk_number) {
+                delete_chunk_shards(&archive_base, chunk.chunk_number);
+            }
+        }
+
+        let extract_dir = temp_dir.path().join("extract");
+        let pattern = format!("{}.c*.s*", archive_base);
+        let metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .file_filters(vec!["data/file07.bin".to_string()])
+            .extract()
+            .unwrap();
+
+        assert_eq!(metadata.files_extracted, 1);
+        assert_eq!(metadata.chunks_failed, 0);
+        assert_eq!(metadata.chunks_skipped, index.chunks.len() - needed.len());
+        assert_eq!(
+            fs::read(extract_dir.join("data/file07.bin")).unwrap(),
+            noise(3000, 8)
+        );
+        assert!(!extract_dir.join("data/file06.bin").exists());
+    }
+
+    #[test]
+    fn test_extract_every_file_by_random_access() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_many_chunk_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // Each file lands at a different point of its chunks, including
+        // entries that start mid-chunk after a spanning file
+        for i in 0..12u32 {
+            let name = format!("data/file{:02}.bin", i);
+            let extract_dir = temp_dir.path().join(format!("extract{}", i));
+            let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+                .file_filters(vec![name.clone()])
+                .extract()
+                .unwrap();
+
+            assert_eq!(metadata.files_extracted, 1, "{}", name);
+            assert_eq!(fs::read(extract_dir.join(&name)).unwrap(), noise(3000, i + 1));
+        }
+    }
+
+    #[test]
+    fn test_extract_random_access_without_recorded_offsets() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_many_chunk_archive(&temp_dir);
+
+        // Archives from before offsets were recorded are located by scanning
+        edit_index(&archive_base, |index| {
+            for entry in &mut index.files {
+                entry.offset = 0;
+                entry.stream_offset = None;
+                entry.header_size = None;
+            }
+        });
+
+        let extract_dir = temp_dir.path().join("extract");
+        let pattern = format!("{}.c*.s*", archive_base);
+        let metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .file_filters(vec!["data/file09.bin".to_string()])
+            .extract()
+            .unwrap();
+
+        assert_eq!(metadata.files_extracted, 1);
+        assert!(metadata.chunks_skipped > 0);
+        assert_eq!(fs::read(extract_dir.join("data/file09.bin")).unwrap(), noise(3000, 10));
+    }
+
+    #[test]
+    fn test_extract_no_selected_files_decodes_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_many_chunk_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let metadata = ArchiveExtractor::new(pattern, Some(temp_dir.path().join("extract")))
+            .file_filters(vec!["nothing-matches".to_string()])
+            .extract()
+            .unwrap();
+
+        assert_eq!(metadata.files_extracted, 0);
+        assert_eq!(metadata.chunks_recovered, 0);
+        assert_eq!(metadata.chunks_skipped, metadata.chunks_total);
+    }
+
+    #[test]
+    fn test_extract_threaded_matches_sequential() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_many_chunk_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // One lost chunk: partial mode must still report it in order
+        delete_chunk_shards(&archive_base, 3);
+
+        let run = |name: &str, threads: usize| {
+            let extract_dir = temp_dir.path().join(name);
+            let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+                .partial(true)
+                .threads(threads)
+                .extract()
+                .unwrap();
+            (metadata, extract_dir)
+        };
+
+        let (sequential, sequential_dir) = run("sequential", 1);
+        let (threaded, threaded_dir) = run("threaded", 4);
+
+        assert!(threaded.chunks_total > 4);
+        assert_eq!(threaded.chunks_recovered, sequential.chunks_recovered);
+        assert_eq!(threaded.chunks_failed, 1);
+        assert_eq!(threaded.files_extracted, sequential.files_extracted);
+        for i in 0..12u32 {
+            let name = format!("data/file{:02}.bin", i);
+            assert_eq!(
+                fs::read(threaded_dir.join(&name)).ok(),
+                fs::read(sequential_dir.join(&name)).ok(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_incremental_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("keep.txt"), b"kept throughout").unwrap();
+        fs::write(test_dir.join("edit.txt"), b"first version").unwrap();
+        fs::write(test_dir.join("gone.txt"), b"deleted in inc1").unwrap();
+
+        let create = |name: &str, parent: Option<&str>| {
+            let base = temp_dir.path().join(name).to_string_lossy().to_string();
+            ArchiveBuilder::new(base.clone())
+                .data_shards(3)
+                .parity_shards(2)
+                .chunk_size(Some(4096))
+                .incremental_from(parent.map(|p| temp_dir.path().join(format!("{}.index.zst", p))))
+                .create(std::slice::from_ref(&test_dir))
+                .unwrap();
+            format!("{}.c*.s*", base)
+        };
+
+        create("full", None);
+        fs::remove_file(test_dir.join("gone.txt")).unwrap();
+        fs::write(test_dir.join("edit.txt"), b"second, longer version").unwrap();
+        fs::write(test_dir.join("new.txt"), b"added in inc1").unwrap();
+        create("inc1", Some("full"));
+        fs::write(test_dir.join("new.txt"), b"changed in inc2").unwrap();
+        let inc2 = create("inc2", Some("inc1"));
+
+        let restore_dir = temp_dir.path().join("restore");
+        let metadata = ArchiveExtractor::new(inc2.clone(), Some(restore_dir.clone()))
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.chunks_failed, 0);
+
+        let restored = restore_dir.join("data");
+        assert_eq!(fs::read(restored.join("keep.txt")).unwrap(), b"kept throughout");
+        assert_eq!(fs::read(restored.join("edit.txt")).unwrap(), b"second, longer version");
+        assert_eq!(fs::read(restored.join("new.txt")).unwrap(), b"changed in inc2");
+        assert!(!restored.join("gone.txt").exists());
+
+        // Without the chain only the archive's own entries come out
+        let own_dir = temp_dir.path().join("own");
+        ArchiveExtractor::new(inc2, Some(own_dir.clone()))
+            .follow_chain(false)
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(own_dir.join("data/new.txt")).unwrap(), b"changed in inc2");
+        assert!(!own_dir.join("data/keep.txt").exists());
+        assert!(!own_dir.join("data/edit.txt").exists());
+    }
+
+    #[test]
+    fn test_extract_incremental_missing_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("file.txt");
+        fs::write(&test_file, b"content").unwrap();
+
+        let full = temp_dir.path().join("full").to_string_lossy().to_string();
+        ArchiveBuilder::new(full.clone())
+            .chunk_size(Some(4096))
+            .create(std::slice::from_ref(&test_file))
+            .unwrap();
+        let inc = temp_dir.path().join("inc").to_string_lossy().to_string();
+        ArchiveBuilder::new(inc.clone())
+            .chunk_size(Some(4096))
+            .incremental_from(Some(PathBuf::from(format!("{}.index.zst", full))))
+            .create(&[test_file])
+            .unwrap();
+
+        fs::remove_file(format!("{}.index.zst", full)).unwrap();
+        for shard in 0..15 {
+            let _ = fs::remove_file(format!("{}.index.s{:02}", full, shard));
+        }
+
+        let result = ArchiveExtractor::new(format!("{}.c*.s*", inc), Some(temp_dir.path().join("out")))
+            .extract();
+        assert!(matches!(result, Err(EctarError::BrokenChain(_))));
+    }
+
+    #[test]
+    fn test_extract_restores_extended_attributes() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let tagged = test_dir.join("tagged.bin");
+        fs::write(&tagged, noise(20_000, 5)).unwrap();
+        fs::write(test_dir.join("plain.txt"), b"no attributes").unwrap();
+
+        // Not every filesystem a test may run on has user attributes or ACLs
+        if xattr::set(&tagged, "user.provenance", b"instrument 7").is_err() {
+            return;
+        }
+        let acl = "user::rw-,user:1000:r--,group::r--,mask::r--,other::r--";
+        let acl_attributes = crate::index::format::ExtendedAttributes {
+            acl_access: Some(acl.to_string()),
+            ..Default::default()
+        };
+        let has_acl = xattrs::apply(&tagged, &acl_attributes).is_ok();
+
+        // Deduplicated, so the attributes share a PAX header with the block list
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .chunk_size(Some(8192))
+            .dedup(true)
+            .dedup_block_size(4096)
+            .create(&[test_dir])
+            .unwrap();
+
+        let index = crate::index::reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let entry = index.files.iter().find(|f| f.path == "data/tagged.bin").unwrap();
+        let recorded = entry.attributes.as_ref().unwrap();
+        assert_eq!(recorded.xattrs.get("user.provenance").map(Vec::as_slice), Some(&b"instrument 7"[..]));
+        if has_acl {
+            assert_eq!(recorded.acl_access.as_deref(), Some(acl));
+        }
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let extract_dir = temp_dir.path().join("out");
+        ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        let extracted = extract_dir.join("data/tagged.bin");
+        assert_eq!(fs::read(&extracted).unwrap(), noise(20_000, 5));
+        let restored = xattrs::read(&extracted).unwrap().unwrap();
+        assert_eq!(restored.xattrs, recorded.xattrs);
+        assert_eq!(restored.acl_access, recorded.acl_access);
+
+        let extract_dir = temp_dir.path().join("bare");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .restore_attributes(false)
+            .extract()
+            .unwrap();
+        let bare = xattrs::read(&extract_dir.join("data/tagged.bin")).unwrap();
+        assert!(bare.map_or(true, |a| a.xattrs.is_empty() && a.acl_access.is_none()));
+    }
+
+    #[test]
+    fn test_extract_restores_hard_links() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.bin"), noise(20_000, 3)).unwrap();
+        fs::write(test_dir.join("m.bin"), noise(9000, 4)).unwrap();
+        fs::hard_link(test_dir.join("a.bin"), test_dir.join("z.bin")).unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .chunk_size(Some(4096))
+            .create(&[test_dir])
+            .unwrap();
+
+        // The second name is a link to the first, stored chunks earlier
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let first = index.files.iter().find(|f| f.path == "data/a.bin").unwrap();
+        let link = index.files.iter().find(|f| f.path == "data/z.bin").unwrap();
+        assert_eq!(first.entry_type, FileType::File);
+        assert_eq!(link.entry_type, FileType::Hardlink);
+        assert_eq!(link.target.as_deref(), Some("data/a.bin"));
+        assert_eq!(link.size, 20_000);
+        assert_eq!(link.checksum, first.checksum);
+        assert!(link.chunk > first.spans_chunks.as_ref().map_or(first.chunk, |c| c[c.len() - 1]));
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let extract_dir = temp_dir.path().join("out");
+        let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.files_extracted, 4);
+        let a = fs::metadata(extract_dir.join("data/a.bin")).unwrap();
+        let z = fs::metadata(extract_dir.join("data/z.bin")).unwrap();
+        assert_eq!(a.ino(), z.ino());
+        assert_eq!(fs::read(extract_dir.join("data/z.bin")).unwrap(), noise(20_000, 3));
+
+        // A link selected without its target gets the target's data
+        let extract_dir = temp_dir.path().join("link-only");
+        let metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .file_filters(vec!["data/z.bin".to_string()])
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.files_extracted, 1);
+        assert_eq!(fs::read(extract_dir.join("data/z.bin")).unwrap(), noise(20_000, 3));
+        assert!(!extract_dir.join("data/a.bin").exists());
+        assert!(!extract_dir.join("data/m.bin").exists());
+    }
+
+    #[test]
+    fn test_hard_link_target_must_stay_inside_output_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let host_file = temp_dir.path().join("host-secret.txt");
+        fs::write(&host_file, b"not for the archive").unwrap();
+        let output_dir = temp_dir.path().join("out");
+        fs::create_dir(&output_dir).unwrap();
+
+        for target in [host_file.clone(), PathBuf::from("../host-secret.txt")] {
+            let mut builder = tar::Builder::new(Vec::new());
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Link);
+            header.set_size(0);
+            builder.append_link(&mut header, "evil", &target).unwrap();
+            let tar_bytes = builder.into_inner().unwrap();
+
+            let extractor = ArchiveExtractor::new(String::new(), Some(output_dir.clone()));
+            let mut block_store = BlockStore::new(&output_dir);
+            let result = extractor.unpack_tar(tar_bytes.as_slice(), false, &mut block_store, true);
+            assert!(matches!(result, Err(EctarError::Tar(_))), "{} was linked", target.display());
+            assert!(!output_dir.join("evil").exists());
+        }
+        assert_eq!(fs::read(&host_file).unwrap(), b"not for the archive");
+    }
+
+    #[test]
+    fn test_extract_recreates_holes() {
+        use std::os::unix::fs::{FileExt, MetadataExt};
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.txt"), b"before the image").unwrap();
+        let size = 8 << 20;
+        let image = File::create(test_dir.join("disk.img")).unwrap();
+        image.set_len(size).unwrap();
+        image.write_all_at(&noise(6000, 2), 0).unwrap();
+        image.write_all_at(&noise(3000, 3), 5 << 20).unwrap();
+        drop(image);
+        fs::write(test_dir.join("z.txt"), b"after the image").unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .chunk_size(Some(4096))
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let entry = index.files.iter().find(|f| f.path == "data/disk.img").unwrap();
+        assert_eq!(entry.size, size);
+        // Filesystems without holes store the image densely
+        if !entry.sparse {
+            return;
+        }
+        assert!(entry.stored_size.unwrap() < 64 * 1024);
+        assert!(index.chunks.len() < 10);
+
+        let original = fs::read(test_dir.join("disk.img")).unwrap();
+        let pattern = format!("{}.c*.s*", archive_base);
+        for (dir, filters) in [("out", vec![]), ("image-only", vec!["data/disk.img".to_string()])] {
+            let extract_dir = temp_dir.path().join(dir);
+            ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+                .file_filters(filters)
+                .extract()
+                .unwrap();
+            let extracted = extract_dir.join("data/disk.img");
+            assert_eq!(fs::read(&extracted).unwrap(), original);
+            assert!(fs::metadata(&extracted).unwrap().blocks() * 512 < size / 8);
+        }
+        assert_eq!(fs::read(temp_dir.path().join("out/data/z.txt")).unwrap(), b"after the image");
+    }
+
+    #[test]
+    fn test_extract_recreates_special_files() {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("rootfs");
+        fs::create_dir_all(test_dir.join("dev")).unwrap();
+        fs::write(test_dir.join("etc.conf"), b"setting=1").unwrap();
+        let fifo = std::ffi::CString::new(test_dir.join("dev/initctl").to_str().unwrap()).unwrap();
+        // SAFETY: fifo is a valid NUL-terminated path
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        // Sockets cannot be archived and are left out
+        let _socket = std::os::unix::net::UnixListener::bind(test_dir.join("dev/log")).unwrap();
+        let has_device = special::can_create_devices()
+            && std::process::Command::new("mknod")
+                .arg(test_dir.join("dev/null"))
+                .args(["c", "1", "3"])
+                .status()
+                .is_ok_and(|s| s.success());
+        if has_device {
+            fs::set_permissions(test_dir.join("dev/null"), fs::Permissions::from_mode(0o666)).unwrap();
+        }
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .chunk_size(Some(4096))
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        assert!(index.files.iter().all(|f| f.path != "rootfs/dev/log"));
+        let pipe = index.files.iter().find(|f| f.path == "rootfs/dev/initctl").unwrap();
+        assert_eq!(pipe.entry_type, FileType::Fifo);
+        assert_eq!(pipe.device_major, None);
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let extract_dir = temp_dir.path().join("out");
+        let metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.files_extracted, index.files.len());
+        let restored = fs::symlink_metadata(extract_dir.join("rootfs/dev/initctl")).unwrap();
+        assert!(restored.file_type().is_fifo());
+        assert_eq!(restored.permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read(extract_dir.join("rootfs/etc.conf")).unwrap(), b"setting=1");
+
+        if has_device {
+            let null = index.files.iter().find(|f| f.path == "rootfs/dev/null").unwrap();
+            assert_eq!(null.entry_type, FileType::CharDevice);
+            assert_eq!((null.device_major, null.device_minor), (Some(1), Some(3)));
+            let restored = fs::symlink_metadata(extract_dir.join("rootfs/dev/null")).unwrap();
+            assert!(restored.file_type().is_char_device());
+            assert_eq!(special::device_numbers(&restored), (1, 3));
+            assert_eq!(restored.permissions().mode() & 0o777, 0o666);
+        }
+    }
+
+    #[test]
+    fn test_extract_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("share");
+        fs::create_dir(&test_dir).unwrap();
+        // A Latin-1 name, and the UTF-8 name it turns into when read lossily
+        let latin1 = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(test_dir.join(latin1), b"latin-1").unwrap();
+        fs::write(test_dir.join("caf\u{fffd}.txt"), b"utf-8").unwrap();
+        std::os::unix::fs::symlink(latin1, test_dir.join("menu")).unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .chunk_size(Some(4096))
+            .create(std::slice::from_ref(&test_dir))
+            .unwrap();
+
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let raw = index.files.iter().find(|f| f.path_bytes.is_some()).unwrap();
+        assert_eq!(raw.path, "share/caf\u{fffd}.txt");
+        assert_eq!(raw.path_bytes.as_deref(), Some(&b"share/caf\xe9.txt"[..]));
+        assert_eq!(raw.archive_path(), Path::new("share").join(latin1));
+        let link = index.files.iter().find(|f| f.path == "share/menu").unwrap();
+        assert_eq!(link.target_bytes.as_deref(), Some(&b"caf\xe9.txt"[..]));
+        assert_eq!(index.files.iter().filter(|f| f.path == raw.path).count(), 2);
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let extract_dir = temp_dir.path().join("out");
+        ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        let out = extract_dir.join("share");
+        assert_eq!(fs::read(out.join(latin1)).unwrap(), b"latin-1");
+        assert_eq!(fs::read(out.join("caf\u{fffd}.txt")).unwrap(), b"utf-8");
+        assert_eq!(fs::read_link(out.join("menu")).unwrap(), Path::new(latin1));
+
+        // The raw name selects only its own file, not the lossy look-alike
+        let extract_dir = temp_dir.path().join("selected");
+        let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .file_filters(vec![Path::new("share").join(latin1).into_os_string()])
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.files_extracted, 1);
+        assert_eq!(fs::read(extract_dir.join("share").join(latin1)).unwrap(), b"latin-1");
+        assert!(!extract_dir.join("share/caf\u{fffd}.txt").exists());
+
+        // Without the index, each file is checked against its own manifest entry
+        fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+        for shard in 0..15 {
+            let _ = fs::remove_file(format!("{}.index.s{:02}", archive_base, shard));
+        }
+        let extract_dir = temp_dir.path().join("no-index");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(extract_dir.join("share").join(latin1)).unwrap(), b"latin-1");
+        assert_eq!(fs::read(extract_dir.join("share/caf\u{fffd}.txt")).unwrap(), b"utf-8");
+    }
+
+    #[test]
+    fn test_extract_deduplicated_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let shared = noise(40_000, 3);
+        fs::write(test_dir.join("first.bin"), &shared).unwrap();
+        fs::write(test_dir.join("middle.bin"), noise(40_000, 4)).unwrap();
+        fs::write(test_dir.join("second.bin"), &shared).unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(8192))
+            .dedup(true)
+            .dedup_block_size(4096)
+            .create(&[test_dir])
+            .unwrap();
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let extract_dir = temp_dir.path().join("all");
+        let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.files_extracted, 4);
+        assert_eq!(fs::read(extract_dir.join("data/first.bin")).unwrap(), shared);
+        assert_eq!(fs::read(extract_dir.join("data/second.bin")).unwrap(), shared);
+        assert_eq!(fs::read(extract_dir.join("data/middle.bin")).unwrap(), noise(40_000, 4));
+        assert_eq!(fs::read_dir(&extract_dir).unwrap().count(), 1);
+
+        // The second copy's blocks live where the first copy was stored
+        let extract_dir = temp_dir.path().join("one");
+        let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .file_filters(vec!["data/second.bin".to_string()])
+            .extract()
+            .unwrap();
+        assert!(metadata.chunks_skipped > 0);
+        assert_eq!(fs::read(extract_dir.join("data/second.bin")).unwrap(), shared);
+        assert!(!extract_dir.join("data/first.bin").exists());
+
+        // Without an index the blocks are found in the tar stream
+        for index_file in glob::glob(&format!("{}.index*", archive_base)).unwrap() {
+            fs::remove_file(index_file.unwrap()).unwrap();
+        }
+        let extract_dir = temp_dir.path().join("no-index");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone())).extract().unwrap();
+        assert_eq!(fs::read(extract_dir.join("data/second.bin")).unwrap(), shared);
+        assert_eq!(fs::read(extract_dir.join("data/middle.bin")).unwrap(), noise(40_000, 4));
+    }
+
+    #[test]
+    fn test_extract_encrypted_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        for i in 0..4u32 {
+            fs::write(test_dir.join(format!("file{}.bin", i)), noise(5000, i + 1)).unwrap();
+        }
+        let passphrase = || Some(Secret::Passphrase("correct horse".to_string()));
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(4096))
+            .encryption(passphrase())
+            .kdf_params(crypto::TEST_KDF)
+            .encrypt_index(true)
+            .create(&[test_dir])
+            .unwrap();
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let extract_dir = temp_dir.path().join("all");
+        let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .decryption(passphrase())
+            .extract()
+            .unwrap();
+        assert_eq!(metadata.files_extracted, 5);
+        assert_eq!(fs::read(extract_dir.join("data/file2.bin")).unwrap(), noise(5000, 3));
+
+        // Random access decrypts only the chunks it needs
+        let extract_dir = temp_dir.path().join("one");
+        let metadata = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .decryption(passphrase())
+            .file_filters(vec!["data/file3.bin".to_string()])
+            .extract()
+            .unwrap();
+        assert!(metadata.chunks_skipped > 0);
+        assert_eq!(fs::read(extract_dir.join("data/file3.bin")).unwrap(), noise(5000, 4));
+
+        let wrong = Some(Secret::Passphrase("wrong horse".to_string()));
+        for secret in [None, wrong] {
+            let result = ArchiveExtractor::new(pattern.clone(), Some(temp_dir.path().join("denied")))
+                .decryption(secret)
+                .extract();
+            assert!(matches!(result, Err(EctarError::Decryption(_))));
+        }
+    }
+
+    #[test]
+    fn test_extract_encrypted_archive_without_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("file.bin"), noise(10_000, 1)).unwrap();
+        let key = || Some(Secret::Key([5u8; 32]));
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(4096))
+            .encryption(key())
+            .create(&[test_dir])
+            .unwrap();
+        for index_file in glob::glob(&format!("{}.index*", archive_base)).unwrap() {
+            fs::remove_file(index_file.unwrap()).unwrap();
+        }
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // Every chunk carries what is needed to decrypt it
+        let extract_dir = temp_dir.path().join("extract");
+        ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()))
+            .decryption(key())
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(extract_dir.join("data/file.bin")).unwrap(), noise(10_000, 1));
+
+        let result = ArchiveExtractor::new(pattern, Some(temp_dir.path().join("denied"))).extract();
+        assert!(matches!(result, Err(EctarError::Decryption(_))));
+    }
+
+    #[test]
+    fn test_extract_rejects_plaintext_in_place_of_sealed_data() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("file.bin"), noise(10_000, 1)).unwrap();
+        let key = || Some(Secret::Key([6u8; 32]));
+
+        let create = |name: &str, secret: Option<Secret>| {
+            let base = temp_dir.path().join(name).to_string_lossy().to_string();
+            ArchiveBuilder::new(base.clone())
+                .data_shards(3)
+                .parity_shards(2)
+                .chunk_size(Some(4096))
+                .encrypt_index(secret.is_some())
+                .encryption(secret)
+                .create(std::slice::from_ref(&test_dir))
+                .unwrap();
+            base
+        };
+        let archive_base = create("archive", key());
+        let plain_base = create("plain", None);
+        let index_path = PathBuf::from(format!("{}.index.zst", archive_base));
+        let encryption = reader::read_index_with_keys(&index_path, Some(&KeyRing::new(key().unwrap())))
+            .unwrap()
+            .parameters
+            .encryption;
+
+        // Swap in the plaintext archive's chunks and index, keeping the
+        // encrypted archive's parameters so the key still checks out
+        for shard in glob::glob(&format!("{}.c*", archive_base)).unwrap() {
+            fs::remove_file(shard.unwrap()).unwrap();
+        }
+        for shard in glob::glob(&format!("{}.c*", plain_base)).unwrap() {
+            let shard = shard.unwrap();
+            let name = shard.file_name().unwrap().to_string_lossy().replacen("plain", "archive", 1);
+            fs::copy(&shard, temp_dir.path().join(name)).unwrap();
+        }
+        let mut index = reader::read_index(Path::new(&format!("{}.index.zst", plain_base))).unwrap();
+        index.parameters.encryption = encryption;
+        crate::index::builder::write_index(&index, &archive_base).unwrap();
+
+        let extract = || {
+            ArchiveExtractor::new(format!("{}.c*.s*", archive_base), Some(temp_dir.path().join("extract")))
+                .decryption(key())
+                .extract()
+        };
+        assert!(matches!(extract(), Err(EctarError::Decryption(_))));
+
+        // With the index admitted as left in the clear, the chunks give it away
+        index.parameters.encryption.as_mut().unwrap().index_encrypted = false;
+        crate::index::builder::write_index(&index, &archive_base).unwrap();
+        assert!(matches!(extract(), Err(EctarError::Decryption(_))));
+        assert!(!temp_dir.path().join("extract/data/file.bin").exists());
+    }
+
     #[test]
     fn test_extract_with_verify_disabled() {
         let temp_dir = TempDir::new().unwrap();
@@ -872,7 +2583,9 @@ mod tests {
             chunks_total: 5,
             chunks_recovered: 4,
             chunks_failed: 1,
+            chunks_skipped: 0,
             files_extracted: 10,
+            corrupt_shards: Vec::new(),
         };
 
         assert_eq!(metadata.chunks_total, 5);
@@ -0,0 +1,276 @@
+use crate::erasure::decoder::ShardData;
+use crate::erasure::encoder;
+use crate::error::{EctarError, Result};
+use crate::index::format::{ArchiveParameters, ChunkInfo, FileEntry};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// What an archive is being created from and with; a run can only be
+/// resumed with the same settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalHeader {
+    pub parameters: ArchiveParameters,
+    pub no_compression: bool,
+    /// Average block size, if file data is deduplicated
+    pub dedup_block_size: Option<u64>,
+    /// Paths given on the command line
+    pub paths: Vec<String>,
+}
+
+/// Position in the list of files to pick up from, right after a chunk
+///
+/// The file at `files` is the one whose data crosses the end of the chunk
+/// (or starts right there); it is written again from `stream_offset`, with
+/// the bytes the chunk already holds discarded. That only works if the
+/// file is unchanged, so its size and modification time are kept too.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ResumePoint {
+    /// Files whose entries lie entirely in this and earlier chunks
+    pub files: usize,
+    /// Blocks of deduplicated files stored before file `files`
+    pub blocks: usize,
+    /// Position in the tar stream where file `files` starts
+    pub stream_offset: u64,
+    /// Size of file `files` when it was written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Modification time of file `files` when it was written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<DateTime<Utc>>,
+}
+
+/// One completed chunk, appended once all of its shards are written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub chunk: ChunkInfo,
+    pub resume: ResumePoint,
+    /// Entries finished since the previous record
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileEntry>,
+    /// Blocks stored since the previous record
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<FileEntry>,
+}
+
+/// Append-only log of the chunks a running `create` has completed
+///
+/// The first line holds the header, each further line one record. A record
+/// is only appended (and synced) after its chunk's shards are complete, so
+/// a crash can at worst leave a torn last line, which loading ignores.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Path of the journal for an archive ("backup.journal")
+    pub fn path(output_base: &str) -> PathBuf {
+        PathBuf::from(format!("{}.journal", output_base))
+    }
+
+    /// Start a new journal, replacing any previous one
+    pub fn create(output_base: &str, header: &JournalHeader) -> Result<Self> {
+        let mut journal = Self {
+            file: File::create(Self::path(output_base))?,
+        };
+        journal.append(header)?;
+        Ok(journal)
+    }
+
+    /// Record a completed chunk
+    pub fn record(&mut self, record: &JournalRecord) -> Result<()> {
+        self.append(record)
+    }
+
+    fn append<T: Serialize>(&mut self, line: &T) -> Result<()> {
+        let mut bytes = serde_json::to_vec(line)?;
+        bytes.push(b'\n');
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Delete the journal of an archive that was completed
+    pub fn remove(output_base: &str) -> Result<()> {
+        match std::fs::remove_file(Self::path(output_base)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The state an interrupted `create` left in its journal
+#[derive(Debug)]
+pub struct JournalState {
+    pub header: JournalHeader,
+    pub records: Vec<JournalRecord>,
+}
+
+impl JournalState {
+    /// Read the journal of an archive
+    pub fn load(output_base: &str) -> Result<Self> {
+        let path = Journal::path(output_base);
+        let file = File::open(&path).map_err(|e| {
+            EctarError::InvalidParameters(format!("Cannot resume, no journal at {}: {}", path.display(), e))
+        })?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => {
+                return Err(EctarError::InvalidParameters(format!(
+                    "Cannot resume, {} is empty",
+                    path.display()
+                )))
+            }
+        };
+
+        let mut records = Vec::new();
+        for line in lines {
+            match serde_json::from_str::<JournalRecord>(&line?) {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    log::warn!("Ignoring torn journal record: {}", e);
+                    break;
+                }
+            }
+        }
+
+        Ok(Self { header, records })
+    }
+
+    /// Drop the records from the first chunk whose shards are no longer
+    /// intact onwards; returns the number of chunks kept
+    pub fn keep_intact_chunks(&mut self, output_base: &str) -> usize {
+        let intact = self
+            .records
+            .iter()
+            .take_while(|record| chunk_is_intact(output_base, &record.chunk))
+            .count();
+        if intact < self.records.len() {
+            log::warn!(
+                "Chunk {} of the interrupted run is damaged, writing it again",
+                self.records[intact].chunk.chunk_number
+            );
+        }
+        self.records.truncate(intact);
+        intact
+    }
+
+    /// Where to resume: after the last recorded chunk
+    pub fn resume_point(&self) -> Option<ResumePoint> {
+        self.records.last().map(|record| record.resume)
+    }
+
+    /// Completed chunks, in order
+    pub fn chunks(&self) -> Vec<ChunkInfo> {
+        self.records.iter().map(|record| record.chunk.clone()).collect()
+    }
+
+    /// Every entry recorded, in the order they were written
+    pub fn files(&self) -> Vec<FileEntry> {
+        self.records.iter().flat_map(|record| record.files.iter().cloned()).collect()
+    }
+
+    /// Every stored block recorded, in the order they were written
+    pub fn blocks(&self) -> Vec<FileEntry> {
+        self.records.iter().flat_map(|record| record.blocks.iter().cloned()).collect()
+    }
+}
+
+/// Whether all of a chunk's shard files exist and match their checksums
+fn chunk_is_intact(output_base: &str, chunk: &ChunkInfo) -> bool {
+    chunk.shard_checksums.iter().enumerate().all(|(shard_number, expected)| {
+        let path = encoder::format_shard_path(output_base, chunk.chunk_number, shard_number);
+        ShardData::from_file(&path).is_ok_and(|shard| shard.matches_checksum(expected))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    fn header() -> JournalHeader {
+        JournalHeader {
+            parameters: ArchiveParameters {
+                data_shards: 2,
+                parity_shards: 1,
+                chunk_size: Some(1024),
+                compression_level: 3,
+                shard_trailers: false,
+                stripe_size: None,
+                encryption: None,
+            },
+            no_compression: false,
+            dedup_block_size: None,
+            paths: vec!["data".to_string()],
+        }
+    }
+
+    fn record(chunk_number: usize) -> JournalRecord {
+        JournalRecord {
+            chunk: ChunkInfo {
+                chunk_number,
+                compressed_size: 10,
+                uncompressed_size: 1024,
+                shard_size: 5,
+                checksum: String::new(),
+                uncompressed_checksum: None,
+                shard_checksums: Vec::new(),
+            },
+            resume: ResumePoint {
+                files: chunk_number,
+                blocks: 0,
+                stream_offset: 512 * chunk_number as u64,
+                size: None,
+                mtime: None,
+            },
+            files: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+        let mut journal = Journal::create(&base, &header()).unwrap();
+        journal.record(&record(1)).unwrap();
+        journal.record(&record(2)).unwrap();
+
+        let state = JournalState::load(&base).unwrap();
+        assert_eq!(state.header, header());
+        assert_eq!(state.chunks().len(), 2);
+        assert_eq!(state.resume_point().unwrap().files, 2);
+
+        Journal::remove(&base).unwrap();
+        assert!(!Journal::path(&base).exists());
+        Journal::remove(&base).unwrap();
+    }
+
+    #[test]
+    fn test_torn_record_is_ignored() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+        let mut journal = Journal::create(&base, &header()).unwrap();
+        journal.record(&record(1)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(Journal::path(&base)).unwrap();
+        file.write_all(b"{\"chunk\":{\"chunk_num").unwrap();
+
+        let state = JournalState::load(&base).unwrap();
+        assert_eq!(state.records.len(), 1);
+    }
+
+    #[test]
+    fn test_missing_journal() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        assert!(matches!(JournalState::load(&base), Err(EctarError::InvalidParameters(_))));
+    }
+}
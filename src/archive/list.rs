@@ -1,15 +1,16 @@
-use crate::compression;
+use crate::crypto::{KeyRing, Secret};
 use crate::error::{EctarError, Result};
 use crate::index::format::{ArchiveIndex, FileEntry, FileType};
+use crate::index::reader;
 use crate::io::shard_reader;
-use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct ArchiveLister {
     input: String,
     filter_pattern: Option<String>,
     long_format: bool,
     output_format: OutputFormat,
+    keys: Option<KeyRing>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +27,7 @@ impl ArchiveLister {
             filter_pattern: None,
             long_format: false,
             output_format: OutputFormat::Text,
+            keys: None,
         }
     }
 
@@ -39,6 +41,12 @@ impl ArchiveLister {
         self
     }
 
+    /// Key or passphrase for an archive whose index is encrypted
+    pub fn decryption(mut self, secret: Option<Secret>) -> Self {
+        self.keys = secret.map(KeyRing::new);
+        self
+    }
+
     pub fn output_format(mut self, format: &str) -> Result<Self> {
         self.output_format = match format.to_lowercase().as_str() {
             "text" => OutputFormat::Text,
@@ -81,15 +89,8 @@ impl ArchiveLister {
         })
     }
 
-    fn read_index(&self, index_path: &PathBuf) -> Result<ArchiveIndex> {
-        let index_file = File::open(index_path)?;
-        let mut decoder = compression::create_decoder(index_file)?;
-
-        let mut json = String::new();
-        std::io::Read::read_to_string(&mut decoder, &mut json)?;
-
-        let index: ArchiveIndex = serde_json::from_str(&json)?;
-        Ok(index)
+    fn read_index(&self, index_path: &Path) -> Result<ArchiveIndex> {
+        reader::read_index_with_keys(index_path, self.keys.as_ref())
     }
 
     fn matches_pattern(&self, path: &str, pattern: &str) -> bool {
@@ -105,8 +106,11 @@ impl ArchiveLister {
             println!("Created: {}", index.created);
             println!("Files: {}", files.len());
             println!();
-            println!("{:<10} {:<12} {:<8} {:<10} {}", "Type", "Size", "Chunk", "Mode", "Path");
-            println!("{}", "-".repeat(80));
+            println!(
+                "{:<10} {:<12} {:<12} {:<8} {:<10} {:<10} {:<17} Path",
+                "Type", "Size", "Stored", "Chunk", "Offset", "Mode", "Attributes"
+            );
+            println!("{}", "-".repeat(111));
 
             for file in files {
                 let file_type = match file.entry_type {
@@ -114,15 +118,16 @@ impl ArchiveLister {
                     FileType::Directory => "dir",
                     FileType::Symlink => "symlink",
                     FileType::Hardlink => "hardlink",
+                    FileType::CharDevice => "chardev",
+                    FileType::BlockDevice => "blockdev",
+                    FileType::Fifo => "fifo",
                     FileType::Other => "other",
                 };
 
-                let size_str = if file.size > 1024 * 1024 {
-                    format!("{:.2}MB", file.size as f64 / (1024.0 * 1024.0))
-                } else if file.size > 1024 {
-                    format!("{:.2}KB", file.size as f64 / 1024.0)
-                } else {
-                    format!("{}B", file.size)
+                // Device nodes show their device numbers instead, as ls does
+                let size_str = match (file.device_major, file.device_minor) {
+                    (Some(major), Some(minor)) => format!("{}, {}", major, minor),
+                    _ => Self::format_size(file.size),
                 };
 
                 let chunks_info = if let Some(ref spans) = file.spans_chunks {
@@ -131,11 +136,14 @@ impl ArchiveLister {
                     file.chunk.to_string()
                 };
 
-                println!("{:<10} {:<12} {:<8} {:<10o} {}",
+                println!("{:<10} {:<12} {:<12} {:<8} {:<10} {:<10o} {:<17} {}",
                     file_type,
                     size_str,
+                    Self::stored_size(file),
                     chunks_info,
+                    file.offset,
                     file.mode,
+                    Self::attribute_kinds(file),
                     file.path
                 );
             }
@@ -146,29 +154,70 @@ impl ArchiveLister {
         }
     }
 
+    fn format_size(size: u64) -> String {
+        if size > 1024 * 1024 {
+            format!("{:.2}MB", size as f64 / (1024.0 * 1024.0))
+        } else if size > 1024 {
+            format!("{:.2}KB", size as f64 / 1024.0)
+        } else {
+            format!("{}B", size)
+        }
+    }
+
+    /// Bytes of data an entry stores itself: less than its size for sparse
+    /// files, `-` for entries whose data is elsewhere or that have none
+    fn stored_size(file: &FileEntry) -> String {
+        match file.stored_size {
+            Some(stored) => Self::format_size(stored),
+            None if file.entry_type == FileType::File && file.blocks.is_none() => Self::format_size(file.size),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Which kinds of extended attributes an entry carries, e.g. `xattr,acl`
+    fn attribute_kinds(file: &FileEntry) -> String {
+        let Some(ref attributes) = file.attributes else {
+            return "-".to_string();
+        };
+        let kinds: Vec<&str> = [
+            (!attributes.xattrs.is_empty(), "xattr"),
+            (attributes.acl_access.is_some() || attributes.acl_default.is_some(), "acl"),
+            (attributes.selinux_label.is_some(), "selinux"),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, kind)| *kind)
+        .collect();
+        kinds.join(",")
+    }
+
     fn display_json(&self, files: &[&FileEntry]) {
         let json = serde_json::to_string_pretty(&files).unwrap();
         println!("{}", json);
     }
 
     fn display_csv(&self, files: &[&FileEntry]) {
-        println!("path,type,size,chunk,mode,mtime,checksum");
+        println!("path,type,size,chunk,offset,mode,mtime,checksum");
         for file in files {
             let file_type = match file.entry_type {
                 FileType::File => "file",
                 FileType::Directory => "directory",
                 FileType::Symlink => "symlink",
                 FileType::Hardlink => "hardlink",
+                FileType::CharDevice => "chardevice",
+                FileType::BlockDevice => "blockdevice",
+                FileType::Fifo => "fifo",
                 FileType::Other => "other",
             };
 
-            let checksum = file.checksum.as_ref().map(|s| s.as_str()).unwrap_or("");
+            let checksum = file.checksum.as_deref().unwrap_or("");
 
-            println!("{},{},{},{},{},{},{}",
+            println!("{},{},{},{},{},{},{},{}",
                 file.path,
                 file_type,
                 file.size,
                 file.chunk,
+                file.offset,
                 file.mode,
                 file.mtime,
                 checksum
@@ -407,6 +456,44 @@ mod tests {
         assert_eq!(format!("{:?}", format), "Csv");
     }
 
+    #[test]
+    fn test_attribute_kinds() {
+        use crate::index::format::ExtendedAttributes;
+
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive_with_files(&temp_dir);
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let mut file = index.files[0].clone();
+        file.attributes = None;
+        assert_eq!(ArchiveLister::attribute_kinds(&file), "-");
+
+        let mut attributes = ExtendedAttributes {
+            acl_default: Some("user::rwx,group::r-x,other::r-x".to_string()),
+            ..Default::default()
+        };
+        attributes.xattrs.insert("user.origin".to_string(), b"lab".to_vec());
+        file.attributes = Some(attributes);
+        assert_eq!(ArchiveLister::attribute_kinds(&file), "xattr,acl");
+    }
+
+    #[test]
+    fn test_stored_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive_with_files(&temp_dir);
+        let index = reader::read_index(Path::new(&format!("{}.index.zst", archive_base))).unwrap();
+        let mut file = index.files.iter().find(|f| f.entry_type == FileType::File).unwrap().clone();
+        file.size = 3 * 1024 * 1024;
+        assert_eq!(ArchiveLister::stored_size(&file), "3.00MB");
+
+        file.sparse = true;
+        file.stored_size = Some(2048);
+        assert_eq!(ArchiveLister::stored_size(&file), "2.00KB");
+
+        file.entry_type = FileType::Hardlink;
+        file.stored_size = None;
+        assert_eq!(ArchiveLister::stored_size(&file), "-");
+    }
+
     #[test]
     fn test_matches_pattern_simple() {
         let lister = ArchiveLister::new("test".to_string());
@@ -1,3 +1,10 @@
 pub mod create;
+pub mod dedup;
 pub mod extract;
+pub mod journal;
 pub mod list;
+pub mod paths;
+pub mod sparse;
+pub mod special;
+pub mod tar_header;
+pub mod xattrs;
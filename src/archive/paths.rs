@@ -0,0 +1,76 @@
+/// PAX keys overriding an entry's path and link target
+pub const PAX_PATH: &str = "path";
+pub const PAX_LINKPATH: &str = "linkpath";
+
+/// PAX key that, set to `BINARY`, tells readers the path records are raw
+/// bytes rather than UTF-8, as GNU tar writes for such names
+pub const PAX_HDRCHARSET: &str = "hdrcharset";
+
+/// PAX records carrying a path and link target that are not valid UTF-8
+/// byte for byte; none for UTF-8 names, which the headers hold as they are
+pub fn pax_records(path_bytes: Option<&[u8]>, target_bytes: Option<&[u8]>) -> Vec<(String, Vec<u8>)> {
+    let mut records = Vec::new();
+    if path_bytes.is_none() && target_bytes.is_none() {
+        return records;
+    }
+
+    records.push((PAX_HDRCHARSET.to_string(), b"BINARY".to_vec()));
+    if let Some(bytes) = path_bytes {
+        records.push((PAX_PATH.to_string(), bytes.to_vec()));
+    }
+    if let Some(bytes) = target_bytes {
+        records.push((PAX_LINKPATH.to_string(), bytes.to_vec()));
+    }
+    records
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::index::format::path_fields;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_utf8_names_need_no_records() {
+        assert!(pax_records(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_non_utf8_names_survive_the_tar_stream() {
+        let name = Path::new(OsStr::from_bytes(b"data/caf\xe9.txt"));
+        let target = Path::new(OsStr::from_bytes(b"../na\xefve"));
+        let (path, path_bytes) = path_fields(name);
+        let (_, target_bytes) = path_fields(target);
+        assert_eq!(path, "data/caf\u{fffd}.txt");
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let records = pax_records(path_bytes.as_deref(), target_bytes.as_deref());
+        assert_eq!(records.len(), 3);
+        builder
+            .append_pax_extensions(records.iter().map(|(key, value)| (key.as_str(), value.as_slice())))
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, name, target).unwrap();
+        let tar_bytes = builder.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(tar_bytes.as_slice());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path_bytes().as_ref(), name.as_os_str().as_bytes());
+        assert_eq!(entry.link_name_bytes().unwrap().as_ref(), target.as_os_str().as_bytes());
+
+        let pax: Vec<_> = entry
+            .pax_extensions()
+            .unwrap()
+            .unwrap()
+            .map(|record| {
+                let record = record.unwrap();
+                (record.key().unwrap().to_string(), record.value_bytes().to_vec())
+            })
+            .collect();
+        assert_eq!(pax, records);
+    }
+}
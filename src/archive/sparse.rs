@@ -0,0 +1,272 @@
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// Size of a tar block; every sparse region but the last must start and end
+/// on one for readers to accept the entry
+const BLOCK_SIZE: u64 = 512;
+
+/// Region descriptors that fit in the main GNU header, and in each
+/// extension header after it
+const HEADER_REGIONS: usize = 4;
+const EXTENSION_REGIONS: usize = 21;
+
+/// Data regions of a file with holes, found with SEEK_DATA/SEEK_HOLE and
+/// widened to whole tar blocks; `None` if the file has no holes or the
+/// filesystem cannot tell
+#[cfg(target_os = "linux")]
+pub fn data_regions(file: &File, size: u64) -> io::Result<Option<Vec<Range<u64>>>> {
+    use std::os::unix::io::AsRawFd;
+
+    let seek = |offset: u64, whence: libc::c_int| -> io::Result<Option<u64>> {
+        // SAFETY: lseek64 only reads the descriptor, which `file` keeps open
+        match unsafe { libc::lseek64(file.as_raw_fd(), offset as libc::off64_t, whence) } {
+            -1 => match io::Error::last_os_error() {
+                // No data at or after the offset
+                e if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+                e => Err(e),
+            },
+            position => Ok(Some(position as u64)),
+        }
+    };
+
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let mut regions: Vec<Range<u64>> = Vec::new();
+    let mut position = 0;
+    while position < size {
+        let start = match seek(position, libc::SEEK_DATA) {
+            Ok(Some(start)) => start.min(size),
+            Ok(None) => break,
+            // Filesystems without hole support say so on the first seek
+            Err(e) if position == 0 && e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let end = seek(start, libc::SEEK_HOLE)?.unwrap_or(size).min(size);
+        if end <= start {
+            break;
+        }
+
+        let aligned = (start / BLOCK_SIZE * BLOCK_SIZE)..(end.div_ceil(BLOCK_SIZE) * BLOCK_SIZE).min(size);
+        match regions.last_mut() {
+            Some(last) if last.end >= aligned.start => last.end = aligned.end,
+            _ => regions.push(aligned),
+        }
+        position = end;
+    }
+
+    if regions.len() == 1 && regions[0] == (0..size) {
+        Ok(None)
+    } else {
+        Ok(Some(regions))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn data_regions(_file: &File, _size: u64) -> io::Result<Option<Vec<Range<u64>>>> {
+    Ok(None)
+}
+
+/// Bytes of data the regions hold
+pub fn stored_size(regions: &[Range<u64>]) -> u64 {
+    regions.iter().map(|r| r.end - r.start).sum()
+}
+
+/// Append a file with holes as an old-GNU sparse entry, returning the bytes
+/// of data stored
+///
+/// The header's size is that of the stored data and its real size that of
+/// the whole file. Region descriptors past the four that fit in the header
+/// go into extension headers just after it, and only the regions' bytes
+/// follow; a final empty region at the end of the file marks a trailing
+/// hole. `header` carries the file's metadata.
+pub fn append<W: Write>(
+    tar_builder: &mut tar::Builder<W>,
+    header: &mut tar::Header,
+    path: &Path,
+    file: &mut File,
+    size: u64,
+    regions: &[Range<u64>],
+) -> io::Result<u64> {
+    let stored = stored_size(regions);
+    let mut descriptors: Vec<(u64, u64)> = regions.iter().map(|r| (r.start, r.end - r.start)).collect();
+    if regions.last().map_or(true, |last| last.end < size) {
+        descriptors.push((size, 0));
+    }
+
+    header.set_entry_type(tar::EntryType::GNUSparse);
+    header.set_size(stored);
+    let gnu = header
+        .as_gnu_mut()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "sparse entries need a GNU header"))?;
+    gnu.set_real_size(size);
+    for (slot, &(offset, length)) in gnu.sparse.iter_mut().zip(&descriptors) {
+        slot.set_offset(offset);
+        slot.set_length(length);
+    }
+    gnu.set_is_extended(descriptors.len() > HEADER_REGIONS);
+
+    let mut extensions = Vec::new();
+    let rest: Vec<_> = descriptors.iter().skip(HEADER_REGIONS).collect();
+    let groups: Vec<_> = rest.chunks(EXTENSION_REGIONS).collect();
+    for (position, group) in groups.iter().enumerate() {
+        let mut extension = tar::GnuExtSparseHeader::new();
+        for (slot, &&(offset, length)) in extension.sparse.iter_mut().zip(group.iter()) {
+            slot.set_offset(offset);
+            slot.set_length(length);
+        }
+        extension.set_is_extended(position + 1 < groups.len());
+        extensions.extend_from_slice(extension.as_bytes());
+    }
+
+    let data = Cursor::new(extensions).chain(RegionReader {
+        file,
+        regions: regions.iter(),
+        remaining: 0,
+    });
+    tar_builder.append_data(header, path, data)?;
+    Ok(stored)
+}
+
+/// Reads the data regions of a file one after another
+struct RegionReader<'a> {
+    file: &'a mut File,
+    regions: std::slice::Iter<'a, Range<u64>>,
+    remaining: u64,
+}
+
+impl Read for RegionReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let Some(region) = self.regions.next() else {
+                return Ok(0);
+            };
+            self.file.seek(SeekFrom::Start(region.start))?;
+            self.remaining = region.end - region.start;
+        }
+
+        let len = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let read = self.file.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being archived"));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A file of `size` bytes with data only in the given ranges
+    fn sparse_file(dir: &TempDir, name: &str, size: u64, data: &[Range<u64>]) -> File {
+        let path = dir.path().join(name);
+        let mut file = File::options().read(true).write(true).create(true).truncate(true).open(path).unwrap();
+        file.set_len(size).unwrap();
+        for range in data {
+            file.seek(SeekFrom::Start(range.start)).unwrap();
+            let bytes: Vec<u8> = (range.start..range.end).map(|i| (i % 251) as u8 + 1).collect();
+            file.write_all(&bytes).unwrap();
+        }
+        file.sync_all().unwrap();
+        file
+    }
+
+    fn unpack(tar_bytes: &[u8], dir: &Path) -> Vec<u8> {
+        let mut archive = tar::Archive::new(tar_bytes);
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.header().entry_type(), tar::EntryType::GNUSparse);
+        let out = dir.join("out");
+        entry.unpack(&out).unwrap();
+        std::fs::read(out).unwrap()
+    }
+
+    #[test]
+    fn test_dense_file_has_no_regions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("dense");
+        std::fs::write(&path, vec![1u8; 10_000]).unwrap();
+        let file = File::open(path).unwrap();
+        assert_eq!(data_regions(&file, 10_000).unwrap(), None);
+        assert_eq!(data_regions(&file, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_sparse_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let size = 1 << 22;
+        let mut file = sparse_file(&dir, "image", size, &[0..5000, (1 << 21)..(1 << 21) + 100]);
+
+        // Filesystems without holes report the file as dense
+        let Some(regions) = data_regions(&file, size).unwrap() else {
+            return;
+        };
+        assert!(regions.len() >= 2);
+        assert!(regions.iter().all(|r| r.start % BLOCK_SIZE == 0));
+        let stored = stored_size(&regions);
+        assert!(stored < size / 4);
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&file.metadata().unwrap());
+        assert_eq!(append(&mut builder, &mut header, Path::new("image"), &mut file, size, &regions).unwrap(), stored);
+        let tar_bytes = builder.into_inner().unwrap();
+
+        let mut original = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut original).unwrap();
+        assert_eq!(unpack(&tar_bytes, dir.path()), original);
+    }
+
+    #[test]
+    fn test_many_regions_use_extension_headers() {
+        let dir = TempDir::new().unwrap();
+        let size = 64 * 8192 + 4096;
+        let data: Vec<_> = (0..64).map(|i| i * 8192..i * 8192 + 100).collect();
+        let mut file = sparse_file(&dir, "striped", size, &data);
+        let Some(regions) = data_regions(&file, size).unwrap() else {
+            return;
+        };
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&file.metadata().unwrap());
+        append(&mut builder, &mut header, Path::new("striped"), &mut file, size, &regions).unwrap();
+        let tar_bytes = builder.into_inner().unwrap();
+
+        let mut original = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut original).unwrap();
+        assert_eq!(unpack(&tar_bytes, dir.path()), original);
+
+        // Every extension header but the last flags, after its region
+        // descriptors, that another follows
+        let expected = (regions.len() + 1 - HEADER_REGIONS).div_ceil(EXTENSION_REGIONS);
+        let flag = EXTENSION_REGIONS * 24;
+        let extensions = tar_bytes[512..].chunks(512).position(|block| block[flag] == 0);
+        assert_eq!(extensions.map(|last| last + 1), Some(expected));
+    }
+
+    #[test]
+    fn test_file_of_only_holes() {
+        let dir = TempDir::new().unwrap();
+        let size = 1 << 20;
+        let mut file = sparse_file(&dir, "empty", size, &[]);
+        let Some(regions) = data_regions(&file, size).unwrap() else {
+            return;
+        };
+        assert!(regions.is_empty());
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&file.metadata().unwrap());
+        assert_eq!(append(&mut builder, &mut header, Path::new("empty"), &mut file, size, &regions).unwrap(), 0);
+        let tar_bytes = builder.into_inner().unwrap();
+        assert_eq!(unpack(&tar_bytes, dir.path()), vec![0u8; size as usize]);
+    }
+}
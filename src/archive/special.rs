@@ -0,0 +1,206 @@
+use crate::error::Result;
+use crate::index::format::FileType;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Tar entry type of a device node or FIFO; `None` for other kinds
+pub fn entry_type(kind: &FileType) -> Option<tar::EntryType> {
+    match kind {
+        FileType::CharDevice => Some(tar::EntryType::Char),
+        FileType::BlockDevice => Some(tar::EntryType::Block),
+        FileType::Fifo => Some(tar::EntryType::Fifo),
+        _ => None,
+    }
+}
+
+/// Kind of special file a tar entry type stands for, if any
+pub fn kind(entry_type: tar::EntryType) -> Option<FileType> {
+    match entry_type {
+        tar::EntryType::Char => Some(FileType::CharDevice),
+        tar::EntryType::Block => Some(FileType::BlockDevice),
+        tar::EntryType::Fifo => Some(FileType::Fifo),
+        _ => None,
+    }
+}
+
+/// Major and minor numbers of the device a device node refers to
+#[cfg(target_os = "linux")]
+pub fn device_numbers(metadata: &std::fs::Metadata) -> (u32, u32) {
+    use std::os::unix::fs::MetadataExt;
+    (libc::major(metadata.rdev()), libc::minor(metadata.rdev()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn device_numbers(_metadata: &std::fs::Metadata) -> (u32, u32) {
+    (0, 0)
+}
+
+/// Append a device node or FIFO as a tar entry without data, carrying the
+/// device numbers of a device node
+pub fn append<W: Write>(
+    tar_builder: &mut tar::Builder<W>,
+    metadata: &std::fs::Metadata,
+    path: &Path,
+    kind: &FileType,
+    device: Option<(u32, u32)>,
+) -> io::Result<()> {
+    let entry_type = entry_type(kind)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is not a special file", kind)))?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(metadata);
+    header.set_entry_type(entry_type);
+    header.set_size(0);
+    if let Some((major, minor)) = device {
+        header.set_device_major(major)?;
+        header.set_device_minor(minor)?;
+    }
+    tar_builder.append_data(&mut header, path, io::empty())
+}
+
+/// Whether device nodes can be created, which takes root
+#[cfg(unix)]
+pub fn can_create_devices() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn can_create_devices() -> bool {
+    false
+}
+
+/// Create the device node or FIFO a tar entry describes, with its mode,
+/// mtime and, as root, its owner
+#[cfg(target_os = "linux")]
+pub fn create(path: &Path, header: &tar::Header, kind: &FileType) -> Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| invalid("path contains a NUL byte"))?;
+    let mode = header.mode()? & 0o7777;
+    let (file_type, device) = match kind {
+        FileType::CharDevice | FileType::BlockDevice => {
+            let major = header.device_major()?.ok_or_else(|| invalid("device node without a major number"))?;
+            let minor = header.device_minor()?.ok_or_else(|| invalid("device node without a minor number"))?;
+            let file_type = if *kind == FileType::CharDevice { libc::S_IFCHR } else { libc::S_IFBLK };
+            (file_type, libc::makedev(major, minor))
+        }
+        FileType::Fifo => (libc::S_IFIFO, 0),
+        _ => return Err(invalid("not a special file").into()),
+    };
+
+    // SAFETY: c_path is a NUL-terminated path that outlives the call
+    if unsafe { libc::mknod(c_path.as_ptr(), file_type | mode as libc::mode_t, device) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // mknod applies the umask
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    if can_create_devices() {
+        std::os::unix::fs::lchown(path, Some(header.uid()? as u32), Some(header.gid()? as u32))?;
+    }
+
+    let mtime = libc::timespec {
+        tv_sec: header.mtime()? as libc::time_t,
+        tv_nsec: 0,
+    };
+    // Access time is left alone, as for unpacked files
+    let times = [
+        libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        },
+        mtime,
+    ];
+    // SAFETY: c_path is NUL-terminated and times holds two timespecs
+    if unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn create(path: &Path, _header: &tar::Header, _kind: &FileType) -> Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{}: special files cannot be created on this platform", path.display()),
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn unpack_first(tar_bytes: &[u8], path: &Path) -> tar::Header {
+        let mut archive = tar::Archive::new(tar_bytes);
+        let entry = archive.entries().unwrap().next().unwrap().unwrap();
+        let header = entry.header().clone();
+        let kind = kind(header.entry_type()).unwrap();
+        create(path, &header, &kind).unwrap();
+        header
+    }
+
+    #[test]
+    fn test_entry_type_round_trip() {
+        for file_type in [FileType::CharDevice, FileType::BlockDevice, FileType::Fifo] {
+            assert_eq!(kind(entry_type(&file_type).unwrap()), Some(file_type));
+        }
+        assert_eq!(entry_type(&FileType::File), None);
+        assert_eq!(kind(tar::EntryType::Regular), None);
+    }
+
+    #[test]
+    fn test_fifo_round_trip() {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+
+        let dir = TempDir::new().unwrap();
+        let fifo = dir.path().join("pipe");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        // SAFETY: c_path is a valid NUL-terminated path
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o640) }, 0);
+        std::fs::set_permissions(&fifo, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let metadata = std::fs::symlink_metadata(&fifo).unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, &metadata, Path::new("pipe"), &FileType::Fifo, None).unwrap();
+        let tar_bytes = builder.into_inner().unwrap();
+
+        let restored = dir.path().join("restored");
+        let header = unpack_first(&tar_bytes, &restored);
+        assert_eq!(header.entry_type(), tar::EntryType::Fifo);
+        let restored = std::fs::symlink_metadata(&restored).unwrap();
+        assert!(restored.file_type().is_fifo());
+        assert_eq!(restored.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(restored.mtime(), metadata.mtime());
+    }
+
+    #[test]
+    fn test_device_node_round_trip() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let null = std::fs::symlink_metadata("/dev/null").unwrap();
+        let (major, minor) = device_numbers(&null);
+        assert_eq!((major, minor), (1, 3));
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, &null, Path::new("dev/null"), &FileType::CharDevice, Some((major, minor))).unwrap();
+        let tar_bytes = builder.into_inner().unwrap();
+
+        // Creating device nodes needs root
+        if !can_create_devices() {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let restored = dir.path().join("null");
+        let header = unpack_first(&tar_bytes, &restored);
+        assert_eq!(header.device_major().unwrap(), Some(1));
+        let restored = std::fs::symlink_metadata(&restored).unwrap();
+        assert!(restored.file_type().is_char_device());
+        assert_eq!(device_numbers(&restored), (1, 3));
+    }
+}
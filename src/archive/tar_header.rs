@@ -0,0 +1,71 @@
+/// Size of a tar header block
+pub const TAR_BLOCK_SIZE: usize = 512;
+
+/// Check the magic and checksum of a candidate tar header block
+pub fn is_tar_header(block: &[u8]) -> bool {
+    if block.len() < TAR_BLOCK_SIZE || &block[257..262] != b"ustar" {
+        return false;
+    }
+
+    let recorded = std::str::from_utf8(&block[148..156])
+        .ok()
+        .map(|s| s.trim_matches(|c: char| c == '\0' || c == ' '))
+        .and_then(|s| u32::from_str_radix(s, 8).ok());
+
+    // The checksum field itself counts as eight spaces
+    let computed: u32 = block[..TAR_BLOCK_SIZE]
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+        .sum();
+
+    recorded == Some(computed)
+}
+
+/// Offsets of every valid-looking tar header in a byte range
+///
+/// Chunk boundaries are not block aligned, so every byte offset is a
+/// candidate. File data can contain header-shaped bytes; callers confirm a
+/// match against what they expect to find there.
+pub fn header_offsets(data: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    data.windows(TAR_BLOCK_SIZE)
+        .enumerate()
+        .filter(|(_, block)| is_tar_header(block))
+        .map(|(offset, _)| offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_header(path: &str) -> tar::Header {
+        let mut header = tar::Header::new_ustar();
+        header.set_path(path).unwrap();
+        header.set_size(3);
+        header.set_cksum();
+        header
+    }
+
+    #[test]
+    fn test_is_tar_header() {
+        let header = test_header("file.txt");
+        assert!(is_tar_header(header.as_bytes()));
+
+        let mut damaged = *header.as_bytes();
+        damaged[0] ^= 0xFF;
+        assert!(!is_tar_header(&damaged));
+        assert!(!is_tar_header(&[0u8; TAR_BLOCK_SIZE]));
+        assert!(!is_tar_header(&[0u8; 10]));
+    }
+
+    #[test]
+    fn test_header_offsets() {
+        let mut data = vec![7u8; 100];
+        data.extend_from_slice(test_header("a.txt").as_bytes());
+        data.extend_from_slice(&[0u8; 3]);
+        data.extend_from_slice(test_header("b.txt").as_bytes());
+
+        let offsets: Vec<usize> = header_offsets(&data).collect();
+        assert_eq!(offsets, vec![100, 100 + TAR_BLOCK_SIZE + 3]);
+    }
+}
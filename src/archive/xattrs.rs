@@ -0,0 +1,338 @@
+use crate::error::{EctarError, Result};
+use crate::index::format::ExtendedAttributes;
+use std::io::Read;
+use std::path::Path;
+
+/// Prefix of the PAX keys holding extended attributes, as written and read
+/// by star, GNU tar and bsdtar
+pub const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+/// PAX key holding an entry's access ACL in short text form
+pub const PAX_ACL_ACCESS: &str = "SCHILY.acl.access";
+
+/// PAX key holding a directory's default ACL in short text form
+pub const PAX_ACL_DEFAULT: &str = "SCHILY.acl.default";
+
+/// Extended attributes Linux keeps POSIX ACLs and SELinux labels in
+const XATTR_ACL_ACCESS: &str = "system.posix_acl_access";
+const XATTR_ACL_DEFAULT: &str = "system.posix_acl_default";
+const XATTR_SELINUX: &str = "security.selinux";
+
+/// Version of the binary ACL format in `system.posix_acl_*` attributes
+const ACL_VERSION: u32 = 2;
+
+/// Qualifier of the ACL entries that do not name a user or group
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+/// Read a file's extended attributes, ACLs and SELinux label, without
+/// following symlinks; `None` if it has none
+///
+/// Attributes that cannot be read, such as `trusted.*` ones without
+/// privileges, are skipped, as are all of them on filesystems without
+/// extended attribute support.
+pub fn read(path: &Path) -> Result<Option<ExtendedAttributes>> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) if is_unsupported(&e) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut attributes = ExtendedAttributes::default();
+    for name in names {
+        let Some(name) = name.to_str().map(str::to_string) else {
+            log::warn!("{}: skipping extended attribute with a non-UTF-8 name {:?}", path.display(), name);
+            continue;
+        };
+        let value = match xattr::get(path, &name) {
+            Ok(Some(value)) => value,
+            Ok(None) => continue,
+            Err(e) => {
+                log::debug!("{}: cannot read extended attribute {}: {}", path.display(), name, e);
+                continue;
+            }
+        };
+
+        match name.as_str() {
+            XATTR_ACL_ACCESS => attributes.acl_access = Some(acl_to_text(&value)?),
+            XATTR_ACL_DEFAULT => attributes.acl_default = Some(acl_to_text(&value)?),
+            XATTR_SELINUX => attributes.selinux_label = Some(selinux_label(&value)),
+            _ => {
+                attributes.xattrs.insert(name, value);
+            }
+        }
+    }
+
+    Ok((!attributes.is_empty()).then_some(attributes))
+}
+
+/// PAX records carrying an entry's attributes: `SCHILY.xattr.<name>` for
+/// each extended attribute (the SELinux label included) and
+/// `SCHILY.acl.access`/`SCHILY.acl.default` for ACLs
+pub fn pax_records(attributes: &ExtendedAttributes) -> Vec<(String, Vec<u8>)> {
+    let mut records: Vec<(String, Vec<u8>)> = attributes
+        .xattrs
+        .iter()
+        .map(|(name, value)| (format!("{}{}", PAX_XATTR_PREFIX, name), value.clone()))
+        .collect();
+    if let Some(ref label) = attributes.selinux_label {
+        records.push((format!("{}{}", PAX_XATTR_PREFIX, XATTR_SELINUX), label.as_bytes().to_vec()));
+    }
+    if let Some(ref acl) = attributes.acl_access {
+        records.push((PAX_ACL_ACCESS.to_string(), acl.as_bytes().to_vec()));
+    }
+    if let Some(ref acl) = attributes.acl_default {
+        records.push((PAX_ACL_DEFAULT.to_string(), acl.as_bytes().to_vec()));
+    }
+    records
+}
+
+/// Read the attributes from a tar entry's PAX records; `None` if it carries
+/// none
+pub fn from_entry<R: Read>(entry: &mut tar::Entry<R>) -> Result<Option<ExtendedAttributes>> {
+    let extensions = match entry.pax_extensions()? {
+        Some(extensions) => extensions,
+        None => return Ok(None),
+    };
+
+    let mut attributes = ExtendedAttributes::default();
+    for extension in extensions {
+        let extension = extension?;
+        let Ok(key) = extension.key() else {
+            continue;
+        };
+        let text = || {
+            extension
+                .value()
+                .map(str::to_string)
+                .map_err(|e| EctarError::Tar(format!("Invalid {} record: {}", key, e)))
+        };
+
+        match key {
+            PAX_ACL_ACCESS => attributes.acl_access = Some(text()?),
+            PAX_ACL_DEFAULT => attributes.acl_default = Some(text()?),
+            _ => match key.strip_prefix(PAX_XATTR_PREFIX) {
+                Some(XATTR_SELINUX) => attributes.selinux_label = Some(selinux_label(extension.value_bytes())),
+                // GNU tar without --acls can carry ACLs as raw attributes
+                Some(XATTR_ACL_ACCESS) => attributes.acl_access = Some(acl_to_text(extension.value_bytes())?),
+                Some(XATTR_ACL_DEFAULT) => attributes.acl_default = Some(acl_to_text(extension.value_bytes())?),
+                Some(name) => {
+                    attributes.xattrs.insert(name.to_string(), extension.value_bytes().to_vec());
+                }
+                None => {}
+            },
+        }
+    }
+
+    Ok((!attributes.is_empty()).then_some(attributes))
+}
+
+/// Set a file's extended attributes, ACLs and SELinux label, without
+/// following symlinks
+///
+/// Every attribute is attempted; the first failure is returned, since
+/// filesystems, privileges and security policies vary between the machine
+/// an archive was made on and the one it is restored to.
+pub fn apply(path: &Path, attributes: &ExtendedAttributes) -> Result<()> {
+    let mut values: Vec<(&str, Vec<u8>)> =
+        attributes.xattrs.iter().map(|(name, value)| (name.as_str(), value.clone())).collect();
+    if let Some(ref label) = attributes.selinux_label {
+        let mut value = label.as_bytes().to_vec();
+        value.push(0);
+        values.push((XATTR_SELINUX, value));
+    }
+    if let Some(ref acl) = attributes.acl_access {
+        values.push((XATTR_ACL_ACCESS, acl_from_text(acl)?));
+    }
+    if let Some(ref acl) = attributes.acl_default {
+        values.push((XATTR_ACL_DEFAULT, acl_from_text(acl)?));
+    }
+
+    let mut first_error = None;
+    for (name, value) in values {
+        if let Err(e) = xattr::set(path, name, &value) {
+            log::debug!("{}: cannot set extended attribute {}: {}", path.display(), name, e);
+            first_error.get_or_insert(EctarError::Io(std::io::Error::new(e.kind(), format!("{}: {}", name, e))));
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Whether an error means the filesystem or platform has no extended
+/// attributes at all
+fn is_unsupported(error: &std::io::Error) -> bool {
+    error.kind() == std::io::ErrorKind::Unsupported
+        || error.raw_os_error().is_some_and(|code| code == libc::EOPNOTSUPP || code == libc::ENOTSUP)
+        || !xattr::SUPPORTED_PLATFORM
+}
+
+/// The kernel NUL-terminates labels; archives keep them without
+fn selinux_label(value: &[u8]) -> String {
+    String::from_utf8_lossy(value.strip_suffix(&[0]).unwrap_or(value)).to_string()
+}
+
+/// Convert a `system.posix_acl_*` attribute to short text form, with
+/// numeric user and group ids
+fn acl_to_text(value: &[u8]) -> Result<String> {
+    let invalid = || EctarError::InvalidHeader("Malformed POSIX ACL attribute".to_string());
+    if value.len() < 4 || (value.len() - 4) % 8 != 0 {
+        return Err(invalid());
+    }
+    if u32::from_le_bytes(value[..4].try_into().unwrap()) != ACL_VERSION {
+        return Err(invalid());
+    }
+
+    let entries = value[4..]
+        .chunks(8)
+        .map(|entry| {
+            let tag = u16::from_le_bytes([entry[0], entry[1]]);
+            let perm = u16::from_le_bytes([entry[2], entry[3]]);
+            let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+            let (kind, qualifier) = match tag {
+                ACL_USER_OBJ => ("user", String::new()),
+                ACL_USER => ("user", id.to_string()),
+                ACL_GROUP_OBJ => ("group", String::new()),
+                ACL_GROUP => ("group", id.to_string()),
+                ACL_MASK => ("mask", String::new()),
+                ACL_OTHER => ("other", String::new()),
+                _ => return Err(invalid()),
+            };
+            let perms: String = [(4, 'r'), (2, 'w'), (1, 'x')]
+                .iter()
+                .map(|&(bit, c)| if perm & bit != 0 { c } else { '-' })
+                .collect();
+            Ok(format!("{}:{}:{}", kind, qualifier, perms))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(entries.join(","))
+}
+
+/// Convert an ACL in short text form back to a `system.posix_acl_*`
+/// attribute
+///
+/// Entries may also be in star's form, `user:name:perms:id`, in which case
+/// the numeric id is used.
+fn acl_from_text(text: &str) -> Result<Vec<u8>> {
+    let invalid = |entry: &str| EctarError::InvalidHeader(format!("Invalid ACL entry: {}", entry));
+
+    let mut value = ACL_VERSION.to_le_bytes().to_vec();
+    for entry in text.split([',', '\n']).map(str::trim).filter(|e| !e.is_empty() && !e.starts_with('#')) {
+        let fields: Vec<&str> = entry.split(':').collect();
+        let (kind, qualifier, perms) = match fields.as_slice() {
+            [kind, qualifier, perms] => (*kind, *qualifier, *perms),
+            [kind, _, perms, id] => (*kind, *id, *perms),
+            _ => return Err(invalid(entry)),
+        };
+
+        let id = if qualifier.is_empty() {
+            ACL_UNDEFINED_ID
+        } else {
+            qualifier.parse().map_err(|_| invalid(entry))?
+        };
+        let tag = match (kind, id == ACL_UNDEFINED_ID) {
+            ("user" | "u", true) => ACL_USER_OBJ,
+            ("user" | "u", false) => ACL_USER,
+            ("group" | "g", true) => ACL_GROUP_OBJ,
+            ("group" | "g", false) => ACL_GROUP,
+            ("mask" | "m", true) => ACL_MASK,
+            ("other" | "o", true) => ACL_OTHER,
+            _ => return Err(invalid(entry)),
+        };
+        let mut perm = 0u16;
+        for c in perms.chars() {
+            perm |= match c {
+                'r' => 4,
+                'w' => 2,
+                'x' => 1,
+                '-' => 0,
+                _ => return Err(invalid(entry)),
+            };
+        }
+
+        value.extend_from_slice(&tag.to_le_bytes());
+        value.extend_from_slice(&perm.to_le_bytes());
+        value.extend_from_slice(&id.to_le_bytes());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const ACL: &str = "user::rw-,user:1000:r--,group::r--,group:50:rwx,mask::rwx,other::---";
+
+    #[test]
+    fn test_acl_text_round_trip() {
+        let value = acl_from_text(ACL).unwrap();
+        assert_eq!(value.len(), 4 + 6 * 8);
+        assert_eq!(acl_to_text(&value).unwrap(), ACL);
+
+        // star's form carries the name and the id
+        let star = acl_from_text("user::rw-,user:alice:r--:1000,group::r--,mask::r--,other::r--").unwrap();
+        assert_eq!(
+            acl_to_text(&star).unwrap(),
+            "user::rw-,user:1000:r--,group::r--,mask::r--,other::r--"
+        );
+
+        assert!(acl_from_text("user:alice:r--").is_err());
+        assert!(acl_from_text("other::rwz").is_err());
+        assert!(acl_to_text(&[2, 0, 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_pax_records_round_trip() {
+        let mut attributes = ExtendedAttributes {
+            acl_access: Some(ACL.to_string()),
+            acl_default: Some("user::rwx,group::r-x,other::r-x".to_string()),
+            selinux_label: Some("unconfined_u:object_r:user_home_t:s0".to_string()),
+            ..Default::default()
+        };
+        attributes.xattrs.insert("user.provenance".to_string(), b"instrument 7".to_vec());
+        attributes.xattrs.insert("user.binary".to_string(), vec![0, 159, 255]);
+
+        let mut data = Vec::new();
+        {
+            let mut builder = tar::Builder::new(&mut data);
+            let records = pax_records(&attributes);
+            builder
+                .append_pax_extensions(records.iter().map(|(k, v)| (k.as_str(), v.as_slice())))
+                .unwrap();
+            let mut header = tar::Header::new_gnu();
+            header.set_size(0);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, "file.txt", std::io::empty()).unwrap();
+            builder.finish().unwrap();
+        }
+
+        let mut archive = tar::Archive::new(data.as_slice());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(from_entry(&mut entry).unwrap(), Some(attributes));
+    }
+
+    #[test]
+    fn test_read_and_apply_user_xattrs() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        let copy = temp_dir.path().join("copy.txt");
+        std::fs::write(&source, b"data").unwrap();
+        std::fs::write(&copy, b"data").unwrap();
+
+        // Not every filesystem a test may run on has user attributes
+        if xattr::set(&source, "user.provenance", b"instrument 7").is_err() {
+            return;
+        }
+        let attributes = read(&source).unwrap().unwrap();
+        assert_eq!(attributes.xattrs.get("user.provenance").map(Vec::as_slice), Some(&b"instrument 7"[..]));
+
+        apply(&copy, &attributes).unwrap();
+        assert_eq!(read(&copy).unwrap().unwrap().xattrs, attributes.xattrs);
+    }
+}
@@ -0,0 +1,35 @@
+/// Encode bytes as lowercase hex digits
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode any even number of hex digits; `None` if the text is not hex
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = vec![0x00, 0x7f, 0xe9, 0xff];
+        assert_eq!(encode(&bytes), "007fe9ff");
+        assert_eq!(decode("007fE9ff").unwrap(), bytes);
+        assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_rejects_non_hex() {
+        assert!(decode("abc").is_none());
+        assert!(decode("zz").is_none());
+        assert!(decode("+f").is_none());
+    }
+}
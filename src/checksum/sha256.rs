@@ -1,7 +1,7 @@
 use crate::error::Result;
 use digest::Digest;
 use sha2::Sha256;
-use std::io::Read;
+use std::io::{Read, Write};
 
 pub fn compute_checksum<R: Read>(mut reader: R) -> Result<String> {
     let mut hasher = Sha256::new();
@@ -15,8 +15,17 @@ pub fn compute_checksum<R: Read>(mut reader: R) -> Result<String> {
         hasher.update(&buffer[..bytes_read]);
     }
 
-    let result = hasher.finalize();
-    Ok(format!("sha256:{:x}", result))
+    Ok(finalize_checksum(hasher))
+}
+
+/// Finish a running hash and format it as `sha256:<hex>`
+pub fn finalize_checksum(hasher: Sha256) -> String {
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+/// Compute the checksum of an in-memory buffer
+pub fn checksum_bytes(data: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(data))
 }
 
 pub fn verify_checksum<R: Read>(reader: R, expected: &str) -> Result<bool> {
@@ -24,6 +33,38 @@ pub fn verify_checksum<R: Read>(reader: R, expected: &str) -> Result<bool> {
     Ok(computed == expected)
 }
 
+/// Writer adapter that hashes everything passed through to the inner writer
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Return the inner writer and the checksum of all bytes written
+    pub fn finish(self) -> (W, String) {
+        (self.inner, finalize_checksum(self.hasher))
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,6 +130,26 @@ mod tests {
         assert_ne!(checksum1, checksum2);
     }
 
+    #[test]
+    fn test_checksum_bytes_matches_reader() {
+        let data = b"Hello, World!";
+        assert_eq!(
+            checksum_bytes(data),
+            compute_checksum(Cursor::new(data)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_checksum_writer() {
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writer.write_all(b"Hello, ").unwrap();
+        writer.write_all(b"World!").unwrap();
+
+        let (inner, checksum) = writer.finish();
+        assert_eq!(inner, b"Hello, World!");
+        assert_eq!(checksum, checksum_bytes(b"Hello, World!"));
+    }
+
     #[test]
     fn test_checksum_deterministic() {
         let data = b"deterministic test";
@@ -93,7 +93,7 @@ impl<W: Write> Write for ChunkingWriter<W> {
         // Start first chunk if needed
         if self.current_writer.is_none() {
             self.start_new_chunk()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                .map_err(|e| io::Error::other(e.to_string()))?;
         }
 
         let mut bytes_written = 0;
@@ -106,7 +106,7 @@ impl<W: Write> Write for ChunkingWriter<W> {
             if to_write == 0 {
                 // Current chunk is full, start a new one
                 self.start_new_chunk()
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                    .map_err(|e| io::Error::other(e.to_string()))?;
                 continue;
             }
 
@@ -136,10 +136,8 @@ mod tests {
 
     #[test]
     fn test_chunking_writer() {
-        let mut buffers: Vec<Vec<u8>> = Vec::new();
-
         {
-            let mut chunker = ChunkingWriter::new(100, |chunk_num| {
+            let mut chunker = ChunkingWriter::new(100, |_chunk_num| {
                 Ok(Cursor::new(Vec::new()))
             });
 
@@ -137,7 +137,7 @@ impl Write for CompressedChunkingWriter {
         // Start first chunk if needed
         if self.current_encoder.is_none() {
             self.start_new_chunk()
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
         }
 
         let mut bytes_written = 0;
@@ -150,7 +150,7 @@ impl Write for CompressedChunkingWriter {
             if to_write == 0 {
                 // Current chunk is full, start a new one
                 self.start_new_chunk()
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+                    .map_err(|e| std::io::Error::other(e.to_string()))?;
                 continue;
             }
 
@@ -263,7 +263,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let output_base = temp_dir.path().join("test");
 
-        let mut writer = CompressedChunkingWriter::new(
+        let writer = CompressedChunkingWriter::new(
             output_base,
             1024,
             3,
@@ -0,0 +1,283 @@
+use crate::checksum::sha256::checksum_bytes;
+use crate::chunking::streaming_erasure_chunker::ChunkInfo;
+use crate::error::{EctarError, Result};
+use crate::io::durable::Durability;
+use crate::io::streaming_shard_writer::StreamingShardWriter;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A compressed chunk waiting to be erasure-coded
+pub struct PendingChunk {
+    pub chunk_number: usize,
+    /// The chunk as it will be stored (manifest frame included)
+    pub data: Vec<u8>,
+    pub uncompressed_size: u64,
+    pub uncompressed_checksum: String,
+}
+
+/// Where a chunk's shards go and how many there are
+#[derive(Debug, Clone)]
+pub struct ShardLayout {
+    pub output_base: String,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub shard_trailers: bool,
+    pub durability: Durability,
+}
+
+impl ShardLayout {
+    /// Checksum a chunk, apply Reed-Solomon erasure coding and write its shards
+    pub fn encode(&self, chunk: PendingChunk) -> Result<ChunkInfo> {
+        let chunk_data = &chunk.data;
+        log::debug!(
+            "Encoding chunk {} ({} bytes) into {} data + {} parity shards",
+            chunk.chunk_number,
+            chunk_data.len(),
+            self.data_shards,
+            self.parity_shards
+        );
+
+        // Calculate shard size (round up to ensure all data fits)
+        let shard_size = chunk_data.len().div_ceil(self.data_shards);
+
+        // Calculate padding for zfec header (number of padding bytes in the last shard)
+        let total_data_bytes = self.data_shards * shard_size;
+        let padlen = total_data_bytes - chunk_data.len();
+
+        // Create Reed-Solomon encoder
+        let encoder = ReedSolomon::new(self.data_shards, self.parity_shards)
+            .map_err(|e| EctarError::ErasureCoding(format!("Failed to create encoder: {:?}", e)))?;
+
+        // Create shards - initialize all to shard_size with zeros
+        let mut shards: Vec<Vec<u8>> = vec![vec![0u8; shard_size]; self.data_shards + self.parity_shards];
+
+        // Copy chunk data into data shards
+        for (i, piece) in chunk_data.chunks(shard_size).enumerate() {
+            shards[i][..piece.len()].copy_from_slice(piece);
+            // Remaining bytes are already zero-padded
+        }
+
+        // Encode to generate parity shards
+        encoder
+            .encode(&mut shards)
+            .map_err(|e| EctarError::ErasureCoding(format!("Encoding failed: {:?}", e)))?;
+
+        // Write shards using StreamingShardWriter with zfec headers
+        let total_shards = self.data_shards + self.parity_shards;
+
+        // Validate parameters fit in u8 for zfec headers
+        if self.data_shards > 255 || total_shards > 255 {
+            return Err(EctarError::InvalidParameters(
+                "Shard counts must be <= 255 for zfec headers".to_string(),
+            ));
+        }
+
+        let mut shard_writer = StreamingShardWriter::for_chunk_with_headers(
+            &self.output_base,
+            chunk.chunk_number,
+            self.data_shards as u8,
+            total_shards as u8,
+            padlen,
+        )?
+        .with_trailers(self.shard_trailers)
+        .with_durability(self.durability);
+
+        shard_writer.write_shards(&shards)?;
+        let shard_checksums = shard_writer.shard_checksums();
+        shard_writer.finish()?;
+
+        log::info!(
+            "Chunk {}: created {} shards (shard size: {} bytes, padding: {} bytes)",
+            chunk.chunk_number,
+            shards.len(),
+            shard_size,
+            padlen
+        );
+
+        Ok(ChunkInfo {
+            chunk_number: chunk.chunk_number,
+            compressed_size: chunk_data.len() as u64,
+            uncompressed_size: chunk.uncompressed_size,
+            shard_size: shard_size as u64,
+            checksum: checksum_bytes(chunk_data),
+            uncompressed_checksum: chunk.uncompressed_checksum,
+            shard_checksums,
+        })
+    }
+}
+
+/// Worker threads that erasure-code and write finished chunks
+///
+/// Chunks are handed over through a queue that holds a single chunk, so
+/// compressing the next chunk overlaps with encoding the previous ones while
+/// at most `threads + 2` chunks are held in memory.
+pub struct EncodePool {
+    sender: SyncSender<PendingChunk>,
+    workers: Vec<JoinHandle<()>>,
+    results: Arc<Mutex<Vec<ChunkInfo>>>,
+    error: Arc<Mutex<Option<EctarError>>>,
+}
+
+impl EncodePool {
+    pub fn new(layout: ShardLayout, threads: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let error = Arc::new(Mutex::new(None));
+
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let layout = layout.clone();
+                let receiver = Arc::clone(&receiver);
+                let results = Arc::clone(&results);
+                let error = Arc::clone(&error);
+                std::thread::spawn(move || Self::work(&layout, &receiver, &results, &error))
+            })
+            .collect();
+
+        Self {
+            sender,
+            workers,
+            results,
+            error,
+        }
+    }
+
+    fn work(
+        layout: &ShardLayout,
+        receiver: &Mutex<Receiver<PendingChunk>>,
+        results: &Mutex<Vec<ChunkInfo>>,
+        error: &Mutex<Option<EctarError>>,
+    ) {
+        loop {
+            // Hold the lock only while waiting, not while encoding
+            let chunk = match receiver.lock().expect("queue lock poisoned").recv() {
+                Ok(chunk) => chunk,
+                Err(_) => return,
+            };
+
+            let chunk_number = chunk.chunk_number;
+            match layout.encode(chunk) {
+                Ok(info) => results.lock().expect("results lock poisoned").push(info),
+                Err(e) => {
+                    log::error!("Chunk {}: encoding failed: {}", chunk_number, e);
+                    error.lock().expect("error lock poisoned").get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    fn take_error(&self) -> Option<EctarError> {
+        self.error.lock().expect("error lock poisoned").take()
+    }
+
+    /// Queue a chunk, waiting while the queue is full
+    ///
+    /// Fails with the error of any chunk that has failed so far.
+    pub fn submit(&mut self, chunk: PendingChunk) -> Result<()> {
+        if let Some(e) = self.take_error() {
+            return Err(e);
+        }
+        self.sender
+            .send(chunk)
+            .map_err(|_| EctarError::ErasureCoding("Encoding workers have stopped".to_string()))
+    }
+
+    /// Number of chunks whose shards are completely written
+    pub fn completed_count(&self) -> usize {
+        self.results.lock().expect("results lock poisoned").len()
+    }
+
+    /// Metadata of the completed chunks numbered above `after`, in chunk order
+    pub fn completed_after(&self, after: usize) -> Vec<ChunkInfo> {
+        let mut chunks: Vec<ChunkInfo> = self
+            .results
+            .lock()
+            .expect("results lock poisoned")
+            .iter()
+            .filter(|c| c.chunk_number > after)
+            .cloned()
+            .collect();
+        chunks.sort_by_key(|c| c.chunk_number);
+        chunks
+    }
+
+    /// Wait for every queued chunk and return their metadata in chunk order
+    pub fn finish(self) -> Result<Vec<ChunkInfo>> {
+        drop(self.sender);
+        for worker in self.workers {
+            worker
+                .join()
+                .map_err(|_| EctarError::ErasureCoding("Encoding worker panicked".to_string()))?;
+        }
+
+        if let Some(e) = self.error.lock().expect("error lock poisoned").take() {
+            return Err(e);
+        }
+
+        let mut chunks = std::mem::take(&mut *self.results.lock().expect("results lock poisoned"));
+        chunks.sort_by_key(|c| c.chunk_number);
+        Ok(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn layout(temp_dir: &TempDir, name: &str) -> ShardLayout {
+        ShardLayout {
+            output_base: temp_dir.path().join(name).to_string_lossy().to_string(),
+            data_shards: 3,
+            parity_shards: 2,
+            shard_trailers: false,
+            durability: Durability::default(),
+        }
+    }
+
+    fn pending(chunk_number: usize) -> PendingChunk {
+        PendingChunk {
+            chunk_number,
+            data: vec![chunk_number as u8; 1000 + chunk_number],
+            uncompressed_size: 0,
+            uncompressed_checksum: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_pool_matches_sequential_encoding() {
+        let temp_dir = TempDir::new().unwrap();
+        let sequential = layout(&temp_dir, "seq");
+        let expected: Vec<ChunkInfo> = (1..=8).map(|n| sequential.encode(pending(n)).unwrap()).collect();
+
+        let mut pool = EncodePool::new(layout(&temp_dir, "par"), 4);
+        for n in 1..=8 {
+            pool.submit(pending(n)).unwrap();
+        }
+        let chunks = pool.finish().unwrap();
+
+        assert_eq!(chunks.len(), 8);
+        for (chunk, expected) in chunks.iter().zip(&expected) {
+            assert_eq!(chunk.chunk_number, expected.chunk_number);
+            assert_eq!(chunk.checksum, expected.checksum);
+            assert_eq!(chunk.shard_checksums, expected.shard_checksums);
+        }
+        for shard in 0..5 {
+            let name = |base: &str| temp_dir.path().join(format!("{}.c008.s{:02}", base, shard));
+            assert_eq!(std::fs::read(name("par")).unwrap(), std::fs::read(name("seq")).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_pool_reports_failed_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut pool = EncodePool::new(layout(&temp_dir, "missing/dir/test"), 2);
+
+        // The failure surfaces on a later submission or at the latest on finish
+        let submitted = (1..=4).try_for_each(|n| pool.submit(pending(n)));
+        assert!(submitted.is_err() || pool.finish().is_err());
+    }
+}
@@ -1,8 +1,11 @@
 pub mod chunker;
 pub mod compressed_chunker;
+pub mod encode_pool;
+pub mod ordered_pool;
 pub mod reassembler;
 pub mod streaming_erasure_chunker;
 
 pub use chunker::{ChunkMetadata, ChunkingWriter};
 pub use compressed_chunker::{ChunkInfo, CompressedChunkingWriter};
+pub use reassembler::{ChunkSender, ChunkStreamReader, StreamChunk};
 pub use streaming_erasure_chunker::StreamingErasureChunkingWriter;
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Results finished ahead of the consumer
+struct Window<R> {
+    ready: BTreeMap<usize, R>,
+    consumed: usize,
+    stopped: bool,
+}
+
+/// Stops the consumer from waiting on a worker that panicked
+struct PanicGuard<'a, R> {
+    state: &'a Mutex<Window<R>>,
+    changed: &'a Condvar,
+}
+
+impl<R> Drop for PanicGuard<'_, R> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            if let Ok(mut window) = self.state.lock() {
+                window.stopped = true;
+            }
+            self.changed.notify_all();
+        }
+    }
+}
+
+/// Apply `map` to every item on `threads` threads and hand the results to
+/// `consume` in item order
+///
+/// Workers never run more than `threads` items ahead of the consumer, so at
+/// most `threads + 1` results exist at once however many items there are.
+/// Returning `ControlFlow::Break` from `consume` stops the remaining work.
+/// With a single thread everything runs on the calling thread.
+pub fn map_ordered<T, R, M, C>(items: &[T], threads: usize, map: M, mut consume: C)
+where
+    T: Sync,
+    R: Send,
+    M: Fn(&T) -> R + Sync,
+    C: FnMut(R) -> ControlFlow<()>,
+{
+    if threads <= 1 {
+        for item in items {
+            if consume(map(item)).is_break() {
+                return;
+            }
+        }
+        return;
+    }
+
+    let state = Mutex::new(Window {
+        ready: BTreeMap::new(),
+        consumed: 0,
+        stopped: false,
+    });
+    let changed = Condvar::new();
+    let next = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(items.len()) {
+            scope.spawn(|| {
+                let _guard = PanicGuard { state: &state, changed: &changed };
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= items.len() {
+                        return;
+                    }
+
+                    // Wait until item i is within reach of the consumer
+                    let mut window = state.lock().expect("window lock poisoned");
+                    while !window.stopped && i >= window.consumed + threads {
+                        window = changed.wait(window).expect("window lock poisoned");
+                    }
+                    if window.stopped {
+                        return;
+                    }
+                    drop(window);
+
+                    let result = map(&items[i]);
+                    state.lock().expect("window lock poisoned").ready.insert(i, result);
+                    changed.notify_all();
+                }
+            });
+        }
+
+        for i in 0..items.len() {
+            let result = {
+                let mut window = state.lock().expect("window lock poisoned");
+                loop {
+                    if let Some(result) = window.ready.remove(&i) {
+                        window.consumed = i + 1;
+                        break Some(result);
+                    }
+                    if window.stopped {
+                        break None;
+                    }
+                    window = changed.wait(window).expect("window lock poisoned");
+                }
+            };
+            changed.notify_all();
+
+            // None means a worker panicked; the scope re-raises the panic
+            let flow = match result {
+                Some(result) => consume(result),
+                None => ControlFlow::Break(()),
+            };
+            if flow.is_break() {
+                state.lock().expect("window lock poisoned").stopped = true;
+                changed.notify_all();
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_results_arrive_in_order() {
+        let items: Vec<u64> = (0..50).collect();
+        let mut seen = Vec::new();
+
+        map_ordered(
+            &items,
+            4,
+            |&n| {
+                // Later items finish first
+                std::thread::sleep(std::time::Duration::from_millis(50 - n));
+                n * 2
+            },
+            |r| {
+                seen.push(r);
+                ControlFlow::Continue(())
+            },
+        );
+
+        assert_eq!(seen, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_workers_stay_within_window() {
+        let items: Vec<usize> = (0..40).collect();
+        let started = AtomicUsize::new(0);
+        let mut consumed = 0;
+
+        map_ordered(
+            &items,
+            3,
+            |&i| {
+                started.fetch_add(1, Ordering::SeqCst);
+                i
+            },
+            |i| {
+                // Nothing beyond the window can have been started yet
+                assert!(started.load(Ordering::SeqCst) <= i + 1 + 3);
+                consumed += 1;
+                ControlFlow::Continue(())
+            },
+        );
+        assert_eq!(consumed, 40);
+    }
+
+    #[test]
+    fn test_break_stops_work() {
+        let items: Vec<usize> = (0..1000).collect();
+        let mapped = AtomicUsize::new(0);
+        let mut seen = Vec::new();
+
+        map_ordered(
+            &items,
+            4,
+            |&i| {
+                mapped.fetch_add(1, Ordering::SeqCst);
+                i
+            },
+            |i| {
+                seen.push(i);
+                if i == 5 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        );
+
+        assert_eq!(seen, vec![0, 1, 2, 3, 4, 5]);
+        assert!(mapped.load(Ordering::SeqCst) <= 6 + 4);
+    }
+
+    #[test]
+    fn test_single_thread_runs_inline() {
+        let items = [1, 2, 3];
+        let caller = std::thread::current().id();
+        let mut total = 0;
+
+        map_ordered(
+            &items,
+            1,
+            |&n| {
+                assert_eq!(std::thread::current().id(), caller);
+                n
+            },
+            |n| {
+                total += n;
+                ControlFlow::Continue(())
+            },
+        );
+        assert_eq!(total, 6);
+    }
+}
@@ -1,3 +1,231 @@
-use crate::error::Result;
+use crate::checksum::sha256::finalize_checksum;
+use crate::compression;
+use crate::error::{EctarError, Result};
+use digest::Digest;
+use sha2::Sha256;
+use std::io::{self, BufReader, Cursor, Read};
+use std::sync::mpsc::{self, Receiver, SyncSender};
 
-// Chunk reassembly logic will be implemented here
+/// A reconstructed chunk on its way to the tar reader
+pub struct StreamChunk {
+    pub chunk_number: usize,
+    /// The chunk's zstd frames, manifest frame included (decrypted if the
+    /// archive is encrypted)
+    pub data: Vec<u8>,
+    /// Expected SHA-256 of the chunk's tar bytes, if the index records one
+    pub uncompressed_checksum: Option<String>,
+}
+
+/// Sending half used by the thread that reconstructs chunks
+///
+/// Sending an `Err` aborts the stream with that error.
+pub type ChunkSender = SyncSender<Result<StreamChunk>>;
+
+/// Chunk currently being decompressed
+struct ActiveChunk {
+    chunk_number: usize,
+    decoder: zstd::stream::read::Decoder<'static, BufReader<Cursor<Vec<u8>>>>,
+    hasher: Sha256,
+    expected: Option<String>,
+}
+
+/// Reads the tar stream of consecutive chunks as one continuous `Read`
+///
+/// Chunks arrive from a decoding thread over a channel that holds one chunk,
+/// so chunk N+1 is reconstructed while chunk N is decompressed and unpacked.
+/// Nothing is written to disk and at most a few compressed chunks are held
+/// in memory.
+pub struct ChunkStreamReader {
+    receiver: Receiver<Result<StreamChunk>>,
+    current: Option<ActiveChunk>,
+    verify_checksums: bool,
+    tolerate_mismatch: bool,
+    error: Option<EctarError>,
+}
+
+impl ChunkStreamReader {
+    /// Create a reader and the sender that feeds it chunks in order
+    pub fn channel() -> (ChunkSender, Self) {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let reader = Self {
+            receiver,
+            current: None,
+            verify_checksums: true,
+            tolerate_mismatch: false,
+            error: None,
+        };
+        (sender, reader)
+    }
+
+    /// Check each chunk's tar bytes against its uncompressed checksum
+    pub fn verify_checksums(mut self, verify: bool) -> Self {
+        self.verify_checksums = verify;
+        self
+    }
+
+    /// Log uncompressed checksum mismatches instead of failing the stream
+    pub fn tolerate_mismatch(mut self, tolerate: bool) -> Self {
+        self.tolerate_mismatch = tolerate;
+        self
+    }
+
+    /// The error that ended the stream, if it came from chunk decoding or
+    /// checksum verification rather than from reading
+    pub fn take_error(&mut self) -> Option<EctarError> {
+        self.error.take()
+    }
+
+    fn fail(&mut self, error: EctarError) -> io::Error {
+        let io_error = io::Error::other(error.to_string());
+        self.error = Some(error);
+        io_error
+    }
+
+    fn start_chunk(&mut self, chunk: StreamChunk) -> io::Result<()> {
+        log::debug!("Decompressing chunk {}...", chunk.chunk_number);
+        let decoder = compression::create_decoder(Cursor::new(chunk.data))
+            .map_err(|e| self.fail(e))?;
+
+        self.current = Some(ActiveChunk {
+            chunk_number: chunk.chunk_number,
+            decoder,
+            hasher: Sha256::new(),
+            expected: chunk.uncompressed_checksum,
+        });
+        Ok(())
+    }
+
+    fn finish_chunk(&mut self, chunk: ActiveChunk) -> io::Result<()> {
+        let expected = match chunk.expected {
+            Some(expected) if self.verify_checksums => expected,
+            _ => return Ok(()),
+        };
+
+        if finalize_checksum(chunk.hasher) != expected {
+            if self.tolerate_mismatch {
+                // The bytes have already been handed on; all that is left is to say so
+                log::warn!(
+                    "Chunk {}: uncompressed checksum mismatch (partial mode)",
+                    chunk.chunk_number
+                );
+                return Ok(());
+            }
+            return Err(self.fail(EctarError::ChunkChecksumMismatch {
+                chunk: chunk.chunk_number,
+                stage: "uncompressed",
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for ChunkStreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.current.is_none() {
+                match self.receiver.recv() {
+                    Ok(Ok(chunk)) => self.start_chunk(chunk)?,
+                    Ok(Err(e)) => return Err(self.fail(e)),
+                    // The decoding thread has sent every chunk
+                    Err(_) => return Ok(0),
+                }
+            }
+
+            let current = self.current.as_mut().expect("chunk is active");
+            let n = current.decoder.read(buf)?;
+            if n > 0 {
+                current.hasher.update(&buf[..n]);
+                return Ok(n);
+            }
+
+            let finished = self.current.take().expect("chunk is active");
+            self.finish_chunk(finished)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::sha256::checksum_bytes;
+
+    fn stream_chunk(chunk_number: usize, tar_bytes: &[u8], checksum: Option<String>) -> StreamChunk {
+        StreamChunk {
+            chunk_number,
+            data: zstd::encode_all(tar_bytes, 3).unwrap(),
+            uncompressed_checksum: checksum,
+        }
+    }
+
+    #[test]
+    fn test_chains_chunks_in_order() {
+        let (sender, mut reader) = ChunkStreamReader::channel();
+
+        let feeder = std::thread::spawn(move || {
+            for (n, part) in [b"first ".as_slice(), b"second ", b"third"].iter().enumerate() {
+                let checksum = Some(checksum_bytes(part));
+                sender.send(Ok(stream_chunk(n + 1, part, checksum))).unwrap();
+            }
+        });
+
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        feeder.join().unwrap();
+
+        assert_eq!(output, "first second third");
+        assert!(reader.take_error().is_none());
+    }
+
+    #[test]
+    fn test_uncompressed_checksum_mismatch() {
+        let (sender, mut reader) = ChunkStreamReader::channel();
+        let bad = Some(format!("sha256:{}", "0".repeat(64)));
+        sender.send(Ok(stream_chunk(4, b"chunk bytes", bad))).unwrap();
+        drop(sender);
+
+        let mut output = Vec::new();
+        assert!(reader.read_to_end(&mut output).is_err());
+        assert!(matches!(
+            reader.take_error(),
+            Some(EctarError::ChunkChecksumMismatch { chunk: 4, stage: "uncompressed" })
+        ));
+    }
+
+    #[test]
+    fn test_mismatch_tolerated_or_unchecked() {
+        for (verify, tolerate) in [(true, true), (false, false)] {
+            let (sender, reader) = ChunkStreamReader::channel();
+            let mut reader = reader.verify_checksums(verify).tolerate_mismatch(tolerate);
+            let bad = Some(format!("sha256:{}", "0".repeat(64)));
+            sender.send(Ok(stream_chunk(1, b"chunk bytes", bad))).unwrap();
+            drop(sender);
+
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).unwrap();
+            assert_eq!(output, b"chunk bytes");
+        }
+    }
+
+    #[test]
+    fn test_decoder_error_ends_stream() {
+        let (sender, mut reader) = ChunkStreamReader::channel();
+        sender.send(Ok(stream_chunk(1, b"good ", None))).unwrap();
+
+        let feeder = std::thread::spawn(move || {
+            sender
+                .send(Err(EctarError::ErasureCoding("chunk 2 lost".to_string())))
+                .unwrap();
+        });
+
+        let mut output = Vec::new();
+        assert!(reader.read_to_end(&mut output).is_err());
+        feeder.join().unwrap();
+        assert_eq!(output, b"good ");
+        assert!(matches!(reader.take_error(), Some(EctarError::ErasureCoding(_))));
+    }
+}
@@ -1,7 +1,14 @@
+use crate::checksum::sha256::finalize_checksum;
+use crate::chunking::encode_pool::{EncodePool, PendingChunk, ShardLayout};
 use crate::compression;
-use crate::error::{EctarError, Result};
-use crate::io::streaming_shard_writer::StreamingShardWriter;
-use reed_solomon_erasure::galois_8::ReedSolomon;
+use crate::crypto::{Context, Encryptor};
+use crate::erasure::stripe::StripeEncoder;
+use crate::error::Result;
+use crate::index::format::FileEntry;
+use crate::index::manifest::ChunkManifest;
+use crate::io::durable::Durability;
+use digest::Digest;
+use sha2::Sha256;
 use std::io::Write;
 use std::path::PathBuf;
 
@@ -12,14 +19,32 @@ pub struct StreamingErasureChunkingWriter {
     chunk_size: u64,
     compression_level: i32,
     no_compression: bool,
+    shard_trailers: bool,
+    stripe_size: Option<usize>,
+    threads: usize,
+    durability: Durability,
+    // Encrypts each chunk between compression and erasure coding
+    encryptor: Option<Encryptor>,
+    // Workers encoding finished chunks (created on the first chunk when threads > 1)
+    pool: Option<EncodePool>,
     data_shards: usize,
     parity_shards: usize,
     current_chunk: usize,
     bytes_in_current_chunk: u64,
+    // Uncompressed bytes written across all chunks
+    stream_position: u64,
+    // Tar bytes up to here are already stored in chunks of an interrupted run
+    skip_until: u64,
     // Zstd encoder that writes to an internal buffer (None if no compression)
     current_encoder: Option<zstd::stream::write::Encoder<'static, Vec<u8>>>,
+    // Zstd encoder that feeds shards stripe by stripe (striped mode)
+    current_striped: Option<zstd::stream::write::Encoder<'static, StripeEncoder>>,
     // Raw buffer for uncompressed mode
     current_buffer: Option<Vec<u8>>,
+    // Running hash of the tar bytes fed into the current chunk
+    uncompressed_hasher: Sha256,
+    // Entries not yet written into a chunk manifest
+    manifest_entries: Vec<FileEntry>,
     chunks_created: Vec<ChunkInfo>,
 }
 
@@ -29,6 +54,12 @@ pub struct ChunkInfo {
     pub compressed_size: u64,
     pub uncompressed_size: u64,
     pub shard_size: u64,
+    /// SHA-256 of the chunk as stored (after compression)
+    pub checksum: String,
+    /// SHA-256 of the chunk's tar bytes before compression
+    pub uncompressed_checksum: String,
+    /// SHA-256 of each shard's data, by shard number
+    pub shard_checksums: Vec<String>,
 }
 
 impl StreamingErasureChunkingWriter {
@@ -44,12 +75,23 @@ impl StreamingErasureChunkingWriter {
             chunk_size,
             compression_level,
             no_compression: false,
+            shard_trailers: false,
+            stripe_size: None,
+            threads: 1,
+            durability: Durability::default(),
+            encryptor: None,
+            pool: None,
             data_shards,
             parity_shards,
             current_chunk: 0,
             bytes_in_current_chunk: 0,
+            stream_position: 0,
+            skip_until: 0,
             current_encoder: None,
+            current_striped: None,
             current_buffer: None,
+            uncompressed_hasher: Sha256::new(),
+            manifest_entries: Vec::new(),
             chunks_created: Vec::new(),
         }
     }
@@ -59,21 +101,133 @@ impl StreamingErasureChunkingWriter {
         self
     }
 
+    /// Append a checksum trailer to every shard file
+    pub fn shard_trailers(mut self, trailers: bool) -> Self {
+        self.shard_trailers = trailers;
+        self
+    }
+
+    /// Erasure-code compressed chunks in stripes of this many bytes per shard
+    ///
+    /// Each stripe's parity is computed as soon as the stripe is full and
+    /// written straight to the shard files, so memory no longer grows with
+    /// the chunk size. Has no effect without compression.
+    pub fn stripe_size(mut self, stripe_size: Option<usize>) -> Self {
+        self.stripe_size = stripe_size;
+        self
+    }
+
+    /// Erasure-code and write finished chunks on this many worker threads
+    ///
+    /// With more than one thread, the next chunk is compressed while earlier
+    /// chunks are still being encoded, at the cost of holding up to
+    /// `threads + 2` chunks in memory. Striped chunks are encoded as they are
+    /// written and do not use the workers.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// How durably shard files are committed once written
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Encrypt each chunk after compression, before erasure coding
+    ///
+    /// Not supported together with striping, whose shards are written
+    /// before the chunk is complete.
+    pub fn encryption(mut self, encryptor: Option<Encryptor>) -> Self {
+        self.encryptor = encryptor;
+        self
+    }
+
+    /// Continue an interrupted run whose first chunks are already written
+    ///
+    /// The caller writes the tar stream again from `stream_offset`, which
+    /// must lie within those chunks. Bytes they already hold are discarded,
+    /// and writing picks up with the next chunk number.
+    pub fn resume(mut self, chunks: Vec<ChunkInfo>, stream_offset: u64) -> Self {
+        self.skip_until = chunks.iter().map(|c| c.uncompressed_size).sum();
+        self.chunks_created = chunks;
+        self.skip(stream_offset);
+        self
+    }
+
+    /// Advance the stream position without writing, keeping chunk positions
+    /// as they were when the bytes were first written
+    fn skip(&mut self, len: u64) {
+        let mut left = len;
+        while left > 0 {
+            if self.current_chunk == 0 || self.bytes_in_current_chunk >= self.chunk_size {
+                self.current_chunk += 1;
+                self.bytes_in_current_chunk = 0;
+            }
+            let step = left.min(self.chunk_size - self.bytes_in_current_chunk);
+            self.bytes_in_current_chunk += step;
+            self.stream_position += step;
+            left -= step;
+        }
+    }
+
+    fn shard_layout(&self) -> ShardLayout {
+        ShardLayout {
+            output_base: self.output_base.clone(),
+            data_shards: self.data_shards,
+            parity_shards: self.parity_shards,
+            shard_trailers: self.shard_trailers,
+            durability: self.durability,
+        }
+    }
+
+    /// Whether a chunk is currently open for writing
+    fn chunk_open(&self) -> bool {
+        self.current_encoder.is_some() || self.current_striped.is_some() || self.current_buffer.is_some()
+    }
+
+    /// Record an entry for the manifest of the chunk it starts in
+    ///
+    /// Call before writing the entry's tar data; `entry.chunk` must be the
+    /// chunk the entry starts in. Manifests are only embedded in compressed
+    /// chunks, since in uncompressed mode they would corrupt the tar stream.
+    pub fn add_manifest_entry(&mut self, entry: FileEntry) {
+        // The manifest of a chunk from an interrupted run is already written
+        let written = entry.stream_offset.is_some_and(|offset| offset < self.skip_until);
+        if !self.no_compression && !written {
+            self.manifest_entries.push(entry);
+        }
+    }
+
     /// Start a new chunk with fresh compression (or raw buffer if no compression)
     fn start_new_chunk(&mut self) -> Result<()> {
         // Finish current chunk if exists
-        if self.current_encoder.is_some() || self.current_buffer.is_some() {
+        if self.chunk_open() {
             self.finish_current_chunk()?;
         } else {
-            // First chunk - start at 1
-            self.current_chunk = 1;
+            // First chunk, or the first after those of an interrupted run
+            self.current_chunk += 1;
         }
 
         self.bytes_in_current_chunk = 0;
+        self.uncompressed_hasher = Sha256::new();
 
         if self.no_compression {
             // Use raw buffer for uncompressed mode
             self.current_buffer = Some(Vec::new());
+        } else if let Some(stripe_size) = self.stripe_size {
+            // Compressed bytes go straight to the stripe encoder
+            let stripes = StripeEncoder::new(
+                &self.output_base,
+                self.current_chunk,
+                self.data_shards,
+                self.parity_shards,
+                stripe_size,
+                self.shard_trailers,
+            )?
+            .with_durability(self.durability);
+            let encoder = compression::create_encoder(stripes, self.compression_level)?;
+            self.current_striped = Some(encoder);
         } else {
             // Create encoder that writes to a new Vec
             let buffer = Vec::new();
@@ -91,10 +245,16 @@ impl StreamingErasureChunkingWriter {
 
     /// Finish the current chunk: compress (if enabled), encode with erasure coding, and write shards
     fn finish_current_chunk(&mut self) -> Result<()> {
+        if let Some(encoder) = self.current_striped.take() {
+            return self.finish_striped_chunk(encoder);
+        }
+
         // Get chunk data from either compressed encoder or raw buffer
         let (chunk_buffer, uncompressed_size) = if let Some(encoder) = self.current_encoder.take() {
-            // Finish compression and get the compressed data
-            let buffer = encoder.finish()?;
+            // Finish compression and put the chunk manifest in front of the data
+            let compressed = encoder.finish()?;
+            let mut buffer = self.take_manifest().encode_frame()?;
+            buffer.extend_from_slice(&compressed);
             (buffer, self.bytes_in_current_chunk)
         } else if let Some(buffer) = self.current_buffer.take() {
             // Uncompressed mode - use raw buffer directly
@@ -104,12 +264,16 @@ impl StreamingErasureChunkingWriter {
             return Ok(());
         };
 
-        let compressed_size = chunk_buffer.len() as u64;
-
-        if compressed_size == 0 {
+        if chunk_buffer.is_empty() {
             return Ok(());
         }
 
+        let chunk_buffer = match self.encryptor {
+            Some(ref encryptor) => encryptor.seal(Context::Chunk(self.current_chunk), &chunk_buffer)?,
+            None => chunk_buffer,
+        };
+        let compressed_size = chunk_buffer.len() as u64;
+
         log::debug!(
             "Finishing chunk {} ({} bytes{})",
             self.current_chunk,
@@ -117,15 +281,24 @@ impl StreamingErasureChunkingWriter {
             if self.no_compression { "" } else { " compressed" }
         );
 
-        // Apply erasure coding to the chunk
-        let shard_size = self.encode_and_write_shards(&chunk_buffer)?;
-
-        self.chunks_created.push(ChunkInfo {
+        let chunk = PendingChunk {
             chunk_number: self.current_chunk,
-            compressed_size,
+            data: chunk_buffer,
             uncompressed_size,
-            shard_size,
-        });
+            uncompressed_checksum: finalize_checksum(std::mem::take(&mut self.uncompressed_hasher)),
+        };
+
+        // Apply erasure coding to the chunk, on the workers if there are any
+        if self.threads > 1 {
+            let layout = self.shard_layout();
+            let threads = self.threads;
+            self.pool
+                .get_or_insert_with(|| EncodePool::new(layout, threads))
+                .submit(chunk)?;
+        } else {
+            let info = self.shard_layout().encode(chunk)?;
+            self.chunks_created.push(info);
+        }
 
         // Increment chunk number for next chunk
         self.current_chunk += 1;
@@ -133,71 +306,43 @@ impl StreamingErasureChunkingWriter {
         Ok(())
     }
 
-    /// Apply Reed-Solomon erasure coding and write shards
-    fn encode_and_write_shards(&self, chunk_data: &[u8]) -> Result<u64> {
+    /// Finish a striped chunk: its data is already in the shard files, so
+    /// only the manifest, footer and final stripe remain
+    fn finish_striped_chunk(&mut self, encoder: zstd::stream::write::Encoder<'static, StripeEncoder>) -> Result<()> {
+        let stripes = encoder.finish()?;
+        let manifest = self.take_manifest().encode_frame()?;
+        let striped = stripes.finish(&manifest)?;
+
         log::debug!(
-            "Encoding chunk {} ({} bytes) into {} data + {} parity shards",
+            "Finished striped chunk {} ({} bytes compressed)",
             self.current_chunk,
-            chunk_data.len(),
-            self.data_shards,
-            self.parity_shards
+            striped.size
         );
 
-        // Calculate shard size (round up to ensure all data fits)
-        let shard_size = (chunk_data.len() + self.data_shards - 1) / self.data_shards;
-
-        // Calculate padding for zfec header (number of padding bytes in the last shard)
-        let total_data_bytes = self.data_shards * shard_size;
-        let padlen = total_data_bytes - chunk_data.len();
-
-        // Create Reed-Solomon encoder
-        let encoder = ReedSolomon::new(self.data_shards, self.parity_shards)
-            .map_err(|e| EctarError::ErasureCoding(format!("Failed to create encoder: {:?}", e)))?;
-
-        // Create shards - initialize all to shard_size with zeros
-        let mut shards: Vec<Vec<u8>> = vec![vec![0u8; shard_size]; self.data_shards + self.parity_shards];
-
-        // Copy chunk data into data shards
-        for (i, chunk) in chunk_data.chunks(shard_size).enumerate() {
-            shards[i][..chunk.len()].copy_from_slice(chunk);
-            // Remaining bytes are already zero-padded
-        }
-
-        // Encode to generate parity shards
-        encoder
-            .encode(&mut shards)
-            .map_err(|e| EctarError::ErasureCoding(format!("Encoding failed: {:?}", e)))?;
-
-        // Write shards using StreamingShardWriter with zfec headers
-        let total_shards = self.data_shards + self.parity_shards;
+        self.chunks_created.push(ChunkInfo {
+            chunk_number: self.current_chunk,
+            compressed_size: striped.size,
+            uncompressed_size: self.bytes_in_current_chunk,
+            shard_size: striped.shard_size,
+            checksum: striped.checksum,
+            uncompressed_checksum: finalize_checksum(std::mem::take(&mut self.uncompressed_hasher)),
+            shard_checksums: striped.shard_checksums,
+        });
 
-        // Validate parameters fit in u8 for zfec headers
-        if self.data_shards > 255 || total_shards > 255 {
-            return Err(EctarError::InvalidParameters(
-                "Shard counts must be <= 255 for zfec headers".to_string(),
-            ));
-        }
+        self.current_chunk += 1;
 
-        let mut shard_writer = StreamingShardWriter::for_chunk_with_headers(
-            &self.output_base,
-            self.current_chunk,
-            self.data_shards as u8,
-            total_shards as u8,
-            padlen,
-        )?;
+        Ok(())
+    }
 
-        shard_writer.write_shards(&shards)?;
-        shard_writer.finish()?;
+    /// Build the manifest for the current chunk from the pending entries
+    fn take_manifest(&mut self) -> ChunkManifest {
+        let chunk = self.current_chunk;
+        let (files, later): (Vec<FileEntry>, Vec<FileEntry>) = std::mem::take(&mut self.manifest_entries)
+            .into_iter()
+            .partition(|entry| entry.chunk <= chunk);
+        self.manifest_entries = later;
 
-        log::info!(
-            "Chunk {}: created {} shards (shard size: {} bytes, padding: {} bytes)",
-            self.current_chunk,
-            shards.len(),
-            shard_size,
-            padlen
-        );
-
-        Ok(shard_size as u64)
+        ChunkManifest::new(chunk, files)
     }
 
     /// Get the current chunk number
@@ -210,16 +355,56 @@ impl StreamingErasureChunkingWriter {
         }
     }
 
+    /// Chunk and uncompressed in-chunk offset the next written byte lands at
+    ///
+    /// Unlike `current_chunk_number`, this accounts for a full chunk: the
+    /// next byte then starts the following chunk at offset 0.
+    pub fn chunk_position(&self) -> (usize, u64) {
+        if self.current_chunk > 0 && self.bytes_in_current_chunk >= self.chunk_size {
+            (self.current_chunk + 1, 0)
+        } else {
+            (self.current_chunk_number(), self.bytes_in_current_chunk)
+        }
+    }
+
+    /// Uncompressed bytes written so far, i.e. the position in the tar stream
+    pub fn stream_position(&self) -> u64 {
+        self.stream_position
+    }
+
+    /// Number of chunks whose shards are completely written
+    pub fn chunks_completed(&self) -> usize {
+        self.chunks_created.len() + self.pool.as_ref().map_or(0, |pool| pool.completed_count())
+    }
+
+    /// Metadata of the completed chunks numbered above `after`, in chunk order
+    pub fn completed_chunks_after(&self, after: usize) -> Vec<ChunkInfo> {
+        let mut chunks: Vec<ChunkInfo> = self
+            .chunks_created
+            .iter()
+            .filter(|c| c.chunk_number > after)
+            .cloned()
+            .collect();
+        if let Some(ref pool) = self.pool {
+            chunks.extend(pool.completed_after(after));
+            chunks.sort_by_key(|c| c.chunk_number);
+        }
+        chunks
+    }
+
     /// Finish writing and return chunk metadata
     pub fn finish(mut self) -> Result<Vec<ChunkInfo>> {
         // Finish the last chunk
         // Finish the last chunk if there's data
-        if (self.current_encoder.is_some() || self.current_buffer.is_some())
-            && self.bytes_in_current_chunk > 0
-        {
+        if self.chunk_open() && self.bytes_in_current_chunk > 0 {
             self.finish_current_chunk()?;
         }
 
+        if let Some(pool) = self.pool.take() {
+            self.chunks_created.extend(pool.finish()?);
+            self.chunks_created.sort_by_key(|c| c.chunk_number);
+        }
+
         log::info!(
             "Created {} chunks with erasure coding, total uncompressed: {} bytes",
             self.chunks_created.len(),
@@ -239,10 +424,16 @@ impl Write for StreamingErasureChunkingWriter {
             return Ok(0);
         }
 
+        if self.stream_position < self.skip_until {
+            let len = (self.skip_until - self.stream_position).min(buf.len() as u64);
+            self.skip(len);
+            return Ok(len as usize);
+        }
+
         // Start first chunk if needed
-        if self.current_encoder.is_none() && self.current_buffer.is_none() {
+        if !self.chunk_open() {
             self.start_new_chunk()
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
         }
 
         let mut bytes_written = 0;
@@ -255,25 +446,26 @@ impl Write for StreamingErasureChunkingWriter {
             if to_write == 0 {
                 // Current chunk is full, start a new one
                 self.start_new_chunk()
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+                    .map_err(|e| std::io::Error::other(e.to_string()))?;
                 continue;
             }
 
             // Write to current chunk's encoder or buffer
             let n = if let Some(encoder) = self.current_encoder.as_mut() {
                 encoder.write(&buf[bytes_written..bytes_written + to_write])?
+            } else if let Some(encoder) = self.current_striped.as_mut() {
+                encoder.write(&buf[bytes_written..bytes_written + to_write])?
             } else if let Some(buffer) = self.current_buffer.as_mut() {
                 buffer.extend_from_slice(&buf[bytes_written..bytes_written + to_write]);
                 to_write
             } else {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "No active chunk",
-                ));
+                return Err(std::io::Error::other("No active chunk"));
             };
 
+            self.uncompressed_hasher.update(&buf[bytes_written..bytes_written + n]);
             bytes_written += n;
             self.bytes_in_current_chunk += n as u64;
+            self.stream_position += n as u64;
         }
 
         Ok(bytes_written)
@@ -283,6 +475,9 @@ impl Write for StreamingErasureChunkingWriter {
         if let Some(encoder) = &mut self.current_encoder {
             encoder.flush()?;
         }
+        if let Some(encoder) = &mut self.current_striped {
+            encoder.flush()?;
+        }
         // Raw buffer doesn't need flushing
         Ok(())
     }
@@ -291,6 +486,7 @@ impl Write for StreamingErasureChunkingWriter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::checksum::sha256::checksum_bytes;
     use std::io::Write;
     use tempfile::TempDir;
 
@@ -351,6 +547,64 @@ mod tests {
         assert_eq!(chunks.len(), 1);
     }
 
+    #[test]
+    fn test_shard_checksums_recorded() {
+        use crate::erasure::ShardData;
+
+        let temp_dir = TempDir::new().unwrap();
+        let output_base = temp_dir.path().join("test");
+
+        let mut writer = StreamingErasureChunkingWriter::new(
+            output_base,
+            1024,
+            3,
+            4,
+            2,
+        ).shard_trailers(true);
+
+        writer.write_all(&[7u8; 512]).unwrap();
+        let chunks = writer.finish().unwrap();
+        assert_eq!(chunks[0].shard_checksums.len(), 6);
+
+        for (shard_idx, expected) in chunks[0].shard_checksums.iter().enumerate() {
+            let shard_path = temp_dir.path().join(format!("test.c001.s{:02}", shard_idx));
+            let shard = ShardData::from_file(&shard_path).unwrap();
+            assert!(shard.trailer.is_some());
+            assert!(shard.matches_checksum(expected));
+        }
+    }
+
+    #[test]
+    fn test_chunk_checksums() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let data = vec![42u8; 512];
+        let mut writer = StreamingErasureChunkingWriter::new(
+            temp_dir.path().join("compressed"),
+            1024,
+            3,
+            4,
+            2,
+        );
+        writer.write_all(&data).unwrap();
+        let chunks = writer.finish().unwrap();
+        assert_eq!(chunks[0].uncompressed_checksum, checksum_bytes(&data));
+        assert_ne!(chunks[0].checksum, chunks[0].uncompressed_checksum);
+
+        // Without compression the stored bytes are the tar bytes
+        let mut writer = StreamingErasureChunkingWriter::new(
+            temp_dir.path().join("raw"),
+            1024,
+            3,
+            4,
+            2,
+        ).no_compression(true);
+        writer.write_all(&data).unwrap();
+        let chunks = writer.finish().unwrap();
+        assert_eq!(chunks[0].checksum, checksum_bytes(&data));
+        assert_eq!(chunks[0].uncompressed_checksum, checksum_bytes(&data));
+    }
+
     #[test]
     fn test_current_chunk_number_before_write() {
         let temp_dir = TempDir::new().unwrap();
@@ -418,6 +672,9 @@ mod tests {
             compressed_size: 1000,
             uncompressed_size: 2000,
             shard_size: 500,
+            checksum: String::new(),
+            uncompressed_checksum: String::new(),
+            shard_checksums: Vec::new(),
         };
 
         assert_eq!(info.chunk_number, 5);
@@ -469,6 +726,135 @@ mod tests {
 
         let chunks = writer.finish().unwrap();
         // 100 * 50 = 5000 bytes, should create multiple chunks
-        assert!(chunks.len() >= 1);
+        assert!(!chunks.is_empty());
+    }
+
+    #[test]
+    fn test_chunk_and_stream_position() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = StreamingErasureChunkingWriter::new(
+            temp_dir.path().join("test"),
+            1024,
+            3,
+            4,
+            2,
+        );
+        assert_eq!(writer.chunk_position(), (1, 0));
+
+        writer.write_all(&[1u8; 300]).unwrap();
+        assert_eq!(writer.chunk_position(), (1, 300));
+        assert_eq!(writer.stream_position(), 300);
+
+        // A full chunk is only closed by the next write, but the next byte
+        // already belongs to chunk 2
+        writer.write_all(&[2u8; 724]).unwrap();
+        assert_eq!(writer.current_chunk_number(), 1);
+        assert_eq!(writer.chunk_position(), (2, 0));
+
+        writer.write_all(&[3u8; 1100]).unwrap();
+        assert_eq!(writer.chunk_position(), (3, 76));
+        assert_eq!(writer.stream_position(), 2124);
+
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_striped_chunks_decode() {
+        use crate::erasure::{decoder, ShardData};
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = 88172645463325252u64;
+        let data: Vec<u8> = (0..6000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let mut writer = StreamingErasureChunkingWriter::new(
+            temp_dir.path().join("test"),
+            4096,
+            3,
+            3,
+            2,
+        ).stripe_size(Some(128));
+        writer.write_all(&data).unwrap();
+        let chunks = writer.finish().unwrap();
+        assert_eq!(chunks.len(), 2);
+
+        let mut decoded = Vec::new();
+        for chunk in &chunks {
+            // Drop a data shard so reconstruction is exercised
+            let shards: Vec<ShardData> = (1..5)
+                .map(|i| {
+                    let path = temp_dir.path().join(format!("test.c{:03}.s{:02}", chunk.chunk_number, i));
+                    ShardData::from_file(&path).unwrap()
+                })
+                .collect();
+            assert!(shards.iter().all(|s| s.data.len() as u64 == chunk.shard_size));
+
+            let stored = decoder::decode_chunk_bytes(shards, 3, 2, Some(chunk.compressed_size)).unwrap();
+            assert_eq!(checksum_bytes(&stored), chunk.checksum);
+            assert!(ChunkManifest::from_chunk(&stored).unwrap().is_some());
+            decoded.extend(zstd::decode_all(&stored[..]).unwrap());
+        }
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_threaded_encoding_matches_sequential() {
+        let temp_dir = TempDir::new().unwrap();
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        let encode = |name: &str, threads: usize| {
+            let mut writer = StreamingErasureChunkingWriter::new(
+                temp_dir.path().join(name),
+                1024,
+                3,
+                4,
+                2,
+            ).threads(threads);
+            writer.write_all(&data).unwrap();
+            writer.finish().unwrap()
+        };
+        let sequential = encode("seq", 1);
+        let threaded = encode("par", 4);
+
+        assert_eq!(threaded.len(), sequential.len());
+        for (i, (a, b)) in threaded.iter().zip(&sequential).enumerate() {
+            assert_eq!(a.chunk_number, i + 1);
+            assert_eq!(a.checksum, b.checksum);
+            assert_eq!(a.shard_checksums, b.shard_checksums);
+        }
+    }
+
+    #[test]
+    fn test_resume_after_written_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_base = temp_dir.path().join("test");
+        let data: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let writer = || StreamingErasureChunkingWriter::new(output_base.clone(), 1024, 3, 4, 2);
+
+        let mut original = writer();
+        original.write_all(&data).unwrap();
+        assert_eq!(original.chunks_completed(), 2);
+        let completed = original.completed_chunks_after(0);
+        let chunks = original.finish().unwrap();
+
+        // Pick up mid-way through chunk 2; its bytes are discarded
+        let mut resumed = writer().resume(completed, 1500);
+        assert_eq!(resumed.stream_position(), 1500);
+        assert_eq!(resumed.chunk_position(), (2, 476));
+        resumed.write_all(&data[1500..2048]).unwrap();
+        assert_eq!(resumed.chunk_position(), (3, 0));
+        resumed.write_all(&data[2048..]).unwrap();
+        let resumed = resumed.finish().unwrap();
+
+        assert_eq!(resumed.len(), 3);
+        assert_eq!(resumed[2].chunk_number, 3);
+        assert_eq!(resumed[2].checksum, chunks[2].checksum);
+        assert_eq!(resumed[2].shard_checksums, chunks[2].shard_checksums);
     }
 }
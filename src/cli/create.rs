@@ -1,3 +1 @@
-use crate::error::Result;
-
 // Create subcommand will be implemented here
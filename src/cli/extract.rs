@@ -1,3 +1 @@
-use crate::error::Result;
-
 // Extract subcommand will be implemented here
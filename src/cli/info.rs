@@ -1,13 +1,15 @@
-use crate::compression;
+use crate::crypto::signature::{self, SignatureCheck, SignatureStatus};
+use crate::crypto::{KeyRing, PublicKey, Secret};
 use crate::error::{EctarError, Result};
 use crate::index::format::ArchiveIndex;
+use crate::index::reader;
 use crate::io::shard_reader;
-use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct ArchiveInfo {
     input: String,
     output_format: OutputFormat,
+    keys: Option<KeyRing>,
 }
 
 #[derive(Debug, Clone)]
@@ -21,9 +23,16 @@ impl ArchiveInfo {
         Self {
             input,
             output_format: OutputFormat::Text,
+            keys: None,
         }
     }
 
+    /// Key or passphrase for an archive whose index is encrypted
+    pub fn decryption(mut self, secret: Option<Secret>) -> Self {
+        self.keys = secret.map(KeyRing::new);
+        self
+    }
+
     pub fn output_format(mut self, format: &str) -> Result<Self> {
         self.output_format = match format.to_lowercase().as_str() {
             "text" => OutputFormat::Text,
@@ -45,31 +54,34 @@ impl ArchiveInfo {
 
         // Display based on format
         match self.output_format {
-            OutputFormat::Text => self.display_text(&index),
+            OutputFormat::Text => {
+                let check = signature::check_signature(&reader::index_base(&index_path), &index, &[]);
+                self.display_text(&index, &check)
+            }
             OutputFormat::Json => self.display_json(&index),
         }
 
         Ok(())
     }
 
-    fn read_index(&self, index_path: &PathBuf) -> Result<ArchiveIndex> {
-        let index_file = File::open(index_path)?;
-        let mut decoder = compression::create_decoder(index_file)?;
-
-        let mut json = String::new();
-        std::io::Read::read_to_string(&mut decoder, &mut json)?;
-
-        let index: ArchiveIndex = serde_json::from_str(&json)?;
-        Ok(index)
+    fn read_index(&self, index_path: &Path) -> Result<ArchiveIndex> {
+        reader::read_index_with_keys(index_path, self.keys.as_ref())
     }
 
-    fn display_text(&self, index: &ArchiveIndex) {
+    fn display_text(&self, index: &ArchiveIndex, signature: &SignatureCheck) {
         println!("Archive Information");
         println!("{}", "=".repeat(60));
         println!("Name:              {}", index.archive_name);
         println!("Created:           {}", index.created);
+        if let Some(last_repaired) = index.last_repaired {
+            println!("Last Repaired:     {}", last_repaired);
+        }
         println!("Tool Version:      {}", index.tool_version);
         println!("Index Version:     {}", index.version);
+        if let Some(ref parent) = index.parent {
+            println!("Incremental From:  {} ({})", parent.archive_name, parent.index);
+            println!("Deleted Entries:   {}", index.deleted.len());
+        }
         println!();
 
         println!("Erasure Coding Parameters");
@@ -87,7 +99,31 @@ impl ArchiveInfo {
                 chunk_size as f64 / (1024.0 * 1024.0)
             );
         }
+        if let Some(stripe_size) = index.parameters.stripe_size {
+            println!("Stripe Size:       {} bytes per shard", stripe_size);
+        }
         println!("Compression Level: {}", index.parameters.compression_level);
+        if let Some(ref encryption) = index.parameters.encryption {
+            println!("Encryption:        {} ({}{})",
+                encryption.cipher,
+                encryption.key.describe(),
+                if encryption.index_encrypted { ", index encrypted" } else { "" }
+            );
+            for wrapped in &encryption.recipients {
+                println!("Recipient:         {}", PublicKey(wrapped.recipient));
+            }
+        }
+        if let Some(ref signer) = signature.signer {
+            println!("Signed By:         {}", signer);
+        }
+        println!(
+            "Signature:         {}",
+            match signature.status {
+                SignatureStatus::Valid | SignatureStatus::Untrusted => "valid",
+                SignatureStatus::Invalid => "INVALID",
+                SignatureStatus::Missing => "none",
+            }
+        );
         println!();
 
         println!("Archive Statistics");
@@ -114,6 +150,17 @@ impl ArchiveInfo {
             total_shards_size as f64 / (1024.0 * 1024.0)
         );
 
+        if !index.blocks.is_empty() {
+            let logical: u64 = index.files.iter().filter(|f| f.blocks.is_some()).map(|f| f.size).sum();
+            let stored: u64 = index.blocks.iter().map(|b| b.size).sum();
+            println!("Dedup Ratio:       {:.2}x ({} unique blocks, {} bytes stored for {} bytes of file data)",
+                logical as f64 / stored.max(1) as f64,
+                index.blocks.len(),
+                stored,
+                logical
+            );
+        }
+
         if total_uncompressed > 0 {
             println!("Compression Ratio: {:.2}%",
                 (total_compressed as f64 / total_uncompressed as f64) * 100.0
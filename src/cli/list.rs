@@ -1,3 +1 @@
-use crate::error::Result;
-
 // List subcommand will be implemented here
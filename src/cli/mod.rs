@@ -2,4 +2,6 @@ pub mod create;
 pub mod extract;
 pub mod info;
 pub mod list;
+pub mod rebuild_index;
+pub mod repair;
 pub mod verify;
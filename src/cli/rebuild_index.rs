@@ -0,0 +1,906 @@
+use crate::archive::dedup::{self, DedupedFile};
+use crate::archive::special;
+use crate::archive::tar_header::{self, TAR_BLOCK_SIZE};
+use crate::archive::xattrs;
+use crate::checksum::sha256::{self, ChecksumWriter};
+use crate::compression;
+use crate::crypto::envelope::{self, CIPHER};
+use crate::crypto::signature::{self, SignatureStatus};
+use crate::crypto::{Context, Encryptor, KeyDerivation, KeyRing, Recipients, Secret};
+use crate::erasure::{decoder, stripe, ShardData};
+use crate::error::{EctarError, Result};
+use crate::index::builder;
+use crate::index::format::{
+    self, ArchiveIndex, ArchiveParameters, ChunkInfo, EncryptionParameters, FileEntry, FileType,
+};
+use crate::index::manifest::ChunkManifest;
+use crate::index::reader;
+use crate::io::durable::Durability;
+use crate::io::shard_reader;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+pub struct IndexRebuilder {
+    input: String,
+    force: bool,
+    keys: Option<KeyRing>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebuildReport {
+    pub archive_name: String,
+    pub chunks_recovered: Vec<usize>,
+    /// Chunks that are missing or could not be decoded
+    pub chunks_lost: Vec<usize>,
+    pub files_indexed: usize,
+    pub index_path: PathBuf,
+}
+
+/// Where the chunks of the archive are decoded from
+struct ChunkSource<'a> {
+    shards_by_chunk: &'a HashMap<usize, Vec<ShardData>>,
+    erasure: (usize, usize),
+    keys: Option<&'a KeyRing>,
+    last_chunk: usize,
+}
+
+/// A run of consecutive decoded chunks whose tar bytes are contiguous
+///
+/// Chunks are decoded one at a time as their bytes are read, so only the
+/// chunk being read is held in memory.
+#[derive(Default)]
+struct Segment {
+    /// (chunk number, offset of the chunk's first tar byte in the segment)
+    chunk_starts: Vec<(usize, u64)>,
+    /// Manifests of the segment's chunks, by chunk number
+    manifests: HashMap<usize, ChunkManifest>,
+    /// Chunks decoded, in order
+    chunks: Vec<ChunkInfo>,
+    /// The chunk that could not be decoded, ending the segment
+    lost: Option<usize>,
+    /// An error that stops the whole rebuild
+    fatal: Option<EctarError>,
+    stripe_size: Option<u64>,
+    derivation: Option<KeyDerivation>,
+    next_chunk: usize,
+    /// Decompressor of the chunk being read
+    decoder: Option<Box<dyn Read>>,
+    /// Bytes read ahead while resynchronising, read again first
+    pending: Vec<u8>,
+    /// Segment offset of the next byte read
+    position: u64,
+}
+
+impl Segment {
+    /// Segment offset of a chunk's first tar byte
+    fn chunk_start(&self, chunk: usize) -> u64 {
+        self.chunk_starts
+            .iter()
+            .find(|(c, _)| *c == chunk)
+            .map(|(_, start)| *start)
+            .unwrap_or(0)
+    }
+
+    /// Chunk holding the given segment offset
+    fn chunk_at(&self, offset: u64) -> usize {
+        self.chunk_starts
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= offset)
+            .map(|(chunk, _)| *chunk)
+            .unwrap_or(self.chunk_starts[0].0)
+    }
+
+    /// Decode the next chunk and start reading it; a chunk that cannot be
+    /// decoded ends the segment
+    fn open_next(&mut self, source: &ChunkSource) -> io::Result<()> {
+        let chunk_num = self.next_chunk;
+        self.next_chunk += 1;
+
+        let decoded = source
+            .shards_by_chunk
+            .get(&chunk_num)
+            .ok_or_else(|| EctarError::ErasureCoding("No shards found".to_string()))
+            .and_then(|shards| decode_chunk(chunk_num, shards, source.erasure, source.keys))
+            .and_then(|(chunk_info, compressed, derivation)| {
+                let manifest = ChunkManifest::from_chunk(&compressed);
+                let stripe_size = stripe::StripeFooter::from_tail(&compressed).map(|f| f.stripe_size as u64);
+                let decoder = compression::create_decoder(io::Cursor::new(compressed))?;
+                Ok((chunk_info, decoder, derivation, manifest, stripe_size))
+            });
+
+        match decoded {
+            Ok((chunk_info, decoder, derivation, manifest, stripe_size)) => {
+                match manifest {
+                    Ok(Some(manifest)) => {
+                        self.manifests.insert(chunk_num, manifest);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Chunk {}: unreadable manifest: {}", chunk_num, e),
+                }
+                self.derivation = self.derivation.take().or(derivation);
+                self.stripe_size = self.stripe_size.or(stripe_size);
+                self.chunk_starts.push((chunk_num, self.position));
+                self.chunks.push(chunk_info);
+                self.decoder = Some(Box::new(decoder));
+            }
+            // Every other chunk would fail the same way
+            Err(e @ EctarError::Decryption(_)) => {
+                let error = io::Error::other(e.to_string());
+                self.fatal = Some(e);
+                return Err(error);
+            }
+            Err(e) => {
+                log::error!("Chunk {}: lost ({})", chunk_num, e);
+                self.lost = Some(chunk_num);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, source: &ChunkSource, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.pending.is_empty() {
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            self.position += n as u64;
+            return Ok(n);
+        }
+
+        loop {
+            if let Some(ref mut decoder) = self.decoder {
+                let n = decoder.read(buf)?;
+                if n > 0 {
+                    self.position += n as u64;
+                    return Ok(n);
+                }
+                self.decoder = None;
+            }
+            if self.lost.is_some() || self.fatal.is_some() || self.next_chunk > source.last_chunk {
+                return Ok(0);
+            }
+            self.open_next(source)?;
+        }
+    }
+}
+
+/// Reads the tar bytes of a segment, starting with the given chunk
+///
+/// It is read through a shared reference, so where the stream stands can
+/// be looked up between the entries the tar reader hands out.
+struct SegmentReader<'a> {
+    source: &'a ChunkSource<'a>,
+    segment: RefCell<Segment>,
+}
+
+impl<'a> SegmentReader<'a> {
+    fn new(source: &'a ChunkSource<'a>, first_chunk: usize) -> Result<Self> {
+        let mut segment = Segment {
+            next_chunk: first_chunk,
+            ..Default::default()
+        };
+        if segment.open_next(source).is_err() {
+            return Err(segment.fatal.take().expect("only fatal errors are returned"));
+        }
+        Ok(Self {
+            source,
+            segment: RefCell::new(segment),
+        })
+    }
+
+    /// Number of the segment's first chunk, or `None` if it lost even that
+    fn first_chunk(&self) -> Option<usize> {
+        self.segment.borrow().chunk_starts.first().map(|(chunk, _)| *chunk)
+    }
+
+    /// Segment offset of the next byte read
+    fn position(&self) -> u64 {
+        self.segment.borrow().position
+    }
+
+    fn chunk_start(&self, chunk: usize) -> u64 {
+        self.segment.borrow().chunk_start(chunk)
+    }
+
+    fn chunk_at(&self, offset: u64) -> usize {
+        self.segment.borrow().chunk_at(offset)
+    }
+
+    /// What the manifest of `chunk` records for an entry
+    fn manifest_entry(&self, chunk: usize, path: &Path) -> Option<FileEntry> {
+        let segment = self.segment.borrow();
+        segment.manifests.get(&chunk)?.files.iter().find(|f| f.archive_path() == path).cloned()
+    }
+
+    /// Skip ahead to the first offset holding a valid tar header, which is
+    /// read next; returns that offset
+    fn resync(&self) -> io::Result<Option<u64>> {
+        let mut window = Vec::new();
+        let mut window_start = self.position();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut stream = self;
+        loop {
+            let n = stream.read(&mut buf)?;
+            window.extend_from_slice(&buf[..n]);
+            let found = tar_header::header_offsets(&window).next();
+            if let Some(offset) = found {
+                let mut segment = self.segment.borrow_mut();
+                segment.pending = window.split_off(offset);
+                segment.position -= segment.pending.len() as u64;
+                return Ok(Some(window_start + offset as u64));
+            }
+            if n == 0 {
+                return Ok(None);
+            }
+            // A header may start in the last bytes
+            let keep = window.len().min(TAR_BLOCK_SIZE - 1);
+            window_start += (window.len() - keep) as u64;
+            window.drain(..window.len() - keep);
+        }
+    }
+
+    /// Decode what is left of the segment, returning what it recovered
+    fn finish(self) -> Result<Segment> {
+        let mut rest = &self;
+        let drained = io::copy(&mut rest, &mut io::sink());
+        let mut segment = self.segment.into_inner();
+        if let Some(e) = segment.fatal.take() {
+            return Err(e);
+        }
+        if let Err(e) = drained {
+            log::warn!("Chunk {}: decompression failed: {}", segment.next_chunk - 1, e);
+        }
+        Ok(segment)
+    }
+}
+
+impl Read for &SegmentReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.segment.borrow_mut().read(self.source, buf)
+    }
+}
+
+impl IndexRebuilder {
+    pub fn new(input: String) -> Self {
+        Self {
+            input,
+            force: false,
+            keys: None,
+        }
+    }
+
+    /// Overwrite an existing index
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Key file or passphrase to decrypt the chunks of an encrypted archive
+    ///
+    /// The rebuilt index of an encrypted archive is encrypted too.
+    pub fn decryption(mut self, secret: Option<Secret>) -> Self {
+        self.keys = secret.map(KeyRing::new);
+        self
+    }
+
+    pub fn rebuild(&self) -> Result<RebuildReport> {
+        let output_base = shard_reader::archive_base(&self.input);
+        let index_path = PathBuf::from(format!("{}.index.zst", output_base));
+
+        if !self.force && (index_path.exists() || !reader::find_index_shards(&output_base).is_empty()) {
+            return Err(EctarError::InvalidParameters(format!(
+                "{} already has an index; use --force to replace it",
+                output_base
+            )));
+        }
+
+        if let Some(ref keys) = self.keys {
+            keys.load_recipients(&output_base)?;
+        }
+
+        let shards_by_chunk = shard_reader::discover_shards(&self.input)?;
+        let (data_shards, parity_shards) = erasure_parameters(&shards_by_chunk)?;
+        let shard_trailers = shards_by_chunk
+            .values()
+            .flatten()
+            .any(|s| s.trailer.is_some());
+
+        let mut chunk_numbers: Vec<usize> = shards_by_chunk.keys().copied().collect();
+        chunk_numbers.sort_unstable();
+        let last_chunk = chunk_numbers.last().copied().unwrap_or(0);
+
+        let source = ChunkSource {
+            shards_by_chunk: &shards_by_chunk,
+            erasure: (data_shards, parity_shards),
+            keys: self.keys.as_ref(),
+            last_chunk,
+        };
+        let mut chunks = Vec::new();
+        let mut chunks_lost = Vec::new();
+        let mut files = Vec::new();
+        let mut stripe_size = None;
+        let mut derivation = None;
+
+        // Scan each segment as its chunks are decoded, starting a new one
+        // after every lost chunk
+        let mut next_chunk = 1;
+        while next_chunk <= last_chunk {
+            let reader = SegmentReader::new(&source, next_chunk)?;
+            let scanned = match reader.first_chunk() {
+                Some(first_chunk) => self.scan_segment(&reader, first_chunk),
+                None => Ok(Vec::new()),
+            };
+            // A chunk that cannot be decrypted is reported over what it
+            // did to the scan
+            let segment = reader.finish()?;
+            files.extend(scanned?);
+
+            chunks.extend(segment.chunks);
+            chunks_lost.extend(segment.lost);
+            stripe_size = stripe_size.or(segment.stripe_size);
+            derivation = derivation.or(segment.derivation);
+            next_chunk = segment.next_chunk;
+        }
+
+        if chunks.is_empty() {
+            return Err(EctarError::ErasureCoding(
+                "No chunks could be recovered".to_string(),
+            ));
+        }
+
+        let (blocks, files): (Vec<FileEntry>, Vec<FileEntry>) = files
+            .into_iter()
+            .partition(|f| dedup::block_hash_of(Path::new(&f.path)).is_some());
+
+        let encryptor = match (derivation, self.keys.as_ref()) {
+            (Some(derivation), Some(keys)) => Some(self.encryptor(keys, &output_base, derivation)?),
+            _ => None,
+        };
+
+        let index = ArchiveIndex {
+            version: "1.0".to_string(),
+            created: Utc::now(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            archive_name: output_base.clone(),
+            last_repaired: None,
+            parameters: ArchiveParameters {
+                data_shards,
+                parity_shards,
+                // Neither is recorded in the shards
+                chunk_size: None,
+                compression_level: 0,
+                shard_trailers,
+                stripe_size,
+                encryption: encryptor.as_ref().map(|encryptor| EncryptionParameters {
+                    cipher: CIPHER.to_string(),
+                    key: encryptor.derivation().clone(),
+                    key_check: encryptor.key().check_value(),
+                    index_encrypted: true,
+                    recipients: encryptor
+                        .recipients()
+                        .map(|recipients| recipients.recipients.clone())
+                        .unwrap_or_default(),
+                }),
+            },
+            parent: None,
+            deleted: Vec::new(),
+            deleted_bytes: Vec::new(),
+            chunks,
+            files,
+            blocks,
+        };
+
+        let index_path =
+            builder::write_index_with(&index, &output_base, Durability::default(), encryptor.as_ref())?;
+        if signature::check_signature(&output_base, &index, &[]).status == SignatureStatus::Invalid {
+            log::warn!(
+                "The archive's signature does not match the rebuilt index, so verify --trusted-key will reject it"
+            );
+        }
+
+        let report = RebuildReport {
+            archive_name: output_base,
+            chunks_recovered: index.chunks.iter().map(|c| c.chunk_number).collect(),
+            chunks_lost,
+            files_indexed: index.files.len(),
+            index_path,
+        };
+
+        self.display_report(&report);
+
+        Ok(report)
+    }
+
+    /// Encryptor for the rebuilt index, under the key the chunks use and
+    /// with the recipients it is wrapped for
+    fn encryptor(&self, keys: &KeyRing, output_base: &str, derivation: KeyDerivation) -> Result<Encryptor> {
+        let key = keys.key(&derivation)?;
+        let recipients = match derivation {
+            KeyDerivation::X25519 { key_id } => Recipients::read(output_base)?.filter(|r| r.key_id == key_id),
+            _ => None,
+        };
+        Ok(match recipients {
+            Some(recipients) => Encryptor::with_recipients(key, recipients),
+            None => Encryptor::new(key, derivation),
+        })
+    }
+
+    /// Walk the tar entries of one segment and build their file entries
+    fn scan_segment(&self, reader: &SegmentReader, first_chunk: usize) -> Result<Vec<FileEntry>> {
+        // After a lost chunk the segment starts mid-entry; resynchronise
+        // on the next tar header
+        let start = if first_chunk == 1 {
+            0
+        } else {
+            match reader.resync()? {
+                Some(offset) => {
+                    log::info!(
+                        "Chunk {}: resynchronised on a tar header at byte {}",
+                        first_chunk, offset
+                    );
+                    offset
+                }
+                None => {
+                    log::warn!("Chunks from {}: no tar header found", first_chunk);
+                    return Ok(Vec::new());
+                }
+            }
+        };
+
+        let mut files = Vec::new();
+        let mut archive = tar::Archive::new(reader);
+        let entries = match archive.entries() {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Chunks from {}: cannot read tar stream: {}", first_chunk, e);
+                return Ok(files);
+            }
+        };
+
+        // Where the next entry's first header (GNU long-name or PAX header
+        // included) starts
+        let mut next_entry_start = start;
+
+        for entry in entries {
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("Chunks from {}: tar stream ends early: {}", first_chunk, e);
+                    break;
+                }
+            };
+
+            let header = entry.header().clone();
+            let header_offset = next_entry_start;
+            // The entry's headers have all been read, a sparse entry's
+            // extension headers included, so the data comes next
+            let data_offset = reader.position();
+            let sparse = header.entry_type().is_gnu_sparse();
+            let size = entry.size();
+            // A sparse entry's data leaves out the holes `size` counts
+            let stored = if sparse { header.entry_size()? } else { size };
+            // Last byte of the entry; data_offset follows at least one header
+            let data_end = data_offset + stored - 1;
+            let chunk = reader.chunk_at(header_offset);
+            next_entry_start = data_offset + stored.div_ceil(TAR_BLOCK_SIZE as u64) * TAR_BLOCK_SIZE as u64;
+
+            let raw_path = entry.path()?.into_owned();
+            let (path, path_bytes) = format::path_fields(&raw_path);
+            let entry_type = match header.entry_type() {
+                tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::GNUSparse => FileType::File,
+                tar::EntryType::Directory => FileType::Directory,
+                tar::EntryType::Symlink => FileType::Symlink,
+                tar::EntryType::Link => FileType::Hardlink,
+                other => special::kind(other).unwrap_or(FileType::Other),
+            };
+            let is_device = matches!(entry_type, FileType::CharDevice | FileType::BlockDevice);
+            let (target, target_bytes) = match entry.link_name()? {
+                Some(target) => {
+                    let (target, target_bytes) = format::path_fields(&target);
+                    (Some(target), target_bytes)
+                }
+                None => (None, None),
+            };
+
+            let manifest_entry = reader.manifest_entry(chunk, &raw_path);
+            let deduped = DedupedFile::from_entry(&mut entry)?;
+            let attributes = xattrs::from_entry(&mut entry)?;
+
+            // Entries cut off by a lost chunk keep the manifest's checksum,
+            // as do deduplicated files, whose data lives in their blocks, and
+            // hard links, whose data was stored with their target
+            let mut complete = true;
+            let checksum = if deduped.is_some() || entry_type == FileType::Hardlink {
+                manifest_entry.as_ref().and_then(|f| f.checksum.clone())
+            } else if entry_type == FileType::File {
+                let mut writer = ChecksumWriter::new(io::sink());
+                match io::copy(&mut entry, &mut writer) {
+                    Ok(_) => Some(writer.finish().1),
+                    Err(e) => {
+                        log::warn!("{}: data is incomplete: {}", path, e);
+                        complete = false;
+                        manifest_entry.as_ref().and_then(|f| f.checksum.clone())
+                    }
+                }
+            } else {
+                None
+            };
+
+            // Chunks are decoded as they are read, so the ones the entry
+            // spans are known once its data has been read through
+            if complete {
+                complete = io::copy(&mut entry, &mut io::sink()).is_ok();
+            }
+            let final_chunk = reader.chunk_at(data_end);
+
+            files.push(FileEntry {
+                path,
+                path_bytes,
+                chunk,
+                offset: header_offset - reader.chunk_start(chunk),
+                // Positions in the stream are only known up to the first lost chunk
+                stream_offset: if first_chunk == 1 { Some(header_offset) } else { None },
+                header_size: Some(data_offset - header_offset),
+                size: match deduped {
+                    Some(ref deduped) => deduped.size,
+                    None if entry_type == FileType::Hardlink => manifest_entry.as_ref().map_or(0, |f| f.size),
+                    None => size,
+                },
+                compressed_size: None,
+                checksum,
+                mode: header.mode().unwrap_or(0o644),
+                mtime: header
+                    .mtime()
+                    .ok()
+                    .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                    .unwrap_or_else(Utc::now),
+                uid: header.uid().ok(),
+                gid: header.gid().ok(),
+                user: None,
+                group: None,
+                entry_type,
+                target,
+                target_bytes,
+                spans_chunks: if final_chunk != chunk {
+                    Some((chunk..=final_chunk).collect())
+                } else {
+                    None
+                },
+                blocks: deduped.map(|d| d.blocks),
+                attributes,
+                sparse,
+                stored_size: sparse.then_some(stored),
+                device_major: header.device_major().ok().flatten().filter(|_| is_device),
+                device_minor: header.device_minor().ok().flatten().filter(|_| is_device),
+            });
+
+            if !complete {
+                break;
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn display_report(&self, report: &RebuildReport) {
+        println!("\nIndex Rebuild Report for: {}", report.archive_name);
+        println!("{}", "=".repeat(60));
+        println!("  Chunks Recovered:  {}", report.chunks_recovered.len());
+        println!("  Chunks Lost:       {}", report.chunks_lost.len());
+        println!("  Files Indexed:     {}", report.files_indexed);
+        println!("  Index Written To:  {}", report.index_path.display());
+        println!();
+
+        if !report.chunks_lost.is_empty() {
+            println!("⚠ WARNING: entries in these chunks are missing from the index:");
+            for chunk_num in &report.chunks_lost {
+                println!("  - Chunk {}", chunk_num);
+            }
+            println!();
+        }
+    }
+}
+
+/// Read k and m from the zfec header of the first shard that has one
+fn erasure_parameters(shards_by_chunk: &HashMap<usize, Vec<ShardData>>) -> Result<(usize, usize)> {
+    let header = shards_by_chunk
+        .values()
+        .flatten()
+        .find_map(|s| s.header.clone())
+        .ok_or_else(|| {
+            EctarError::InvalidHeader(
+                "No zfec header found in shards - cannot rebuild the index".to_string(),
+            )
+        })?;
+
+    let k = header.k as usize;
+    let m = header.m as usize;
+    Ok((k, m - k))
+}
+
+/// Reconstruct one chunk and describe it as the index would
+///
+/// Returns the chunk's zstd frames (decrypted if need be) and how its key
+/// is derived, if it is encrypted.
+fn decode_chunk(
+    chunk_num: usize,
+    shards: &[ShardData],
+    (data_shards, parity_shards): (usize, usize),
+    keys: Option<&KeyRing>,
+) -> Result<(ChunkInfo, Vec<u8>, Option<KeyDerivation>)> {
+    let padlen = shards
+        .iter()
+        .find_map(|s| s.header.as_ref().map(|h| h.padlen))
+        .unwrap_or(0);
+
+    let all_shards = decoder::reconstruct_shards(shards.to_vec(), data_shards, parity_shards)?;
+    let shard_size = all_shards[0].len();
+
+    let mut stored = stripe::assemble_chunk(&all_shards, data_shards);
+    stored.truncate((shard_size * data_shards).saturating_sub(padlen));
+
+    let derivation = if envelope::is_sealed(&stored) {
+        Some(envelope::derivation_of(&stored)?)
+    } else {
+        None
+    };
+    let compressed = envelope::open_if_sealed(keys, Context::Chunk(chunk_num), stored.clone(), keys.is_some())?;
+
+    let mut decoder = compression::create_decoder(&compressed[..])?;
+    let mut writer = ChecksumWriter::new(io::sink());
+    let uncompressed_size = io::copy(&mut decoder, &mut writer)?;
+    let (_, uncompressed_checksum) = writer.finish();
+
+    let chunk_info = ChunkInfo {
+        chunk_number: chunk_num,
+        compressed_size: stored.len() as u64,
+        uncompressed_size,
+        shard_size: shard_size as u64,
+        checksum: sha256::checksum_bytes(&stored),
+        uncompressed_checksum: Some(uncompressed_checksum),
+        shard_checksums: all_shards.iter().map(|s| sha256::checksum_bytes(s)).collect(),
+    };
+
+    Ok((chunk_info, compressed, derivation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::create::ArchiveBuilder;
+    use crate::archive::extract::ArchiveExtractor;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    fn create_test_archive(temp_dir: &TempDir, chunk_size: u64) -> String {
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("small.txt"), b"small file").unwrap();
+        let large: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        fs::write(test_dir.join("large.bin"), &large).unwrap();
+        fs::write(test_dir.join("tail.txt"), b"last file").unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(chunk_size))
+            .no_index(true)
+            .create(&[test_dir])
+            .unwrap();
+        archive_base
+    }
+
+    #[test]
+    fn test_rebuild_index_for_archive_without_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, 8 * 1024);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let report = IndexRebuilder::new(pattern.clone()).rebuild().unwrap();
+        assert!(report.chunks_lost.is_empty());
+        assert!(report.chunks_recovered.len() > 1);
+
+        let index = reader::read_index(&report.index_path).unwrap();
+        assert_eq!(index.parameters.data_shards, 3);
+        assert_eq!(index.parameters.parity_shards, 2);
+
+        let large = index.files.iter().find(|f| f.path == "data/large.bin").unwrap();
+        assert_eq!(large.size, 20_000);
+        assert!(large.spans_chunks.is_some());
+        let content = fs::read(temp_dir.path().join("data/large.bin")).unwrap();
+        assert_eq!(large.checksum, Some(sha256::checksum_bytes(&content)));
+
+        let small = index.files.iter().find(|f| f.path == "data/small.txt").unwrap();
+        assert_eq!(small.entry_type, FileType::File);
+        assert_eq!(small.checksum, Some(sha256::checksum_bytes(b"small file")));
+
+        // The rebuilt index carries full chunk checksums, so extraction verifies them
+        let extract_dir = temp_dir.path().join("extract");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .file_filters(vec!["tail.txt".to_string()])
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(extract_dir.join("data/tail.txt")).unwrap(), b"last file");
+        assert!(!extract_dir.join("data/small.txt").exists());
+    }
+
+    #[test]
+    fn test_rebuilt_positions_match_original_index() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::FileExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("a.txt"), b"aaa").unwrap();
+        fs::write(test_dir.join(format!("{}.txt", "long".repeat(40))), vec![9u8; 5000]).unwrap();
+        fs::write(test_dir.join("z.txt"), b"zzz").unwrap();
+        // Not valid UTF-8, so preceded by PAX records with the raw name
+        fs::write(test_dir.join(OsStr::from_bytes(b"na\xefve.txt")), b"latin-1").unwrap();
+
+        // Sparse, with more regions than fit in its first header
+        let image = File::create(test_dir.join("m.img")).unwrap();
+        image.set_len(40 * 8192).unwrap();
+        for i in 0..30 {
+            image.write_all_at(b"data", i * 8192).unwrap();
+        }
+        drop(image);
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(2048))
+            .create(&[test_dir])
+            .unwrap();
+        let index_path = PathBuf::from(format!("{}.index.zst", archive_base));
+        let original = reader::read_index(&index_path).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = IndexRebuilder::new(pattern).force(true).rebuild().unwrap();
+        let rebuilt = reader::read_index(&report.index_path).unwrap();
+
+        assert_eq!(rebuilt.files.len(), original.files.len());
+        for (a, b) in original.files.iter().zip(&rebuilt.files) {
+            assert_eq!(a.path, b.path);
+            assert_eq!(a.path_bytes, b.path_bytes, "{}", a.path);
+            assert_eq!(a.chunk, b.chunk, "{}", a.path);
+            assert_eq!(a.offset, b.offset, "{}", a.path);
+            assert_eq!(a.stream_offset, b.stream_offset, "{}", a.path);
+            assert_eq!(a.header_size, b.header_size, "{}", a.path);
+            assert_eq!(a.spans_chunks, b.spans_chunks, "{}", a.path);
+            assert_eq!(a.checksum, b.checksum, "{}", a.path);
+            assert_eq!((a.sparse, a.stored_size), (b.sparse, b.stored_size), "{}", a.path);
+        }
+        assert!(rebuilt.files.iter().any(|f| f.path_bytes.as_deref() == Some(&b"data/na\xefve.txt"[..])));
+    }
+
+    #[test]
+    fn test_rebuild_index_keeps_dedup_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let content: Vec<u8> = (0..30_000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        fs::write(test_dir.join("a.bin"), &content).unwrap();
+        fs::write(test_dir.join("b.bin"), &content).unwrap();
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(8192))
+            .dedup(true)
+            .dedup_block_size(4096)
+            .create(&[test_dir])
+            .unwrap();
+        let index_path = PathBuf::from(format!("{}.index.zst", archive_base));
+        let original = reader::read_index(&index_path).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = IndexRebuilder::new(pattern.clone()).force(true).rebuild().unwrap();
+        let rebuilt = reader::read_index(&report.index_path).unwrap();
+
+        let paths = |entries: &[FileEntry]| entries.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&rebuilt.blocks), paths(&original.blocks));
+        assert_eq!(paths(&rebuilt.files), paths(&original.files));
+        let entries = |index: &ArchiveIndex| {
+            let files = index.files.iter().filter(|f| f.entry_type == FileType::File);
+            files.chain(&index.blocks).cloned().collect::<Vec<_>>()
+        };
+        for (a, b) in entries(&original).iter().zip(&entries(&rebuilt)) {
+            assert_eq!(a.size, b.size, "{}", a.path);
+            assert_eq!(a.checksum, b.checksum, "{}", a.path);
+            assert_eq!(a.blocks, b.blocks, "{}", a.path);
+            assert_eq!(a.stream_offset, b.stream_offset, "{}", a.path);
+            assert_eq!(a.spans_chunks, b.spans_chunks, "{}", a.path);
+        }
+
+        let extract_dir = temp_dir.path().join("extract");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .file_filters(vec!["b.bin".to_string()])
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(extract_dir.join("data/b.bin")).unwrap(), content);
+    }
+
+    #[test]
+    fn test_rebuild_index_resynchronises_after_lost_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, 8 * 1024);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // Lose the whole second chunk, which holds the middle of large.bin
+        for shard in 0..5 {
+            fs::remove_file(format!("{}.c002.s{:02}", archive_base, shard)).unwrap();
+        }
+
+        let report = IndexRebuilder::new(pattern).rebuild().unwrap();
+        assert_eq!(report.chunks_lost, vec![2]);
+
+        let index = reader::read_index(&report.index_path).unwrap();
+        assert!(index.chunks.iter().all(|c| c.chunk_number != 2));
+
+        // large.bin's header survives in chunk 1; tail.txt is found after resync
+        assert!(index.files.iter().any(|f| f.path == "data/large.bin"));
+        let tail = index.files.iter().find(|f| f.path == "data/tail.txt").unwrap();
+        assert_eq!(tail.checksum, Some(sha256::checksum_bytes(b"last file")));
+    }
+
+    #[test]
+    fn test_rebuild_index_refuses_to_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir, 8 * 1024);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        IndexRebuilder::new(pattern.clone()).rebuild().unwrap();
+
+        let result = IndexRebuilder::new(pattern.clone()).rebuild();
+        assert!(matches!(result, Err(EctarError::InvalidParameters(_))));
+
+        IndexRebuilder::new(pattern).force(true).rebuild().unwrap();
+    }
+
+    #[test]
+    fn test_rebuild_index_of_encrypted_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let large: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        fs::write(test_dir.join("large.bin"), &large).unwrap();
+        let key = || Some(Secret::Key([3u8; 32]));
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(8 * 1024))
+            .encryption(key())
+            .no_index(true)
+            .create(&[test_dir])
+            .unwrap();
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let result = IndexRebuilder::new(pattern.clone()).rebuild();
+        assert!(matches!(result, Err(EctarError::Decryption(_))));
+
+        // The rebuilt index is encrypted under the same key
+        let report = IndexRebuilder::new(pattern.clone()).decryption(key()).rebuild().unwrap();
+        assert!(matches!(reader::read_index(&report.index_path), Err(EctarError::Decryption(_))));
+        let keys = KeyRing::new(Secret::Key([3u8; 32]));
+        let index = reader::read_index_with_keys(&report.index_path, Some(&keys)).unwrap();
+        assert!(index.parameters.encryption.unwrap().index_encrypted);
+
+        let extract_dir = temp_dir.path().join("extract");
+        ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+            .decryption(key())
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(extract_dir.join("data/large.bin")).unwrap(), large);
+    }
+}
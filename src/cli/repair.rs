@@ -0,0 +1,620 @@
+use crate::crypto::{Encryptor, IndexSignature, KeyRing, Recipients, Secret};
+use crate::erasure::{decoder, encoder, ShardData, ShardTrailer, ZfecHeader};
+use crate::error::{EctarError, Result};
+use crate::index::format::{ArchiveIndex, ChunkInfo};
+use crate::index::{builder, reader};
+use crate::io::durable::Durability;
+use crate::io::shard_reader;
+use crate::io::streaming_shard_writer::{FileShardOutput, ShardOutput};
+use chrono::Utc;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub struct ArchiveRepairer {
+    input: String,
+    dry_run: bool,
+    keys: Option<KeyRing>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub archive_name: String,
+    pub total_chunks: usize,
+    pub chunks_repaired: Vec<usize>,
+    pub chunks_unrecoverable: Vec<usize>,
+    /// Chunks whose surviving shards disagree with each other (not repaired)
+    pub chunks_inconsistent: Vec<usize>,
+    pub shards_regenerated: Vec<PathBuf>,
+    /// Whether the index file or its index shards were (or would be) rewritten
+    #[serde(default)]
+    pub index_repaired: bool,
+    /// Whether the recipients file or its shards were (or would be) rewritten
+    #[serde(default)]
+    pub recipients_repaired: bool,
+    /// Whether the signature file or its shards were (or would be) rewritten
+    #[serde(default)]
+    pub signature_repaired: bool,
+    pub dry_run: bool,
+    pub status: RepairStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RepairStatus {
+    Healthy,
+    Repaired,
+    Failed,
+}
+
+impl ArchiveRepairer {
+    pub fn new(input: String) -> Self {
+        Self {
+            input,
+            dry_run: false,
+            keys: None,
+        }
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Key file or passphrase for an archive whose index is encrypted
+    ///
+    /// Chunks are repaired without decrypting them; the key is only needed
+    /// to read the index and to encrypt it again when it is rewritten.
+    pub fn decryption(mut self, secret: Option<Secret>) -> Self {
+        self.keys = secret.map(KeyRing::new);
+        self
+    }
+
+    pub fn repair(&self) -> Result<RepairReport> {
+        let index_path = shard_reader::find_index_file(&self.input)
+            .ok_or_else(|| EctarError::MissingIndex(PathBuf::from(&self.input)))?;
+
+        let mut index = self.read_index(&index_path)?;
+
+        let mut shards_by_chunk = shard_reader::discover_shards(&self.input)?;
+        let output_base = shard_reader::archive_base(&self.input);
+
+        let mut report = RepairReport {
+            archive_name: index.archive_name.clone(),
+            total_chunks: index.chunks.len(),
+            chunks_repaired: Vec::new(),
+            chunks_unrecoverable: Vec::new(),
+            chunks_inconsistent: Vec::new(),
+            shards_regenerated: Vec::new(),
+            index_repaired: false,
+            recipients_repaired: false,
+            signature_repaired: false,
+            dry_run: self.dry_run,
+            status: RepairStatus::Healthy,
+        };
+
+        for chunk_info in &index.chunks {
+            let chunk_num = chunk_info.chunk_number;
+            let shards = shards_by_chunk.remove(&chunk_num).unwrap_or_default();
+
+            match self.repair_chunk(&output_base, &index, chunk_info, shards) {
+                Ok(regenerated) if regenerated.is_empty() => {
+                    log::info!("Chunk {}: healthy, nothing to repair", chunk_num);
+                }
+                Ok(regenerated) => {
+                    report.chunks_repaired.push(chunk_num);
+                    report.shards_regenerated.extend(regenerated);
+                }
+                Err(EctarError::InsufficientShards { needed, available, .. }) => {
+                    log::error!(
+                        "Chunk {}: insufficient shards ({}/{}), cannot repair",
+                        chunk_num, available, needed
+                    );
+                    report.chunks_unrecoverable.push(chunk_num);
+                }
+                Err(e) => {
+                    log::error!("Chunk {}: repair failed: {}", chunk_num, e);
+                    report.chunks_inconsistent.push(chunk_num);
+                }
+            }
+        }
+
+        // Rewrite the index if its file or any index shard is missing or damaged
+        let total_shards = index.parameters.data_shards + index.parameters.parity_shards;
+        report.index_repaired = !reader::index_is_intact(&index_path, total_shards, self.keys.as_ref());
+
+        // Likewise the recipients file, from the copy of the recipients in the index
+        let recipients = index.parameters.encryption.as_ref().and_then(Recipients::from_parameters);
+        report.recipients_repaired =
+            recipients.is_some() && !Recipients::is_intact(&output_base, total_shards);
+
+        // The signature has no copy elsewhere, but can be rebuilt from
+        // whatever survives of its own file and shards
+        let signature = IndexSignature::read(&output_base).unwrap_or_else(|e| {
+            log::warn!("Cannot recover the archive's signature: {}", e);
+            None
+        });
+        report.signature_repaired =
+            signature.is_some() && !IndexSignature::is_intact(&output_base, total_shards);
+
+        report.status = if !report.chunks_unrecoverable.is_empty()
+            || !report.chunks_inconsistent.is_empty()
+        {
+            RepairStatus::Failed
+        } else if !report.chunks_repaired.is_empty() || report.index_repaired
+            || report.recipients_repaired
+            || report.signature_repaired
+        {
+            RepairStatus::Repaired
+        } else {
+            RepairStatus::Healthy
+        };
+
+        if !self.dry_run && (!report.chunks_repaired.is_empty() || report.index_repaired) {
+            index.last_repaired = Some(Utc::now());
+            let encryptor = self.index_encryptor(&index)?;
+            builder::write_index_with(&index, &output_base, Durability::default(), encryptor.as_ref())?;
+            log::info!("Updated index file: {}", index_path.display());
+        }
+        if let Some(recipients) = recipients.filter(|_| !self.dry_run && report.recipients_repaired) {
+            let path = recipients.write(
+                &output_base,
+                index.parameters.data_shards,
+                index.parameters.parity_shards,
+                Durability::default(),
+            )?;
+            log::info!("Rewrote recipients file: {}", path.display());
+        }
+        if let Some(signature) = signature.filter(|_| !self.dry_run && report.signature_repaired) {
+            let path = signature.write(
+                &output_base,
+                index.parameters.data_shards,
+                index.parameters.parity_shards,
+                Durability::default(),
+            )?;
+            log::info!("Rewrote signature file: {}", path.display());
+        }
+
+        self.display_report(&report);
+
+        Ok(report)
+    }
+
+    /// Regenerate the missing or corrupt shards of one chunk.
+    /// Returns the paths of the shards that were (or would be) rewritten.
+    fn repair_chunk(
+        &self,
+        output_base: &str,
+        index: &ArchiveIndex,
+        chunk_info: &ChunkInfo,
+        shards: Vec<ShardData>,
+    ) -> Result<Vec<PathBuf>> {
+        let data_shards = index.parameters.data_shards;
+        let parity_shards = index.parameters.parity_shards;
+        let total_shards = data_shards + parity_shards;
+        let chunk_num = chunk_info.chunk_number;
+
+        // Shards of the wrong size, with foreign parameters or failing their
+        // checksum cannot be trusted
+        let expected_len = chunk_info.shard_size as usize;
+        let (good, bad): (Vec<ShardData>, Vec<ShardData>) = shards.into_iter().partition(|s| {
+            let header_ok = s.header.as_ref().map_or(true, |h| {
+                h.k as usize == data_shards && h.m as usize == total_shards
+            });
+            let checksum_ok = chunk_info
+                .shard_checksums
+                .get(s.shard_number)
+                .map_or(true, |expected| s.matches_checksum(expected));
+            s.shard_number < total_shards
+                && header_ok
+                && checksum_ok
+                && s.data.len() == expected_len
+        });
+
+        for shard in &bad {
+            log::warn!(
+                "Chunk {}: shard {} is corrupt, it will be regenerated",
+                chunk_num, shard.shard_number
+            );
+        }
+
+        let missing: Vec<usize> = (0..total_shards)
+            .filter(|n| !good.iter().any(|s| s.shard_number == *n))
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if good.len() < data_shards {
+            return Err(EctarError::InsufficientShards {
+                chunk: chunk_num,
+                needed: data_shards,
+                available: good.len(),
+            });
+        }
+
+        let padlen = match good.iter().find_map(|s| s.header.as_ref()) {
+            Some(header) => header.padlen,
+            None => (chunk_info.shard_size as usize * data_shards)
+                .saturating_sub(chunk_info.compressed_size as usize),
+        };
+
+        let all_shards = decoder::reconstruct_shards(good, data_shards, parity_shards)?;
+
+        // With more than k survivors the parity must agree with the data,
+        // otherwise one of the "good" shards is silently corrupted
+        let rs = ReedSolomon::new(data_shards, parity_shards)
+            .map_err(|e| EctarError::ErasureCoding(format!("Failed to create encoder: {:?}", e)))?;
+        let consistent = rs
+            .verify(&all_shards)
+            .map_err(|e| EctarError::ErasureCoding(format!("Verification failed: {:?}", e)))?;
+        if !consistent {
+            return Err(EctarError::ErasureCoding(format!(
+                "Chunk {}: surviving shards are inconsistent, refusing to regenerate",
+                chunk_num
+            )));
+        }
+
+        let mut regenerated = Vec::new();
+
+        for shard_num in missing {
+            let shard_path = encoder::format_shard_path(output_base, chunk_num, shard_num);
+
+            if self.dry_run {
+                log::info!("Chunk {}: would regenerate {}", chunk_num, shard_path.display());
+            } else {
+                let header = ZfecHeader::new(
+                    data_shards as u8,
+                    total_shards as u8,
+                    shard_num as u8,
+                    padlen,
+                )?;
+                write_shard(
+                    &shard_path,
+                    &header,
+                    &all_shards[shard_num],
+                    index.parameters.shard_trailers,
+                )?;
+                log::info!("Chunk {}: regenerated {}", chunk_num, shard_path.display());
+            }
+
+            regenerated.push(shard_path);
+        }
+
+        Ok(regenerated)
+    }
+
+    fn read_index(&self, index_path: &Path) -> Result<ArchiveIndex> {
+        reader::read_index_with_keys(index_path, self.keys.as_ref())
+    }
+
+    /// Encryptor that seals a rewritten index the way it was sealed before
+    fn index_encryptor(&self, index: &ArchiveIndex) -> Result<Option<Encryptor>> {
+        let encryption = match index.parameters.encryption {
+            Some(ref encryption) if encryption.index_encrypted => encryption,
+            _ => return Ok(None),
+        };
+        let keys = self.keys.as_ref().ok_or_else(|| {
+            EctarError::Decryption("Rewriting the encrypted index needs the key".to_string())
+        })?;
+        Ok(Some(Encryptor::new(keys.key(&encryption.key)?, encryption.key.clone())))
+    }
+
+    fn display_report(&self, report: &RepairReport) {
+        println!("\nRepair Report for: {}", report.archive_name);
+        println!("{}", "=".repeat(60));
+
+        let status_str = match report.status {
+            RepairStatus::Healthy => "✓ HEALTHY (nothing to repair)",
+            RepairStatus::Repaired if report.dry_run => "⚠ NEEDS REPAIR",
+            RepairStatus::Repaired => "✓ REPAIRED",
+            RepairStatus::Failed => "✗ FAILED",
+        };
+        println!("Overall Status: {}", status_str);
+        println!();
+
+        println!("Summary:");
+        println!("  Total Chunks:          {}", report.total_chunks);
+        println!("  Chunks Repaired:       {}", report.chunks_repaired.len());
+        println!("  Chunks Unrecoverable:  {}", report.chunks_unrecoverable.len());
+        println!("  Chunks Inconsistent:   {}", report.chunks_inconsistent.len());
+        println!("  Shards Regenerated:    {}", report.shards_regenerated.len());
+        println!("  Index Rewritten:       {}", if report.index_repaired { "yes" } else { "no" });
+        if report.recipients_repaired {
+            println!("  Recipients Rewritten:  yes");
+        }
+        if report.signature_repaired {
+            println!("  Signature Rewritten:   yes");
+        }
+        println!();
+
+        if !report.shards_regenerated.is_empty() {
+            if report.dry_run {
+                println!("Shards that would be regenerated (dry run):");
+            } else {
+                println!("Regenerated shards:");
+            }
+            for path in &report.shards_regenerated {
+                println!("  - {}", path.display());
+            }
+            println!();
+        }
+
+        if !report.chunks_unrecoverable.is_empty() {
+            println!("⚠ WARNING: {} chunks are UNRECOVERABLE:", report.chunks_unrecoverable.len());
+            for chunk_num in &report.chunks_unrecoverable {
+                println!("  - Chunk {}", chunk_num);
+            }
+            println!();
+        }
+
+        if !report.chunks_inconsistent.is_empty() {
+            println!("⚠ WARNING: {} chunks have inconsistent shards and were not repaired:",
+                report.chunks_inconsistent.len());
+            for chunk_num in &report.chunks_inconsistent {
+                println!("  - Chunk {}", chunk_num);
+            }
+            println!();
+        }
+    }
+}
+
+/// Write a single shard file with its zfec header and optional checksum trailer
+fn write_shard(path: &Path, header: &ZfecHeader, data: &[u8], trailer: bool) -> Result<()> {
+    let mut output = FileShardOutput::new(path.to_path_buf())?;
+    output.write_all(&header.encode())?;
+    output.write_all(data)?;
+    if trailer {
+        output.write_all(&ShardTrailer::for_data(data).encode())?;
+    }
+    output.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::create::ArchiveBuilder;
+    use crate::cli::verify::{ArchiveVerifier, VerificationStatus};
+    use std::fs::{self, File};
+    use std::io::Write as IoWriteTrait;
+    use tempfile::TempDir;
+
+    fn create_test_archive(temp_dir: &TempDir) -> String {
+        let test_file = temp_dir.path().join("test.txt");
+        let mut file = File::create(&test_file).unwrap();
+        for i in 0..200 {
+            writeln!(file, "Line {} of test data for repair", i).unwrap();
+        }
+        drop(file);
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let builder = ArchiveBuilder::new(archive_base.clone())
+            .data_shards(4)
+            .parity_shards(2)
+            .chunk_size(Some(1024 * 1024));
+
+        builder.create(&[test_file]).unwrap();
+        archive_base
+    }
+
+    #[test]
+    fn test_repairer_new() {
+        let repairer = ArchiveRepairer::new("test_pattern".to_string());
+        assert_eq!(repairer.input, "test_pattern");
+        assert!(!repairer.dry_run);
+    }
+
+    #[test]
+    fn test_repair_healthy_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert_eq!(report.status, RepairStatus::Healthy);
+        assert!(report.shards_regenerated.is_empty());
+    }
+
+    #[test]
+    fn test_repair_missing_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+
+        let data_shard = temp_dir.path().join("archive.c001.s01");
+        let parity_shard = temp_dir.path().join("archive.c001.s05");
+        let original_data = fs::read(&data_shard).unwrap();
+        let original_parity = fs::read(&parity_shard).unwrap();
+        fs::remove_file(&data_shard).unwrap();
+        fs::remove_file(&parity_shard).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern.clone()).repair().unwrap();
+
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert_eq!(report.chunks_repaired, vec![1]);
+        assert_eq!(report.shards_regenerated.len(), 2);
+
+        // Regenerated shards are byte-identical to the originals
+        assert_eq!(fs::read(&data_shard).unwrap(), original_data);
+        assert_eq!(fs::read(&parity_shard).unwrap(), original_parity);
+
+        let verification = ArchiveVerifier::new(pattern).verify().unwrap();
+        assert_eq!(verification.status, VerificationStatus::Healthy);
+    }
+
+    #[test]
+    fn test_repair_truncated_shard() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+
+        let shard = temp_dir.path().join("archive.c001.s02");
+        let original = fs::read(&shard).unwrap();
+        fs::write(&shard, &original[..original.len() / 2]).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert_eq!(fs::read(&shard).unwrap(), original);
+    }
+
+    #[test]
+    fn test_repair_bit_rotted_shard() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+
+        // Same size, different content: only the shard checksum can catch this
+        let shard = temp_dir.path().join("archive.c001.s03");
+        let original = fs::read(&shard).unwrap();
+        let mut rotted = original.clone();
+        let last = rotted.len() - 1;
+        rotted[last] ^= 0x01;
+        fs::write(&shard, &rotted).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert_eq!(report.shards_regenerated, vec![shard.clone()]);
+        assert_eq!(fs::read(&shard).unwrap(), original);
+    }
+
+    #[test]
+    fn test_repair_updates_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+        fs::remove_file(temp_dir.path().join("archive.c001.s00")).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let repairer = ArchiveRepairer::new(pattern.clone());
+        repairer.repair().unwrap();
+
+        let index_path = shard_reader::find_index_file(&pattern).unwrap();
+        let index = repairer.read_index(&index_path).unwrap();
+        assert!(index.last_repaired.is_some());
+    }
+
+    #[test]
+    fn test_repair_rewrites_lost_index_pieces() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+        let index_path = temp_dir.path().join("archive.index.zst");
+        let index_shard = temp_dir.path().join("archive.index.s02");
+        fs::remove_file(&index_path).unwrap();
+        fs::remove_file(&index_shard).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert!(report.index_repaired);
+        assert!(report.shards_regenerated.is_empty());
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert!(index_path.exists());
+        assert!(index_shard.exists());
+    }
+
+    #[test]
+    fn test_repair_rewrites_lost_recipients_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, b"data for a team").unwrap();
+        let identity = crate::crypto::Identity::generate();
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(4)
+            .parity_shards(2)
+            .chunk_size(Some(1024 * 1024))
+            .recipients(vec![identity.public_key()])
+            .create(&[test_file])
+            .unwrap();
+
+        let recipients_path = temp_dir.path().join("archive.recipients");
+        let recipients_shard = temp_dir.path().join("archive.recipients.s01");
+        fs::remove_file(&recipients_path).unwrap();
+        fs::remove_file(&recipients_shard).unwrap();
+
+        // The recipients in the index are enough; no key is needed
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert!(report.recipients_repaired);
+        assert!(!report.index_repaired);
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert!(recipients_path.exists());
+        assert!(recipients_shard.exists());
+    }
+
+    #[test]
+    fn test_repair_rewrites_lost_signature_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, b"signed data").unwrap();
+        let key = crate::crypto::SigningKey::generate();
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(4)
+            .parity_shards(2)
+            .chunk_size(Some(1024 * 1024))
+            .sign_key(Some(key.clone()))
+            .create(&[test_file])
+            .unwrap();
+
+        let signature_path = temp_dir.path().join("archive.signature");
+        fs::remove_file(&signature_path).unwrap();
+        fs::remove_file(temp_dir.path().join("archive.signature.s00")).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert!(report.signature_repaired);
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert!(signature_path.exists());
+        let signature = IndexSignature::read(&archive_base).unwrap().unwrap();
+        assert_eq!(signature.signer(), key.verifying_key());
+    }
+
+    #[test]
+    fn test_repair_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+
+        let shard = temp_dir.path().join("archive.c001.s03");
+        fs::remove_file(&shard).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).dry_run(true).repair().unwrap();
+
+        assert_eq!(report.status, RepairStatus::Repaired);
+        assert_eq!(report.shards_regenerated, vec![shard.clone()]);
+        assert!(!shard.exists());
+    }
+
+    #[test]
+    fn test_repair_unrecoverable_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+
+        for i in 0..3 {
+            fs::remove_file(temp_dir.path().join(format!("archive.c001.s{:02}", i))).unwrap();
+        }
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveRepairer::new(pattern).repair().unwrap();
+
+        assert_eq!(report.status, RepairStatus::Failed);
+        assert_eq!(report.chunks_unrecoverable, vec![1]);
+        assert!(report.shards_regenerated.is_empty());
+    }
+
+    #[test]
+    fn test_repair_missing_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let pattern = temp_dir.path().join("nonexistent.c*.s*").to_string_lossy().to_string();
+
+        let result = ArchiveRepairer::new(pattern).repair();
+        assert!(result.is_err());
+    }
+}
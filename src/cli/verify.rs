@@ -1,19 +1,30 @@
+use crate::checksum::sha256;
+use crate::chunking::ordered_pool::map_ordered;
 use crate::compression;
-use crate::erasure::decoder;
+use crate::crypto::signature::{self, SignatureCheck, SignatureStatus};
+use crate::crypto::{self, envelope, Context, KeyRing, Secret, VerifyingKey};
+use crate::erasure::{decoder, hunt};
 use crate::error::{EctarError, Result};
-use crate::index::format::ArchiveIndex;
+use crate::index::format::{ArchiveIndex, ChunkInfo};
+use crate::index::reader;
 use crate::io::shard_reader;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Write as IoWrite;
-use std::path::PathBuf;
+use std::io::{Read, Write as IoWrite};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 
 pub struct ArchiveVerifier {
     input: String,
     quick_mode: bool,
     full_mode: bool,
+    hunt_mode: bool,
+    hunt_attempts: usize,
+    threads: usize,
     report_path: Option<PathBuf>,
+    keys: Option<KeyRing>,
+    trusted_keys: Vec<VerifyingKey>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,7 +36,14 @@ pub struct VerificationReport {
     pub chunks_unrecoverable: Vec<usize>,
     pub total_shards: usize,
     pub missing_shards: usize,
+    /// Shards present on disk whose data failed its checksum
+    #[serde(default)]
+    pub corrupt_shards: usize,
     pub status: VerificationStatus,
+    /// Signer and status of the index signature, if the archive is signed
+    /// or trusted keys were given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureCheck>,
     pub details: Vec<ChunkVerificationDetail>,
 }
 
@@ -37,6 +55,9 @@ pub struct ChunkVerificationDetail {
     pub is_recoverable: bool,
     pub verification_performed: bool,
     pub checksum_valid: Option<bool>,
+    /// Shards identified as corrupt by `--hunt-corruption`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub corrupt_shards: Vec<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -52,7 +73,12 @@ impl ArchiveVerifier {
             input,
             quick_mode: false,
             full_mode: false,
+            hunt_mode: false,
+            hunt_attempts: hunt::DEFAULT_MAX_ATTEMPTS,
+            threads: 1,
             report_path: None,
+            keys: None,
+            trusted_keys: Vec::new(),
         }
     }
 
@@ -66,20 +92,61 @@ impl ArchiveVerifier {
         self
     }
 
+    /// When a chunk fails to verify, reconstruct it from different subsets of
+    /// its shards to find and name the corrupt ones (implies full mode)
+    pub fn hunt_corruption(mut self) -> Self {
+        self.hunt_mode = true;
+        self.full_mode = true;
+        self
+    }
+
+    /// Give up hunting a chunk's corrupt shards after this many reconstructions
+    pub fn hunt_attempts(mut self, attempts: usize) -> Self {
+        self.hunt_attempts = attempts;
+        self
+    }
+
+    /// Decode and check up to this many chunks at once in full mode
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
     pub fn report(mut self, path: Option<PathBuf>) -> Self {
         self.report_path = path;
         self
     }
 
+    /// Key or passphrase for an encrypted archive; without it, full mode
+    /// checks the stored (encrypted) chunks but cannot look inside them
+    pub fn decryption(mut self, secret: Option<Secret>) -> Self {
+        self.keys = secret.map(KeyRing::new);
+        self
+    }
+
+    /// Require the index to be signed by one of these keys, and every chunk
+    /// and shard to match the signed index
+    pub fn trusted_keys(mut self, keys: Vec<VerifyingKey>) -> Self {
+        self.trusted_keys = keys;
+        self
+    }
+
     pub fn verify(&self) -> Result<VerificationReport> {
         // Read index file
         let index_path = shard_reader::find_index_file(&self.input)
             .ok_or_else(|| EctarError::MissingIndex(PathBuf::from(&self.input)))?;
 
         let index = self.read_index(&index_path)?;
+        if let Some(ref encryption) = index.parameters.encryption {
+            if self.keys.is_some() {
+                crypto::check_key(self.keys.as_ref(), encryption)?;
+            } else if self.full_mode {
+                log::warn!("Archive is encrypted; without the key only the stored chunk checksums are verified");
+            }
+        }
 
         // Discover available shards
-        let shards_by_chunk = shard_reader::discover_shards(&self.input)?;
+        let mut shards_by_chunk = shard_reader::discover_shards(&self.input)?;
 
         let mut report = VerificationReport {
             archive_name: index.archive_name.clone(),
@@ -89,13 +156,66 @@ impl ArchiveVerifier {
             chunks_unrecoverable: Vec::new(),
             total_shards: 0,
             missing_shards: 0,
+            corrupt_shards: 0,
             status: VerificationStatus::Healthy,
+            signature: None,
             details: Vec::new(),
         };
 
-        // Verify each chunk
+        let signature_check =
+            signature::check_signature(&reader::index_base(&index_path), &index, &self.trusted_keys);
+        if signature_check.status != SignatureStatus::Missing || !self.trusted_keys.is_empty() {
+            report.signature = Some(signature_check);
+        }
+
+        // Corrupt shards count as missing
         for chunk_info in &index.chunks {
+            if let Some(shards) = shards_by_chunk.remove(&chunk_info.chunk_number) {
+                let found = shards.len();
+                let good = decoder::drop_corrupt_shards(shards, &chunk_info.shard_checksums);
+                report.corrupt_shards += found - good.len();
+                shards_by_chunk.insert(chunk_info.chunk_number, good);
+            }
+        }
+
+        // Full verification decodes up to `threads` chunks at once; the
+        // report is still built in chunk order
+        let full_check = |chunk_info: &ChunkInfo| {
             let chunk_num = chunk_info.chunk_number;
+            let shards_available = shards_by_chunk.get(&chunk_num).map_or(0, |s| s.len());
+            if !self.full_mode || shards_available < index.parameters.data_shards {
+                return None;
+            }
+
+            let result = self.verify_chunk_full(chunk_num, &shards_by_chunk, &index, chunk_info);
+            if let Err(ref e) = result {
+                log::error!("Chunk {}: verification failed: {}", chunk_num, e);
+            }
+            let hunted = match result {
+                Err(_) if self.hunt_mode => {
+                    log::info!("Chunk {}: hunting for corrupt shards", chunk_num);
+                    let shards = shards_by_chunk.get(&chunk_num).map(Vec::as_slice).unwrap_or(&[]);
+                    hunt::hunt_corruption(
+                        shards,
+                        index.parameters.data_shards,
+                        index.parameters.parity_shards,
+                        chunk_info,
+                        self.hunt_attempts,
+                    )
+                    .map_err(|e| log::error!("{}", e))
+                    .ok()
+                }
+                _ => None,
+            };
+            Some((result, hunted))
+        };
+
+        // Verify each chunk
+        let mut chunks = index.chunks.iter();
+        map_ordered(&index.chunks, self.threads, full_check, |checked| {
+            let chunk_info = chunks.next().expect("one result per chunk");
+            let chunk_num = chunk_info.chunk_number;
+
             let shards_available = shards_by_chunk.get(&chunk_num)
                 .map(|s| s.len())
                 .unwrap_or(0);
@@ -115,6 +235,7 @@ impl ArchiveVerifier {
                 is_recoverable,
                 verification_performed: false,
                 checksum_valid: None,
+                corrupt_shards: Vec::new(),
             };
 
             if !is_recoverable {
@@ -134,19 +255,27 @@ impl ArchiveVerifier {
             }
 
             // Full verification: actually decode and verify
-            if self.full_mode && is_recoverable {
-                match self.verify_chunk_full(chunk_num, &shards_by_chunk, &index, chunk_info) {
+            if let Some((result, hunted)) = checked {
+                detail.verification_performed = true;
+                match result {
                     Ok(()) => {
-                        detail.verification_performed = true;
                         detail.checksum_valid = Some(true);
                         report.chunks_verified += 1;
                         log::info!("Chunk {}: verified successfully", chunk_num);
                     }
-                    Err(e) => {
-                        detail.verification_performed = true;
-                        detail.checksum_valid = Some(false);
-                        report.chunks_failed.push(chunk_num);
-                        log::error!("Chunk {}: verification failed: {}", chunk_num, e);
+                    Err(_) => {
+                        match hunted {
+                            Some(result) => {
+                                detail.checksum_valid = Some(true);
+                                report.corrupt_shards += result.corrupt_shards.len();
+                                detail.corrupt_shards = result.corrupt_shards;
+                                report.chunks_verified += 1;
+                            }
+                            None => {
+                                detail.checksum_valid = Some(false);
+                                report.chunks_failed.push(chunk_num);
+                            }
+                        }
                         if report.status != VerificationStatus::Failed {
                             report.status = VerificationStatus::Degraded;
                         }
@@ -157,6 +286,24 @@ impl ArchiveVerifier {
             }
 
             report.details.push(detail);
+            ControlFlow::Continue(())
+        });
+
+        // A signature that does not match, and with trusted keys anything
+        // that differs from the signed index, may be tampering
+        match report.signature {
+            Some(ref check) if check.status == SignatureStatus::Invalid => {
+                report.status = VerificationStatus::Failed;
+            }
+            Some(ref check)
+                if !self.trusted_keys.is_empty()
+                    && (check.status != SignatureStatus::Valid
+                        || report.corrupt_shards > 0
+                        || !report.chunks_failed.is_empty()) =>
+            {
+                report.status = VerificationStatus::Failed;
+            }
+            _ => {}
         }
 
         // Display report
@@ -170,15 +317,8 @@ impl ArchiveVerifier {
         Ok(report)
     }
 
-    fn read_index(&self, index_path: &PathBuf) -> Result<ArchiveIndex> {
-        let index_file = File::open(index_path)?;
-        let mut decoder = compression::create_decoder(index_file)?;
-
-        let mut json = String::new();
-        std::io::Read::read_to_string(&mut decoder, &mut json)?;
-
-        let index: ArchiveIndex = serde_json::from_str(&json)?;
-        Ok(index)
+    fn read_index(&self, index_path: &Path) -> Result<ArchiveIndex> {
+        reader::read_index_with_keys(index_path, self.keys.as_ref())
     }
 
     fn verify_chunk_full(
@@ -186,7 +326,7 @@ impl ArchiveVerifier {
         chunk_num: usize,
         shards_by_chunk: &HashMap<usize, Vec<decoder::ShardData>>,
         index: &ArchiveIndex,
-        chunk_info: &crate::index::format::ChunkInfo,
+        chunk_info: &ChunkInfo,
     ) -> Result<()> {
         let shards = shards_by_chunk.get(&chunk_num)
             .ok_or_else(|| EctarError::ErasureCoding("No shards found".to_string()))?;
@@ -214,6 +354,53 @@ impl ArchiveVerifier {
             )));
         }
 
+        // Archives written before chunk checksums existed have nothing more to
+        // check, unless hunting, where a zstd decode has to stand in for one
+        if chunk_info.checksum.is_empty() {
+            if self.hunt_mode && !hunt::chunk_is_valid(&std::fs::read(&chunk_path)?, chunk_info) {
+                return Err(EctarError::Decompression(format!(
+                    "Chunk {} does not decode to {} bytes",
+                    chunk_num, chunk_info.uncompressed_size
+                )));
+            }
+            return Ok(());
+        }
+
+        let checksum = sha256::compute_checksum(File::open(&chunk_path)?)?;
+        if checksum != chunk_info.checksum {
+            return Err(EctarError::ChunkChecksumMismatch {
+                chunk: chunk_num,
+                stage: "compressed",
+            });
+        }
+
+        // Encrypted chunks are authenticated on decryption; without the key
+        // the checksum of the stored bytes is all that can be checked
+        let mut magic = [0u8; envelope::MAGIC.len()];
+        let sealed = File::open(&chunk_path)?.read_exact(&mut magic).is_ok() && envelope::is_sealed(&magic);
+        let compressed: Box<dyn Read> = match self.keys {
+            Some(ref keys) => {
+                let data = std::fs::read(&chunk_path)?;
+                let data = envelope::open_if_sealed(Some(keys), Context::Chunk(chunk_num), data, true)?;
+                Box::new(std::io::Cursor::new(data))
+            }
+            None if sealed => return Ok(()),
+            None => Box::new(File::open(&chunk_path)?),
+        };
+
+        // Uncompressed chunks hash the same before and after compression
+        if let Some(ref expected) = chunk_info.uncompressed_checksum {
+            if *expected != checksum {
+                let decoder = compression::create_decoder(compressed)?;
+                if sha256::compute_checksum(decoder)? != *expected {
+                    return Err(EctarError::ChunkChecksumMismatch {
+                        chunk: chunk_num,
+                        stage: "uncompressed",
+                    });
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -236,7 +423,22 @@ impl ArchiveVerifier {
         println!("  Chunks Unrecoverable:  {}", report.chunks_unrecoverable.len());
         println!("  Total Shards:          {}", report.total_shards);
         println!("  Missing Shards:        {}", report.missing_shards);
+        if report.corrupt_shards > 0 {
+            println!("  Corrupt Shards:        {}", report.corrupt_shards);
+        }
 
+        if let Some(ref check) = report.signature {
+            let status = match check.status {
+                SignatureStatus::Valid => "✓ VALID",
+                SignatureStatus::Untrusted => "✗ UNTRUSTED SIGNER",
+                SignatureStatus::Invalid => "✗ INVALID",
+                SignatureStatus::Missing => "✗ MISSING",
+            };
+            println!("  Signature:             {}", status);
+            if let Some(ref signer) = check.signer {
+                println!("  Signed By:             {}", signer);
+            }
+        }
         if report.missing_shards > 0 {
             println!("  Redundancy Loss:       {:.1}%",
                 (report.missing_shards as f64 / report.total_shards as f64) * 100.0);
@@ -260,6 +462,19 @@ impl ArchiveVerifier {
             println!();
         }
 
+        let hunted: Vec<_> = report.details.iter()
+            .filter(|d| !d.corrupt_shards.is_empty())
+            .collect();
+        if !hunted.is_empty() {
+            println!("Corrupt shards found:");
+            for detail in hunted {
+                for shard_num in &detail.corrupt_shards {
+                    println!("  - Chunk {} shard {}", detail.chunk_number, shard_num);
+                }
+            }
+            println!();
+        }
+
         if self.quick_mode || self.full_mode {
             println!("Chunk Details:");
             println!("{:<8} {:<15} {:<12} {:<15}",
@@ -351,6 +566,20 @@ mod tests {
         assert!(verifier.full_mode);
     }
 
+    #[test]
+    fn test_verifier_hunt_corruption() {
+        let verifier = ArchiveVerifier::new("test".to_string()).hunt_corruption();
+        assert!(verifier.hunt_mode);
+        assert!(verifier.full_mode);
+        assert_eq!(verifier.hunt_attempts, hunt::DEFAULT_MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn test_verifier_threads() {
+        let verifier = ArchiveVerifier::new("test".to_string()).threads(4);
+        assert_eq!(verifier.threads, 4);
+    }
+
     #[test]
     fn test_verifier_report() {
         let verifier = ArchiveVerifier::new("test".to_string())
@@ -414,6 +643,216 @@ mod tests {
         assert!(report.missing_shards > 0);
     }
 
+    #[test]
+    fn test_verify_detects_bit_rot() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+
+        // Flip a byte in the last shard's data, keeping its size
+        let shard_path = temp_dir.path().join("archive.c001.s05");
+        let mut data = fs::read(&shard_path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&shard_path, data).unwrap();
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let report = ArchiveVerifier::new(pattern).full().verify().unwrap();
+
+        assert_eq!(report.status, VerificationStatus::Degraded);
+        assert_eq!(report.corrupt_shards, 1);
+        assert!(report.chunks_failed.is_empty());
+    }
+
+    #[test]
+    fn test_verify_signed_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, b"Test data for a signed archive").unwrap();
+        let key = crate::crypto::SigningKey::generate();
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(4)
+            .parity_shards(2)
+            .chunk_size(Some(1024 * 1024))
+            .sign_key(Some(key.clone()))
+            .create(&[test_file])
+            .unwrap();
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let report = ArchiveVerifier::new(pattern.clone()).full().verify().unwrap();
+        assert_eq!(report.status, VerificationStatus::Healthy);
+        let check = report.signature.unwrap();
+        assert_eq!(check.status, SignatureStatus::Valid);
+        assert_eq!(check.signer, Some(key.verifying_key().to_string()));
+
+        let report = ArchiveVerifier::new(pattern.clone())
+            .trusted_keys(vec![key.verifying_key()])
+            .verify()
+            .unwrap();
+        assert_eq!(report.status, VerificationStatus::Healthy);
+
+        let other = crate::crypto::SigningKey::generate().verifying_key();
+        let report = ArchiveVerifier::new(pattern.clone()).trusted_keys(vec![other]).verify().unwrap();
+        assert_eq!(report.status, VerificationStatus::Failed);
+        assert_eq!(report.signature.unwrap().status, SignatureStatus::Untrusted);
+
+        // A shard that no longer matches the signed index fails verification
+        // against a trusted key, even though the chunk is still recoverable
+        let shard_path = temp_dir.path().join("archive.c001.s05");
+        let mut data = fs::read(&shard_path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&shard_path, data).unwrap();
+        let report = ArchiveVerifier::new(pattern)
+            .full()
+            .trusted_keys(vec![key.verifying_key()])
+            .verify()
+            .unwrap();
+        assert_eq!(report.corrupt_shards, 1);
+        assert_eq!(report.status, VerificationStatus::Failed);
+    }
+
+    #[test]
+    fn test_verify_unsigned_archive_against_trusted_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        let report = ArchiveVerifier::new(pattern.clone()).verify().unwrap();
+        assert_eq!(report.status, VerificationStatus::Healthy);
+        assert!(report.signature.is_none());
+
+        let key = crate::crypto::SigningKey::generate().verifying_key();
+        let report = ArchiveVerifier::new(pattern).trusted_keys(vec![key]).verify().unwrap();
+        assert_eq!(report.status, VerificationStatus::Failed);
+        assert_eq!(report.signature.unwrap().status, SignatureStatus::Missing);
+    }
+
+    #[test]
+    fn test_verify_hunt_corruption_names_bad_shard() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // Without shard checksums the bad shard is only found by hunting
+        let verifier = ArchiveVerifier::new(pattern.clone()).hunt_corruption();
+        let index_path = shard_reader::find_index_file(&pattern).unwrap();
+        let mut index = verifier.read_index(&index_path).unwrap();
+        index.chunks[0].shard_checksums.clear();
+        let json = serde_json::to_string(&index).unwrap();
+        compression::compress(json.as_bytes(), File::create(&index_path).unwrap(), 3).unwrap();
+
+        let shard_path = temp_dir.path().join("archive.c001.s01");
+        let mut data = fs::read(&shard_path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&shard_path, data).unwrap();
+
+        let report = ArchiveVerifier::new(pattern.clone()).full().verify().unwrap();
+        assert_eq!(report.chunks_failed, vec![1]);
+
+        let report = verifier.verify().unwrap();
+        assert!(report.chunks_failed.is_empty());
+        assert_eq!(report.details[0].corrupt_shards, vec![1]);
+        assert_eq!(report.status, VerificationStatus::Degraded);
+    }
+
+    #[test]
+    fn test_verify_full_detects_chunk_checksum_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_base = create_test_archive(&temp_dir);
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // Point the index at a different chunk checksum
+        let verifier = ArchiveVerifier::new(pattern.clone()).full();
+        let index_path = shard_reader::find_index_file(&pattern).unwrap();
+        let mut index = verifier.read_index(&index_path).unwrap();
+        index.chunks[0].uncompressed_checksum = Some(format!("sha256:{}", "0".repeat(64)));
+        let json = serde_json::to_string(&index).unwrap();
+        compression::compress(json.as_bytes(), File::create(&index_path).unwrap(), 3).unwrap();
+
+        let report = verifier.verify().unwrap();
+
+        assert_eq!(report.chunks_failed, vec![1]);
+        assert_eq!(report.details[0].checksum_valid, Some(false));
+        assert_eq!(report.status, VerificationStatus::Degraded);
+    }
+
+    #[test]
+    fn test_verify_full_threaded() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_dir = temp_dir.path().join("data");
+        fs::create_dir(&test_dir).unwrap();
+        let mut state = 0x9E3779B9u32;
+        for i in 0..8 {
+            // Incompressible so the files spread over many chunks
+            let data: Vec<u8> = (0..5000)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect();
+            fs::write(test_dir.join(format!("file{}.bin", i)), data).unwrap();
+        }
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(3)
+            .parity_shards(2)
+            .chunk_size(Some(4096))
+            .create(&[test_dir])
+            .unwrap();
+
+        // Lose one chunk entirely so results must stay in chunk order
+        for shard in 0..3 {
+            fs::remove_file(temp_dir.path().join(format!("archive.c002.s{:02}", shard))).unwrap();
+        }
+
+        let pattern = format!("{}.c*.s*", archive_base);
+        let sequential = ArchiveVerifier::new(pattern.clone()).full().verify().unwrap();
+        let threaded = ArchiveVerifier::new(pattern).full().threads(4).verify().unwrap();
+
+        assert!(threaded.total_chunks > 4);
+        assert_eq!(threaded.status, VerificationStatus::Failed);
+        assert_eq!(threaded.chunks_unrecoverable, vec![2]);
+        assert_eq!(threaded.chunks_verified, sequential.chunks_verified);
+        assert_eq!(threaded.chunks_verified, threaded.total_chunks - 1);
+        let order: Vec<usize> = threaded.details.iter().map(|d| d.chunk_number).collect();
+        assert_eq!(order, (1..=threaded.total_chunks).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_verify_full_encrypted_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        std::fs::write(&test_file, b"Test data for verification").unwrap();
+        let key = || Some(Secret::Key([4u8; 32]));
+
+        let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        ArchiveBuilder::new(archive_base.clone())
+            .data_shards(4)
+            .parity_shards(2)
+            .chunk_size(Some(1024 * 1024))
+            .encryption(key())
+            .create(&[test_file])
+            .unwrap();
+        let pattern = format!("{}.c*.s*", archive_base);
+
+        // Without the key the stored chunks can still be checked
+        for secret in [key(), None] {
+            let report = ArchiveVerifier::new(pattern.clone()).full().decryption(secret).verify().unwrap();
+            assert_eq!(report.status, VerificationStatus::Healthy);
+            assert_eq!(report.chunks_verified, report.total_chunks);
+        }
+
+        let result = ArchiveVerifier::new(pattern)
+            .full()
+            .decryption(Some(Secret::Key([5u8; 32])))
+            .verify();
+        assert!(matches!(result, Err(EctarError::Decryption(_))));
+    }
+
     #[test]
     fn test_verify_failed_archive() {
         let temp_dir = TempDir::new().unwrap();
@@ -472,7 +911,9 @@ mod tests {
             chunks_unrecoverable: vec![],
             total_shards: 12,
             missing_shards: 0,
+            corrupt_shards: 0,
             status: VerificationStatus::Healthy,
+            signature: None,
             details: vec![
                 ChunkVerificationDetail {
                     chunk_number: 1,
@@ -481,6 +922,7 @@ mod tests {
                     is_recoverable: true,
                     verification_performed: true,
                     checksum_valid: Some(true),
+                    corrupt_shards: Vec::new(),
                 },
             ],
         };
@@ -507,6 +949,7 @@ mod tests {
             is_recoverable: true,
             verification_performed: false,
             checksum_valid: None,
+            corrupt_shards: Vec::new(),
         };
 
         let json = serde_json::to_string(&detail).unwrap();
@@ -46,7 +46,7 @@ pub fn decompress<R: Read, W: Write>(
 
 /// Validate compression level is within acceptable range
 pub fn validate_compression_level(level: i32) -> Result<i32> {
-    if level < MIN_COMPRESSION_LEVEL || level > MAX_COMPRESSION_LEVEL {
+    if !(MIN_COMPRESSION_LEVEL..=MAX_COMPRESSION_LEVEL).contains(&level) {
         return Err(EctarError::InvalidParameters(format!(
             "Compression level must be between {} and {}, got {}",
             MIN_COMPRESSION_LEVEL, MAX_COMPRESSION_LEVEL, level
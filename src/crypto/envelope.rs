@@ -0,0 +1,308 @@
+use crate::crypto::key::{DataKey, KdfParams, KeyDerivation, KeyRing, SALT_LEN};
+use crate::crypto::recipient::{Recipients, KEY_ID_LEN};
+use crate::error::{EctarError, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+/// Marks encrypted data, at the start of every encrypted chunk and index
+pub const MAGIC: &[u8; 8] = b"ECTARENC";
+
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 24;
+
+const KIND_KEY_FILE: u8 = 1;
+const KIND_ARGON2ID: u8 = 2;
+const KIND_X25519: u8 = 3;
+
+/// Name of the cipher, as recorded in the index
+pub const CIPHER: &str = "xchacha20-poly1305";
+
+/// What a sealed blob holds; bound into the authentication tag so an
+/// encrypted chunk cannot be passed off as another chunk or as the index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Chunk(usize),
+    Index,
+}
+
+impl Context {
+    fn encode(self) -> Vec<u8> {
+        match self {
+            Context::Chunk(number) => {
+                let mut bytes = vec![b'c'];
+                bytes.extend_from_slice(&(number as u64).to_le_bytes());
+                bytes
+            }
+            Context::Index => vec![b'i'],
+        }
+    }
+}
+
+/// Encrypts chunks and the index of one archive
+///
+/// Each call to `seal` produces a self-describing envelope:
+///
+/// ```text
+/// "ECTARENC" | version | kind | [m_cost t_cost p_cost salt | key_id] | nonce | ciphertext+tag
+/// ```
+///
+/// The Argon2 parameters and salt are repeated in every envelope, so a
+/// chunk can be decrypted from its shards alone, without the index. For
+/// recipients the envelope names the key id, and the wrapped keys come from
+/// the archive's recipients file.
+#[derive(Debug, Clone)]
+pub struct Encryptor {
+    key: DataKey,
+    derivation: KeyDerivation,
+    recipients: Option<Recipients>,
+}
+
+impl Encryptor {
+    pub fn new(key: DataKey, derivation: KeyDerivation) -> Self {
+        Self {
+            key,
+            derivation,
+            recipients: None,
+        }
+    }
+
+    /// Encryptor for a key wrapped to recipients, recorded with the index
+    pub fn with_recipients(key: DataKey, recipients: Recipients) -> Self {
+        Self {
+            key,
+            derivation: recipients.derivation(),
+            recipients: Some(recipients),
+        }
+    }
+
+    /// The recipients the key is wrapped for, if it is
+    pub fn recipients(&self) -> Option<&Recipients> {
+        self.recipients.as_ref()
+    }
+
+    /// How the key is obtained, for recording in the index
+    pub fn derivation(&self) -> &KeyDerivation {
+        &self.derivation
+    }
+
+    pub fn key(&self) -> &DataKey {
+        &self.key
+    }
+
+    /// Encrypt and authenticate `plaintext` under a random nonce
+    pub fn seal(&self, context: Context, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = encode_header(&self.derivation);
+        sealed.extend_from_slice(&nonce);
+
+        let mut aad = sealed.clone();
+        aad.extend_from_slice(&context.encode());
+        let ciphertext = cipher(&self.key)
+            .encrypt(&nonce, Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| EctarError::Encryption("Encryption failed".to_string()))?;
+
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+}
+
+/// Whether data starts with an encryption envelope
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// How the key for an envelope is obtained
+pub fn derivation_of(data: &[u8]) -> Result<KeyDerivation> {
+    parse_header(data).map(|(derivation, _)| derivation)
+}
+
+/// Decrypt an envelope, checking that it was sealed for `context`
+pub fn open(keys: &KeyRing, context: Context, data: &[u8]) -> Result<Vec<u8>> {
+    let (derivation, header_len) = parse_header(data)?;
+    let key = keys.key(&derivation)?;
+
+    let nonce_end = header_len + NONCE_LEN;
+    let mut aad = data[..nonce_end].to_vec();
+    aad.extend_from_slice(&context.encode());
+    cipher(&key)
+        .decrypt(
+            XNonce::from_slice(&data[header_len..nonce_end]),
+            Payload { msg: &data[nonce_end..], aad: &aad },
+        )
+        .map_err(|_| {
+            EctarError::Decryption(format!(
+                "{} could not be authenticated (wrong key or damaged data)",
+                match context {
+                    Context::Chunk(number) => format!("Chunk {}", number),
+                    Context::Index => "Index".to_string(),
+                }
+            ))
+        })
+}
+
+/// Decrypt data if it is sealed, otherwise return it as it is
+///
+/// With `require_sealed`, unsealed data is rejected instead: once the
+/// archive is known to be encrypted, plaintext in place of an envelope
+/// means someone swapped it in to get past the authentication.
+pub fn open_if_sealed(
+    keys: Option<&KeyRing>,
+    context: Context,
+    data: Vec<u8>,
+    require_sealed: bool,
+) -> Result<Vec<u8>> {
+    let subject = match context {
+        Context::Chunk(number) => format!("Chunk {}", number),
+        Context::Index => "The index".to_string(),
+    };
+    if !is_sealed(&data) {
+        if require_sealed {
+            return Err(EctarError::Decryption(format!(
+                "{} is not encrypted, but the archive is",
+                subject
+            )));
+        }
+        return Ok(data);
+    }
+    match keys {
+        Some(keys) => open(keys, context, &data),
+        None => Err(EctarError::Decryption(format!(
+            "{} is encrypted; supply --key-file, --passphrase-file or --identity",
+            subject
+        ))),
+    }
+}
+
+fn cipher(key: &DataKey) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new((&key.0).into())
+}
+
+fn encode_header(derivation: &KeyDerivation) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    match derivation {
+        KeyDerivation::KeyFile => header.push(KIND_KEY_FILE),
+        KeyDerivation::Argon2id { params, salt } => {
+            header.push(KIND_ARGON2ID);
+            header.extend_from_slice(&params.m_cost.to_le_bytes());
+            header.extend_from_slice(&params.t_cost.to_le_bytes());
+            header.extend_from_slice(&params.p_cost.to_le_bytes());
+            header.extend_from_slice(salt);
+        }
+        KeyDerivation::X25519 { key_id } => {
+            header.push(KIND_X25519);
+            header.extend_from_slice(key_id);
+        }
+    }
+    header
+}
+
+/// Parse an envelope header; returns the derivation and the header length
+/// (up to the nonce)
+fn parse_header(data: &[u8]) -> Result<(KeyDerivation, usize)> {
+    let invalid = |what: &str| EctarError::InvalidHeader(format!("Encryption envelope: {}", what));
+
+    if !is_sealed(data) {
+        return Err(invalid("missing magic"));
+    }
+    let version = *data.get(MAGIC.len()).ok_or_else(|| invalid("truncated"))?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported version {}", version)));
+    }
+
+    let mut pos = MAGIC.len() + 2;
+    let derivation = match data.get(MAGIC.len() + 1) {
+        Some(&KIND_KEY_FILE) => KeyDerivation::KeyFile,
+        Some(&KIND_ARGON2ID) => {
+            let fields = data.get(pos..pos + 12 + SALT_LEN).ok_or_else(|| invalid("truncated"))?;
+            let word = |i: usize| u32::from_le_bytes(fields[i * 4..i * 4 + 4].try_into().expect("4 bytes"));
+            pos += 12 + SALT_LEN;
+            KeyDerivation::Argon2id {
+                params: KdfParams {
+                    m_cost: word(0),
+                    t_cost: word(1),
+                    p_cost: word(2),
+                },
+                salt: fields[12..].try_into().expect("salt length"),
+            }
+        }
+        Some(&KIND_X25519) => {
+            let key_id = data.get(pos..pos + KEY_ID_LEN).ok_or_else(|| invalid("truncated"))?;
+            pos += KEY_ID_LEN;
+            KeyDerivation::X25519 {
+                key_id: key_id.try_into().expect("key id length"),
+            }
+        }
+        Some(kind) => return Err(invalid(&format!("unknown key kind {}", kind))),
+        None => return Err(invalid("truncated")),
+    };
+
+    if data.len() < pos + NONCE_LEN {
+        return Err(invalid("truncated"));
+    }
+    Ok((derivation, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key::Secret;
+    use crate::crypto::TEST_KDF;
+
+    fn encryptor(secret: &Secret) -> Encryptor {
+        let derivation = KeyDerivation::generate(secret, TEST_KDF);
+        Encryptor::new(secret.derive(&derivation).unwrap(), derivation)
+    }
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        for secret in [Secret::Key([9u8; 32]), Secret::Passphrase("pass".to_string())] {
+            let encryptor = encryptor(&secret);
+            let sealed = encryptor.seal(Context::Chunk(3), b"chunk data").unwrap();
+            assert!(is_sealed(&sealed));
+            assert_eq!(&derivation_of(&sealed).unwrap(), encryptor.derivation());
+
+            let keys = KeyRing::new(secret);
+            assert_eq!(open(&keys, Context::Chunk(3), &sealed).unwrap(), b"chunk data");
+        }
+    }
+
+    #[test]
+    fn test_open_rejects_wrong_key_context_or_damage() {
+        let secret = Secret::Key([9u8; 32]);
+        let sealed = encryptor(&secret).seal(Context::Chunk(1), b"chunk data").unwrap();
+        let keys = KeyRing::new(secret);
+
+        let wrong = KeyRing::new(Secret::Key([8u8; 32]));
+        assert!(matches!(open(&wrong, Context::Chunk(1), &sealed), Err(EctarError::Decryption(_))));
+        // A chunk cannot be swapped for another one or for the index
+        assert!(open(&keys, Context::Chunk(2), &sealed).is_err());
+        assert!(open(&keys, Context::Index, &sealed).is_err());
+
+        let mut damaged = sealed.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert!(open(&keys, Context::Chunk(1), &damaged).is_err());
+        assert!(matches!(
+            open(&keys, Context::Chunk(1), &sealed[..20]),
+            Err(EctarError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_if_sealed() {
+        let secret = Secret::Key([9u8; 32]);
+        let sealed = encryptor(&secret).seal(Context::Index, b"index").unwrap();
+        let keys = KeyRing::new(secret);
+
+        assert_eq!(open_if_sealed(None, Context::Index, b"plain".to_vec(), false).unwrap(), b"plain");
+        assert_eq!(open_if_sealed(Some(&keys), Context::Index, sealed.clone(), true).unwrap(), b"index");
+        assert!(matches!(
+            open_if_sealed(None, Context::Index, sealed, false),
+            Err(EctarError::Decryption(_))
+        ));
+        assert!(matches!(
+            open_if_sealed(Some(&keys), Context::Index, b"plain".to_vec(), true),
+            Err(EctarError::Decryption(_))
+        ));
+    }
+}
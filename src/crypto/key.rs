@@ -0,0 +1,390 @@
+use crate::checksum::hex;
+use crate::crypto::recipient::{Identity, Recipients, KEY_ID_LEN};
+use crate::error::{EctarError, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use digest::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Length of a data key in bytes
+pub const KEY_LEN: usize = 32;
+
+/// Length of the salt used to derive a key from a passphrase
+pub const SALT_LEN: usize = 16;
+
+/// Argon2id cost parameters for deriving a key from a passphrase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub m_cost: u32,
+    /// Number of passes
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        // Archives may sit on tape for years; spend more than the
+        // interactive-login defaults
+        Self {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+}
+
+/// How an archive's data key is obtained from what the user supplies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kdf", rename_all = "lowercase")]
+pub enum KeyDerivation {
+    /// The key file holds the data key itself
+    KeyFile,
+    /// The data key is derived from a passphrase with Argon2id
+    Argon2id {
+        #[serde(flatten)]
+        params: KdfParams,
+        #[serde(with = "hex_bytes")]
+        salt: [u8; SALT_LEN],
+    },
+    /// The data key is random and wrapped to each recipient's public key
+    X25519 {
+        #[serde(with = "hex_bytes")]
+        key_id: [u8; KEY_ID_LEN],
+    },
+}
+
+impl KeyDerivation {
+    /// Derivation for a new archive: a fresh salt for passphrases, a fresh
+    /// key id for recipients
+    pub fn generate(secret: &Secret, params: KdfParams) -> Self {
+        match secret {
+            Secret::Key(_) => KeyDerivation::KeyFile,
+            Secret::Passphrase(_) => {
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                KeyDerivation::Argon2id { params, salt }
+            }
+            Secret::Identity(_) => {
+                let mut key_id = [0u8; KEY_ID_LEN];
+                OsRng.fill_bytes(&mut key_id);
+                KeyDerivation::X25519 { key_id }
+            }
+        }
+    }
+
+    /// Short description for display ("argon2id passphrase")
+    pub fn describe(&self) -> &'static str {
+        match self {
+            KeyDerivation::KeyFile => "key file",
+            KeyDerivation::Argon2id { .. } => "argon2id passphrase",
+            KeyDerivation::X25519 { .. } => "x25519 recipient key",
+        }
+    }
+
+    /// What the user supplies for this derivation ("passphrase")
+    pub fn secret_name(&self) -> &'static str {
+        match self {
+            KeyDerivation::KeyFile => "key",
+            KeyDerivation::Argon2id { .. } => "passphrase",
+            KeyDerivation::X25519 { .. } => "identity",
+        }
+    }
+}
+
+/// Symmetric key that chunks and the index are encrypted with
+#[derive(Clone, PartialEq, Eq)]
+pub struct DataKey(pub(crate) [u8; KEY_LEN]);
+
+impl DataKey {
+    /// A random key, for archives encrypted to recipients
+    pub fn generate() -> Self {
+        let mut key = [0u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        DataKey(key)
+    }
+
+    /// Short fingerprint recorded in the index, so a wrong key is reported
+    /// as such instead of as corrupt chunks
+    pub fn check_value(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"ectar key check");
+        hasher.update(self.0);
+        format!("{:x}", hasher.finalize())[..16].to_string()
+    }
+}
+
+impl fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DataKey(..)")
+    }
+}
+
+/// What the user supplies to encrypt or decrypt an archive
+#[derive(Clone)]
+pub enum Secret {
+    Passphrase(String),
+    Key([u8; KEY_LEN]),
+    /// A private key able to unwrap archives encrypted to its public key
+    Identity(Identity),
+}
+
+impl Secret {
+    /// Read a key file holding 32 raw bytes or 64 hex digits
+    pub fn from_key_file(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        let key = match std::str::from_utf8(&data).map(str::trim) {
+            Ok(text) if text.len() == KEY_LEN * 2 => decode_hex(text),
+            _ => data.as_slice().try_into().ok(),
+        };
+        key.map(Secret::Key).ok_or_else(|| {
+            EctarError::InvalidParameters(format!(
+                "Key file {} must hold {} bytes or {} hex digits",
+                path.display(),
+                KEY_LEN,
+                KEY_LEN * 2
+            ))
+        })
+    }
+
+    /// Read a passphrase from the first line of a file
+    pub fn from_passphrase_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let passphrase = text.lines().next().unwrap_or_default();
+        if passphrase.is_empty() {
+            return Err(EctarError::InvalidParameters(format!(
+                "Passphrase file {} is empty",
+                path.display()
+            )));
+        }
+        Ok(Secret::Passphrase(passphrase.to_string()))
+    }
+
+    /// Read an identity file written by `ectar keygen`
+    pub fn from_identity_file(path: &Path) -> Result<Self> {
+        Identity::from_file(path).map(Secret::Identity)
+    }
+
+    /// Obtain the data key the way an archive records
+    ///
+    /// An identity cannot derive a key by itself; the key ring unwraps it
+    /// from the archive's recipients.
+    pub fn derive(&self, derivation: &KeyDerivation) -> Result<DataKey> {
+        match (self, derivation) {
+            (Secret::Key(key), KeyDerivation::KeyFile) => Ok(DataKey(*key)),
+            (Secret::Passphrase(passphrase), KeyDerivation::Argon2id { params, salt }) => {
+                let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_LEN))
+                    .map_err(|e| EctarError::Encryption(format!("Invalid Argon2 parameters: {}", e)))?;
+                let mut key = [0u8; KEY_LEN];
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+                    .map_err(|e| EctarError::Encryption(format!("Key derivation failed: {}", e)))?;
+                Ok(DataKey(key))
+            }
+            (Secret::Identity(_), KeyDerivation::X25519 { .. }) => Err(EctarError::Decryption(
+                "An identity needs the archive's recipients to unwrap its key".to_string(),
+            )),
+            (_, derivation) => Err(EctarError::Decryption(format!(
+                "Archive is encrypted with a {}",
+                derivation.describe()
+            ))),
+        }
+    }
+}
+
+/// A secret plus the keys derived from it so far
+///
+/// Readers may meet several derivations (an incremental chain made with the
+/// same passphrase has one salt per archive); each is derived only once.
+/// An identity unwraps keys from the recipients added to the ring.
+pub struct KeyRing {
+    secret: Secret,
+    derived: Mutex<Vec<(KeyDerivation, DataKey)>>,
+    recipients: Mutex<Vec<Recipients>>,
+}
+
+impl KeyRing {
+    pub fn new(secret: Secret) -> Self {
+        Self {
+            secret,
+            derived: Mutex::new(Vec::new()),
+            recipients: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The data key for a derivation
+    pub fn key(&self, derivation: &KeyDerivation) -> Result<DataKey> {
+        let mut derived = self.derived.lock().expect("key ring lock poisoned");
+        if let Some((_, key)) = derived.iter().find(|(d, _)| d == derivation) {
+            return Ok(key.clone());
+        }
+        let key = match (&self.secret, derivation) {
+            (Secret::Identity(identity), KeyDerivation::X25519 { key_id }) => self.unwrap(identity, key_id)?,
+            _ => self.secret.derive(derivation)?,
+        };
+        derived.push((derivation.clone(), key.clone()));
+        Ok(key)
+    }
+
+    /// Make an archive's wrapped data key available to an identity
+    pub fn add_recipients(&self, recipients: Recipients) {
+        let mut known = self.recipients.lock().expect("key ring lock poisoned");
+        if !known.iter().any(|r| r.key_id == recipients.key_id) {
+            known.push(recipients);
+        }
+    }
+
+    /// Add the recipients file of an archive, if it has one and the secret
+    /// is an identity
+    pub fn load_recipients(&self, base: &str) -> Result<()> {
+        if let Secret::Identity(_) = self.secret {
+            if let Some(recipients) = Recipients::read(base)? {
+                self.add_recipients(recipients);
+            }
+        }
+        Ok(())
+    }
+
+    fn unwrap(&self, identity: &Identity, key_id: &[u8; KEY_ID_LEN]) -> Result<DataKey> {
+        let known = self.recipients.lock().expect("key ring lock poisoned");
+        match known.iter().find(|r| &r.key_id == key_id) {
+            Some(recipients) => recipients.unwrap(identity),
+            None => Err(EctarError::Decryption(
+                "The archive's recipients are unknown; its .recipients file, recipients shards and index are all missing"
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+/// Read a private key file: the key as 64 hex digits on the first line
+/// that is not a `#` comment
+pub(crate) fn read_private_key_file(path: &Path, what: &str) -> Result<[u8; KEY_LEN]> {
+    let text = std::fs::read_to_string(path)?;
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .and_then(decode_hex)
+        .ok_or_else(|| {
+            EctarError::InvalidParameters(format!(
+                "{} file {} must hold a private key of {} hex digits",
+                what,
+                path.display(),
+                KEY_LEN * 2
+            ))
+        })
+}
+
+/// Write a new private key file, readable only by its owner
+pub(crate) fn write_private_key_file(path: &Path, contents: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn decode_hex(text: &str) -> Option<[u8; KEY_LEN]> {
+    hex::decode(text)?.try_into().ok()
+}
+
+/// Serialize fixed-size byte arrays as hex strings
+pub(crate) mod hex_bytes {
+    use crate::checksum::hex;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(bytes: &[u8; N], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(deserializer: D) -> Result<[u8; N], D::Error> {
+        let text = String::deserialize(deserializer)?;
+        hex::decode(&text)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| D::Error::custom(format!("expected {} hex digits", N * 2)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::TEST_KDF;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_key_file_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        let raw = temp_dir.path().join("raw.key");
+        std::fs::write(&raw, [7u8; KEY_LEN]).unwrap();
+        let hex = temp_dir.path().join("hex.key");
+        std::fs::write(&hex, format!("{}\n", "07".repeat(KEY_LEN))).unwrap();
+        let short = temp_dir.path().join("short.key");
+        std::fs::write(&short, [7u8; 16]).unwrap();
+
+        for path in [&raw, &hex] {
+            let key = Secret::from_key_file(path).unwrap().derive(&KeyDerivation::KeyFile).unwrap();
+            assert_eq!(key, DataKey([7u8; KEY_LEN]));
+        }
+        assert!(matches!(
+            Secret::from_key_file(&short),
+            Err(EctarError::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    fn test_passphrase_derivation() {
+        let secret = Secret::Passphrase("correct horse".to_string());
+        let derivation = KeyDerivation::generate(&secret, TEST_KDF);
+        let key = secret.derive(&derivation).unwrap();
+        assert_eq!(key, secret.derive(&derivation).unwrap());
+
+        // A new salt gives a new key
+        let other = KeyDerivation::generate(&secret, TEST_KDF);
+        assert_ne!(key, secret.derive(&other).unwrap());
+
+        let wrong = Secret::Passphrase("wrong horse".to_string());
+        assert_ne!(key.check_value(), wrong.derive(&derivation).unwrap().check_value());
+
+        // A passphrase cannot stand in for a key file
+        assert!(matches!(
+            secret.derive(&KeyDerivation::KeyFile),
+            Err(EctarError::Decryption(_))
+        ));
+    }
+
+    #[test]
+    fn test_derivation_serialization() {
+        let derivation = KeyDerivation::Argon2id {
+            params: TEST_KDF,
+            salt: [0xab; SALT_LEN],
+        };
+        let json = serde_json::to_string(&derivation).unwrap();
+        assert!(json.contains("\"kdf\":\"argon2id\""));
+        assert!(json.contains(&"ab".repeat(SALT_LEN)));
+        assert_eq!(serde_json::from_str::<KeyDerivation>(&json).unwrap(), derivation);
+    }
+
+    #[test]
+    fn test_decode_hex_needs_a_whole_key() {
+        assert_eq!(decode_hex(&"07".repeat(KEY_LEN)), Some([7u8; KEY_LEN]));
+        assert!(decode_hex(&"07".repeat(KEY_LEN - 1)).is_none());
+    }
+
+    #[test]
+    fn test_key_ring_caches_derived_keys() {
+        let secret = Secret::Passphrase("pass".to_string());
+        let derivation = KeyDerivation::generate(&secret, TEST_KDF);
+        let ring = KeyRing::new(secret.clone());
+        assert_eq!(ring.key(&derivation).unwrap(), secret.derive(&derivation).unwrap());
+        ring.key(&derivation).unwrap();
+        assert_eq!(ring.derived.lock().unwrap().len(), 1);
+    }
+}
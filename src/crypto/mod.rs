@@ -0,0 +1,38 @@
+pub mod envelope;
+pub mod key;
+pub mod recipient;
+pub mod signature;
+
+pub use envelope::{Context, Encryptor};
+pub use key::{DataKey, KdfParams, KeyDerivation, KeyRing, Secret};
+pub use recipient::{Identity, PublicKey, Recipients};
+pub use signature::{IndexSignature, SigningKey, VerifyingKey};
+
+use crate::error::{EctarError, Result};
+use crate::index::format::EncryptionParameters;
+
+/// Check up front that the supplied key is the one an archive was
+/// encrypted with, so a wrong key is not reported as damaged chunks
+pub fn check_key(keys: Option<&KeyRing>, encryption: &EncryptionParameters) -> Result<()> {
+    let keys = keys.ok_or_else(|| {
+        EctarError::Decryption(format!(
+            "Archive is encrypted with a {}; supply --key-file, --passphrase-file or --identity",
+            encryption.key.describe()
+        ))
+    })?;
+    if keys.key(&encryption.key)?.check_value() != encryption.key_check {
+        return Err(EctarError::Decryption(format!(
+            "Wrong {} for this archive",
+            encryption.key.secret_name()
+        )));
+    }
+    Ok(())
+}
+
+/// Cheap Argon2 parameters so tests stay fast
+#[cfg(test)]
+pub(crate) const TEST_KDF: KdfParams = KdfParams {
+    m_cost: 64,
+    t_cost: 1,
+    p_cost: 1,
+};
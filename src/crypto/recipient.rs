@@ -0,0 +1,281 @@
+use crate::checksum::hex;
+use crate::crypto::key::{self, decode_hex, hex_bytes, DataKey, KeyDerivation, KEY_LEN};
+use crate::error::{EctarError, Result};
+use crate::index::format::EncryptionParameters;
+use crate::index::{builder, reader};
+use crate::io::durable::Durability;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use x25519_dalek::{EphemeralSecret, StaticSecret};
+
+/// Length of the random id that ties encrypted chunks to their recipients
+pub const KEY_ID_LEN: usize = 16;
+
+/// Length of a wrapped data key: the key plus its authentication tag
+const WRAPPED_LEN: usize = KEY_LEN + 16;
+
+const WRAP_INFO: &[u8] = b"ectar x25519 v1";
+
+/// Name of the erasure-coded file holding an archive's wrapped keys
+const FILE_NAME: &str = "recipients";
+
+/// An X25519 public key that an archive's data key can be encrypted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey(pub(crate) [u8; KEY_LEN]);
+
+impl FromStr for PublicKey {
+    type Err = EctarError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        decode_hex(s.trim()).map(PublicKey).ok_or_else(|| {
+            EctarError::InvalidParameters(format!(
+                "Invalid public key: {}. Expected {} hex digits",
+                s,
+                KEY_LEN * 2
+            ))
+        })
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(&self.0))
+    }
+}
+
+/// An X25519 private key that can unwrap the data key of archives
+/// encrypted to its public key
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    pub fn generate() -> Self {
+        Identity(StaticSecret::random_from_rng(OsRng))
+    }
+
+    /// Read an identity file: the private key as 64 hex digits on the first
+    /// line that is not a `#` comment
+    pub fn from_file(path: &Path) -> Result<Self> {
+        key::read_private_key_file(path, "Identity").map(|key| Identity(StaticSecret::from(key)))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(x25519_dalek::PublicKey::from(&self.0).to_bytes())
+    }
+
+    /// Write a new identity file, readable only by its owner
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        key::write_private_key_file(path, &self.to_file_contents())
+    }
+
+    /// Contents of an identity file, with the public key as a comment
+    pub fn to_file_contents(&self) -> String {
+        format!(
+            "# ectar identity\n# public key: {}\n{}\n",
+            self.public_key(),
+            hex::encode(self.0.as_bytes())
+        )
+    }
+}
+
+/// An archive's data key encrypted to one recipient
+///
+/// The key is wrapped age-style: an ephemeral X25519 key agreement with the
+/// recipient, HKDF-SHA256 over the shared secret, and ChaCha20-Poly1305.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WrappedKey {
+    #[serde(with = "hex_bytes")]
+    pub recipient: [u8; KEY_LEN],
+    #[serde(with = "hex_bytes")]
+    ephemeral: [u8; KEY_LEN],
+    #[serde(with = "hex_bytes")]
+    wrapped_key: [u8; WRAPPED_LEN],
+}
+
+impl WrappedKey {
+    pub fn wrap(key: &DataKey, recipient: &PublicKey) -> Result<Self> {
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral).to_bytes();
+        let shared = ephemeral.diffie_hellman(&x25519_dalek::PublicKey::from(recipient.0));
+        if !shared.was_contributory() {
+            return Err(EctarError::InvalidParameters(format!(
+                "Public key {} cannot be encrypted to",
+                recipient
+            )));
+        }
+
+        let wrapped = wrapping_cipher(shared.as_bytes(), &ephemeral_public, &recipient.0)
+            .encrypt(&Nonce::default(), key.0.as_slice())
+            .map_err(|_| EctarError::Encryption("Wrapping the data key failed".to_string()))?;
+        Ok(Self {
+            recipient: recipient.0,
+            ephemeral: ephemeral_public,
+            wrapped_key: wrapped.try_into().expect("wrapped key length"),
+        })
+    }
+
+    fn unwrap(&self, identity: &Identity) -> Option<DataKey> {
+        let shared = identity.0.diffie_hellman(&x25519_dalek::PublicKey::from(self.ephemeral));
+        let key = wrapping_cipher(shared.as_bytes(), &self.ephemeral, &self.recipient)
+            .decrypt(&Nonce::default(), self.wrapped_key.as_slice())
+            .ok()?;
+        Some(DataKey(key.try_into().ok()?))
+    }
+}
+
+/// The data key of an archive, wrapped for each of its recipients
+///
+/// Recorded in the index and also in `<base>.recipients`, a small JSON file
+/// erasure-coded into `<base>.recipients.sNN` shards like the index, so an
+/// archive whose index is lost or encrypted can still be opened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recipients {
+    #[serde(with = "hex_bytes")]
+    pub key_id: [u8; KEY_ID_LEN],
+    pub recipients: Vec<WrappedKey>,
+}
+
+impl Recipients {
+    /// Wrap a new archive's data key for each public key
+    pub fn new(key: &DataKey, public_keys: &[PublicKey]) -> Result<Self> {
+        let mut key_id = [0u8; KEY_ID_LEN];
+        OsRng.fill_bytes(&mut key_id);
+        let recipients = public_keys
+            .iter()
+            .map(|public_key| WrappedKey::wrap(key, public_key))
+            .collect::<Result<_>>()?;
+        Ok(Self { key_id, recipients })
+    }
+
+    /// The recipients recorded in an archive's encryption parameters
+    pub fn from_parameters(encryption: &EncryptionParameters) -> Option<Self> {
+        match encryption.key {
+            KeyDerivation::X25519 { key_id } => Some(Self {
+                key_id,
+                recipients: encryption.recipients.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// How the data key is obtained, as recorded in every encrypted chunk
+    pub fn derivation(&self) -> KeyDerivation {
+        KeyDerivation::X25519 { key_id: self.key_id }
+    }
+
+    /// Public keys the data key is wrapped for
+    pub fn public_keys(&self) -> Vec<PublicKey> {
+        self.recipients.iter().map(|r| PublicKey(r.recipient)).collect()
+    }
+
+    /// Recover the data key with one recipient's identity
+    pub fn unwrap(&self, identity: &Identity) -> Result<DataKey> {
+        self.recipients
+            .iter()
+            .find_map(|wrapped| wrapped.unwrap(identity))
+            .ok_or_else(|| {
+                EctarError::Decryption(format!(
+                    "Identity {} is not among the archive's recipients",
+                    identity.public_key()
+                ))
+            })
+    }
+
+    /// Write `<base>.recipients` and its shards
+    pub fn write(
+        &self,
+        output_base: &str,
+        data_shards: usize,
+        parity_shards: usize,
+        durability: Durability,
+    ) -> Result<PathBuf> {
+        builder::write_json_file(self, output_base, FILE_NAME, data_shards, parity_shards, durability)
+    }
+
+    /// Read an archive's recipients, reconstructing them from their shards
+    /// when the file is missing or damaged
+    ///
+    /// Returns `None` if the archive has no recipients file at all.
+    pub fn read(base: &str) -> Result<Option<Self>> {
+        reader::read_json_file(base, FILE_NAME)
+    }
+
+    /// Whether the recipients file and all of its shards are present and
+    /// undamaged
+    pub fn is_intact(base: &str, total_shards: usize) -> bool {
+        reader::json_file_is_intact::<Self>(base, FILE_NAME, total_shards)
+    }
+}
+
+fn wrapping_cipher(shared: &[u8], ephemeral: &[u8; KEY_LEN], recipient: &[u8; KEY_LEN]) -> ChaCha20Poly1305 {
+    let mut salt = ephemeral.to_vec();
+    salt.extend_from_slice(recipient);
+    let mut key = [0u8; KEY_LEN];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    ChaCha20Poly1305::new(&key.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_any_recipient_unwraps_the_key() {
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let key = DataKey([6u8; KEY_LEN]);
+
+        let recipients = Recipients::new(&key, &[alice.public_key(), bob.public_key()]).unwrap();
+        assert_eq!(recipients.public_keys(), vec![alice.public_key(), bob.public_key()]);
+        assert_eq!(recipients.unwrap(&alice).unwrap(), key);
+        assert_eq!(recipients.unwrap(&bob).unwrap(), key);
+
+        let outsider = Identity::generate();
+        assert!(matches!(recipients.unwrap(&outsider), Err(EctarError::Decryption(_))));
+    }
+
+    #[test]
+    fn test_identity_file_and_public_key_parsing() {
+        let temp_dir = TempDir::new().unwrap();
+        let identity = Identity::generate();
+        let path = temp_dir.path().join("id.key");
+        std::fs::write(&path, identity.to_file_contents()).unwrap();
+
+        let read = Identity::from_file(&path).unwrap();
+        assert_eq!(read.public_key(), identity.public_key());
+        let public_key: PublicKey = identity.public_key().to_string().parse().unwrap();
+        assert_eq!(public_key, identity.public_key());
+
+        assert!("abcd".parse::<PublicKey>().is_err());
+        std::fs::write(&path, "# nothing here\n").unwrap();
+        assert!(Identity::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_recipients_file_survives_losing_the_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        assert!(Recipients::read(&base).unwrap().is_none());
+
+        let identity = Identity::generate();
+        let recipients = Recipients::new(&DataKey([1u8; KEY_LEN]), &[identity.public_key()]).unwrap();
+        recipients.write(&base, 3, 2, Durability::None).unwrap();
+        assert!(Recipients::is_intact(&base, 5));
+        assert_eq!(Recipients::read(&base).unwrap().unwrap(), recipients);
+
+        std::fs::remove_file(format!("{}.{}", base, FILE_NAME)).unwrap();
+        std::fs::remove_file(builder::format_file_shard_path(&base, FILE_NAME, 0)).unwrap();
+        assert!(!Recipients::is_intact(&base, 5));
+        assert_eq!(Recipients::read(&base).unwrap().unwrap(), recipients);
+    }
+}
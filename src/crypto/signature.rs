@@ -0,0 +1,305 @@
+use crate::checksum::hex;
+use crate::crypto::key::{self, decode_hex, hex_bytes, KEY_LEN};
+use crate::error::{EctarError, Result};
+use crate::index::format::ArchiveIndex;
+use crate::index::{builder, reader};
+use crate::io::durable::Durability;
+use chacha20poly1305::aead::OsRng;
+use ed25519_dalek::{Signature, Signer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Name of the signature scheme, as recorded in the signature file
+pub const ALGORITHM: &str = "ed25519";
+
+/// Name of the erasure-coded file holding an archive's signature
+const FILE_NAME: &str = "signature";
+
+/// Prefixed to the signed bytes so an index signature cannot be passed off
+/// as a signature over anything else
+const DOMAIN: &[u8] = b"ectar index signature v1\n";
+
+/// An Ed25519 public key that archive signatures are checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(pub(crate) [u8; KEY_LEN]);
+
+impl FromStr for VerifyingKey {
+    type Err = EctarError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        decode_hex(s.trim())
+            .filter(|key| ed25519_dalek::VerifyingKey::from_bytes(key).is_ok())
+            .map(VerifyingKey)
+            .ok_or_else(|| {
+                EctarError::InvalidParameters(format!(
+                    "Invalid verifying key: {}. Expected {} hex digits",
+                    s,
+                    KEY_LEN * 2
+                ))
+            })
+    }
+}
+
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(&self.0))
+    }
+}
+
+/// An Ed25519 private key that archives are signed with
+#[derive(Clone)]
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    pub fn generate() -> Self {
+        SigningKey(ed25519_dalek::SigningKey::generate(&mut OsRng))
+    }
+
+    /// Read a signing key file written by `ectar keygen --signing`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        key::read_private_key_file(path, "Signing key").map(|key| SigningKey(ed25519_dalek::SigningKey::from_bytes(&key)))
+    }
+
+    /// Write a new signing key file, readable only by its owner
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        let contents = format!(
+            "# ectar signing key\n# public key: {}\n{}\n",
+            self.verifying_key(),
+            hex::encode(self.0.as_bytes())
+        );
+        key::write_private_key_file(path, &contents)
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key().to_bytes())
+    }
+}
+
+/// An Ed25519 signature over an archive's index
+///
+/// The index lists every chunk's and shard's SHA-256, so the signature
+/// covers all of the archive's data. It is kept in `<base>.signature`, a
+/// small JSON file erasure-coded into `<base>.signature.sNN` shards like
+/// the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexSignature {
+    pub algorithm: String,
+    #[serde(with = "hex_bytes")]
+    signer: [u8; KEY_LEN],
+    #[serde(with = "hex_bytes")]
+    signature: [u8; 64],
+}
+
+impl IndexSignature {
+    pub fn sign(index: &ArchiveIndex, key: &SigningKey) -> Result<Self> {
+        Ok(Self {
+            algorithm: ALGORITHM.to_string(),
+            signer: key.verifying_key().0,
+            signature: key.0.sign(&signed_bytes(index)?).to_bytes(),
+        })
+    }
+
+    /// Public key of whoever made the signature
+    pub fn signer(&self) -> VerifyingKey {
+        VerifyingKey(self.signer)
+    }
+
+    /// Check that the signature matches the index
+    pub fn verify(&self, index: &ArchiveIndex) -> Result<()> {
+        let invalid = |why: &str| EctarError::InvalidSignature(why.to_string());
+        if self.algorithm != ALGORITHM {
+            return Err(invalid(&format!("unsupported algorithm {}", self.algorithm)));
+        }
+        let signer = ed25519_dalek::VerifyingKey::from_bytes(&self.signer).map_err(|_| invalid("malformed signer key"))?;
+        signer
+            .verify_strict(&signed_bytes(index)?, &Signature::from_bytes(&self.signature))
+            .map_err(|_| invalid("the index does not match its signature"))
+    }
+
+    /// Write `<base>.signature` and its shards
+    pub fn write(
+        &self,
+        output_base: &str,
+        data_shards: usize,
+        parity_shards: usize,
+        durability: Durability,
+    ) -> Result<PathBuf> {
+        builder::write_json_file(self, output_base, FILE_NAME, data_shards, parity_shards, durability)
+    }
+
+    /// Read an archive's signature, reconstructing it from its shards when
+    /// the file is missing or damaged
+    ///
+    /// Returns `None` if the archive is not signed.
+    pub fn read(base: &str) -> Result<Option<Self>> {
+        reader::read_json_file(base, FILE_NAME)
+    }
+
+    /// Whether the signature file and all of its shards are present and
+    /// undamaged
+    pub fn is_intact(base: &str, total_shards: usize) -> bool {
+        reader::json_file_is_intact::<Self>(base, FILE_NAME, total_shards)
+    }
+}
+
+/// Outcome of checking an archive's signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// Signed by a trusted key (or, with no trusted keys given, by anyone)
+    /// and matching the index
+    Valid,
+    /// Matching the index, but signed by a key that is not trusted
+    Untrusted,
+    /// Unreadable, or not matching the index
+    Invalid,
+    /// The archive is not signed
+    Missing,
+}
+
+/// An archive's signer and signature status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureCheck {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    pub status: SignatureStatus,
+}
+
+/// Check an archive's signature against its index and, if any are given,
+/// against the trusted keys
+pub fn check_signature(base: &str, index: &ArchiveIndex, trusted: &[VerifyingKey]) -> SignatureCheck {
+    let signature = match IndexSignature::read(base) {
+        Ok(Some(signature)) => signature,
+        Ok(None) => {
+            return SignatureCheck {
+                signer: None,
+                status: SignatureStatus::Missing,
+            }
+        }
+        Err(e) => {
+            log::error!("Cannot read the archive's signature: {}", e);
+            return SignatureCheck {
+                signer: None,
+                status: SignatureStatus::Invalid,
+            };
+        }
+    };
+
+    let status = match signature.verify(index) {
+        Err(e) => {
+            log::error!("{}", e);
+            SignatureStatus::Invalid
+        }
+        Ok(()) if !trusted.is_empty() && !trusted.contains(&signature.signer()) => SignatureStatus::Untrusted,
+        Ok(()) => SignatureStatus::Valid,
+    };
+    SignatureCheck {
+        signer: Some(signature.signer().to_string()),
+        status,
+    }
+}
+
+/// The bytes an index signature covers: the index as compact JSON
+///
+/// `last_repaired` is left out, so repairing an archive keeps its signature
+/// valid; repair never changes anything else the index records.
+fn signed_bytes(index: &ArchiveIndex) -> Result<Vec<u8>> {
+    let mut index = index.clone();
+    index.last_repaired = None;
+    let mut bytes = DOMAIN.to_vec();
+    serde_json::to_writer(&mut bytes, &index)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::format::ArchiveParameters;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn test_index() -> ArchiveIndex {
+        ArchiveIndex {
+            version: "1.0".to_string(),
+            created: Utc::now(),
+            tool_version: "0.1.0".to_string(),
+            archive_name: "test".to_string(),
+            last_repaired: None,
+            parameters: ArchiveParameters {
+                data_shards: 3,
+                parity_shards: 2,
+                chunk_size: None,
+                compression_level: 3,
+                shard_trailers: false,
+                stripe_size: None,
+                encryption: None,
+            },
+            parent: None,
+            deleted: Vec::new(),
+            deleted_bytes: Vec::new(),
+            chunks: Vec::new(),
+            files: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_signature_detects_changes() {
+        let key = SigningKey::generate();
+        let mut index = test_index();
+        let signature = IndexSignature::sign(&index, &key).unwrap();
+        assert_eq!(signature.signer(), key.verifying_key());
+        signature.verify(&index).unwrap();
+
+        // Repairs do not invalidate the signature
+        index.last_repaired = Some(Utc::now());
+        signature.verify(&index).unwrap();
+
+        index.parameters.parity_shards = 3;
+        assert!(matches!(signature.verify(&index), Err(EctarError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn test_signing_key_file_and_verifying_key_parsing() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = SigningKey::generate();
+        let path = temp_dir.path().join("sign.key");
+        key.write_file(&path).unwrap();
+        assert!(key.write_file(&path).is_err());
+
+        let read = SigningKey::from_file(&path).unwrap();
+        assert_eq!(read.verifying_key(), key.verifying_key());
+        let parsed: VerifyingKey = key.verifying_key().to_string().parse().unwrap();
+        assert_eq!(parsed, key.verifying_key());
+        assert!("abcd".parse::<VerifyingKey>().is_err());
+    }
+
+    #[test]
+    fn test_check_signature() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let index = test_index();
+        assert_eq!(check_signature(&base, &index, &[]).status, SignatureStatus::Missing);
+
+        let key = SigningKey::generate();
+        IndexSignature::sign(&index, &key)
+            .unwrap()
+            .write(&base, 3, 2, Durability::None)
+            .unwrap();
+        assert!(IndexSignature::is_intact(&base, 5));
+
+        let check = check_signature(&base, &index, &[]);
+        assert_eq!(check.status, SignatureStatus::Valid);
+        assert_eq!(check.signer, Some(key.verifying_key().to_string()));
+        let trusted = [key.verifying_key()];
+        assert_eq!(check_signature(&base, &index, &trusted).status, SignatureStatus::Valid);
+        let other = [SigningKey::generate().verifying_key()];
+        assert_eq!(check_signature(&base, &index, &other).status, SignatureStatus::Untrusted);
+
+        let mut tampered = index.clone();
+        tampered.archive_name = "other".to_string();
+        assert_eq!(check_signature(&base, &tampered, &trusted).status, SignatureStatus::Invalid);
+    }
+}
@@ -1,5 +1,6 @@
+use crate::checksum::sha256::checksum_bytes;
 use crate::error::{EctarError, Result};
-use crate::erasure::ZfecHeader;
+use crate::erasure::{stripe, ShardTrailer, ZfecHeader};
 use reed_solomon_erasure::galois_8::ReedSolomon;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -13,32 +14,83 @@ pub fn decode_chunk(
     output_path: &PathBuf,
     expected_size: Option<u64>,
 ) -> Result<u64> {
-    let total_shards = data_shards + parity_shards;
+    let reconstructed = decode_chunk_bytes(available_shards, data_shards, parity_shards, expected_size)?;
+
+    // Write reconstructed chunk to file
+    let mut output_file = File::create(output_path)?;
+    output_file.write_all(&reconstructed)?;
+
+    let bytes_written = reconstructed.len() as u64;
+
+    log::info!(
+        "Successfully reconstructed chunk to {} ({} bytes)",
+        output_path.display(),
+        bytes_written
+    );
+
+    Ok(bytes_written)
+}
 
+/// Reconstruct a chunk in memory from available shards
+pub fn decode_chunk_bytes(
+    available_shards: Vec<ShardData>,
+    data_shards: usize,
+    parity_shards: usize,
+    expected_size: Option<u64>,
+) -> Result<Vec<u8>> {
     log::info!(
         "Reconstructing chunk from {} available shards (need {}, have {})",
         available_shards.len(),
         data_shards,
-        total_shards
+        data_shards + parity_shards
     );
 
+    let shards = reconstruct_shards(available_shards, data_shards, parity_shards)?;
+
+    // Combine data shards to get original chunk
+    let mut reconstructed = stripe::assemble_chunk(&shards, data_shards);
+
+    // Trim to expected size if provided (removes zero-padding)
+    if let Some(expected) = expected_size {
+        let original_len = reconstructed.len();
+        if original_len > expected as usize {
+            reconstructed.truncate(expected as usize);
+            log::debug!(
+                "Trimmed reconstructed chunk from {} to {} bytes",
+                original_len,
+                expected
+            );
+        }
+    }
+
+    Ok(reconstructed)
+}
+
+/// Reconstruct the complete set of data and parity shards for a chunk
+///
+/// Returns all `data_shards + parity_shards` shards in shard-number order,
+/// regenerating any that were not supplied.
+pub fn reconstruct_shards(
+    available_shards: Vec<ShardData>,
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let total_shards = data_shards + parity_shards;
+
     // Validate we have enough shards
     if available_shards.len() < data_shards {
         return Err(EctarError::InsufficientShards {
-            chunk: available_shards.get(0).map(|s| s.chunk_number).unwrap_or(0),
+            chunk: available_shards.first().map(|s| s.chunk_number).unwrap_or(0),
             needed: data_shards,
             available: available_shards.len(),
         });
     }
 
-    // Determine shard size (all shards should be the same size)
-    let shard_size = if let Some(first) = available_shards.first() {
-        first.data.len()
-    } else {
+    if available_shards.is_empty() {
         return Err(EctarError::ErasureCoding(
             "No shards available".to_string(),
         ));
-    };
+    }
 
     // Create Reed-Solomon decoder
     let decoder = ReedSolomon::new(data_shards, parity_shards)
@@ -59,44 +111,14 @@ pub fn decode_chunk(
         .reconstruct(&mut shards)
         .map_err(|e| EctarError::ErasureCoding(format!("Reconstruction failed: {:?}", e)))?;
 
-    // Combine data shards to get original chunk
-    let mut reconstructed = Vec::new();
-    for i in 0..data_shards {
-        if let Some(ref shard) = shards[i] {
-            reconstructed.extend_from_slice(shard);
-        } else {
-            return Err(EctarError::ErasureCoding(
-                "Missing data shard after reconstruction".to_string(),
-            ));
-        }
-    }
-
-    // Trim to expected size if provided (removes zero-padding)
-    if let Some(expected) = expected_size {
-        let original_len = reconstructed.len();
-        if original_len > expected as usize {
-            reconstructed.truncate(expected as usize);
-            log::debug!(
-                "Trimmed reconstructed chunk from {} to {} bytes",
-                original_len,
-                expected
-            );
-        }
-    }
-
-    // Write reconstructed chunk to file
-    let mut output_file = File::create(output_path)?;
-    output_file.write_all(&reconstructed)?;
-
-    let bytes_written = reconstructed.len() as u64;
-
-    log::info!(
-        "Successfully reconstructed chunk to {} ({} bytes)",
-        output_path.display(),
-        bytes_written
-    );
-
-    Ok(bytes_written)
+    shards
+        .into_iter()
+        .map(|shard| {
+            shard.ok_or_else(|| {
+                EctarError::ErasureCoding("Missing shard after reconstruction".to_string())
+            })
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -106,6 +128,8 @@ pub struct ShardData {
     pub data: Vec<u8>,
     /// Optional zfec header read from the shard file
     pub header: Option<ZfecHeader>,
+    /// Optional checksum trailer read from the shard file
+    pub trailer: Option<ShardTrailer>,
 }
 
 impl ShardData {
@@ -123,6 +147,13 @@ impl ShardData {
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
 
+        Self::from_bytes(chunk_number, shard_number, data, filename)
+    }
+
+    /// Parse shard file contents, stripping any zfec header and checksum trailer
+    ///
+    /// `name` is only used in log and error messages.
+    pub fn from_bytes(chunk_number: usize, shard_number: usize, data: Vec<u8>, name: &str) -> Result<Self> {
         // Try to detect and parse zfec header (2-4 bytes)
         let mut header = None;
         let mut header_size = 0;
@@ -136,7 +167,7 @@ impl ShardData {
                     if h.sharenum == shard_number as u8 {
                         log::debug!(
                             "Detected zfec header in {}: k={}, m={}, sharenum={}, padlen={}",
-                            filename, h.k, h.m, h.sharenum, h.padlen
+                            name, h.k, h.m, h.sharenum, h.padlen
                         );
                         header = Some(h);
                         header_size = size;
@@ -147,19 +178,59 @@ impl ShardData {
         }
 
         // If header was found, strip it from the data
-        let shard_data = if header_size > 0 {
+        let mut shard_data = if header_size > 0 {
             data[header_size..].to_vec()
         } else {
             data
         };
 
+        // Strip and check the checksum trailer, if the shard has one
+        let mut trailer = None;
+        if let Some((payload, t)) = ShardTrailer::split(&shard_data) {
+            if !t.matches(payload) {
+                return Err(EctarError::CorruptShard {
+                    shard: name.to_string(),
+                });
+            }
+            let payload_len = payload.len();
+            shard_data.truncate(payload_len);
+            trailer = Some(t);
+        }
+
         Ok(ShardData {
             chunk_number,
             shard_number,
             data: shard_data,
             header,
+            trailer,
         })
     }
+
+    /// Check the shard data against a checksum recorded in the index
+    pub fn matches_checksum(&self, expected: &str) -> bool {
+        checksum_bytes(&self.data) == expected
+    }
+}
+
+/// Drop shards whose data does not match the per-shard checksums from the index
+///
+/// `shard_checksums` is indexed by shard number; shards without a recorded
+/// checksum are kept as-is.
+pub fn drop_corrupt_shards(shards: Vec<ShardData>, shard_checksums: &[String]) -> Vec<ShardData> {
+    shards
+        .into_iter()
+        .filter(|shard| match shard_checksums.get(shard.shard_number) {
+            Some(expected) if !shard.matches_checksum(expected) => {
+                log::warn!(
+                    "Chunk {}: shard {} failed checksum verification, ignoring it",
+                    shard.chunk_number,
+                    shard.shard_number
+                );
+                false
+            }
+            _ => true,
+        })
+        .collect()
 }
 
 /// Parse a shard filename like "backup.c001.s05" into (chunk_number, shard_number)
@@ -395,6 +466,7 @@ mod tests {
             shard_number: 10,
             data: vec![1, 2, 3, 4, 5],
             header: None,
+            trailer: None,
         };
 
         assert_eq!(shard.chunk_number, 5);
@@ -403,6 +475,56 @@ mod tests {
         assert!(shard.header.is_none());
     }
 
+    #[test]
+    fn test_from_file_with_trailer() {
+        let temp_dir = TempDir::new().unwrap();
+        let shard_path = temp_dir.path().join("test.c001.s00");
+
+        let payload = b"shard payload with trailer".to_vec();
+        let mut bytes = payload.clone();
+        bytes.extend_from_slice(&ShardTrailer::for_data(&payload).encode());
+        std::fs::write(&shard_path, &bytes).unwrap();
+
+        let shard = ShardData::from_file(&shard_path).unwrap();
+        assert_eq!(shard.data, payload);
+        assert!(shard.trailer.is_some());
+    }
+
+    #[test]
+    fn test_from_file_with_corrupt_trailer() {
+        let temp_dir = TempDir::new().unwrap();
+        let shard_path = temp_dir.path().join("test.c001.s00");
+
+        let payload = b"shard payload with trailer".to_vec();
+        let trailer = ShardTrailer::for_data(&payload).encode();
+        let mut bytes = payload.clone();
+        bytes[0] ^= 0xFF;
+        bytes.extend_from_slice(&trailer);
+        std::fs::write(&shard_path, &bytes).unwrap();
+
+        let result = ShardData::from_file(&shard_path);
+        assert!(matches!(result, Err(EctarError::CorruptShard { .. })));
+    }
+
+    #[test]
+    fn test_drop_corrupt_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_data = b"Data protected by per-shard checksums".repeat(10);
+        let mut shards = create_test_shards(&temp_dir, &test_data, 4, 2);
+
+        let checksums: Vec<String> = shards.iter().map(|s| checksum_bytes(&s.data)).collect();
+        shards[1].data[0] ^= 0xFF;
+
+        let good = drop_corrupt_shards(shards, &checksums);
+        assert_eq!(good.len(), 5);
+        assert!(good.iter().all(|s| s.shard_number != 1));
+
+        // The remaining shards still decode to the original data
+        let output_path = temp_dir.path().join("decoded.bin");
+        decode_chunk(good, 4, 2, &output_path, Some(test_data.len() as u64)).unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), test_data);
+    }
+
     #[test]
     fn test_decode_chunk_large_data() {
         let temp_dir = TempDir::new().unwrap();
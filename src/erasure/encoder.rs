@@ -43,7 +43,7 @@ pub fn encode_chunk(
     );
 
     // Calculate shard size (round up to ensure all data fits)
-    let shard_size = (chunk_data.len() + data_shards - 1) / data_shards;
+    let shard_size = chunk_data.len().div_ceil(data_shards);
 
     // Create Reed-Solomon encoder
     let encoder = ReedSolomon::new(data_shards, parity_shards)
@@ -233,7 +233,7 @@ mod tests {
         assert_eq!(shards.len(), 15);
 
         // Each shard should have the correct size (rounded up)
-        let expected_shard_size = (large_data.len() + 10 - 1) / 10;
+        let expected_shard_size = large_data.len().div_ceil(10);
         for shard in &shards {
             assert_eq!(shard.size as usize, expected_shard_size);
         }
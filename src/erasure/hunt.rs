@@ -0,0 +1,356 @@
+use crate::checksum::sha256::checksum_bytes;
+use crate::compression;
+use crate::erasure::decoder::{self, ShardData};
+use crate::erasure::stripe;
+use crate::error::{EctarError, Result};
+use crate::index::format::ChunkInfo;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+/// Outcome of a successful corruption hunt
+#[derive(Debug, Clone)]
+pub struct HuntResult {
+    /// Reconstructed chunk data, trimmed to the chunk's compressed size
+    pub data: Vec<u8>,
+    /// Shard numbers whose contents disagree with the validated reconstruction
+    pub corrupt_shards: Vec<usize>,
+}
+
+/// Check reconstructed chunk data against what the index knows about the chunk
+///
+/// Uses the chunk checksum when the index has one. Older archives have no
+/// chunk checksums, so fall back to a full zstd decode that must produce
+/// exactly `uncompressed_size` bytes. That fallback misses some damage, which
+/// is why `hunt_corruption` prefers subsets with spare shards to cross-check.
+pub fn chunk_is_valid(data: &[u8], chunk_info: &ChunkInfo) -> bool {
+    if !chunk_info.checksum.is_empty() {
+        return checksum_bytes(data) == chunk_info.checksum;
+    }
+
+    let mut decoder = match compression::create_decoder(data) {
+        Ok(decoder) => decoder,
+        Err(_) => return false,
+    };
+    match std::io::copy(&mut decoder, &mut std::io::sink()) {
+        Ok(n) => n == chunk_info.uncompressed_size,
+        Err(_) => false,
+    }
+}
+
+/// Reconstructions `hunt_corruption` tries per chunk unless told otherwise
+///
+/// Enough to exclude every group of up to three shards at the default 10+5.
+pub const DEFAULT_MAX_ATTEMPTS: usize = 1000;
+
+/// Find the corrupt shards of a chunk by reconstructing from subsets of the
+/// available shards until the result validates
+///
+/// Shards are excluded in growing groups (every single shard first, then
+/// every pair, ...) so a single bad shard is found in at most n attempts.
+/// The number of subsets grows combinatorially with the shard count, so the
+/// hunt gives up after `max_attempts` reconstructions.
+/// Needs more than `data_shards` shards to have anything to choose from.
+pub fn hunt_corruption(
+    shards: &[ShardData],
+    data_shards: usize,
+    parity_shards: usize,
+    chunk_info: &ChunkInfo,
+    max_attempts: usize,
+) -> Result<HuntResult> {
+    let chunk_num = chunk_info.chunk_number;
+
+    // Shards of the wrong size can never take part in a reconstruction
+    let expected_len = chunk_info.shard_size as usize;
+    let (candidates, misfits): (Vec<&ShardData>, Vec<&ShardData>) = shards
+        .iter()
+        .filter(|s| s.shard_number < data_shards + parity_shards)
+        .partition(|s| s.data.len() == expected_len);
+
+    if candidates.len() <= data_shards {
+        return Err(EctarError::ErasureCoding(format!(
+            "Chunk {}: {} usable shards, need more than {} to hunt for corruption",
+            chunk_num,
+            candidates.len(),
+            data_shards
+        )));
+    }
+
+    let rs = ReedSolomon::new(data_shards, parity_shards)
+        .map_err(|e| EctarError::ErasureCoding(format!("Failed to create decoder: {:?}", e)))?;
+
+    let n = candidates.len();
+    let mut attempts = 0;
+    for excluded_count in 0..=(n - data_shards) {
+        for excluded in Combinations::new(n, excluded_count) {
+            if attempts == max_attempts {
+                return Err(EctarError::ErasureCoding(format!(
+                    "Chunk {}: gave up after {} attempts without valid data",
+                    chunk_num, attempts
+                )));
+            }
+            attempts += 1;
+
+            let subset: Vec<ShardData> = candidates
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !excluded.contains(i))
+                .map(|(_, s)| (*s).clone())
+                .collect();
+            let subset_len = subset.len();
+
+            let all_shards = match decoder::reconstruct_shards(subset, data_shards, parity_shards) {
+                Ok(all_shards) => all_shards,
+                Err(_) => continue,
+            };
+
+            // With spare shards in the subset, parity must agree with the data
+            if subset_len > data_shards && !rs.verify(&all_shards).unwrap_or(false) {
+                continue;
+            }
+
+            let mut data = stripe::assemble_chunk(&all_shards, data_shards);
+            data.truncate(chunk_info.compressed_size as usize);
+
+            if !chunk_is_valid(&data, chunk_info) {
+                continue;
+            }
+
+            // Anything that differs from the validated reconstruction is corrupt
+            let mut corrupt_shards: Vec<usize> = candidates
+                .iter()
+                .filter(|s| s.data != all_shards[s.shard_number])
+                .chain(misfits.iter())
+                .map(|s| s.shard_number)
+                .collect();
+            corrupt_shards.sort_unstable();
+
+            for shard_num in &corrupt_shards {
+                log::warn!("Chunk {}: shard {} is corrupt", chunk_num, shard_num);
+            }
+
+            return Ok(HuntResult {
+                data,
+                corrupt_shards,
+            });
+        }
+    }
+
+    Err(EctarError::ErasureCoding(format!(
+        "Chunk {}: no combination of shards produced valid data ({} attempts)",
+        chunk_num, attempts
+    )))
+}
+
+/// Iterator over all `k`-element combinations of `0..n`, in lexicographic order
+struct Combinations {
+    n: usize,
+    indices: Vec<usize>,
+    done: bool,
+}
+
+impl Combinations {
+    fn new(n: usize, k: usize) -> Self {
+        Self {
+            n,
+            indices: (0..k).collect(),
+            done: k > n,
+        }
+    }
+}
+
+impl Iterator for Combinations {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        if self.done {
+            return None;
+        }
+        let current = self.indices.clone();
+
+        // Advance to the next combination
+        let k = self.indices.len();
+        let mut i = k;
+        loop {
+            if i == 0 {
+                self.done = true;
+                break;
+            }
+            i -= 1;
+            if self.indices[i] < self.n - k + i {
+                self.indices[i] += 1;
+                for j in i + 1..k {
+                    self.indices[j] = self.indices[j - 1] + 1;
+                }
+                break;
+            }
+        }
+
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::erasure::encoder;
+    use std::io::Write;
+    use tempfile::{NamedTempFile, TempDir};
+
+    fn encode_test_chunk(
+        temp_dir: &TempDir,
+        data: &[u8],
+        data_shards: usize,
+        parity_shards: usize,
+    ) -> (Vec<ShardData>, ChunkInfo) {
+        let mut chunk_file = NamedTempFile::new().unwrap();
+        chunk_file.write_all(data).unwrap();
+        chunk_file.flush().unwrap();
+
+        let output_base = temp_dir.path().join("test").to_string_lossy().to_string();
+        let infos = encoder::encode_chunk(
+            &chunk_file.path().to_path_buf(),
+            &output_base,
+            1,
+            data_shards,
+            parity_shards,
+        )
+        .unwrap();
+
+        let shards: Vec<ShardData> = infos
+            .iter()
+            .map(|info| ShardData::from_file(&info.path).unwrap())
+            .collect();
+
+        let chunk_info = ChunkInfo {
+            chunk_number: 1,
+            compressed_size: data.len() as u64,
+            uncompressed_size: 0,
+            shard_size: shards[0].data.len() as u64,
+            checksum: checksum_bytes(data),
+            uncompressed_checksum: None,
+            shard_checksums: Vec::new(),
+        };
+
+        (shards, chunk_info)
+    }
+
+    #[test]
+    fn test_combinations() {
+        let all: Vec<Vec<usize>> = Combinations::new(4, 2).collect();
+        assert_eq!(
+            all,
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3]
+            ]
+        );
+        assert_eq!(Combinations::new(3, 0).count(), 1);
+        assert_eq!(Combinations::new(2, 3).count(), 0);
+    }
+
+    #[test]
+    fn test_hunt_finds_single_corrupt_data_shard() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = b"Chunk content that gets silently corrupted".repeat(20);
+        let (mut shards, chunk_info) = encode_test_chunk(&temp_dir, &data, 4, 2);
+
+        shards[2].data[5] ^= 0xFF;
+
+        let result = hunt_corruption(&shards, 4, 2, &chunk_info, DEFAULT_MAX_ATTEMPTS).unwrap();
+        assert_eq!(result.data, data);
+        assert_eq!(result.corrupt_shards, vec![2]);
+    }
+
+    #[test]
+    fn test_hunt_finds_two_corrupt_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = b"Two shards rot at once".repeat(30);
+        let (mut shards, chunk_info) = encode_test_chunk(&temp_dir, &data, 3, 3);
+
+        shards[0].data[0] ^= 0x01;
+        shards[4].data[1] ^= 0x01;
+
+        let result = hunt_corruption(&shards, 3, 3, &chunk_info, DEFAULT_MAX_ATTEMPTS).unwrap();
+        assert_eq!(result.data, data);
+        assert_eq!(result.corrupt_shards, vec![0, 4]);
+    }
+
+    #[test]
+    fn test_hunt_reports_truncated_shard() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = b"Truncated shard".repeat(40);
+        let (mut shards, chunk_info) = encode_test_chunk(&temp_dir, &data, 4, 2);
+
+        shards[1].data.truncate(3);
+
+        let result = hunt_corruption(&shards, 4, 2, &chunk_info, DEFAULT_MAX_ATTEMPTS).unwrap();
+        assert_eq!(result.data, data);
+        assert_eq!(result.corrupt_shards, vec![1]);
+    }
+
+    #[test]
+    fn test_hunt_needs_spare_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = b"No spare shards".repeat(10);
+        let (shards, chunk_info) = encode_test_chunk(&temp_dir, &data, 4, 2);
+
+        let result = hunt_corruption(&shards[..4], 4, 2, &chunk_info, DEFAULT_MAX_ATTEMPTS);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_hunt_too_much_corruption() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = b"Everything is broken".repeat(10);
+        let (mut shards, chunk_info) = encode_test_chunk(&temp_dir, &data, 4, 2);
+
+        for shard in shards.iter_mut().take(3) {
+            shard.data[0] ^= 0xFF;
+        }
+
+        assert!(hunt_corruption(&shards, 4, 2, &chunk_info, DEFAULT_MAX_ATTEMPTS).is_err());
+    }
+
+    #[test]
+    fn test_hunt_gives_up_after_max_attempts() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = b"Too many subsets to try".repeat(20);
+        let (mut shards, chunk_info) = encode_test_chunk(&temp_dir, &data, 3, 3);
+
+        shards[0].data[0] ^= 0x01;
+        shards[4].data[1] ^= 0x01;
+
+        // The two bad shards are first excluded together on attempt 11
+        let err = hunt_corruption(&shards, 3, 3, &chunk_info, 10).unwrap_err();
+        assert!(err.to_string().contains("gave up after 10 attempts"));
+        assert!(hunt_corruption(&shards, 3, 3, &chunk_info, 11).is_ok());
+    }
+
+    #[test]
+    fn test_chunk_is_valid_without_checksum() {
+        let tar_bytes = b"pretend tar stream".repeat(10);
+        let compressed = zstd::encode_all(&tar_bytes[..], 3).unwrap();
+
+        let chunk_info = ChunkInfo {
+            chunk_number: 1,
+            compressed_size: compressed.len() as u64,
+            uncompressed_size: tar_bytes.len() as u64,
+            shard_size: 0,
+            checksum: String::new(),
+            uncompressed_checksum: None,
+            shard_checksums: Vec::new(),
+        };
+
+        assert!(chunk_is_valid(&compressed, &chunk_info));
+
+        let mut bad_magic = compressed.clone();
+        bad_magic[0] ^= 0xFF;
+        assert!(!chunk_is_valid(&bad_magic, &chunk_info));
+
+        let truncated = &compressed[..compressed.len() - 4];
+        assert!(!chunk_is_valid(truncated, &chunk_info));
+    }
+}
@@ -1,6 +1,10 @@
 pub mod encoder;
+pub mod hunt;
 pub mod decoder;
+pub mod shard_trailer;
+pub mod stripe;
 pub mod zfec_header;
 
 pub use decoder::ShardData;
+pub use shard_trailer::ShardTrailer;
 pub use zfec_header::ZfecHeader;
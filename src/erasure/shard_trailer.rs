@@ -0,0 +1,102 @@
+use crate::checksum::hex;
+use digest::Digest;
+use sha2::Sha256;
+
+/// Magic bytes marking the end of a shard checksum trailer
+pub const TRAILER_MAGIC: &[u8; 8] = b"ECTSUM01";
+
+/// Size of the trailer in bytes (SHA-256 digest + magic)
+pub const TRAILER_SIZE: usize = 32 + TRAILER_MAGIC.len();
+
+/// Optional checksum trailer appended to shard files
+///
+/// Layout: 32-byte SHA-256 of the shard data (excluding the zfec header),
+/// followed by the 8-byte magic `ECTSUM01`. The trailer lets a shard be
+/// checked without the index, at the cost of zunfec treating it as data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardTrailer {
+    pub digest: [u8; 32],
+}
+
+impl ShardTrailer {
+    /// Create a trailer for the given shard data
+    pub fn for_data(data: &[u8]) -> Self {
+        Self::from_hasher(Sha256::new_with_prefix(data))
+    }
+
+    /// Create a trailer from a hasher that has consumed the shard data
+    pub fn from_hasher(hasher: Sha256) -> Self {
+        Self {
+            digest: hasher.finalize().into(),
+        }
+    }
+
+    /// Checksum string in the same format as the index (`sha256:<hex>`)
+    pub fn checksum(&self) -> String {
+        format!("sha256:{}", hex::encode(&self.digest))
+    }
+
+    /// Encode the trailer into bytes
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(TRAILER_SIZE);
+        bytes.extend_from_slice(&self.digest);
+        bytes.extend_from_slice(TRAILER_MAGIC);
+        bytes
+    }
+
+    /// Split a trailer off the end of shard bytes, if one is present
+    pub fn split(bytes: &[u8]) -> Option<(&[u8], ShardTrailer)> {
+        if bytes.len() < TRAILER_SIZE || !bytes.ends_with(TRAILER_MAGIC) {
+            return None;
+        }
+
+        let data_len = bytes.len() - TRAILER_SIZE;
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&bytes[data_len..data_len + 32]);
+
+        Some((&bytes[..data_len], ShardTrailer { digest }))
+    }
+
+    /// Check whether the trailer matches the given shard data
+    pub fn matches(&self, data: &[u8]) -> bool {
+        *self == Self::for_data(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::sha256::checksum_bytes;
+
+    #[test]
+    fn test_encode_split_roundtrip() {
+        let data = b"shard payload".to_vec();
+        let trailer = ShardTrailer::for_data(&data);
+
+        let mut bytes = data.clone();
+        bytes.extend_from_slice(&trailer.encode());
+
+        let (payload, parsed) = ShardTrailer::split(&bytes).unwrap();
+        assert_eq!(payload, &data[..]);
+        assert_eq!(parsed, trailer);
+        assert!(parsed.matches(payload));
+    }
+
+    #[test]
+    fn test_checksum_matches_index_format() {
+        let data = b"shard payload";
+        assert_eq!(ShardTrailer::for_data(data).checksum(), checksum_bytes(data));
+    }
+
+    #[test]
+    fn test_split_without_trailer() {
+        assert!(ShardTrailer::split(b"short").is_none());
+        assert!(ShardTrailer::split(&[0u8; 100]).is_none());
+    }
+
+    #[test]
+    fn test_trailer_detects_corruption() {
+        let trailer = ShardTrailer::for_data(b"original");
+        assert!(!trailer.matches(b"0riginal"));
+    }
+}
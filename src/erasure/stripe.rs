@@ -0,0 +1,370 @@
+use crate::checksum::sha256::finalize_checksum;
+use crate::error::{EctarError, Result};
+use crate::io::durable::Durability;
+use crate::io::streaming_shard_writer::StreamingShardWriter;
+use digest::Digest;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sha2::Sha256;
+use std::io::{self, Write};
+
+/// Magic number of the zstd skippable frame that ends a striped chunk
+pub const STRIPE_FOOTER_MAGIC: u32 = 0x184D2A5D;
+
+/// Magic number of the zstd skippable frame used to pad a striped chunk
+pub const STRIPE_PADDING_MAGIC: u32 = 0x184D2A5C;
+
+/// Size of the stripe footer frame (magic, payload length, 8-byte payload)
+pub const STRIPE_FOOTER_SIZE: usize = 16;
+
+/// Smallest skippable frame (magic and payload length, no payload)
+const MIN_FRAME_SIZE: usize = 8;
+
+/// Footer marking a chunk whose shards were written stripe by stripe
+///
+/// A striped chunk is cut into stripes of `stripe_size` bytes per data shard,
+/// and each stripe is spread over the data shards before moving on to the
+/// next, so shard i holds piece i of every stripe. The final stripe may be
+/// shorter. The footer is the last frame of the chunk and so always ends the
+/// last data shard, which is where readers look for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StripeFooter {
+    /// Bytes of each data shard per stripe
+    pub stripe_size: u32,
+    /// Bytes between the compressed data and the footer (manifest and padding)
+    pub tail_len: u32,
+}
+
+impl StripeFooter {
+    pub fn encode(&self) -> [u8; STRIPE_FOOTER_SIZE] {
+        let mut bytes = [0u8; STRIPE_FOOTER_SIZE];
+        bytes[0..4].copy_from_slice(&STRIPE_FOOTER_MAGIC.to_le_bytes());
+        bytes[4..8].copy_from_slice(&8u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.stripe_size.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.tail_len.to_le_bytes());
+        bytes
+    }
+
+    /// Parse the footer at the end of a striped chunk or its last data shard
+    ///
+    /// Returns `None` when the bytes do not end with a footer, which is the
+    /// case for every chunk encoded in one piece.
+    pub fn from_tail(bytes: &[u8]) -> Option<Self> {
+        let start = bytes.len().checked_sub(STRIPE_FOOTER_SIZE)?;
+        let field = |at: usize| u32::from_le_bytes(bytes[start + at..start + at + 4].try_into().unwrap());
+
+        if field(0) != STRIPE_FOOTER_MAGIC || field(4) != 8 {
+            return None;
+        }
+        let footer = Self {
+            stripe_size: field(8),
+            tail_len: field(12),
+        };
+        (footer.stripe_size as usize >= STRIPE_FOOTER_SIZE).then_some(footer)
+    }
+}
+
+/// Put a chunk back together from its data shards
+///
+/// Striped chunks are recognised by their footer and interleaved back into
+/// order; all other chunks are the data shards one after another.
+pub fn assemble_chunk(shards: &[Vec<u8>], data_shards: usize) -> Vec<u8> {
+    let data = &shards[..data_shards];
+    let footer = data.last().and_then(|shard| StripeFooter::from_tail(shard));
+
+    let stripe_size = match footer {
+        Some(footer) => footer.stripe_size as usize,
+        None => return data.concat(),
+    };
+
+    let shard_len = data[0].len();
+    let mut chunk = Vec::with_capacity(shard_len * data_shards);
+    let mut start = 0;
+    while start < shard_len {
+        let end = (start + stripe_size).min(shard_len);
+        for shard in data {
+            chunk.extend_from_slice(&shard[start..end]);
+        }
+        start = end;
+    }
+    chunk
+}
+
+/// Bytes of the last stripe's piece in a data shard of `shard_len` bytes
+fn final_piece(shard_len: usize, stripe_size: usize) -> usize {
+    shard_len - (shard_len - 1) / stripe_size * stripe_size
+}
+
+/// Padding to insert before the footer so the chunk needs no zero padding
+/// and the whole footer lands in the last data shard's final piece
+///
+/// `pending` is the number of bytes after the last full stripe. Padding is
+/// a skippable frame, so it is either absent or at least a frame header long.
+fn tail_padding(pending: usize, data_shards: usize, stripe_size: usize) -> usize {
+    let fits = |padding: usize| {
+        let total = pending + padding + STRIPE_FOOTER_SIZE;
+        total % data_shards == 0 && final_piece(total / data_shards, stripe_size) >= STRIPE_FOOTER_SIZE
+    };
+
+    std::iter::once(0)
+        .chain(MIN_FRAME_SIZE..)
+        .find(|&padding| fits(padding))
+        .expect("some padding always fits")
+}
+
+/// Sizes and checksums of a chunk written by a `StripeEncoder`
+#[derive(Debug, Clone)]
+pub struct StripedChunk {
+    /// Bytes of the chunk as stored, footer included
+    pub size: u64,
+    pub shard_size: u64,
+    /// SHA-256 of the chunk as stored
+    pub checksum: String,
+    pub shard_checksums: Vec<String>,
+}
+
+/// Erasure-codes a chunk stripe by stripe as it is written
+///
+/// Only one stripe is buffered, so memory stays at about
+/// `stripe_size * (data_shards + parity_shards)` bytes no matter how large
+/// the chunk grows. Parity for each stripe is computed as soon as the stripe
+/// is full and appended to the shard files with its data.
+pub struct StripeEncoder {
+    chunk_number: usize,
+    data_shards: usize,
+    stripe_size: usize,
+    rs: ReedSolomon,
+    buffer: Vec<u8>,
+    shards: Vec<Vec<u8>>,
+    writer: StreamingShardWriter,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl StripeEncoder {
+    pub fn new(
+        output_base: &str,
+        chunk_number: usize,
+        data_shards: usize,
+        parity_shards: usize,
+        stripe_size: usize,
+        shard_trailers: bool,
+    ) -> Result<Self> {
+        let total_shards = data_shards + parity_shards;
+        if data_shards > 255 || total_shards > 255 {
+            return Err(EctarError::InvalidParameters(
+                "Shard counts must be <= 255 for zfec headers".to_string(),
+            ));
+        }
+        if stripe_size < STRIPE_FOOTER_SIZE || stripe_size > u32::MAX as usize {
+            return Err(EctarError::InvalidParameters(format!(
+                "Stripe size must be between {} and {} bytes",
+                STRIPE_FOOTER_SIZE,
+                u32::MAX
+            )));
+        }
+
+        let rs = ReedSolomon::new(data_shards, parity_shards)
+            .map_err(|e| EctarError::ErasureCoding(format!("Failed to create encoder: {:?}", e)))?;
+
+        // Padding is kept to whole rows, so the zfec padlen is always zero
+        let writer = StreamingShardWriter::for_chunk_with_headers(
+            output_base,
+            chunk_number,
+            data_shards as u8,
+            total_shards as u8,
+            0,
+        )?
+        .with_trailers(shard_trailers);
+
+        Ok(Self {
+            chunk_number,
+            data_shards,
+            stripe_size,
+            rs,
+            buffer: Vec::with_capacity(data_shards * stripe_size),
+            shards: vec![Vec::with_capacity(stripe_size); total_shards],
+            writer,
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    /// How durably the shard files are committed when finishing
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.writer = self.writer.with_durability(durability);
+        self
+    }
+
+    /// Encode the buffered bytes as one stripe and append it to the shards
+    fn write_stripe(&mut self) -> Result<()> {
+        let piece = self.buffer.len() / self.data_shards;
+
+        for (i, shard) in self.shards.iter_mut().enumerate() {
+            shard.clear();
+            match self.buffer.get(i * piece..(i + 1) * piece) {
+                Some(data) if i < self.data_shards => shard.extend_from_slice(data),
+                _ => shard.resize(piece, 0),
+            }
+        }
+
+        self.rs
+            .encode(&mut self.shards)
+            .map_err(|e| EctarError::ErasureCoding(format!("Encoding failed: {:?}", e)))?;
+        self.writer.write_shards(&self.shards)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Append the chunk manifest and footer, encode the final stripe and
+    /// close the shard files
+    pub fn finish(mut self, manifest_frame: &[u8]) -> Result<StripedChunk> {
+        self.write_all(manifest_frame)?;
+
+        let padding = tail_padding(self.buffer.len(), self.data_shards, self.stripe_size);
+        if padding > 0 {
+            let mut frame = Vec::with_capacity(padding);
+            frame.extend_from_slice(&STRIPE_PADDING_MAGIC.to_le_bytes());
+            frame.extend_from_slice(&((padding - MIN_FRAME_SIZE) as u32).to_le_bytes());
+            frame.resize(padding, 0);
+            self.write_all(&frame)?;
+        }
+
+        let footer = StripeFooter {
+            stripe_size: self.stripe_size as u32,
+            tail_len: (manifest_frame.len() + padding) as u32,
+        };
+        self.write_all(&footer.encode())?;
+        if !self.buffer.is_empty() {
+            self.write_stripe()?;
+        }
+
+        let shard_size = self.size / self.data_shards as u64;
+        let shard_checksums = self.writer.shard_checksums();
+        self.writer.finish()?;
+
+        log::info!(
+            "Chunk {}: wrote {} striped shards (shard size: {} bytes, stripe: {} bytes)",
+            self.chunk_number,
+            shard_checksums.len(),
+            shard_size,
+            self.stripe_size
+        );
+
+        Ok(StripedChunk {
+            size: self.size,
+            shard_size,
+            checksum: finalize_checksum(self.hasher),
+            shard_checksums,
+        })
+    }
+}
+
+impl Write for StripeEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let stripe_bytes = self.data_shards * self.stripe_size;
+        let n = buf.len().min(stripe_bytes - self.buffer.len());
+
+        self.buffer.extend_from_slice(&buf[..n]);
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+
+        if self.buffer.len() == stripe_bytes {
+            self.write_stripe().map_err(|e| io::Error::other(e.to_string()))?;
+        }
+        Ok(n)
+    }
+
+    /// A partial stripe cannot be encoded yet, so this only flushes full ones
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::sha256::checksum_bytes;
+    use crate::erasure::{decoder, ShardData};
+    use crate::index::manifest::ChunkManifest;
+    use tempfile::TempDir;
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn read_shards(base: &str, total: usize) -> Vec<ShardData> {
+        (0..total)
+            .map(|i| ShardData::from_file(&format!("{}.c001.s{:02}", base, i).into()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_footer_roundtrip() {
+        let footer = StripeFooter { stripe_size: 4096, tail_len: 77 };
+        let mut chunk = b"compressed data".to_vec();
+        chunk.extend_from_slice(&footer.encode());
+
+        assert_eq!(StripeFooter::from_tail(&chunk), Some(footer));
+        assert_eq!(StripeFooter::from_tail(b"compressed data"), None);
+        assert_eq!(StripeFooter::from_tail(&chunk[..10]), None);
+    }
+
+    #[test]
+    fn test_tail_padding_keeps_footer_in_last_piece() {
+        for data_shards in [1, 2, 3, 7, 10] {
+            for stripe_size in [16, 17, 100] {
+                for pending in 0..data_shards * stripe_size {
+                    let padding = tail_padding(pending, data_shards, stripe_size);
+                    assert!(padding == 0 || padding >= MIN_FRAME_SIZE);
+
+                    let total = pending + padding + STRIPE_FOOTER_SIZE;
+                    assert_eq!(total % data_shards, 0);
+                    assert!(final_piece(total / data_shards, stripe_size) >= STRIPE_FOOTER_SIZE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_striped_chunk_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("test").to_string_lossy().to_string();
+        let data = noise(10_000);
+        let manifest = ChunkManifest::new(1, Vec::new()).encode_frame().unwrap();
+
+        let mut encoder = StripeEncoder::new(&base, 1, 3, 2, 256, true).unwrap();
+        encoder.write_all(&data).unwrap();
+        let chunk = encoder.finish(&manifest).unwrap();
+
+        let shards = read_shards(&base, 5);
+        assert!(shards.iter().all(|s| s.header.as_ref().unwrap().padlen == 0));
+        assert!(shards.iter().all(|s| s.data.len() as u64 == chunk.shard_size));
+        for (shard, expected) in shards.iter().zip(&chunk.shard_checksums) {
+            assert!(shard.matches_checksum(expected));
+        }
+
+        // Lose two shards, including the one holding the footer
+        let survivors: Vec<ShardData> = shards.into_iter().filter(|s| s.shard_number != 0 && s.shard_number != 2).collect();
+        let all_shards = decoder::reconstruct_shards(survivors, 3, 2).unwrap();
+        let assembled = assemble_chunk(&all_shards, 3);
+
+        assert_eq!(assembled.len() as u64, chunk.size);
+        assert_eq!(checksum_bytes(&assembled), chunk.checksum);
+        assert_eq!(&assembled[..data.len()], &data[..]);
+        assert!(ChunkManifest::from_chunk(&assembled).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_unstriped_shards_are_concatenated() {
+        let shards = vec![b"abc".to_vec(), b"def".to_vec(), b"xyz".to_vec()];
+        assert_eq!(assemble_chunk(&shards, 2), b"abcdef");
+    }
+}
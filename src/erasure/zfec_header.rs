@@ -69,7 +69,7 @@ impl ZfecHeader {
         let sharenum_bits = log2_ceil(m as usize);
 
         let total_bits = 8 + k_bits + pad_bits + sharenum_bits;
-        (total_bits + 7) / 8 // ceiling division
+        total_bits.div_ceil(8)
     }
 
     /// Encode this header into bytes (zfec format)
@@ -79,7 +79,7 @@ impl ZfecHeader {
         let sharenum_bits = log2_ceil(self.m as usize);
 
         let total_bits = 8 + k_bits + pad_bits + sharenum_bits;
-        let num_bytes = (total_bits + 7) / 8;
+        let num_bytes = total_bits.div_ceil(8);
 
         // Build the packed value (left to right: m-1, k-1, padlen, sharenum)
         let mut value: u32 = 0;
@@ -100,17 +100,16 @@ impl ZfecHeader {
         // sharenum (sharenum_bits)
         value |= self.sharenum as u32;
 
-        // Serialize as big-endian, MSB-aligned
+        // Serialize as big-endian, MSB-aligned (unused bits at the end)
+        let shift_amount = (num_bytes * 8) - total_bits;
+        let aligned_value = value << shift_amount;
         match num_bytes {
-            2 => (value as u16).to_be_bytes().to_vec(),
+            2 => (aligned_value as u16).to_be_bytes().to_vec(),
             3 => {
-                // Left-shift to align to MSB of 3 bytes (24 bits)
-                let shift_amount = (num_bytes * 8) - total_bits;
-                let aligned_value = value << shift_amount;
                 let bytes = aligned_value.to_be_bytes();
                 bytes[1..].to_vec() // skip first byte of u32
             }
-            4 => value.to_be_bytes().to_vec(),
+            4 => aligned_value.to_be_bytes().to_vec(),
             _ => unreachable!("Header size must be 2, 3, or 4 bytes"),
         }
     }
@@ -170,7 +169,7 @@ impl ZfecHeader {
         // Now we know k, calculate pad_bits and validate header size
         let pad_bits = log2_ceil(k as usize);
         let expected_total_bits = 8 + k_bits + pad_bits + sharenum_bits;
-        let expected_bytes = (expected_total_bits + 7) / 8;
+        let expected_bytes = expected_total_bits.div_ceil(8);
 
         if expected_bytes != bytes.len() {
             return Err(EctarError::InvalidHeader(format!(
@@ -275,6 +274,18 @@ mod tests {
         assert_eq!(header.padlen, decoded.padlen);
     }
 
+    #[test]
+    fn test_encode_decode_unaligned_bits() {
+        // k=2, m=3 packs into 13 bits; k=2, m=200 into 25 bits
+        for (k, m) in [(2u8, 3u8), (1, 2), (2, 200)] {
+            for sharenum in 0..m.min(4) {
+                let header = ZfecHeader::new(k, m, sharenum, k as usize - 1).unwrap();
+                let decoded = ZfecHeader::decode(&header.encode()).unwrap();
+                assert_eq!(header, decoded);
+            }
+        }
+    }
+
     #[test]
     fn test_encode_decode_max_params() {
         // Maximum parameters (m=255, since it's u8)
@@ -15,6 +15,15 @@ pub enum EctarError {
     #[error("Decompression error: {0}")]
     Decompression(String),
 
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Decryption error: {0}")]
+    Decryption(String),
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
     #[error("Erasure coding error: {0}")]
     ErasureCoding(String),
 
@@ -37,6 +46,9 @@ pub enum EctarError {
     #[error("Checksum mismatch for file: {file}")]
     ChecksumMismatch { file: String },
 
+    #[error("Checksum mismatch for chunk {chunk} ({stage} data)")]
+    ChunkChecksumMismatch { chunk: usize, stage: &'static str },
+
     #[error("Invalid shard file: {0}")]
     InvalidShardFile(PathBuf),
 
@@ -54,6 +66,9 @@ pub enum EctarError {
 
     #[error("Invalid chunk size: {0}")]
     InvalidChunkSize(String),
+
+    #[error("Broken incremental chain: {0}")]
+    BrokenChain(String),
 }
 
 impl From<serde_json::Error> for EctarError {
@@ -116,6 +131,9 @@ mod tests {
         let err = EctarError::ChecksumMismatch { file: "test.txt".to_string() };
         assert!(format!("{}", err).contains("Checksum mismatch"));
 
+        let err = EctarError::ChunkChecksumMismatch { chunk: 3, stage: "compressed" };
+        assert_eq!(format!("{}", err), "Checksum mismatch for chunk 3 (compressed data)");
+
         let err = EctarError::InvalidShardFile(PathBuf::from("/test/shard"));
         assert!(format!("{}", err).contains("Invalid shard"));
 
@@ -127,6 +145,9 @@ mod tests {
 
         let err = EctarError::InvalidChunkSize("bad chunk".to_string());
         assert!(format!("{}", err).contains("Invalid chunk size"));
+
+        let err = EctarError::BrokenChain("parent missing".to_string());
+        assert_eq!(format!("{}", err), "Broken incremental chain: parent missing");
     }
 
     #[test]
@@ -1,3 +1,205 @@
-use crate::error::Result;
+use crate::compression;
+use crate::crypto::{Context, Encryptor};
+use crate::erasure::{ShardTrailer, ZfecHeader};
+use crate::error::{EctarError, Result};
+use crate::index::format::ArchiveIndex;
+use crate::io::durable::{self, Durability};
+use crate::io::streaming_shard_writer::{FileShardOutput, ShardOutput};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
 
-// Index builder will be implemented here
+/// Format the path of an index shard ("backup.index.s03")
+pub fn format_index_shard_path(output_base: &str, shard_number: usize) -> PathBuf {
+    format_file_shard_path(output_base, "index", shard_number)
+}
+
+/// Format the path of a shard of one of an archive's small erasure-coded
+/// files ("backup.recipients.s03")
+pub fn format_file_shard_path(output_base: &str, name: &str, shard_number: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}.s{:02}", output_base, name, shard_number))
+}
+
+/// Write the archive index as `<base>.index.zst` plus k+m erasure-coded
+/// index shards, so losing the plain index file loses no metadata
+///
+/// The index shards use the archive's own data/parity parameters and carry
+/// zfec headers. They always get checksum trailers: the index cannot record
+/// checksums for itself, so the trailer is the only way to spot a bad shard.
+pub fn write_index(index: &ArchiveIndex, output_base: &str) -> Result<PathBuf> {
+    write_index_with(index, output_base, Durability::default(), None)
+}
+
+/// Write the archive index, committing each file as durably as asked and
+/// encrypting it if an encryptor is given
+///
+/// Every file is written under a temporary name and renamed into place, so
+/// an interrupted write leaves the previous index (if any) untouched. An
+/// encrypted index is sealed before it is erasure-coded, so the index
+/// shards hold ciphertext too.
+pub fn write_index_with(
+    index: &ArchiveIndex,
+    output_base: &str,
+    durability: Durability,
+    encryptor: Option<&Encryptor>,
+) -> Result<PathBuf> {
+    let json = serde_json::to_string_pretty(index)?;
+    let mut compressed = Vec::new();
+    compression::compress(json.as_bytes(), &mut compressed, 19)?;
+    if let Some(encryptor) = encryptor {
+        compressed = encryptor.seal(Context::Index, &compressed)?;
+    }
+
+    let index_path = PathBuf::from(format!("{}.index.zst", output_base));
+    durable::write_file(index_path.clone(), &compressed, durability)?;
+
+    write_file_shards(
+        &compressed,
+        output_base,
+        "index",
+        index.parameters.data_shards,
+        index.parameters.parity_shards,
+        durability,
+    )?;
+
+    log::info!("Wrote index file: {}", index_path.display());
+
+    Ok(index_path)
+}
+
+/// Write one of an archive's small JSON files as `<base>.<name>` plus
+/// `<base>.<name>.sNN` shards, so it survives losing the file
+pub fn write_json_file<T: Serialize>(
+    value: &T,
+    output_base: &str,
+    name: &str,
+    data_shards: usize,
+    parity_shards: usize,
+    durability: Durability,
+) -> Result<PathBuf> {
+    let json = serde_json::to_string_pretty(value)?;
+    let path = PathBuf::from(format!("{}.{}", output_base, name));
+    durable::write_file(path.clone(), json.as_bytes(), durability)?;
+    write_file_shards(json.as_bytes(), output_base, name, data_shards, parity_shards, durability)?;
+    Ok(path)
+}
+
+/// Erasure-code the bytes of a small file into `<base>.<name>.sNN` shard
+/// files, such as the index shards
+pub fn write_file_shards(
+    compressed: &[u8],
+    output_base: &str,
+    name: &str,
+    data_shards: usize,
+    parity_shards: usize,
+    durability: Durability,
+) -> Result<()> {
+    let total_shards = data_shards + parity_shards;
+    if total_shards > u8::MAX as usize {
+        // Zfec headers cannot describe 256 shards
+        log::warn!(
+            "Not writing {} shards: {} shards exceed the zfec header limit",
+            name,
+            total_shards
+        );
+        return Ok(());
+    }
+
+    let shard_size = compressed.len().div_ceil(data_shards).max(1);
+    let padlen = shard_size * data_shards - compressed.len();
+
+    let encoder = ReedSolomon::new(data_shards, parity_shards)
+        .map_err(|e| EctarError::ErasureCoding(format!("Failed to create encoder: {:?}", e)))?;
+
+    let mut shards: Vec<Vec<u8>> = vec![vec![0u8; shard_size]; total_shards];
+    for (i, piece) in compressed.chunks(shard_size).enumerate() {
+        shards[i][..piece.len()].copy_from_slice(piece);
+    }
+
+    encoder
+        .encode(&mut shards)
+        .map_err(|e| EctarError::ErasureCoding(format!("Encoding failed: {:?}", e)))?;
+
+    for (shard_number, data) in shards.iter().enumerate() {
+        let header = ZfecHeader::new(
+            data_shards as u8,
+            total_shards as u8,
+            shard_number as u8,
+            padlen,
+        )?;
+
+        let path = format_file_shard_path(output_base, name, shard_number);
+        let mut output = FileShardOutput::new(path)?;
+        output.set_durability(durability);
+        output.write_all(&header.encode())?;
+        output.write_all(data)?;
+        output.write_all(&ShardTrailer::for_data(data).encode())?;
+        output.finish()?;
+    }
+
+    log::debug!(
+        "Wrote {} {} shards ({} bytes each)",
+        total_shards,
+        name,
+        shard_size
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::format::ArchiveParameters;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn test_index(data_shards: usize, parity_shards: usize) -> ArchiveIndex {
+        ArchiveIndex {
+            version: "1.0".to_string(),
+            created: Utc::now(),
+            tool_version: "0.1.0".to_string(),
+            archive_name: "test-archive".to_string(),
+            last_repaired: None,
+            parameters: ArchiveParameters {
+                data_shards,
+                parity_shards,
+                chunk_size: Some(1024),
+                compression_level: 3,
+                shard_trailers: false,
+                stripe_size: None,
+                encryption: None,
+            },
+            parent: None,
+            deleted: Vec::new(),
+            deleted_bytes: Vec::new(),
+            chunks: Vec::new(),
+            files: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_format_index_shard_path() {
+        assert_eq!(
+            format_index_shard_path("backup", 3),
+            PathBuf::from("backup.index.s03")
+        );
+    }
+
+    #[test]
+    fn test_write_index_creates_file_and_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+        let index_path = write_index(&test_index(4, 2), &base).unwrap();
+
+        assert_eq!(index_path, PathBuf::from(format!("{}.index.zst", base)));
+        assert!(index_path.exists());
+        for shard_number in 0..6 {
+            assert!(format_index_shard_path(&base, shard_number).exists());
+        }
+        assert!(!format_index_shard_path(&base, 6).exists());
+    }
+}
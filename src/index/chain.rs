@@ -0,0 +1,290 @@
+use crate::crypto::KeyRing;
+use crate::error::{EctarError, Result};
+use crate::index::format::{ArchiveIndex, FileEntry, ParentArchive};
+use crate::index::reader;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// One archive of an incremental chain
+pub struct ChainLink {
+    pub index_path: PathBuf,
+    pub index: ArchiveIndex,
+}
+
+impl ChainLink {
+    /// Pattern matching the archive's data shards
+    pub fn shard_pattern(&self) -> String {
+        format!("{}.c*.s*", reader::index_base(&self.index_path))
+    }
+}
+
+/// Read an archive's index together with those of all its parents, oldest first
+pub fn load_chain(index_path: &Path, keys: Option<&KeyRing>) -> Result<Vec<ChainLink>> {
+    let index = reader::read_index_with_keys(index_path, keys)?;
+    load_ancestors(index_path.to_path_buf(), index, keys)
+}
+
+/// Follow the parent references of an index that has already been read
+///
+/// Returns the chain oldest first, ending with the given index. Fails if a
+/// parent cannot be read, has been replaced by a different archive since
+/// the child was created, or the references loop.
+pub fn load_ancestors(index_path: PathBuf, index: ArchiveIndex, keys: Option<&KeyRing>) -> Result<Vec<ChainLink>> {
+    let mut seen = HashSet::from([index_path.clone()]);
+    let mut chain = vec![ChainLink { index_path, index }];
+
+    loop {
+        let child = chain.last().expect("chain is never empty");
+        let parent = match child.index.parent {
+            Some(ref parent) => parent,
+            None => break,
+        };
+
+        let parent_path = resolve_parent(&child.index_path, parent);
+        if !seen.insert(parent_path.clone()) {
+            return Err(EctarError::BrokenChain(format!(
+                "{} is its own ancestor",
+                parent_path.display()
+            )));
+        }
+
+        let parent_index = reader::read_index_with_keys(&parent_path, keys).map_err(|e| {
+            EctarError::BrokenChain(format!(
+                "cannot read parent {} of {}: {}",
+                parent_path.display(),
+                child.index_path.display(),
+                e
+            ))
+        })?;
+        if parent_index.created != parent.created {
+            return Err(EctarError::BrokenChain(format!(
+                "{} was created at {}, but {} is based on an archive created at {}",
+                parent_path.display(),
+                parent_index.created,
+                child.index_path.display(),
+                parent.created
+            )));
+        }
+
+        log::debug!("{} is based on {}", child.index_path.display(), parent_path.display());
+        chain.push(ChainLink {
+            index_path: parent_path,
+            index: parent_index,
+        });
+    }
+
+    chain.reverse();
+    Ok(chain)
+}
+
+/// Locate the index file a parent reference points at
+pub fn resolve_parent(index_path: &Path, parent: &ParentArchive) -> PathBuf {
+    let path = Path::new(&parent.index);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        index_path.parent().unwrap_or(Path::new("")).join(path)
+    }
+}
+
+/// Build the reference a new archive at `output_base` records to the archive
+/// whose index is at `parent_path`
+///
+/// Parents in the same directory are referenced by file name, so a chain
+/// can be moved as a whole; others by absolute path.
+pub fn parent_reference(
+    parent_path: &Path,
+    parent: &ArchiveIndex,
+    output_base: &str,
+) -> Result<ParentArchive> {
+    let directory = |path: &Path| {
+        path.parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .canonicalize()
+    };
+
+    let name = parent_path.file_name().ok_or_else(|| {
+        EctarError::InvalidParameters(format!("Not an index file: {}", parent_path.display()))
+    })?;
+    let parent_dir = directory(parent_path)?;
+    let index = if directory(Path::new(output_base)).ok().as_ref() == Some(&parent_dir) {
+        PathBuf::from(name)
+    } else {
+        parent_dir.join(name)
+    };
+
+    Ok(ParentArchive {
+        archive_name: parent.archive_name.clone(),
+        index: index.to_string_lossy().to_string(),
+        created: parent.created,
+    })
+}
+
+/// The entries present at the end of a chain, by path, each with the
+/// position in the chain of the archive that stores its latest version
+pub fn snapshot(chain: &[ChainLink]) -> BTreeMap<PathBuf, (usize, &FileEntry)> {
+    let mut entries = BTreeMap::new();
+    for (position, link) in chain.iter().enumerate() {
+        for path in link.index.deleted_paths() {
+            entries.remove(&path);
+        }
+        for entry in &link.index.files {
+            entries.insert(entry.archive_path(), (position, entry));
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+    use crate::index::format::{ArchiveParameters, FileType};
+    use chrono::{Duration, Utc};
+    use tempfile::TempDir;
+
+    fn entry(path: &str) -> FileEntry {
+        FileEntry {
+            path: path.to_string(),
+            chunk: 1,
+            offset: 0,
+            stream_offset: None,
+            header_size: None,
+            size: 0,
+            compressed_size: None,
+            checksum: None,
+            mode: 0o644,
+            mtime: Utc::now(),
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            entry_type: FileType::File,
+            target: None,
+            spans_chunks: None,
+            blocks: None,
+            attributes: None,
+            sparse: false,
+            stored_size: None,
+            device_major: None,
+            device_minor: None,
+            path_bytes: None,
+            target_bytes: None,
+        }
+    }
+
+    fn write_index(
+        temp_dir: &TempDir,
+        name: &str,
+        parent: Option<ParentArchive>,
+        files: &[&str],
+        deleted: &[&str],
+    ) -> (PathBuf, ArchiveIndex) {
+        let base = temp_dir.path().join(name).to_string_lossy().to_string();
+        let index = ArchiveIndex {
+            version: "1.0".to_string(),
+            created: Utc::now(),
+            tool_version: "0.1.0".to_string(),
+            archive_name: base.clone(),
+            last_repaired: None,
+            parameters: ArchiveParameters {
+                data_shards: 2,
+                parity_shards: 1,
+                chunk_size: Some(1024),
+                compression_level: 3,
+                shard_trailers: false,
+                stripe_size: None,
+                encryption: None,
+            },
+            parent,
+            deleted: deleted.iter().map(|p| p.to_string()).collect(),
+            deleted_bytes: Vec::new(),
+            chunks: Vec::new(),
+            files: files.iter().map(|p| entry(p)).collect(),
+            blocks: Vec::new(),
+        };
+        let path = builder::write_index(&index, &base).unwrap();
+        (path, index)
+    }
+
+    #[test]
+    fn test_chain_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let out = |name: &str| temp_dir.path().join(name).to_string_lossy().to_string();
+
+        let (full_path, full) = write_index(&temp_dir, "full", None, &["a", "b", "c"], &[]);
+        let parent = parent_reference(&full_path, &full, &out("inc1")).unwrap();
+        assert_eq!(parent.index, "full.index.zst");
+        let (inc1_path, inc1) = write_index(&temp_dir, "inc1", Some(parent), &["b", "d"], &["c"]);
+        let parent = parent_reference(&inc1_path, &inc1, &out("inc2")).unwrap();
+        let (inc2_path, _) = write_index(&temp_dir, "inc2", Some(parent), &["c"], &["a"]);
+
+        let chain = load_chain(&inc2_path, None).unwrap();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[0].index_path, full_path);
+        assert_eq!(chain[2].shard_pattern(), format!("{}.c*.s*", out("inc2")));
+
+        let snapshot = snapshot(&chain);
+        let sources: Vec<(&str, usize)> = snapshot.iter().map(|(path, (pos, _))| (path.to_str().unwrap(), *pos)).collect();
+        assert_eq!(sources, vec![("b", 1), ("c", 2), ("d", 1)]);
+    }
+
+    #[test]
+    fn test_parent_in_other_directory_is_absolute() {
+        let temp_dir = TempDir::new().unwrap();
+        let (full_path, full) = write_index(&temp_dir, "full", None, &["a"], &[]);
+
+        let elsewhere = temp_dir.path().join("elsewhere");
+        std::fs::create_dir(&elsewhere).unwrap();
+        let output_base = elsewhere.join("inc").to_string_lossy().to_string();
+        let parent = parent_reference(&full_path, &full, &output_base).unwrap();
+
+        assert!(Path::new(&parent.index).is_absolute());
+        let child_index = PathBuf::from(format!("{}.index.zst", output_base));
+        assert_eq!(
+            resolve_parent(&child_index, &parent).canonicalize().unwrap(),
+            full_path.canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_replaced_parent_breaks_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let out = temp_dir.path().join("inc").to_string_lossy().to_string();
+        let (full_path, full) = write_index(&temp_dir, "full", None, &["a"], &[]);
+
+        let mut parent = parent_reference(&full_path, &full, &out).unwrap();
+        parent.created = full.created - Duration::days(1);
+        let (inc_path, _) = write_index(&temp_dir, "inc", Some(parent), &[], &[]);
+
+        assert!(matches!(load_chain(&inc_path, None), Err(EctarError::BrokenChain(_))));
+    }
+
+    #[test]
+    fn test_missing_parent_breaks_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let parent = ParentArchive {
+            archive_name: "gone".to_string(),
+            index: "gone.index.zst".to_string(),
+            created: Utc::now(),
+        };
+        let (inc_path, _) = write_index(&temp_dir, "inc", Some(parent), &[], &[]);
+
+        assert!(matches!(load_chain(&inc_path, None), Err(EctarError::BrokenChain(_))));
+    }
+
+    #[test]
+    fn test_self_referencing_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let (path, mut index) = write_index(&temp_dir, "loop", None, &[], &[]);
+        index.parent = Some(ParentArchive {
+            archive_name: index.archive_name.clone(),
+            index: "loop.index.zst".to_string(),
+            created: index.created,
+        });
+        builder::write_index(&index, &reader::index_base(&path)).unwrap();
+
+        assert!(matches!(load_chain(&path, None), Err(EctarError::BrokenChain(_))));
+    }
+}
@@ -1,5 +1,10 @@
+use crate::checksum::hex;
+use crate::crypto::recipient::WrappedKey;
+use crate::crypto::KeyDerivation;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArchiveIndex {
@@ -7,17 +12,65 @@ pub struct ArchiveIndex {
     pub created: DateTime<Utc>,
     pub tool_version: String,
     pub archive_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_repaired: Option<DateTime<Utc>>,
     pub parameters: ArchiveParameters,
+    /// Archive this one stores the changes since, if it is incremental
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<ParentArchive>,
+    /// Paths present in the parent's snapshot that no longer exist
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted: Vec<String>,
+    /// Deleted paths that are not valid UTF-8, as hex-encoded raw bytes
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "hex_list")]
+    pub deleted_bytes: Vec<Vec<u8>>,
     pub chunks: Vec<ChunkInfo>,
     pub files: Vec<FileEntry>,
+    /// Unique blocks of deduplicated files, stored as entries of their own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<FileEntry>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParentArchive {
+    pub archive_name: String,
+    /// Path of the parent's index file; relative paths are relative to the
+    /// directory holding this archive's index
+    pub index: String,
+    /// Creation time of the parent, to detect a parent that was replaced
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ArchiveParameters {
     pub data_shards: usize,
     pub parity_shards: usize,
     pub chunk_size: Option<u64>,
     pub compression_level: i32,
+    /// Whether each shard file ends with a checksum trailer
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shard_trailers: bool,
+    /// Bytes per shard of each stripe, if chunks were encoded stripe by stripe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stripe_size: Option<u64>,
+    /// How chunks are encrypted, if they are
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionParameters>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EncryptionParameters {
+    pub cipher: String,
+    #[serde(flatten)]
+    pub key: KeyDerivation,
+    /// Fingerprint of the data key, to tell a wrong key from damaged chunks
+    pub key_check: String,
+    /// Whether the index file and index shards are encrypted too
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub index_encrypted: bool,
+    /// The data key wrapped for each recipient, for x25519 archives
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<WrappedKey>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,14 +79,34 @@ pub struct ChunkInfo {
     pub compressed_size: u64,
     pub uncompressed_size: u64,
     pub shard_size: u64,
+    /// SHA-256 of the chunk as stored (after compression)
     pub checksum: String,
+    /// SHA-256 of the chunk's tar bytes before compression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uncompressed_checksum: Option<String>,
+    /// SHA-256 of each shard's data (excluding header and trailer), by shard number
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shard_checksums: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileEntry {
+    /// Path in the archive, with replacement characters for any bytes that
+    /// are not valid UTF-8
     pub path: String,
+    /// Raw bytes of a path that is not valid UTF-8, hex-encoded
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_option")]
+    pub path_bytes: Option<Vec<u8>>,
+    /// Chunk holding the entry's first tar header
     pub chunk: usize,
+    /// Offset of the entry's first tar header within the chunk's uncompressed bytes
     pub offset: u64,
+    /// Offset of the entry's first tar header within the whole tar stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_offset: Option<u64>,
+    /// Bytes of tar headers (including GNU long-name and PAX headers) before the data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_size: Option<u64>,
     pub size: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub compressed_size: Option<u64>,
@@ -52,17 +125,192 @@ pub struct FileEntry {
     pub entry_type: FileType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target: Option<String>,
+    /// Raw bytes of a link target that is not valid UTF-8, hex-encoded
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_option")]
+    pub target_bytes: Option<Vec<u8>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub spans_chunks: Option<Vec<usize>>,
+    /// SHA-256 of each block a deduplicated file is made of, in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<Vec<String>>,
+    /// Extended attributes, ACLs and SELinux label, if the entry has any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<ExtendedAttributes>,
+    /// Whether a regular file was stored as a sparse entry, without its holes
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sparse: bool,
+    /// Bytes of data a sparse file stores; `size` counts its holes as well
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_size: Option<u64>,
+    /// Major and minor number of the device a device node refers to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_major: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_minor: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl ArchiveIndex {
+    /// Paths recorded as deleted, byte for byte
+    pub fn deleted_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        let text = self.deleted.iter().map(PathBuf::from);
+        text.chain(self.deleted_bytes.iter().map(|bytes| path_from_bytes(bytes, "")))
+    }
+}
+
+impl FileEntry {
+    /// Path of the entry in the archive, byte for byte
+    pub fn archive_path(&self) -> PathBuf {
+        match self.path_bytes {
+            Some(ref bytes) => path_from_bytes(bytes, &self.path),
+            None => PathBuf::from(&self.path),
+        }
+    }
+
+    /// Target of a symlink or hard link, byte for byte
+    pub fn link_target(&self) -> Option<PathBuf> {
+        let target = self.target.as_ref()?;
+        Some(match self.target_bytes {
+            Some(ref bytes) => path_from_bytes(bytes, target),
+            None => PathBuf::from(target),
+        })
+    }
+}
+
+/// Index form of a path: its text, lossy if it is not valid UTF-8, and in
+/// that case its raw bytes as well
+pub fn path_fields(path: &Path) -> (String, Option<Vec<u8>>) {
+    match path.to_str() {
+        Some(text) => (text.to_string(), None),
+        None => (path.to_string_lossy().to_string(), Some(path_to_bytes(path))),
+    }
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Path made of raw bytes; where paths are not byte strings, the lossy
+/// text stands in
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8], _text: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8], text: &str) -> PathBuf {
+    if text.is_empty() {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    } else {
+        PathBuf::from(text)
+    }
+}
+
+/// Extended attributes of an entry, with POSIX ACLs and the SELinux label
+/// (themselves stored as extended attributes) kept apart in readable form
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExtendedAttributes {
+    /// Other extended attributes by name, with hex-encoded values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "hex_values")]
+    pub xattrs: BTreeMap<String, Vec<u8>>,
+    /// Access ACL in short text form, e.g. `user::rw-,user:1000:r--,group::r--,mask::r--,other::r--`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl_access: Option<String>,
+    /// Default ACL of a directory, in the same form
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl_default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selinux_label: Option<String>,
+}
+
+impl ExtendedAttributes {
+    pub fn is_empty(&self) -> bool {
+        self.xattrs.is_empty()
+            && self.acl_access.is_none()
+            && self.acl_default.is_none()
+            && self.selinux_label.is_none()
+    }
+}
+
+fn from_hex<E: serde::de::Error>(text: &str, what: &str) -> Result<Vec<u8>, E> {
+    hex::decode(text).ok_or_else(|| E::custom(format!("invalid hex in {}", what)))
+}
+
+/// Serialize a map of byte strings with hex-encoded values
+mod hex_values {
+    use crate::checksum::hex;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(map: &BTreeMap<String, Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(name, value)| (name, hex::encode(value))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Vec<u8>>, D::Error> {
+        BTreeMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, hex)| {
+                let value = super::from_hex(&hex, &name)?;
+                Ok((name, value))
+            })
+            .collect()
+    }
+}
+
+/// Serialize an optional byte string hex-encoded
+mod hex_option {
+    use crate::checksum::hex;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_some(&hex::encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hex| super::from_hex(&hex, "path bytes"))
+            .transpose()
+    }
+}
+
+/// Serialize a list of byte strings hex-encoded
+mod hex_list {
+    use crate::checksum::hex;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(list: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().map(|bytes| hex::encode(bytes)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| super::from_hex(hex, "deleted path bytes"))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum FileType {
+    #[default]
     File,
     Directory,
     Symlink,
     Hardlink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
     Other,
 }
 
@@ -77,6 +325,9 @@ mod tests {
             parity_shards: 5,
             chunk_size: Some(1024 * 1024),
             compression_level: 3,
+            shard_trailers: false,
+            stripe_size: None,
+            encryption: None,
         };
 
         let json = serde_json::to_string(&params).unwrap();
@@ -96,6 +347,8 @@ mod tests {
             uncompressed_size: 10000,
             shard_size: 500,
             checksum: "sha256:abc123".to_string(),
+            uncompressed_checksum: Some("sha256:789abc".to_string()),
+            shard_checksums: vec!["sha256:def456".to_string()],
         };
 
         let json = serde_json::to_string(&chunk).unwrap();
@@ -106,6 +359,16 @@ mod tests {
         assert_eq!(chunk.uncompressed_size, deserialized.uncompressed_size);
         assert_eq!(chunk.shard_size, deserialized.shard_size);
         assert_eq!(chunk.checksum, deserialized.checksum);
+        assert_eq!(chunk.uncompressed_checksum, deserialized.uncompressed_checksum);
+        assert_eq!(chunk.shard_checksums, deserialized.shard_checksums);
+    }
+
+    #[test]
+    fn test_chunk_info_without_shard_checksums() {
+        // Indexes written before shard checksums existed must still load
+        let json = r#"{"chunk_number":1,"compressed_size":10,"uncompressed_size":20,"shard_size":5,"checksum":""}"#;
+        let chunk: ChunkInfo = serde_json::from_str(json).unwrap();
+        assert!(chunk.shard_checksums.is_empty());
     }
 
     #[test]
@@ -115,6 +378,8 @@ mod tests {
             path: "test/file.txt".to_string(),
             chunk: 1,
             offset: 0,
+            stream_offset: None,
+            header_size: None,
             size: 1024,
             compressed_size: Some(512),
             checksum: Some("sha256:test".to_string()),
@@ -127,6 +392,14 @@ mod tests {
             entry_type: FileType::File,
             target: None,
             spans_chunks: None,
+            blocks: None,
+            attributes: None,
+            sparse: false,
+            stored_size: None,
+            device_major: None,
+            device_minor: None,
+            path_bytes: None,
+            target_bytes: None,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -145,6 +418,8 @@ mod tests {
             path: "test.txt".to_string(),
             chunk: 1,
             offset: 0,
+            stream_offset: None,
+            header_size: None,
             size: 100,
             compressed_size: None,
             checksum: None,
@@ -157,6 +432,14 @@ mod tests {
             entry_type: FileType::File,
             target: None,
             spans_chunks: None,
+            blocks: None,
+            attributes: None,
+            sparse: false,
+            stored_size: None,
+            device_major: None,
+            device_minor: None,
+            path_bytes: None,
+            target_bytes: None,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -179,6 +462,9 @@ mod tests {
             (FileType::Directory, "\"directory\""),
             (FileType::Symlink, "\"symlink\""),
             (FileType::Hardlink, "\"hardlink\""),
+            (FileType::CharDevice, "\"chardevice\""),
+            (FileType::BlockDevice, "\"blockdevice\""),
+            (FileType::Fifo, "\"fifo\""),
             (FileType::Other, "\"other\""),
         ];
 
@@ -199,23 +485,34 @@ mod tests {
             created: now,
             tool_version: "0.1.0".to_string(),
             archive_name: "test-archive".to_string(),
+            last_repaired: None,
             parameters: ArchiveParameters {
                 data_shards: 4,
                 parity_shards: 2,
                 chunk_size: Some(1024),
                 compression_level: 3,
+                shard_trailers: false,
+                stripe_size: None,
+                encryption: None,
             },
+            parent: None,
+            deleted: Vec::new(),
+            deleted_bytes: Vec::new(),
             chunks: vec![ChunkInfo {
                 chunk_number: 1,
                 compressed_size: 500,
                 uncompressed_size: 1000,
                 shard_size: 100,
                 checksum: "test".to_string(),
+                uncompressed_checksum: None,
+                shard_checksums: Vec::new(),
             }],
             files: vec![FileEntry {
                 path: "file.txt".to_string(),
                 chunk: 1,
                 offset: 0,
+                stream_offset: None,
+                header_size: None,
                 size: 100,
                 compressed_size: None,
                 checksum: None,
@@ -228,7 +525,16 @@ mod tests {
                 entry_type: FileType::File,
                 target: None,
                 spans_chunks: None,
+                blocks: None,
+                attributes: None,
+                sparse: false,
+                stored_size: None,
+                device_major: None,
+                device_minor: None,
+                path_bytes: None,
+                target_bytes: None,
             }],
+            blocks: Vec::new(),
         };
 
         let json = serde_json::to_string_pretty(&index).unwrap();
@@ -247,6 +553,8 @@ mod tests {
             path: "large-file.bin".to_string(),
             chunk: 1,
             offset: 0,
+            stream_offset: None,
+            header_size: None,
             size: 1000000,
             compressed_size: None,
             checksum: None,
@@ -259,6 +567,14 @@ mod tests {
             entry_type: FileType::File,
             target: None,
             spans_chunks: Some(vec![1, 2, 3]),
+            blocks: None,
+            attributes: None,
+            sparse: false,
+            stored_size: None,
+            device_major: None,
+            device_minor: None,
+            path_bytes: None,
+            target_bytes: None,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
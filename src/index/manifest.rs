@@ -0,0 +1,253 @@
+use crate::compression;
+use crate::erasure::stripe::{StripeFooter, STRIPE_FOOTER_SIZE};
+use crate::error::{EctarError, Result};
+use crate::index::format::FileEntry;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Magic number of the zstd skippable frame that holds a chunk manifest
+///
+/// zstd reserves 0x184D2A50..=0x184D2A5F for skippable frames, which every
+/// decoder (including `zstd -d`) silently skips.
+pub const MANIFEST_FRAME_MAGIC: u32 = 0x184D2A5E;
+
+/// Size of a skippable frame header (magic + payload length)
+const FRAME_HEADER_SIZE: usize = 8;
+
+/// Mini-index embedded in each compressed chunk
+///
+/// The manifest frame starts the chunk, except in striped chunks, which are
+/// written before their entries are all known and so carry it just before
+/// the stripe footer. Lists the entries whose tar headers start in the
+/// chunk, so file names, sizes and checksums can be recovered from whatever
+/// chunks survive the loss of the index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub chunk_number: usize,
+    pub files: Vec<FileEntry>,
+}
+
+impl ChunkManifest {
+    pub fn new(chunk_number: usize, files: Vec<FileEntry>) -> Self {
+        Self { chunk_number, files }
+    }
+
+    /// Encode the manifest as a zstd skippable frame (compressed JSON payload)
+    pub fn encode_frame(&self) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(self)?;
+        let mut payload = Vec::new();
+        compression::compress(&json[..], &mut payload, 3)?;
+
+        let payload_len = u32::try_from(payload.len()).map_err(|_| {
+            EctarError::Compression(format!("Chunk {} manifest is too large", self.chunk_number))
+        })?;
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&MANIFEST_FRAME_MAGIC.to_le_bytes());
+        frame.extend_from_slice(&payload_len.to_le_bytes());
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+
+    /// Parse the manifest frame of a compressed chunk
+    ///
+    /// Returns `None` for chunks written without a manifest.
+    pub fn from_chunk(chunk: &[u8]) -> Result<Option<Self>> {
+        let start = match Self::frame_payload_len(chunk) {
+            Some(_) => 0,
+            None => match StripeFooter::from_tail(chunk) {
+                Some(footer) => chunk
+                    .len()
+                    .checked_sub(STRIPE_FOOTER_SIZE + footer.tail_len as usize)
+                    .ok_or_else(|| EctarError::Decompression("Truncated chunk manifest".to_string()))?,
+                None => return Ok(None),
+            },
+        };
+        let frame = &chunk[start..];
+        let payload_len = match Self::frame_payload_len(frame) {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        let payload = frame
+            .get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + payload_len)
+            .ok_or_else(|| EctarError::Decompression("Truncated chunk manifest".to_string()))?;
+
+        Self::decode_payload(payload).map(Some)
+    }
+
+    /// Read the manifest from a compressed chunk file
+    pub fn read_from_file(path: &Path) -> Result<Option<Self>> {
+        let mut file = File::open(path)?;
+
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        if file.read_exact(&mut header).is_err() {
+            return Ok(None);
+        }
+        let mut payload_len = Self::frame_payload_len(&header);
+
+        // Striped chunks keep the manifest in front of the footer instead
+        if payload_len.is_none() && file.seek(SeekFrom::End(-(STRIPE_FOOTER_SIZE as i64))).is_ok() {
+            let mut tail = [0u8; STRIPE_FOOTER_SIZE];
+            file.read_exact(&mut tail)?;
+            if let Some(footer) = StripeFooter::from_tail(&tail) {
+                let back = (STRIPE_FOOTER_SIZE + footer.tail_len as usize) as i64;
+                file.seek(SeekFrom::End(-back))?;
+                file.read_exact(&mut header)?;
+                payload_len = Self::frame_payload_len(&header);
+            }
+        }
+        let payload_len = match payload_len {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        let mut payload = vec![0u8; payload_len];
+        file.read_exact(&mut payload)
+            .map_err(|_| EctarError::Decompression("Truncated chunk manifest".to_string()))?;
+
+        Self::decode_payload(&payload).map(Some)
+    }
+
+    fn frame_payload_len(bytes: &[u8]) -> Option<usize> {
+        let magic = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+        if magic != MANIFEST_FRAME_MAGIC {
+            return None;
+        }
+        let len = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+        Some(len as usize)
+    }
+
+    fn decode_payload(payload: &[u8]) -> Result<Self> {
+        let mut decoder = compression::create_decoder(payload)?;
+        let mut json = Vec::new();
+        decoder.read_to_end(&mut json)?;
+
+        let manifest: ChunkManifest = serde_json::from_slice(&json)?;
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::format::FileType;
+    use chrono::Utc;
+
+    fn test_entry(path: &str) -> FileEntry {
+        FileEntry {
+            path: path.to_string(),
+            chunk: 2,
+            offset: 0,
+            stream_offset: None,
+            header_size: None,
+            size: 9,
+            compressed_size: None,
+            checksum: Some("sha256:abc".to_string()),
+            mode: 0o644,
+            mtime: Utc::now(),
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            entry_type: FileType::File,
+            target: None,
+            spans_chunks: None,
+            blocks: None,
+            attributes: None,
+            sparse: false,
+            stored_size: None,
+            device_major: None,
+            device_minor: None,
+            path_bytes: None,
+            target_bytes: None,
+        }
+    }
+
+    #[test]
+    fn test_manifest_frame_roundtrip() {
+        let manifest = ChunkManifest::new(2, vec![test_entry("a.txt"), test_entry("b.txt")]);
+
+        let mut chunk = manifest.encode_frame().unwrap();
+        chunk.extend_from_slice(b"compressed chunk data");
+
+        let parsed = ChunkManifest::from_chunk(&chunk).unwrap().unwrap();
+        assert_eq!(parsed.chunk_number, 2);
+        assert_eq!(parsed.files.len(), 2);
+        assert_eq!(parsed.files[1].path, "b.txt");
+    }
+
+    #[test]
+    fn test_chunk_without_manifest() {
+        let compressed = zstd::encode_all(&b"plain chunk"[..], 3).unwrap();
+        assert!(ChunkManifest::from_chunk(&compressed).unwrap().is_none());
+        assert!(ChunkManifest::from_chunk(b"abc").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_zstd_skips_manifest_frame() {
+        let data = b"tar bytes of the chunk".repeat(10);
+        let mut chunk = ChunkManifest::new(1, vec![test_entry("a.txt")])
+            .encode_frame()
+            .unwrap();
+        chunk.extend_from_slice(&zstd::encode_all(&data[..], 3).unwrap());
+
+        let mut decoder = compression::create_decoder(&chunk[..]).unwrap();
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_read_from_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("chunk001.tar.zst");
+        let frame = ChunkManifest::new(1, vec![test_entry("a.txt")])
+            .encode_frame()
+            .unwrap();
+        std::fs::write(&path, &frame).unwrap();
+
+        let manifest = ChunkManifest::read_from_file(&path).unwrap().unwrap();
+        assert_eq!(manifest.files[0].path, "a.txt");
+    }
+
+    #[test]
+    fn test_manifest_before_stripe_footer() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let frame = ChunkManifest::new(3, vec![test_entry("a.txt")])
+            .encode_frame()
+            .unwrap();
+        let footer = StripeFooter {
+            stripe_size: 1024,
+            tail_len: frame.len() as u32,
+        };
+
+        let mut chunk = zstd::encode_all(&b"tar bytes"[..], 3).unwrap();
+        chunk.extend_from_slice(&frame);
+        chunk.extend_from_slice(&footer.encode());
+
+        let manifest = ChunkManifest::from_chunk(&chunk).unwrap().unwrap();
+        assert_eq!(manifest.chunk_number, 3);
+
+        let path = temp_dir.path().join("chunk003.tar.zst");
+        std::fs::write(&path, &chunk).unwrap();
+        let manifest = ChunkManifest::read_from_file(&path).unwrap().unwrap();
+        assert_eq!(manifest.files[0].path, "a.txt");
+
+        // zstd skips the manifest and footer frames like any skippable frame
+        let mut decoder = compression::create_decoder(&chunk[..]).unwrap();
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"tar bytes");
+    }
+
+    #[test]
+    fn test_truncated_manifest() {
+        let frame = ChunkManifest::new(1, vec![test_entry("a.txt")])
+            .encode_frame()
+            .unwrap();
+        assert!(ChunkManifest::from_chunk(&frame[..frame.len() - 3]).is_err());
+    }
+}
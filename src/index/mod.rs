@@ -1,3 +1,5 @@
 pub mod builder;
+pub mod chain;
 pub mod format;
+pub mod manifest;
 pub mod reader;
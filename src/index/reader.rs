@@ -1,3 +1,328 @@
-use crate::error::Result;
+use crate::compression;
+use crate::crypto::envelope::{self, Context};
+use crate::crypto::{KeyRing, Recipients};
+use crate::erasure::decoder::{self, ShardData};
+use crate::error::{EctarError, Result};
+use crate::index::builder;
+use crate::index::format::ArchiveIndex;
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
-// Index reader will be implemented here
+/// Read an archive index, reconstructing it from index shards when the
+/// `.index.zst` file is missing or unreadable
+pub fn read_index(index_path: &Path) -> Result<ArchiveIndex> {
+    read_index_with_keys(index_path, None)
+}
+
+/// Read an archive index that may be encrypted
+///
+/// The archive's recipients, from its recipients file or from the index
+/// itself, are added to the key ring so an identity can unwrap its key.
+pub fn read_index_with_keys(index_path: &Path, keys: Option<&KeyRing>) -> Result<ArchiveIndex> {
+    let keys = match keys {
+        Some(keys) => keys,
+        None => return read_index_or_shards(index_path, None),
+    };
+    if let Err(e) = keys.load_recipients(&index_base(index_path)) {
+        log::warn!("Could not read the recipients of {}: {}", index_path.display(), e);
+    }
+    let index = read_index_or_shards(index_path, Some(keys))?;
+    if let Some(recipients) = index.parameters.encryption.as_ref().and_then(Recipients::from_parameters) {
+        keys.add_recipients(recipients);
+    }
+    Ok(index)
+}
+
+fn read_index_or_shards(index_path: &Path, keys: Option<&KeyRing>) -> Result<ArchiveIndex> {
+    let file_error = match read_index_file(index_path, keys) {
+        Ok(index) => return Ok(index),
+        // Without a key the index shards cannot be read either
+        Err(e @ EctarError::Decryption(_)) if keys.is_none() => return Err(e),
+        Err(e) => e,
+    };
+
+    let base = index_base(index_path);
+    match read_index_shards(&base)? {
+        Some(compressed) => {
+            log::warn!(
+                "Could not read {} ({}), reconstructed index from index shards",
+                index_path.display(),
+                file_error
+            );
+            parse_index(compressed, keys)
+        }
+        None => Err(file_error),
+    }
+}
+
+/// List the index shard files present for an archive base
+pub fn find_index_shards(base: &str) -> Vec<PathBuf> {
+    find_file_shards(base, "index")
+}
+
+/// List the shard files present for one of an archive's small
+/// erasure-coded files
+pub fn find_file_shards(base: &str, name: &str) -> Vec<PathBuf> {
+    let pattern = format!("{}.{}.s*", glob::Pattern::escape(base), name);
+    match glob::glob(&pattern) {
+        Ok(paths) => paths.flatten().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Reconstruct the compressed index from any k of its index shards
+///
+/// Returns `None` if the archive has no index shards at all.
+pub fn read_index_shards(base: &str) -> Result<Option<Vec<u8>>> {
+    read_file_shards(base, "index")
+}
+
+/// Reconstruct a small erasure-coded file from any k of its shards
+///
+/// Returns `None` if none of its shards exist.
+pub fn read_file_shards(base: &str, name: &str) -> Result<Option<Vec<u8>>> {
+    let mut shards = Vec::new();
+
+    for path in find_file_shards(base, name) {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let shard_number = match name.rsplit_once(".s").and_then(|(_, n)| n.parse().ok()) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        let mut data = Vec::new();
+        File::open(&path)?.read_to_end(&mut data)?;
+
+        match ShardData::from_bytes(0, shard_number, data, name) {
+            Ok(shard) if shard.header.is_some() => shards.push(shard),
+            Ok(_) => log::warn!("Skipping {} shard {} without a zfec header", name, path.display()),
+            Err(e) => log::warn!("Skipping {} shard {}: {}", name, path.display(), e),
+        }
+    }
+
+    let header = match shards.first().and_then(|s| s.header.clone()) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    shards.retain(|s| {
+        s.header
+            .as_ref()
+            .is_some_and(|h| h.k == header.k && h.m == header.m && h.padlen == header.padlen)
+    });
+
+    let data_shards = header.k as usize;
+    let parity_shards = header.m as usize - data_shards;
+    let all_shards = decoder::reconstruct_shards(shards, data_shards, parity_shards)
+        .map_err(|e| {
+            EctarError::ErasureCoding(format!("Cannot reconstruct {} from its shards: {}", name, e))
+        })?;
+
+    let mut compressed: Vec<u8> = all_shards.into_iter().take(data_shards).flatten().collect();
+    compressed.truncate(compressed.len().saturating_sub(header.padlen));
+
+    Ok(Some(compressed))
+}
+
+/// Read one of an archive's small JSON files, reconstructing it from its
+/// shards when the file is missing or damaged
+///
+/// Returns `None` if neither the file nor any of its shards exist.
+pub fn read_json_file<T: DeserializeOwned>(base: &str, name: &str) -> Result<Option<T>> {
+    let path = PathBuf::from(format!("{}.{}", base, name));
+    let file_error = match parse_json_file(&path) {
+        Ok(value) => return Ok(Some(value)),
+        Err(e) => e,
+    };
+    match read_file_shards(base, name)? {
+        Some(json) => {
+            log::warn!("Could not read {} ({}), reconstructed it from its shards", path.display(), file_error);
+            Ok(Some(serde_json::from_slice(&json)?))
+        }
+        None if path.exists() => Err(file_error),
+        None => Ok(None),
+    }
+}
+
+/// Whether one of an archive's small JSON files and all of its shards are
+/// present and undamaged
+pub fn json_file_is_intact<T: DeserializeOwned>(base: &str, name: &str, total_shards: usize) -> bool {
+    parse_json_file::<T>(Path::new(&format!("{}.{}", base, name))).is_ok()
+        && file_shards_are_intact(base, name, total_shards)
+}
+
+fn parse_json_file<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Whether the index file and all of its index shards are present and undamaged
+pub fn index_is_intact(index_path: &Path, total_shards: usize, keys: Option<&KeyRing>) -> bool {
+    read_index_file(index_path, keys).is_ok()
+        && file_shards_are_intact(&index_base(index_path), "index", total_shards)
+}
+
+/// Whether all shards of one of an archive's small erasure-coded files are
+/// present and undamaged
+pub fn file_shards_are_intact(base: &str, name: &str, total_shards: usize) -> bool {
+    (0..total_shards).all(|shard_number| {
+        let path = builder::format_file_shard_path(base, name, shard_number);
+        std::fs::read(&path)
+            .ok()
+            .and_then(|data| ShardData::from_bytes(0, shard_number, data, &path.to_string_lossy()).ok())
+            .is_some_and(|shard| shard.header.is_some())
+    })
+}
+
+/// Derive the archive base name from an index path ("backup.index.zst" -> "backup")
+pub fn index_base(index_path: &Path) -> String {
+    let path = index_path.to_string_lossy();
+    path.strip_suffix(".index.zst").unwrap_or(&path).to_string()
+}
+
+fn read_index_file(index_path: &Path, keys: Option<&KeyRing>) -> Result<ArchiveIndex> {
+    parse_index(std::fs::read(index_path)?, keys)
+}
+
+fn parse_index(data: Vec<u8>, keys: Option<&KeyRing>) -> Result<ArchiveIndex> {
+    let sealed = envelope::is_sealed(&data);
+    let compressed = envelope::open_if_sealed(keys, Context::Index, data, false)?;
+    let mut decoder = compression::create_decoder(&compressed[..])?;
+
+    let mut json = String::new();
+    decoder.read_to_string(&mut json)?;
+
+    let index: ArchiveIndex = serde_json::from_str(&json)?;
+
+    // Read with a key, a plaintext index must be that of an encrypted
+    // archive whose index was left in the clear; anything else was swapped
+    // in for the real one
+    if keys.is_some() && !sealed {
+        match index.parameters.encryption {
+            Some(ref encryption) if encryption.index_encrypted => {
+                return Err(EctarError::Decryption(
+                    "The index is not encrypted, but the archive's parameters say it is".to_string(),
+                ))
+            }
+            Some(_) => {}
+            None => {
+                return Err(EctarError::Decryption(
+                    "The archive is not encrypted, but a key was supplied".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder::format_index_shard_path;
+    use crate::index::format::ArchiveParameters;
+    use chrono::Utc;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_test_index(temp_dir: &TempDir) -> (String, PathBuf) {
+        let base = temp_dir.path().join("archive").to_string_lossy().to_string();
+        let index = ArchiveIndex {
+            version: "1.0".to_string(),
+            created: Utc::now(),
+            tool_version: "0.1.0".to_string(),
+            archive_name: "test-archive".to_string(),
+            last_repaired: None,
+            parameters: ArchiveParameters {
+                data_shards: 3,
+                parity_shards: 2,
+                chunk_size: Some(1024),
+                compression_level: 3,
+                shard_trailers: false,
+                stripe_size: None,
+                encryption: None,
+            },
+            parent: None,
+            deleted: Vec::new(),
+            deleted_bytes: Vec::new(),
+            chunks: Vec::new(),
+            files: Vec::new(),
+            blocks: Vec::new(),
+        };
+        let index_path = builder::write_index(&index, &base).unwrap();
+        (base, index_path)
+    }
+
+    #[test]
+    fn test_read_index_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let (_, index_path) = write_test_index(&temp_dir);
+
+        let index = read_index(&index_path).unwrap();
+        assert_eq!(index.archive_name, "test-archive");
+    }
+
+    #[test]
+    fn test_read_index_from_shards_when_file_missing() {
+        let temp_dir = TempDir::new().unwrap();
+        let (base, index_path) = write_test_index(&temp_dir);
+
+        fs::remove_file(&index_path).unwrap();
+        fs::remove_file(format_index_shard_path(&base, 0)).unwrap();
+        fs::remove_file(format_index_shard_path(&base, 4)).unwrap();
+
+        let index = read_index(&index_path).unwrap();
+        assert_eq!(index.archive_name, "test-archive");
+        assert_eq!(index.parameters.data_shards, 3);
+    }
+
+    #[test]
+    fn test_read_index_from_shards_when_file_corrupt() {
+        let temp_dir = TempDir::new().unwrap();
+        let (base, index_path) = write_test_index(&temp_dir);
+
+        fs::write(&index_path, b"not an index").unwrap();
+
+        // A bit-rotted index shard fails its trailer and is left out
+        let shard_path = format_index_shard_path(&base, 1);
+        let mut data = fs::read(&shard_path).unwrap();
+        data[4] ^= 0xFF;
+        fs::write(&shard_path, data).unwrap();
+
+        let index = read_index(&index_path).unwrap();
+        assert_eq!(index.archive_name, "test-archive");
+    }
+
+    #[test]
+    fn test_read_index_too_few_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let (base, index_path) = write_test_index(&temp_dir);
+
+        fs::remove_file(&index_path).unwrap();
+        for shard_number in 0..3 {
+            fs::remove_file(format_index_shard_path(&base, shard_number)).unwrap();
+        }
+
+        assert!(matches!(read_index(&index_path), Err(EctarError::ErasureCoding(_))));
+    }
+
+    #[test]
+    fn test_index_is_intact() {
+        let temp_dir = TempDir::new().unwrap();
+        let (base, index_path) = write_test_index(&temp_dir);
+        assert!(index_is_intact(&index_path, 5, None));
+
+        fs::remove_file(format_index_shard_path(&base, 2)).unwrap();
+        assert!(!index_is_intact(&index_path, 5, None));
+    }
+
+    #[test]
+    fn test_read_index_without_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.index.zst");
+
+        assert!(read_index(&missing).is_err());
+        assert!(read_index_shards(&temp_dir.path().join("missing").to_string_lossy())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,3 +1 @@
-use crate::error::Result;
-
 // Buffering strategies will be implemented here
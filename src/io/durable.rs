@@ -0,0 +1,177 @@
+use crate::error::{EctarError, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+/// How far a finished file is pushed to stable storage before it counts as
+/// written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Durability {
+    /// Rename into place without syncing; after a power loss a file may be
+    /// present but empty or truncated
+    None,
+    /// Sync each file's data before renaming it into place
+    File,
+    /// Also sync the directory after the rename, so the name itself
+    /// survives a power loss
+    #[default]
+    Full,
+}
+
+impl FromStr for Durability {
+    type Err = EctarError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Durability::None),
+            "file" => Ok(Durability::File),
+            "full" => Ok(Durability::Full),
+            _ => Err(EctarError::InvalidParameters(format!(
+                "Invalid durability: {}. Use none, file, or full",
+                s
+            ))),
+        }
+    }
+}
+
+/// A file written under a temporary name beside its destination and only
+/// renamed into place by `commit`
+///
+/// The temporary name starts with a dot and does not share the
+/// destination's prefix, so a half-written file never matches a shard or
+/// index pattern. Dropping an uncommitted file deletes it.
+pub struct AtomicFile {
+    temp: NamedTempFile,
+    path: PathBuf,
+    durability: Durability,
+}
+
+impl AtomicFile {
+    pub fn create(path: PathBuf, durability: Durability) -> Result<Self> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| EctarError::InvalidParameters(format!("Not a file path: {}", path.display())))?;
+        let temp = tempfile::Builder::new()
+            .prefix(&format!(".{}.", name.to_string_lossy()))
+            .suffix(".tmp")
+            .tempfile_in(parent_dir(&path))?;
+        Ok(Self { temp, path, durability })
+    }
+
+    /// Final path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Change how durably the file is committed
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    /// Sync the contents as the durability level asks and rename the file
+    /// into place, replacing any file already there
+    pub fn commit(mut self) -> Result<()> {
+        self.temp.flush()?;
+        if self.durability >= Durability::File {
+            self.temp.as_file().sync_all()?;
+        }
+        let path = self.path;
+        let durability = self.durability;
+        self.temp.persist(&path).map_err(|e| EctarError::Io(e.error))?;
+        if durability >= Durability::Full {
+            sync_dir(parent_dir(&path))?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.temp.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.temp.flush()
+    }
+}
+
+/// Write a whole file atomically
+pub fn write_file(path: PathBuf, data: &[u8], durability: Durability) -> Result<()> {
+    let mut file = AtomicFile::create(path, durability)?;
+    file.write_all(data)?;
+    file.commit()
+}
+
+/// Sync a directory, so entries created or renamed in it are durable
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    // Directories cannot be opened for syncing on other platforms
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_durability() {
+        assert_eq!("none".parse::<Durability>().unwrap(), Durability::None);
+        assert_eq!("File".parse::<Durability>().unwrap(), Durability::File);
+        assert_eq!("full".parse::<Durability>().unwrap(), Durability::Full);
+        assert!("fsync".parse::<Durability>().is_err());
+        assert_eq!(Durability::default(), Durability::Full);
+    }
+
+    #[test]
+    fn test_atomic_file_appears_on_commit() {
+        for durability in [Durability::None, Durability::File, Durability::Full] {
+            let temp_dir = TempDir::new().unwrap();
+            let path = temp_dir.path().join("archive.c001.s00");
+            let mut file = AtomicFile::create(path.clone(), durability).unwrap();
+            file.write_all(b"shard data").unwrap();
+            assert!(!path.exists());
+            // Nothing in the directory looks like a shard yet
+            let pattern = format!("{}/archive.c*.s*", temp_dir.path().display());
+            assert_eq!(glob::glob(&pattern).unwrap().count(), 0);
+
+            file.commit().unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), b"shard data");
+            assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_uncommitted_file_is_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("archive.index.zst");
+
+        let mut file = AtomicFile::create(path.clone(), Durability::Full).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_write_file_replaces_existing() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("archive.index.zst");
+        std::fs::write(&path, b"old").unwrap();
+
+        write_file(path.clone(), b"new", Durability::Full).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+    }
+}
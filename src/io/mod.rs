@@ -1,4 +1,5 @@
 pub mod buffer;
+pub mod durable;
 pub mod shard_reader;
 pub mod shard_writer;
 pub mod streaming_shard_writer;
@@ -1,7 +1,9 @@
-use crate::erasure::decoder::ShardData;
+use crate::erasure::decoder::{self, ShardData};
 use crate::error::Result;
+use crate::index::format::ChunkInfo;
+use crate::index::reader;
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// Discover and read shard files from a pattern
 pub fn discover_shards(pattern: &str) -> Result<HashMap<usize, Vec<ShardData>>> {
@@ -13,7 +15,7 @@ pub fn discover_shards(pattern: &str) -> Result<HashMap<usize, Vec<ShardData>>>
 
     for path_result in paths {
         let path = path_result
-            .map_err(|e| crate::error::EctarError::Io(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))?;
+            .map_err(|e| crate::error::EctarError::Io(std::io::Error::other(e.to_string())))?;
 
         // Read shard file
         match ShardData::from_file(&path) {
@@ -26,7 +28,7 @@ pub fn discover_shards(pattern: &str) -> Result<HashMap<usize, Vec<ShardData>>>
                 );
                 shards_by_chunk
                     .entry(shard.chunk_number)
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(shard);
             }
             Err(e) => {
@@ -47,18 +49,46 @@ pub fn discover_shards(pattern: &str) -> Result<HashMap<usize, Vec<ShardData>>>
     Ok(shards_by_chunk)
 }
 
-/// Find an index file from a shard pattern
-pub fn find_index_file(shard_pattern: &str) -> Option<PathBuf> {
-    // Try to extract base name from pattern
+/// Discover shard files and drop any whose data does not match the
+/// per-shard checksums recorded in the index
+pub fn discover_verified_shards(
+    pattern: &str,
+    chunks: &[ChunkInfo],
+) -> Result<HashMap<usize, Vec<ShardData>>> {
+    let mut shards_by_chunk = discover_shards(pattern)?;
+
+    for chunk_info in chunks {
+        if chunk_info.shard_checksums.is_empty() {
+            continue;
+        }
+        if let Some(shards) = shards_by_chunk.remove(&chunk_info.chunk_number) {
+            let good = decoder::drop_corrupt_shards(shards, &chunk_info.shard_checksums);
+            shards_by_chunk.insert(chunk_info.chunk_number, good);
+        }
+    }
+
+    Ok(shards_by_chunk)
+}
+
+/// Derive the archive base name from a shard pattern
+pub fn archive_base(shard_pattern: &str) -> String {
     // Pattern might be like "backup.c*.s*" or "/path/to/backup.c*.s*"
-    let base = shard_pattern
+    shard_pattern
         .replace(".c*", "")
         .replace(".s*", "")
-        .replace("*", "");
+        .replace("*", "")
+}
+
+/// Find an index file from a shard pattern
+///
+/// Also returns the `.index.zst` path when only index shards survive;
+/// `index::reader::read_index` reconstructs the index from them.
+pub fn find_index_file(shard_pattern: &str) -> Option<PathBuf> {
+    let base = archive_base(shard_pattern);
 
     let index_path = PathBuf::from(format!("{}.index.zst", base));
 
-    if index_path.exists() {
+    if index_path.exists() || !reader::find_index_shards(&base).is_empty() {
         Some(index_path)
     } else {
         None
@@ -89,6 +119,20 @@ mod tests {
         assert_eq!(found.unwrap(), index_path);
     }
 
+    #[test]
+    fn test_find_index_file_from_index_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_path = temp_dir.path().join("test-archive");
+
+        // Only an index shard survives
+        File::create(temp_dir.path().join("test-archive.index.s03")).unwrap();
+
+        let pattern = format!("{}.c*.s*", base_path.to_string_lossy());
+        let found = find_index_file(&pattern);
+
+        assert_eq!(found, Some(temp_dir.path().join("test-archive.index.zst")));
+    }
+
     #[test]
     fn test_find_index_file_not_exists() {
         let temp_dir = TempDir::new().unwrap();
@@ -120,6 +164,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_archive_base() {
+        assert_eq!(archive_base("backup.c*.s*"), "backup");
+        assert_eq!(archive_base("/path/to/backup.c*.s*"), "/path/to/backup");
+        assert_eq!(archive_base("backup*"), "backup");
+    }
+
     #[test]
     fn test_discover_shards_with_files() {
         let temp_dir = TempDir::new().unwrap();
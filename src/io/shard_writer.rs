@@ -1,3 +1 @@
-use crate::error::Result;
-
 // Shard writing logic will be implemented here
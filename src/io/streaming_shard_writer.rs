@@ -1,51 +1,77 @@
 use crate::error::{EctarError, Result};
-use crate::erasure::ZfecHeader;
-use std::fs::File;
+use crate::erasure::{ShardTrailer, ZfecHeader};
+use crate::io::durable::{AtomicFile, Durability};
+use digest::Digest;
+use sha2::Sha256;
 use std::io::Write;
 use std::path::PathBuf;
 
 /// Trait for shard output destinations (files, tape drives, network, etc.)
 pub trait ShardOutput: Write + Send {
     fn finish(&mut self) -> Result<u64>;
+
+    /// Choose how durably `finish` commits the output; destinations without
+    /// such a notion ignore it
+    fn set_durability(&mut self, _durability: Durability) {}
 }
 
 /// File-based shard output
+///
+/// The shard is written under a temporary name and only renamed to its
+/// real name by `finish`, so an interrupted write never leaves a truncated
+/// file that looks like a shard.
 pub struct FileShardOutput {
-    file: File,
+    file: Option<AtomicFile>,
+    durability: Durability,
     bytes_written: u64,
 }
 
 impl FileShardOutput {
     pub fn new(path: PathBuf) -> Result<Self> {
-        let file = File::create(&path)?;
-        log::debug!("Created shard output file: {}", path.display());
+        let file = AtomicFile::create(path, Durability::default())?;
+        log::debug!("Created shard output file: {}", file.path().display());
         Ok(Self {
-            file,
+            file: Some(file),
+            durability: Durability::default(),
             bytes_written: 0,
         })
     }
+
+    fn file(&mut self) -> std::io::Result<&mut AtomicFile> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| std::io::Error::other("shard output already finished"))
+    }
 }
 
 impl Write for FileShardOutput {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let n = self.file.write(buf)?;
+        let n = self.file()?.write(buf)?;
         self.bytes_written += n as u64;
         Ok(n)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.file.flush()
+        self.file()?.flush()
     }
 }
 
 impl ShardOutput for FileShardOutput {
     fn finish(&mut self) -> Result<u64> {
-        self.file.flush()?;
+        if let Some(mut file) = self.file.take() {
+            file.set_durability(self.durability);
+            file.commit()?;
+        }
         Ok(self.bytes_written)
     }
+
+    fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
 }
 
 /// Manages multiple shard outputs for parallel writing
+#[derive(Default)]
 pub struct StreamingShardWriter {
     outputs: Vec<Box<dyn ShardOutput>>,
     current_chunk: usize,
@@ -55,6 +81,10 @@ pub struct StreamingShardWriter {
     padlen: usize,
     /// Whether headers have been written for this chunk
     headers_written: bool,
+    /// Running SHA-256 of the data written to each shard (headers excluded)
+    hashers: Vec<Sha256>,
+    /// Whether a checksum trailer is appended to each shard on finish
+    trailers: bool,
 }
 
 impl StreamingShardWriter {
@@ -66,6 +96,8 @@ impl StreamingShardWriter {
             ec_params: None,
             padlen: 0,
             headers_written: false,
+            hashers: Vec::new(),
+            trailers: false,
         }
     }
 
@@ -84,11 +116,13 @@ impl StreamingShardWriter {
         }
 
         Ok(Self {
+            hashers: vec![Sha256::new(); outputs.len()],
             outputs,
             current_chunk: chunk_number,
             ec_params: None,
             padlen: 0,
             headers_written: false,
+            trailers: false,
         })
     }
 
@@ -109,14 +143,30 @@ impl StreamingShardWriter {
         }
 
         Ok(Self {
+            hashers: vec![Sha256::new(); outputs.len()],
             outputs,
             current_chunk: chunk_number,
             ec_params: Some((data_shards, total_shards)),
             padlen,
             headers_written: false,
+            trailers: false,
         })
     }
 
+    /// Append a checksum trailer to each shard when finishing
+    pub fn with_trailers(mut self, trailers: bool) -> Self {
+        self.trailers = trailers;
+        self
+    }
+
+    /// How durably each shard is committed when finishing
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        for output in self.outputs.iter_mut() {
+            output.set_durability(durability);
+        }
+        self
+    }
+
     /// Write shards in parallel (all shards from the same chunk)
     /// Each shard is written to its corresponding output
     /// If ec_params is set, writes zfec headers before shard data (once)
@@ -148,8 +198,13 @@ impl StreamingShardWriter {
         let mut shard_sizes = Vec::new();
 
         // Write each shard to its output
-        for (shard_data, output) in shards.iter().zip(self.outputs.iter_mut()) {
+        for ((shard_data, output), hasher) in shards
+            .iter()
+            .zip(self.outputs.iter_mut())
+            .zip(self.hashers.iter_mut())
+        {
             output.write_all(shard_data)?;
+            hasher.update(shard_data);
             output.flush()?;
             shard_sizes.push(shard_data.len() as u64);
         }
@@ -164,10 +219,22 @@ impl StreamingShardWriter {
         Ok(shard_sizes)
     }
 
+    /// SHA-256 checksums of the shard data written so far, by shard number
+    pub fn shard_checksums(&self) -> Vec<String> {
+        self.hashers
+            .iter()
+            .map(|hasher| ShardTrailer::from_hasher(hasher.clone()).checksum())
+            .collect()
+    }
+
     /// Finish writing and return bytes written per shard
     pub fn finish(mut self) -> Result<Vec<u64>> {
         let mut sizes = Vec::new();
-        for output in self.outputs.iter_mut() {
+        for (output, hasher) in self.outputs.iter_mut().zip(self.hashers.iter()) {
+            if self.trailers {
+                let trailer = ShardTrailer::from_hasher(hasher.clone());
+                output.write_all(&trailer.encode())?;
+            }
             let size = output.finish()?;
             sizes.push(size);
         }
@@ -347,4 +414,50 @@ mod tests {
             assert_eq!(content.len(), actual_header_size + 100);
         }
     }
+
+    #[test]
+    fn test_write_with_trailers() {
+        use crate::erasure::ShardData;
+
+        let temp_dir = TempDir::new().unwrap();
+        let output_base = temp_dir.path().join("test").to_string_lossy().to_string();
+
+        let mut writer = StreamingShardWriter::for_chunk_with_headers(&output_base, 1, 2, 3, 0)
+            .unwrap()
+            .with_trailers(true);
+
+        let shards = vec![vec![1u8; 64], vec![2u8; 64], vec![3u8; 64]];
+        writer.write_shards(&shards).unwrap();
+        let checksums = writer.shard_checksums();
+        writer.finish().unwrap();
+
+        for (shard_idx, expected) in checksums.iter().enumerate() {
+            let shard = ShardData::from_file(&format_shard_path(&output_base, 1, shard_idx)).unwrap();
+            assert_eq!(shard.data, shards[shard_idx]);
+            assert_eq!(&shard.trailer.as_ref().unwrap().checksum(), expected);
+            assert!(shard.matches_checksum(expected));
+        }
+    }
+
+    #[test]
+    fn test_shards_appear_only_when_finished() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_base = temp_dir.path().join("test").to_string_lossy().to_string();
+
+        let mut writer = StreamingShardWriter::for_chunk(&output_base, 1, 3)
+            .unwrap()
+            .with_durability(Durability::None);
+        writer.write_shards(&[vec![1u8; 10], vec![2u8; 10], vec![3u8; 10]]).unwrap();
+        assert!(!format_shard_path(&output_base, 1, 0).exists());
+
+        // An abandoned writer leaves nothing behind
+        drop(writer);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        let mut writer = StreamingShardWriter::for_chunk(&output_base, 1, 3).unwrap();
+        writer.write_shards(&[vec![1u8; 10], vec![2u8; 10], vec![3u8; 10]]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 3);
+        assert_eq!(std::fs::read(format_shard_path(&output_base, 1, 2)).unwrap(), vec![3u8; 10]);
+    }
 }
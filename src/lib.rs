@@ -3,6 +3,7 @@ pub mod checksum;
 pub mod chunking;
 pub mod cli;
 pub mod compression;
+pub mod crypto;
 pub mod erasure;
 pub mod error;
 pub mod index;
@@ -1,5 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use ectar::crypto::{Identity, PublicKey, Secret, SigningKey, VerifyingKey};
 use ectar::error::Result;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -19,6 +21,33 @@ struct Cli {
     quiet: bool,
 }
 
+/// Key, passphrase or identity for encrypting or reading an encrypted archive
+#[derive(Args)]
+struct KeyArgs {
+    /// File holding a 32-byte key (raw or as 64 hex digits)
+    #[arg(long, conflicts_with_all = ["passphrase_file", "identity"])]
+    key_file: Option<PathBuf>,
+
+    /// File whose first line is a passphrase (key derived with Argon2id)
+    #[arg(long, conflicts_with = "identity")]
+    passphrase_file: Option<PathBuf>,
+
+    /// Identity file from `ectar keygen` (X25519 private key)
+    #[arg(long)]
+    identity: Option<PathBuf>,
+}
+
+impl KeyArgs {
+    fn secret(&self) -> Result<Option<Secret>> {
+        match (&self.key_file, &self.passphrase_file, &self.identity) {
+            (Some(path), _, _) => Secret::from_key_file(path).map(Some),
+            (None, Some(path), _) => Secret::from_passphrase_file(path).map(Some),
+            (None, None, Some(path)) => Secret::from_identity_file(path).map(Some),
+            (None, None, None) => Ok(None),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new erasure-coded archive
@@ -47,6 +76,24 @@ enum Commands {
         #[arg(long)]
         no_compression: bool,
 
+        /// Append a SHA-256 trailer to each shard (not readable by zunfec)
+        #[arg(long)]
+        shard_trailers: bool,
+
+        /// Erasure-code chunks in stripes of this size per shard (e.g., 1MB),
+        /// bounding memory use (not readable by zunfec)
+        #[arg(long)]
+        stripe_size: Option<String>,
+
+        /// Threads for erasure coding and writing chunks in parallel
+        #[arg(long, default_value = "1")]
+        threads: usize,
+
+        /// How far output is synced to disk before files are renamed into
+        /// place: none, file, full
+        #[arg(long, default_value = "full")]
+        durability: String,
+
         /// Don't generate index file
         #[arg(long)]
         no_index: bool,
@@ -63,6 +110,42 @@ enum Commands {
         #[arg(long)]
         no_preserve_permissions: bool,
 
+        /// Store only what changed since the archive with this index file
+        #[arg(long)]
+        incremental_from: Option<PathBuf>,
+
+        /// With --incremental-from, also compare file checksums
+        #[arg(long, requires = "incremental_from")]
+        compare_checksums: bool,
+
+        /// Store identical blocks of file data once (requires --chunk-size)
+        #[arg(long)]
+        dedup: bool,
+
+        /// Average size of deduplicated blocks (e.g., 64KB)
+        #[arg(long, requires = "dedup")]
+        dedup_block_size: Option<String>,
+
+        /// Continue an interrupted run of the same command from its journal
+        #[arg(long)]
+        resume: bool,
+
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Encrypt to this X25519 public key (can be repeated); any one
+        /// recipient's identity can then read the archive
+        #[arg(long, conflicts_with_all = ["key_file", "passphrase_file"])]
+        recipient: Vec<PublicKey>,
+
+        /// Also encrypt the index, hiding file names and sizes
+        #[arg(long)]
+        encrypt_index: bool,
+
+        /// Sign the index with this Ed25519 signing key file
+        #[arg(long, conflicts_with = "no_index")]
+        sign_key: Option<PathBuf>,
+
         /// Show progress bar (not yet implemented)
         #[arg(long)]
         progress: bool,
@@ -86,9 +169,10 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Extract only specific files/paths
+        /// Extract only specific files/paths (matched byte for byte, so
+        /// names that are not valid UTF-8 work too)
         #[arg(long)]
-        files: Vec<String>,
+        files: Vec<OsString>,
 
         /// Exclude files matching pattern
         #[arg(long)]
@@ -114,6 +198,30 @@ enum Commands {
         #[arg(long)]
         partial: bool,
 
+        /// Try shard subsets to recover chunks with undetected corrupt shards
+        #[arg(long)]
+        hunt_corruption: bool,
+
+        /// Shard subsets to try per chunk before giving up the hunt
+        #[arg(long, default_value_t = ectar::erasure::hunt::DEFAULT_MAX_ATTEMPTS)]
+        hunt_attempts: usize,
+
+        /// Number of chunks to reconstruct at once
+        #[arg(long, default_value = "1")]
+        threads: usize,
+
+        /// Extract only the entries stored in an incremental archive itself
+        /// instead of restoring its whole chain
+        #[arg(long)]
+        no_chain: bool,
+
+        /// Do not restore extended attributes, ACLs and SELinux labels
+        #[arg(long)]
+        no_xattrs: bool,
+
+        #[command(flatten)]
+        key: KeyArgs,
+
         /// Show progress bar
         #[arg(long)]
         progress: bool,
@@ -136,6 +244,9 @@ enum Commands {
         /// Output format: text, json, csv
         #[arg(long, default_value = "text")]
         format: String,
+
+        #[command(flatten)]
+        key: KeyArgs,
     },
 
     /// Verify archive integrity
@@ -152,9 +263,57 @@ enum Commands {
         #[arg(long)]
         full: bool,
 
+        /// Full check that also tries shard subsets to pinpoint corrupt shards
+        #[arg(long)]
+        hunt_corruption: bool,
+
+        /// Shard subsets to try per chunk before giving up the hunt
+        #[arg(long, default_value_t = ectar::erasure::hunt::DEFAULT_MAX_ATTEMPTS)]
+        hunt_attempts: usize,
+
+        /// Number of chunks to decode at once in full mode
+        #[arg(long, default_value = "1")]
+        threads: usize,
+
         /// Write detailed report to file
         #[arg(long)]
         report: Option<PathBuf>,
+
+        /// Require the index to be signed by this Ed25519 public key (can be
+        /// repeated) and the archive to match it
+        #[arg(long)]
+        trusted_key: Vec<VerifyingKey>,
+
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+
+    /// Regenerate missing or corrupt shards from surviving ones
+    Repair {
+        /// Input shard pattern
+        #[arg(short, long)]
+        input: String,
+
+        /// Report what would be repaired without writing any shards
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+
+    /// Regenerate a lost index by decoding every chunk and walking the tar stream
+    RebuildIndex {
+        /// Input shard pattern
+        #[arg(short, long)]
+        input: String,
+
+        /// Replace an existing index
+        #[arg(long)]
+        force: bool,
+
+        #[command(flatten)]
+        key: KeyArgs,
     },
 
     /// Display archive metadata
@@ -166,6 +325,21 @@ enum Commands {
         /// Output format: text, json
         #[arg(long, default_value = "text")]
         format: String,
+
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+
+    /// Generate an identity for encrypting archives to its public key, or
+    /// a key for signing them
+    Keygen {
+        /// File to write the identity (private key) to; must not exist
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Generate an Ed25519 signing key instead of an identity
+        #[arg(long)]
+        signing: bool,
     },
 }
 
@@ -196,10 +370,23 @@ fn main() -> Result<()> {
             chunk_size,
             compression_level,
             no_compression,
+            shard_trailers,
+            stripe_size,
+            threads,
+            durability,
             no_index,
             exclude,
             follow_symlinks,
             no_preserve_permissions,
+            incremental_from,
+            compare_checksums,
+            dedup,
+            dedup_block_size,
+            resume,
+            key,
+            recipient,
+            encrypt_index,
+            sign_key,
             progress: _,
             no_progress: _,
             paths,
@@ -213,18 +400,38 @@ fn main() -> Result<()> {
             } else {
                 None
             };
+            let stripe_size_bytes = stripe_size
+                .as_deref()
+                .map(utils::parse_byte_size)
+                .transpose()?;
 
             // Build the archive
-            let mut builder = ArchiveBuilder::new(output.clone())
+            let builder = ArchiveBuilder::new(output.clone())
                 .data_shards(data_shards)
                 .parity_shards(parity_shards)
                 .chunk_size(chunk_size_bytes)
                 .compression_level(compression_level)
                 .no_compression(no_compression)
+                .shard_trailers(shard_trailers)
+                .stripe_size(stripe_size_bytes)
+                .threads(threads)
+                .durability(durability.parse()?)
                 .no_index(no_index)
                 .exclude_patterns(exclude)
                 .follow_symlinks(follow_symlinks)
-                .preserve_permissions(!no_preserve_permissions);
+                .preserve_permissions(!no_preserve_permissions)
+                .incremental_from(incremental_from)
+                .compare_checksums(compare_checksums)
+                .dedup(dedup)
+                .resume(resume)
+                .encryption(key.secret()?)
+                .recipients(recipient)
+                .encrypt_index(encrypt_index)
+                .sign_key(sign_key.as_deref().map(SigningKey::from_file).transpose()?);
+            let builder = match dedup_block_size {
+                Some(ref size) => builder.dedup_block_size(utils::parse_byte_size(size)?),
+                None => builder,
+            };
 
             println!("Creating archive: {}", output);
             if let Some(cs) = chunk_size_bytes {
@@ -235,6 +442,10 @@ fn main() -> Result<()> {
 
             println!("Archive created successfully:");
             println!("  Total files: {}", metadata.total_files);
+            if metadata.files_unchanged > 0 || metadata.files_deleted > 0 {
+                println!("  Unchanged files: {}", metadata.files_unchanged);
+                println!("  Deleted files: {}", metadata.files_deleted);
+            }
             println!("  Total size: {} bytes", metadata.total_size);
             println!("  Compressed size: {} bytes", metadata.compressed_size);
             if metadata.total_size > 0 {
@@ -255,6 +466,12 @@ fn main() -> Result<()> {
             no_verify_checksums,
             force: _,
             partial,
+            hunt_corruption,
+            hunt_attempts,
+            threads,
+            no_chain,
+            no_xattrs,
+            key,
             progress: _,
         } => {
             use ectar::archive::extract::ArchiveExtractor;
@@ -267,6 +484,12 @@ fn main() -> Result<()> {
             let mut extractor = ArchiveExtractor::new(input.clone(), output)
                 .verify_checksums(verify_checksums && !no_verify_checksums)
                 .partial(partial)
+                .hunt_corruption(hunt_corruption)
+                .hunt_attempts(hunt_attempts)
+                .threads(threads)
+                .follow_chain(!no_chain)
+                .restore_attributes(!no_xattrs)
+                .decryption(key.secret()?)
                 .file_filters(files)
                 .exclude_patterns(exclude);
 
@@ -278,10 +501,19 @@ fn main() -> Result<()> {
 
             println!("\nExtraction complete:");
             println!("  Chunks recovered: {}/{}", metadata.chunks_recovered, metadata.chunks_total);
+            if metadata.chunks_skipped > 0 {
+                println!("  Chunks skipped (no selected files): {}", metadata.chunks_skipped);
+            }
             if metadata.chunks_failed > 0 {
                 println!("  Chunks failed: {}", metadata.chunks_failed);
             }
             println!("  Files extracted: {}", metadata.files_extracted);
+            if !metadata.corrupt_shards.is_empty() {
+                println!("  Corrupt shards found:");
+                for shard in &metadata.corrupt_shards {
+                    println!("    {}", shard.display());
+                }
+            }
 
             if metadata.chunks_failed > 0 && partial {
                 log::warn!("Partial extraction: {} chunks could not be recovered", metadata.chunks_failed);
@@ -293,12 +525,14 @@ fn main() -> Result<()> {
             files,
             long,
             format,
+            key,
         } => {
             use ectar::archive::list::ArchiveLister;
 
             let lister = ArchiveLister::new(input)
                 .filter(files)
                 .long_format(long)
+                .decryption(key.secret()?)
                 .output_format(&format)?;
 
             lister.list()?;
@@ -308,7 +542,12 @@ fn main() -> Result<()> {
             input,
             quick,
             full,
+            hunt_corruption,
+            hunt_attempts,
+            threads,
             report,
+            trusted_key,
+            key,
         } => {
             use ectar::cli::verify::ArchiveVerifier;
 
@@ -320,7 +559,15 @@ fn main() -> Result<()> {
             if full {
                 verifier = verifier.full();
             }
-            verifier = verifier.report(report);
+            if hunt_corruption {
+                verifier = verifier.hunt_corruption();
+            }
+            verifier = verifier
+                .hunt_attempts(hunt_attempts)
+                .threads(threads)
+                .report(report)
+                .trusted_keys(trusted_key)
+                .decryption(key.secret()?);
 
             let verification_report = verifier.verify()?;
 
@@ -330,14 +577,52 @@ fn main() -> Result<()> {
             }
         }
 
-        Commands::Info { input, format } => {
+        Commands::Repair { input, dry_run, key } => {
+            use ectar::cli::repair::{ArchiveRepairer, RepairStatus};
+
+            let repair_report = ArchiveRepairer::new(input)
+                .dry_run(dry_run)
+                .decryption(key.secret()?)
+                .repair()?;
+
+            // Exit with error code if some chunks could not be repaired
+            if repair_report.status == RepairStatus::Failed {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::RebuildIndex { input, force, key } => {
+            use ectar::cli::rebuild_index::IndexRebuilder;
+
+            IndexRebuilder::new(input)
+                .force(force)
+                .decryption(key.secret()?)
+                .rebuild()?;
+        }
+
+        Commands::Info { input, format, key } => {
             use ectar::cli::info::ArchiveInfo;
 
             let info = ArchiveInfo::new(input)
+                .decryption(key.secret()?)
                 .output_format(&format)?;
 
             info.show()?;
         }
+
+        Commands::Keygen { output, signing } => {
+            if signing {
+                let key = SigningKey::generate();
+                key.write_file(&output)?;
+                println!("Wrote signing key to {}", output.display());
+                println!("Public key: {}", key.verifying_key());
+            } else {
+                let identity = Identity::generate();
+                identity.write_file(&output)?;
+                println!("Wrote identity to {}", output.display());
+                println!("Public key: {}", identity.public_key());
+            }
+        }
     }
 
     Ok(())
@@ -1,3 +1 @@
-use crate::error::Result;
-
 // Archive metadata structures will be implemented here
@@ -305,6 +305,50 @@ fn test_cli_verify() {
         "Expected health status in output: {:?}", stdout);
 }
 
+#[test]
+fn test_cli_verify_full_threaded() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1KB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let output = Command::new(get_binary_path())
+        .arg("verify")
+        .arg("--full")
+        .arg("--threads")
+        .arg("4")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Verify failed: {:?}", String::from_utf8_lossy(&output.stderr));
+
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(&extract_dir)
+        .arg("--threads")
+        .arg("4")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Extract failed: {:?}", String::from_utf8_lossy(&output.stderr));
+    assert!(extract_dir.exists());
+}
+
 #[test]
 fn test_cli_verify_quick() {
     let temp_dir = TempDir::new().unwrap();
@@ -1121,3 +1165,868 @@ fn test_cli_verify_with_report() {
         String::from_utf8_lossy(&output.stdout));
     assert!(report_path.exists());
 }
+
+#[test]
+fn test_cli_repair() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--data-shards")
+        .arg("4")
+        .arg("--parity-shards")
+        .arg("2")
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    // Lose one data and one parity shard
+    let lost = [
+        temp_dir.path().join("archive.c001.s00"),
+        temp_dir.path().join("archive.c001.s04"),
+    ];
+    for shard_path in &lost {
+        fs::remove_file(shard_path).unwrap();
+    }
+
+    let pattern = format!("{}.c*.s*", archive_base);
+
+    // Dry run reports but does not write
+    let output = Command::new(get_binary_path())
+        .arg("repair")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("--dry-run")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Repair dry run failed: {:?}", String::from_utf8_lossy(&output.stderr));
+    assert!(lost.iter().all(|p| !p.exists()));
+
+    let output = Command::new(get_binary_path())
+        .arg("repair")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Repair failed: {:?}", String::from_utf8_lossy(&output.stderr));
+    assert!(lost.iter().all(|p| p.exists()));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("REPAIRED"), "Expected repair status in output: {:?}", stdout);
+}
+
+#[test]
+fn test_cli_hunt_corruption() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--data-shards")
+        .arg("4")
+        .arg("--parity-shards")
+        .arg("2")
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+
+    let output = Command::new(get_binary_path())
+        .arg("verify")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("--hunt-corruption")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Verify failed: {:?}", String::from_utf8_lossy(&output.stderr));
+
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(&extract_dir)
+        .arg("--hunt-corruption")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Extract failed: {:?}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_cli_rebuild_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--data-shards")
+        .arg("4")
+        .arg("--parity-shards")
+        .arg("2")
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+
+    // Refuses to replace the existing index without --force
+    let output = Command::new(get_binary_path())
+        .arg("rebuild-index")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+    for entry in fs::read_dir(temp_dir.path()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.to_string_lossy().contains(".index.s") {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    let output = Command::new(get_binary_path())
+        .arg("rebuild-index")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Rebuild failed: {:?}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Files Indexed"));
+
+    let output = Command::new(get_binary_path())
+        .arg("list")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("test.txt"));
+}
+
+#[test]
+fn test_cli_extract_needs_no_temp_space() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    fs::write(test_dir.join("big.bin"), (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<u8>>()).unwrap();
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("16KB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    // Chunks stream straight into the unpacker, so an unusable temp
+    // directory does not matter
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .env("TMPDIR", temp_dir.path().join("no-such-dir"))
+        .arg("extract")
+        .arg("-i")
+        .arg(format!("{}.c*.s*", archive_base))
+        .arg("-o")
+        .arg(&extract_dir)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Extract failed: {:?}", String::from_utf8_lossy(&output.stderr));
+
+    assert_eq!(
+        fs::read(extract_dir.join("testdata/big.bin")).unwrap(),
+        fs::read(test_dir.join("big.bin")).unwrap()
+    );
+}
+
+#[test]
+fn test_cli_create_striped() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--stripe-size")
+        .arg("1KB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let output = Command::new(get_binary_path())
+        .arg("info")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Stripe Size:       1024 bytes"));
+
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(extract_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(extract_dir.join("testdata").join("test.txt").exists());
+
+    // Stripes only apply to compressed chunks
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(temp_dir.path().join("raw").to_string_lossy().to_string())
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--stripe-size")
+        .arg("1KB")
+        .arg("--no-compression")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_create_incremental() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let full_base = temp_dir.path().join("full").to_string_lossy().to_string();
+    let inc_base = temp_dir.path().join("inc").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&full_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    fs::write(test_dir.join("added.txt"), b"new in the incremental").unwrap();
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&inc_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--incremental-from")
+        .arg(format!("{}.index.zst", full_base))
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Create failed: {:?}", String::from_utf8_lossy(&output.stderr));
+
+    let pattern = format!("{}.c*.s*", inc_base);
+    let output = Command::new(get_binary_path())
+        .arg("info")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Incremental From:"));
+
+    // The restore combines both archives
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(extract_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Extract failed: {:?}", String::from_utf8_lossy(&output.stderr));
+    assert!(extract_dir.join("testdata").join("test.txt").exists());
+    assert!(extract_dir.join("testdata").join("added.txt").exists());
+}
+
+#[test]
+fn test_cli_create_dedup() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let content = vec![42u8; 50_000];
+    fs::write(test_dir.join("copy1.bin"), &content).unwrap();
+    fs::write(test_dir.join("copy2.bin"), &content).unwrap();
+    let archive_base = temp_dir.path().join("dedup").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--dedup")
+        .arg("--dedup-block-size")
+        .arg("8KB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Create failed: {:?}", String::from_utf8_lossy(&output.stderr));
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let output = Command::new(get_binary_path())
+        .arg("info")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Dedup Ratio:"));
+
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(extract_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Extract failed: {:?}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read(extract_dir.join("testdata").join("copy2.bin")).unwrap(), content);
+}
+
+#[test]
+fn test_cli_create_resume_without_journal() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--resume")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("journal"));
+}
+
+#[test]
+fn test_cli_create_durability() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--durability")
+        .arg("none")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("archive.c001.s00").exists());
+    assert!(temp_dir.path().join("archive.index.zst").exists());
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--durability")
+        .arg("always")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid durability"));
+}
+
+#[test]
+fn test_cli_encrypted_roundtrip() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    let key_file = temp_dir.path().join("archive.key");
+    fs::write(&key_file, format!("{}\n", "ab".repeat(32))).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--key-file")
+        .arg(&key_file)
+        .arg("--encrypt-index")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("list")
+        .arg("-i")
+        .arg(&archive_base)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--key-file"));
+
+    let output = Command::new(get_binary_path())
+        .arg("info")
+        .arg("-i")
+        .arg(&archive_base)
+        .arg("--key-file")
+        .arg(&key_file)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("xchacha20-poly1305"));
+
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(format!("{}.c*.s*", archive_base))
+        .arg("-o")
+        .arg(&extract_dir)
+        .arg("--key-file")
+        .arg(&key_file)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(extract_dir.join("testdata/test.txt")).unwrap(),
+        b"Test content for CLI testing"
+    );
+}
+
+#[test]
+fn test_cli_keygen_and_recipients() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    let identity = temp_dir.path().join("id.key");
+
+    let output = Command::new(get_binary_path())
+        .arg("keygen")
+        .arg("-o")
+        .arg(&identity)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let public_key = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Public key: "))
+        .unwrap()
+        .to_string();
+
+    // An existing identity is never overwritten
+    let output = Command::new(get_binary_path())
+        .arg("keygen")
+        .arg("-o")
+        .arg(&identity)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--recipient")
+        .arg(&public_key)
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("archive.recipients").exists());
+
+    let output = Command::new(get_binary_path())
+        .arg("info")
+        .arg("-i")
+        .arg(&archive_base)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&public_key));
+
+    let extract_dir = temp_dir.path().join("extract");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(format!("{}.c*.s*", archive_base))
+        .arg("-o")
+        .arg(&extract_dir)
+        .arg("--identity")
+        .arg(&identity)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(extract_dir.join("testdata/test.txt")).unwrap(),
+        b"Test content for CLI testing"
+    );
+}
+
+#[test]
+fn test_cli_sign_and_verify_trusted_key() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    let sign_key = temp_dir.path().join("sign.key");
+
+    let output = Command::new(get_binary_path())
+        .arg("keygen")
+        .arg("--signing")
+        .arg("-o")
+        .arg(&sign_key)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let public_key = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Public key: "))
+        .unwrap()
+        .to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg("--sign-key")
+        .arg(&sign_key)
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("archive.signature").exists());
+
+    let output = Command::new(get_binary_path())
+        .arg("info")
+        .arg("-i")
+        .arg(&archive_base)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&public_key));
+    assert!(stdout.contains("Signature:         valid"));
+
+    let verify = |trusted: &str| {
+        Command::new(get_binary_path())
+            .arg("verify")
+            .arg("-i")
+            .arg(format!("{}.c*.s*", archive_base))
+            .arg("--trusted-key")
+            .arg(trusted)
+            .output()
+            .expect("Failed to execute command")
+    };
+    assert!(verify(&public_key).status.success());
+
+    let other_key = temp_dir.path().join("other.key");
+    let output = Command::new(get_binary_path())
+        .arg("keygen")
+        .arg("--signing")
+        .arg("-o")
+        .arg(&other_key)
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let other_public_key = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Public key: "))
+        .unwrap()
+        .to_string();
+    assert!(!verify(&other_public_key).status.success());
+}
+
+#[test]
+fn test_cli_extended_attributes() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    // Not every filesystem a test may run on has user attributes
+    if xattr::set(test_dir.join("test.txt"), "user.provenance", b"instrument 7").is_err() {
+        return;
+    }
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let output = Command::new(get_binary_path())
+        .arg("list")
+        .arg("--long")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|line| line.ends_with("testdata/test.txt")).unwrap();
+    assert!(line.contains("xattr"));
+
+    let extract = |dir: &str, extra: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .arg("extract")
+            .arg("-i")
+            .arg(&pattern)
+            .arg("-o")
+            .arg(temp_dir.path().join(dir))
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        xattr::get(temp_dir.path().join(dir).join("testdata/test.txt"), "user.provenance").unwrap()
+    };
+    assert_eq!(extract("restored", &[]), Some(b"instrument 7".to_vec()));
+    assert_eq!(extract("bare", &["--no-xattrs"]), None);
+}
+
+#[test]
+fn test_cli_hard_links() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    fs::hard_link(test_dir.join("test.txt"), test_dir.join("zz-link.txt")).unwrap();
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let extract = |dir: &str, extra: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .arg("extract")
+            .arg("-i")
+            .arg(&pattern)
+            .arg("-o")
+            .arg(temp_dir.path().join(dir))
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        temp_dir.path().join(dir).join("testdata")
+    };
+
+    let restored = extract("restored", &[]);
+    let original = fs::metadata(restored.join("test.txt")).unwrap();
+    let link = fs::metadata(restored.join("zz-link.txt")).unwrap();
+    assert_eq!(original.ino(), link.ino());
+
+    // Selecting only the link still brings its data along
+    let link_only = extract("link-only", &["--files", "testdata/zz-link.txt"]);
+    assert!(!link_only.join("test.txt").exists());
+    assert_eq!(
+        fs::read(link_only.join("zz-link.txt")).unwrap(),
+        fs::read(test_dir.join("test.txt")).unwrap()
+    );
+}
+
+#[test]
+fn test_cli_sparse_file() {
+    use std::os::unix::fs::{FileExt, MetadataExt};
+
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let image = File::create(test_dir.join("disk.img")).unwrap();
+    image.set_len(16 << 20).unwrap();
+    image.write_all_at(b"boot sector", 0).unwrap();
+    drop(image);
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let output = Command::new(get_binary_path())
+        .arg("list")
+        .arg("--long")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Stored"));
+    let line = stdout.lines().find(|line| line.ends_with("testdata/disk.img")).unwrap();
+    assert!(line.contains("16.00MB"));
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(&extract_dir)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let extracted_path = extract_dir.join("testdata/disk.img");
+    let mut expected = vec![0u8; 16 << 20];
+    expected[..11].copy_from_slice(b"boot sector");
+    assert!(fs::read(&extracted_path).unwrap() == expected);
+
+    // Filesystems without holes store the image densely
+    let source = File::open(test_dir.join("disk.img")).unwrap();
+    if ectar::archive::sparse::data_regions(&source, 16 << 20).unwrap().is_some() {
+        assert!(line.contains("4.00KB"));
+        assert!(fs::metadata(&extracted_path).unwrap().blocks() * 512 < 1 << 20);
+    }
+}
+
+#[test]
+fn test_cli_special_files() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let status = Command::new("mkfifo").arg(test_dir.join("pipe")).status().unwrap();
+    assert!(status.success());
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let output = Command::new(get_binary_path())
+        .arg("list")
+        .arg("--long")
+        .arg("-i")
+        .arg(&pattern)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|line| line.ends_with("testdata/pipe")).unwrap();
+    assert!(line.starts_with("fifo"));
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(&pattern)
+        .arg("-o")
+        .arg(&extract_dir)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let pipe = fs::symlink_metadata(extract_dir.join("testdata/pipe")).unwrap();
+    assert!(pipe.file_type().is_fifo());
+}
+
+#[test]
+fn test_cli_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = create_test_files(&temp_dir);
+    let latin1 = OsStr::from_bytes(b"r\xe9sum\xe9.txt");
+    fs::write(test_dir.join(latin1), b"latin-1 name").unwrap();
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+
+    let output = Command::new(get_binary_path())
+        .arg("create")
+        .arg("-o")
+        .arg(&archive_base)
+        .arg("--chunk-size")
+        .arg("1MB")
+        .arg(test_dir.to_string_lossy().to_string())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    // The raw name is given to --files as it is
+    let extract_dir = temp_dir.path().join("extracted");
+    let output = Command::new(get_binary_path())
+        .arg("extract")
+        .arg("-i")
+        .arg(format!("{}.c*.s*", archive_base))
+        .arg("-o")
+        .arg(&extract_dir)
+        .arg("--files")
+        .arg(std::path::Path::new("testdata").join(latin1))
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let restored = extract_dir.join("testdata");
+    assert_eq!(fs::read(restored.join(latin1)).unwrap(), b"latin-1 name");
+    assert!(!restored.join("test.txt").exists());
+}
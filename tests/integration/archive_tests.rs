@@ -1,3 +1,4 @@
+use super::test_helpers::remove_index;
 use ectar::archive::create::ArchiveBuilder;
 use ectar::archive::extract::ArchiveExtractor;
 use std::fs::{self, File};
@@ -27,7 +28,7 @@ fn test_create_and_extract_single_file() {
         .chunk_size(Some(1024 * 1024));
 
     // Pass the directory to create
-    let metadata = builder.create(&[test_data_dir.clone()]).unwrap();
+    let metadata = builder.create(std::slice::from_ref(&test_data_dir)).unwrap();
     assert!(metadata.total_files >= 1);
 
     // Extract archive
@@ -76,7 +77,7 @@ fn test_create_and_extract_directory() {
         .parity_shards(2)
         .chunk_size(Some(1024 * 1024));
 
-    let metadata = builder.create(&[test_dir.clone()]).unwrap();
+    let metadata = builder.create(std::slice::from_ref(&test_dir)).unwrap();
     assert!(metadata.total_files >= 2); // At least 2 files plus directory entries
 
     // Extract archive
@@ -116,7 +117,7 @@ fn test_large_file_spanning_chunks() {
         .parity_shards(3)
         .chunk_size(Some(50 * 1024)); // 50KB chunks
 
-    let metadata = builder.create(&[test_file.clone()]).unwrap();
+    let metadata = builder.create(std::slice::from_ref(&test_file)).unwrap();
     assert!(metadata.chunks > 1, "Should create multiple chunks");
 
     // Extract archive
@@ -154,7 +155,7 @@ fn test_extract_with_missing_shards() {
         .parity_shards(2)
         .chunk_size(Some(1024 * 1024));
 
-    builder.create(&[test_file.clone()]).unwrap();
+    builder.create(std::slice::from_ref(&test_file)).unwrap();
 
     // Delete one shard (should still be recoverable with 4 data + 2 parity)
     let shard_to_delete = temp_dir.path().join("archive.c001.s00");
@@ -192,7 +193,7 @@ fn test_empty_directory() {
         .parity_shards(2)
         .chunk_size(Some(1024 * 1024));
 
-    let metadata = builder.create(&[empty_dir.clone()]).unwrap();
+    let metadata = builder.create(std::slice::from_ref(&empty_dir)).unwrap();
     assert!(metadata.total_files >= 1); // At least the directory entry
 
     // Extract archive
@@ -297,11 +298,10 @@ fn test_create_with_very_small_chunk_size() {
         .parity_shards(2)
         .chunk_size(Some(1));
 
-    let result = builder.create(&[test_file.clone()]);
+    let result = builder.create(std::slice::from_ref(&test_file));
 
     // Should succeed or fail depending on implementation
-    if result.is_ok() {
-        let metadata = result.unwrap();
+    if let Ok(metadata) = result {
         // With 1-byte chunks and 43-byte file, expect many chunks
         assert!(metadata.chunks > 1);
     }
@@ -384,3 +384,89 @@ fn test_file_spanning_exactly_two_chunks() {
     assert_eq!(extracted_data.len(), chunk_size as usize * 2);
     assert_eq!(extracted_data, data);
 }
+
+#[test]
+fn test_striped_archive_roundtrip() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Incompressible data so every chunk spans many stripes
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let data: Vec<u8> = (0..150 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let test_file = temp_dir.path().join("large.bin");
+    fs::write(&test_file, &data).unwrap();
+
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    let builder = ArchiveBuilder::new(archive_base.clone())
+        .data_shards(6)
+        .parity_shards(3)
+        .chunk_size(Some(50 * 1024))
+        .stripe_size(Some(1024));
+
+    let metadata = builder.create(std::slice::from_ref(&test_file)).unwrap();
+    assert!(metadata.chunks > 1);
+
+    // Lose as many shards of every chunk as the parity allows
+    for chunk in 1..=metadata.chunks {
+        for shard in [0, 4, 5] {
+            fs::remove_file(format!("{}.c{:03}.s{:02}", archive_base, chunk, shard)).unwrap();
+        }
+    }
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    for with_index in [true, false] {
+        if !with_index {
+            remove_index(&archive_base);
+        }
+
+        let extract_dir = temp_dir.path().join(format!("extract-{}", with_index));
+        fs::create_dir(&extract_dir).unwrap();
+        let extractor = ArchiveExtractor::new(pattern.clone(), Some(extract_dir.clone()));
+        extractor.extract().unwrap();
+
+        assert_eq!(fs::read(extract_dir.join("large.bin")).unwrap(), data);
+    }
+}
+
+#[test]
+fn test_threaded_archive_roundtrip() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = temp_dir.path().join("data");
+    fs::create_dir(&test_dir).unwrap();
+    for i in 0..20 {
+        let content: Vec<u8> = (0..8000u32).map(|b| (b * (i + 3) % 251) as u8).collect();
+        fs::write(test_dir.join(format!("file{:02}.bin", i)), content).unwrap();
+    }
+
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    let builder = ArchiveBuilder::new(archive_base.clone())
+        .data_shards(4)
+        .parity_shards(2)
+        .chunk_size(Some(16 * 1024))
+        .threads(4);
+
+    let metadata = builder.create(std::slice::from_ref(&test_dir)).unwrap();
+    assert!(metadata.chunks > 4);
+
+    let extract_dir = temp_dir.path().join("extract");
+    fs::create_dir(&extract_dir).unwrap();
+    let pattern = format!("{}.c*.s*", archive_base);
+    let extract_metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+        .extract()
+        .unwrap();
+    assert_eq!(extract_metadata.chunks_recovered, metadata.chunks);
+
+    for i in 0..20 {
+        let name = format!("file{:02}.bin", i);
+        assert_eq!(
+            fs::read(extract_dir.join("data").join(&name)).unwrap(),
+            fs::read(test_dir.join(&name)).unwrap()
+        );
+    }
+}
@@ -5,7 +5,6 @@ use ectar::cli::info::ArchiveInfo;
 use ectar::cli::verify::ArchiveVerifier;
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
 use tempfile::TempDir;
 
 fn create_test_archive(temp_dir: &TempDir) -> String {
@@ -95,7 +94,7 @@ fn test_create_with_different_shard_configs() {
             .parity_shards(parity)
             .chunk_size(Some(1024 * 1024));
 
-        let metadata = builder.create(&[test_file.clone()]).unwrap();
+        let metadata = builder.create(std::slice::from_ref(&test_file)).unwrap();
         assert_eq!(metadata.total_files, 1);
 
         // Verify shards were created
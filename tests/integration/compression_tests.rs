@@ -10,8 +10,6 @@ use tempfile::TempDir;
 
 #[test]
 fn test_decompress_invalid_zstd_data() {
-    use std::path::PathBuf;
-
     let temp_dir = TempDir::new().unwrap();
 
     // Create a fake .zst file with random/invalid data
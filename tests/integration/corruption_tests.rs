@@ -1,4 +1,6 @@
-use super::test_helpers::{corrupt_file_bytes, corrupt_index_json, create_minimal_archive, IndexCorruption};
+use super::test_helpers::{
+    corrupt_file_bytes, corrupt_index_json, create_minimal_archive, remove_index, IndexCorruption,
+};
 use ectar::archive::create::ArchiveBuilder;
 use ectar::archive::extract::ArchiveExtractor;
 use ectar::cli::verify::ArchiveVerifier;
@@ -165,6 +167,97 @@ fn test_extract_with_corrupted_shard_data() {
     let _ = result;
 }
 
+#[test]
+fn test_extract_skips_bit_rotted_shards() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive_base = create_minimal_archive(&temp_dir).unwrap();
+
+    // Flip bytes in the payload of two shards without changing their size
+    for shard in ["archive.c001.s00", "archive.c001.s04"] {
+        let shard_path = temp_dir.path().join(shard);
+        let len = fs::metadata(&shard_path).unwrap().len() as usize;
+        corrupt_file_bytes(&shard_path, len - 4, 4).unwrap();
+    }
+
+    let extract_dir = temp_dir.path().join("extract");
+    fs::create_dir(&extract_dir).unwrap();
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    let metadata = ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+        .extract()
+        .unwrap();
+
+    assert_eq!(metadata.chunks_recovered, 1);
+    let content = fs::read(extract_dir.join("test.txt")).unwrap();
+    assert_eq!(content, b"Test data");
+}
+
+#[test]
+fn test_extract_without_index_skips_shards_failing_trailer() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("test.txt");
+    fs::write(&test_file, b"Test data protected by shard trailers").unwrap();
+
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    ArchiveBuilder::new(archive_base.clone())
+        .data_shards(4)
+        .parity_shards(2)
+        .chunk_size(Some(1024 * 1024))
+        .shard_trailers(true)
+        .create(&[test_file])
+        .unwrap();
+
+    // Corrupt a shard and lose the index, so only the trailer can catch it
+    corrupt_file_bytes(&temp_dir.path().join("archive.c001.s01"), 4, 2).unwrap();
+    remove_index(&archive_base);
+
+    let extract_dir = temp_dir.path().join("extract");
+    fs::create_dir(&extract_dir).unwrap();
+
+    let pattern = format!("{}.c*.s*", archive_base);
+    ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+        .extract()
+        .unwrap();
+
+    let content = fs::read(extract_dir.join("test.txt")).unwrap();
+    assert_eq!(content, b"Test data protected by shard trailers");
+}
+
+#[test]
+fn test_extract_with_lost_index_file_uses_index_shards() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_dir = temp_dir.path().join("data");
+    fs::create_dir(&test_dir).unwrap();
+    fs::write(test_dir.join("keep.txt"), b"keep me").unwrap();
+    fs::write(test_dir.join("skip.log"), b"skip me").unwrap();
+
+    let archive_base = temp_dir.path().join("archive").to_string_lossy().to_string();
+    ArchiveBuilder::new(archive_base.clone())
+        .data_shards(4)
+        .parity_shards(2)
+        .chunk_size(Some(1024 * 1024))
+        .create(&[test_dir])
+        .unwrap();
+
+    // Lose the index file and as many index shards as there are parity shards
+    fs::remove_file(temp_dir.path().join("archive.index.zst")).unwrap();
+    fs::remove_file(temp_dir.path().join("archive.index.s00")).unwrap();
+    fs::remove_file(temp_dir.path().join("archive.index.s03")).unwrap();
+
+    let extract_dir = temp_dir.path().join("extract");
+    fs::create_dir(&extract_dir).unwrap();
+
+    // File filtering needs the index, so this only works if it was reconstructed
+    let pattern = format!("{}.c*.s*", archive_base);
+    ArchiveExtractor::new(pattern, Some(extract_dir.clone()))
+        .file_filters(vec!["*.txt".to_string()])
+        .extract()
+        .unwrap();
+
+    assert_eq!(fs::read(extract_dir.join("data/keep.txt")).unwrap(), b"keep me");
+    assert!(!extract_dir.join("data/skip.log").exists());
+}
+
 #[test]
 fn test_extract_with_all_shards_corrupted() {
     let temp_dir = TempDir::new().unwrap();
@@ -206,16 +299,17 @@ fn test_extract_with_shard_size_mismatch() {
     file.set_len(original_size / 2).unwrap();
     drop(file);
 
-    // Try to extract - should fail due to size mismatch
+    // The truncated shard fails its checksum and is left out of decoding
     let extract_dir = temp_dir.path().join("extract");
     fs::create_dir(&extract_dir).unwrap();
 
     let pattern = format!("{}.c*.s*", archive_base);
-    let extractor = ArchiveExtractor::new(pattern, Some(extract_dir));
+    let extractor = ArchiveExtractor::new(pattern, Some(extract_dir.clone()));
     let result = extractor.extract();
 
-    // Should fail due to size mismatch between shards
-    assert!(result.is_err());
+    assert!(result.is_ok());
+    let content = fs::read(extract_dir.join("test.txt")).unwrap();
+    assert_eq!(content, b"Test data");
 }
 
 #[test]
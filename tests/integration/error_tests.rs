@@ -1,3 +1,4 @@
+use super::test_helpers::remove_index;
 use ectar::archive::create::ArchiveBuilder;
 use ectar::archive::extract::ArchiveExtractor;
 use std::fs::{self, File};
@@ -57,11 +58,10 @@ fn test_extract_missing_index() {
         .parity_shards(2)
         .chunk_size(Some(1024 * 1024));
 
-    builder.create(&[test_file.clone()]).unwrap();
+    builder.create(std::slice::from_ref(&test_file)).unwrap();
 
-    // Delete index file
-    let index_file = temp_dir.path().join("archive.index.zst");
-    fs::remove_file(index_file).unwrap();
+    // Delete index file and index shards
+    remove_index(&archive_base);
 
     // Extract should now SUCCEED using zfec headers (emergency recovery mode)
     let extract_dir = temp_dir.path().join("extract");
@@ -96,7 +96,7 @@ fn test_create_invalid_shard_count() {
         .data_shards(0)
         .parity_shards(2);
 
-    let result = builder.create(&[test_file.clone()]);
+    let result = builder.create(std::slice::from_ref(&test_file));
     assert!(result.is_err());
 
     // Parity shards = 0 should fail
@@ -104,7 +104,7 @@ fn test_create_invalid_shard_count() {
         .data_shards(4)
         .parity_shards(0);
 
-    let result = builder.create(&[test_file.clone()]);
+    let result = builder.create(std::slice::from_ref(&test_file));
     assert!(result.is_err());
 
     // Total shards > 256 should fail
@@ -148,7 +148,7 @@ fn test_invalid_compression_level() {
         .parity_shards(2)
         .compression_level(0);
 
-    let result = builder.create(&[test_file.clone()]);
+    let result = builder.create(std::slice::from_ref(&test_file));
     assert!(result.is_err());
 
     // Compression level 23 is invalid (max is 22)
@@ -1,3 +1,4 @@
+use super::test_helpers::remove_index;
 use ectar::archive::create::ArchiveBuilder;
 use ectar::archive::extract::ArchiveExtractor;
 use std::fs::{self, File};
@@ -250,7 +251,7 @@ fn test_strip_components_zero() {
         .data_shards(4)
         .parity_shards(2);
 
-    builder.create(&[nested_dir.clone()]).unwrap();
+    builder.create(std::slice::from_ref(&nested_dir)).unwrap();
 
     // Extract with strip_components = 0 (default)
     let extract_dir = temp_dir.path().join("extract");
@@ -289,11 +290,10 @@ fn test_extract_without_index_file() {
         .parity_shards(2)
         .chunk_size(Some(1024)); // Small chunk size to create multiple chunks
 
-    builder.create(&[test_file.clone()]).unwrap();
+    builder.create(std::slice::from_ref(&test_file)).unwrap();
 
-    // Delete the index file
-    let index_path = format!("{}.index.zst", archive_base);
-    fs::remove_file(&index_path).unwrap();
+    // Delete the index file and index shards
+    remove_index(&archive_base);
 
     // Extract without index
     let extract_dir = temp_dir.path().join("extract");
@@ -320,7 +320,7 @@ fn test_extract_without_index_file() {
 
 #[test]
 fn test_zfec_headers_present_in_shards() {
-    use ectar::erasure::{ShardData, ZfecHeader};
+    use ectar::erasure::ShardData;
 
     let temp_dir = TempDir::new().unwrap();
 
@@ -369,14 +369,14 @@ fn test_extract_with_missing_shards_using_headers() {
         .data_shards(3)
         .parity_shards(2);
 
-    builder.create(&[test_file.clone()]).unwrap();
+    builder.create(std::slice::from_ref(&test_file)).unwrap();
 
     // Delete some shards (but keep enough for recovery)
     fs::remove_file(format!("{}.c001.s00", archive_base)).unwrap();
     fs::remove_file(format!("{}.c001.s01", archive_base)).unwrap();
 
     // Delete index file
-    fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+    remove_index(&archive_base);
 
     // Extract with remaining shards (should work - have 3 out of 5, need 3)
     let extract_dir = temp_dir.path().join("extract");
@@ -425,7 +425,7 @@ fn test_extract_without_index_insufficient_shards() {
     fs::remove_file(format!("{}.c001.s02", archive_base)).unwrap();
 
     // Delete index
-    fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+    remove_index(&archive_base);
 
     // Extract should fail
     let extract_dir = temp_dir.path().join("extract");
@@ -465,7 +465,7 @@ fn test_extract_multi_chunk_without_index() {
     builder.create(&[file1.clone(), file2.clone()]).unwrap();
 
     // Delete index
-    fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+    remove_index(&archive_base);
 
     // Extract all chunks without index
     let extract_dir = temp_dir.path().join("extract");
@@ -32,7 +32,7 @@ fn test_create_with_unreadable_input_file() {
         .data_shards(4)
         .parity_shards(2);
 
-    let result = builder.create(&[test_file.clone()]);
+    let result = builder.create(std::slice::from_ref(&test_file));
 
     // Restore permissions for cleanup
     let mut perms = fs::metadata(&test_file).unwrap().permissions();
@@ -144,8 +144,6 @@ fn test_archive_device_files() {
 #[test]
 #[cfg(unix)]
 fn test_archive_fifo_pipe() {
-    use std::os::unix::fs as unix_fs;
-
     let temp_dir = TempDir::new().unwrap();
 
     // Create FIFO (named pipe)
@@ -19,8 +19,8 @@ pub fn corrupt_file_bytes(path: &Path, position: usize, byte_count: usize) -> st
     }
 
     // XOR bytes to corrupt them
-    for i in position..position + byte_count {
-        data[i] ^= 0xFF;
+    for byte in &mut data[position..position + byte_count] {
+        *byte ^= 0xFF;
     }
 
     let mut file = File::create(path)?;
@@ -28,6 +28,14 @@ pub fn corrupt_file_bytes(path: &Path, position: usize, byte_count: usize) -> st
     Ok(())
 }
 
+/// Deletes the index file and all index shards of an archive
+pub fn remove_index(archive_base: &str) {
+    fs::remove_file(format!("{}.index.zst", archive_base)).unwrap();
+    for path in glob::glob(&format!("{}.index.s*", archive_base)).unwrap() {
+        fs::remove_file(path.unwrap()).unwrap();
+    }
+}
+
 /// Creates a minimal archive for testing and returns the archive base path
 pub fn create_minimal_archive(temp_dir: &TempDir) -> Result<String, Box<dyn std::error::Error>> {
     let test_file = temp_dir.path().join("test.txt");
@@ -74,9 +82,25 @@ pub fn corrupt_index_json(index_path: &Path, corruption_type: IndexCorruption) -
             }
         }
         IndexCorruption::MissingField => {
-            // Remove the "chunks" field
+            // Remove the "chunks" field (chunks contain nested arrays,
+            // so find the bracket that closes the outer one)
             if let Some(start) = json_str.find("\"chunks\"") {
-                if let Some(end) = json_str[start..].find(']') {
+                let mut depth = 0;
+                let mut end = None;
+                for (i, c) in json_str[start..].char_indices() {
+                    match c {
+                        '[' => depth += 1,
+                        ']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(i);
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(end) = end {
                     json_str.replace_range(start..start + end + 1, "\"chunks\":[]");
                 }
             }
@@ -95,6 +119,7 @@ pub fn corrupt_index_json(index_path: &Path, corruption_type: IndexCorruption) -
 }
 
 /// Deletes random shards from an archive
+#[allow(dead_code)]
 pub fn delete_random_shards(base_path: &Path, count: usize) -> Vec<PathBuf> {
     let mut deleted = Vec::new();
     let parent = base_path.parent().unwrap_or(base_path);